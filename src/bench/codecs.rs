@@ -0,0 +1,193 @@
+use super::{LoadedDataset, SINK, quote, time_full_read};
+use crate::error::{ExportContext, IoContext, Result};
+use num_format::{Locale, ToFormattedString};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{BrotliLevel, Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Parquet codecs worth comparing, at their default levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Codec {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd,
+    Brotli,
+}
+
+impl Codec {
+    pub fn name(&self) -> String {
+        clap::ValueEnum::to_possible_value(self)
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn compression(&self) -> Compression {
+        match self {
+            Codec::Uncompressed => Compression::UNCOMPRESSED,
+            Codec::Snappy => Compression::SNAPPY,
+            Codec::Gzip => Compression::GZIP(GzipLevel::default()),
+            Codec::Lz4 => Compression::LZ4_RAW,
+            Codec::Zstd => Compression::ZSTD(ZstdLevel::default()),
+            Codec::Brotli => Compression::BROTLI(BrotliLevel::default()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CodecBenchConfig {
+    pub codecs: Vec<Codec>,
+    pub row_group_rows: Vec<usize>,
+    // Each variant is written and read this many times and the fastest kept
+    pub repeat: usize,
+    // Where the variants are written. Removed afterwards unless `keep_files`
+    pub work_dir: PathBuf,
+    pub keep_files: bool,
+}
+
+// How one codec and row group size did
+#[derive(Debug)]
+pub struct CodecResult {
+    pub codec: Codec,
+    pub row_group_rows: usize,
+    pub row_groups: usize,
+    pub bytes: u64,
+    pub write: Duration,
+    pub read: Duration,
+}
+
+// Writes the dataset once per codec and row group size, timing the write and a full read back
+pub fn bench_codecs(
+    dataset: &LoadedDataset,
+    config: &CodecBenchConfig,
+) -> Result<Vec<CodecResult>> {
+    std::fs::create_dir_all(&config.work_dir)
+        .io_context(|| format!("Failed to create {}", config.work_dir.display()))?;
+    info!(
+        "Comparing {} codecs and {} row group sizes over {} rows",
+        config.codecs.len(),
+        config.row_group_rows.len(),
+        dataset.rows().to_formatted_string(&Locale::en)
+    );
+
+    let mut results = Vec::new();
+    for codec in &config.codecs {
+        for row_group_rows in &config.row_group_rows {
+            let path = config
+                .work_dir
+                .join(format!("{}_{row_group_rows}.parquet", codec.name()));
+            let mut write = Duration::MAX;
+            let mut read = Duration::MAX;
+            let mut row_groups = 0;
+            for _ in 0..config.repeat.max(1) {
+                let (elapsed, groups) = write_variant(dataset, *codec, *row_group_rows, &path)?;
+                write = write.min(elapsed);
+                row_groups = groups;
+                read = read.min(time_full_read(&path)?);
+            }
+            let bytes = std::fs::metadata(&path)
+                .io_context(|| format!("Failed to stat {}", path.display()))?
+                .len();
+            info!(
+                "{} with {} row group rows: {} bytes, write {:?}, read {:?}",
+                codec.name(),
+                row_group_rows,
+                bytes,
+                write,
+                read
+            );
+
+            if !config.keep_files {
+                std::fs::remove_file(&path)
+                    .io_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+            results.push(CodecResult {
+                codec: *codec,
+                row_group_rows: *row_group_rows,
+                row_groups,
+                bytes,
+                write,
+                read,
+            });
+        }
+    }
+    if !config.keep_files {
+        // Only goes if nothing else is in there
+        let _ = std::fs::remove_dir(&config.work_dir);
+    }
+    Ok(results)
+}
+
+// Returns the write time and the number of row groups written
+fn write_variant(
+    dataset: &LoadedDataset,
+    codec: Codec,
+    row_group_rows: usize,
+    path: &Path,
+) -> Result<(Duration, usize)> {
+    let start = Instant::now();
+    let file = File::create(path).io_context(|| format!("Failed to create {}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(codec.compression())
+        .set_max_row_group_size(row_group_rows)
+        .set_key_value_metadata(dataset.key_values.clone())
+        .build();
+    let mut writer = ArrowWriter::try_new(file, dataset.schema.clone(), Some(props))
+        .export_context(SINK, || "Failed to create arrow writer")?;
+    for batch in &dataset.batches {
+        writer
+            .write(batch)
+            .export_context(SINK, || "Failed to write record batch")?;
+    }
+    let metadata = writer
+        .close()
+        .export_context(SINK, || "Failed to close Parquet writer")?;
+    Ok((start.elapsed(), metadata.row_groups.len()))
+}
+
+// Comparison table for the terminal. Ratio is decoded size over file size
+pub fn print_codec_table(dataset: &LoadedDataset, results: &[CodecResult]) {
+    let memory = dataset.memory_bytes() as f64;
+    println!(
+        "{:<13}  {:>14}  {:>10}  {:>15}  {:>7}  {:>10}  {:>10}",
+        "codec", "row_group_rows", "row_groups", "bytes", "ratio", "write_ms", "read_ms"
+    );
+    for result in results {
+        println!(
+            "{:<13}  {:>14}  {:>10}  {:>15}  {:>6.2}x  {:>10.1}  {:>10.1}",
+            result.codec.name(),
+            result.row_group_rows.to_formatted_string(&Locale::en),
+            result.row_groups,
+            result.bytes.to_formatted_string(&Locale::en),
+            memory / result.bytes as f64,
+            result.write.as_secs_f64() * 1000.0,
+            result.read.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+pub fn write_codec_report(input: &Path, results: &[CodecResult], report: &Path) -> Result<()> {
+    let mut csv = String::from("input,codec,row_group_rows,row_groups,bytes,write_ms,read_ms\n");
+    for result in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{:.3},{:.3}",
+            quote(&input.display().to_string()),
+            result.codec.name(),
+            result.row_group_rows,
+            result.row_groups,
+            result.bytes,
+            result.write.as_secs_f64() * 1000.0,
+            result.read.as_secs_f64() * 1000.0
+        );
+    }
+    std::fs::write(report, csv).io_context(|| format!("Failed to write {}", report.display()))?;
+    info!("Codec report written to {}", report.display());
+    Ok(())
+}
@@ -0,0 +1,361 @@
+use super::{Codec, LoadedDataset, SINK, quote};
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use crate::exporters::SCHEMA_VERSION_KEY;
+use arrow::array::{
+    Array, ArrayRef, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray, UInt64Array,
+};
+use arrow::compute::{concat_batches, sort_to_indices, take_record_batch};
+use arrow::record_batch::RecordBatch;
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use num_format::{Locale, ToFormattedString};
+use parquet::arrow::ArrowWriter;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Shape of the rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    // One row per reading, as generated
+    Tall,
+    // One row per millisecond since launch with a column per sensor_type
+    Wide,
+}
+
+// How the rows are split into files, as hive style `key=value` directories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Partitioning {
+    Single,
+    BySensor,
+    // Windows of time since launch
+    ByTime,
+}
+
+// Layout and partitioning pairs written. Wide rows hold every sensor so only split by time
+const VARIANTS: [(Layout, Partitioning); 5] = [
+    (Layout::Tall, Partitioning::Single),
+    (Layout::Tall, Partitioning::BySensor),
+    (Layout::Tall, Partitioning::ByTime),
+    (Layout::Wide, Partitioning::Single),
+    (Layout::Wide, Partitioning::ByTime),
+];
+
+impl Layout {
+    fn name(&self) -> &'static str {
+        match self {
+            Layout::Tall => "tall",
+            Layout::Wide => "wide",
+        }
+    }
+}
+
+impl Partitioning {
+    fn name(&self) -> &'static str {
+        match self {
+            Partitioning::Single => "single",
+            Partitioning::BySensor => "sensor",
+            Partitioning::ByTime => "time",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LayoutBenchConfig {
+    pub codec: Codec,
+    // Window of time since launch per `window=` partition
+    pub partition_window_ms: u64,
+    // Where the variants are written, one directory each. Removed afterwards unless `keep_files`
+    pub work_dir: PathBuf,
+    pub keep_files: bool,
+}
+
+// How one layout did
+#[derive(Debug)]
+pub struct LayoutResult {
+    pub layout: Layout,
+    pub partitioning: Partitioning,
+    pub files: usize,
+    pub rows: usize,
+    pub bytes: u64,
+    // Sorting, splitting and writing. Pivoting to wide is timed once on its own
+    pub write: Duration,
+}
+
+// Writes the dataset in every layout and partitioning from the same data and reports sizes and
+// write times. The wide layout is pivoted on time since launch without interpolation, readings
+// that share a millisecond and sensor keep the last value
+pub fn bench_layouts(
+    dataset: &LoadedDataset,
+    config: &LayoutBenchConfig,
+) -> Result<(Vec<LayoutResult>, Duration)> {
+    if config.partition_window_ms == 0 {
+        return Err(TelemetryError::config("Partition window must be above 0"));
+    }
+    let tall = concat_batches(&dataset.schema, &dataset.batches)
+        .export_context(SINK, || "Failed to combine record batches")?;
+
+    let start = Instant::now();
+    let wide = pivot_wide(&tall)?;
+    let pivot = start.elapsed();
+    info!(
+        "Pivoted {} readings into {} wide rows of {} columns in {:?}",
+        tall.num_rows(),
+        wide.num_rows(),
+        wide.num_columns(),
+        pivot
+    );
+
+    // The schema version describes the tall value columns, it doesn't apply to wide files
+    let wide_key_values: Option<Vec<KeyValue>> = dataset.key_values.as_ref().map(|key_values| {
+        key_values
+            .iter()
+            .filter(|kv| kv.key != SCHEMA_VERSION_KEY)
+            .cloned()
+            .collect()
+    });
+
+    let mut results = Vec::new();
+    for (layout, partitioning) in VARIANTS {
+        let (batch, key_values) = match layout {
+            Layout::Tall => (&tall, &dataset.key_values),
+            Layout::Wide => (&wide, &wide_key_values),
+        };
+        let dir = config
+            .work_dir
+            .join(format!("{}_{}", layout.name(), partitioning.name()));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)
+                .io_context(|| format!("Failed to clear {}", dir.display()))?;
+        }
+
+        let start = Instant::now();
+        let files = write_partitioned(batch, partitioning, config, key_values, &dir)?;
+        let write = start.elapsed();
+        let bytes = files
+            .iter()
+            .map(|file| std::fs::metadata(file).map(|m| m.len()).unwrap_or_default())
+            .sum();
+        info!(
+            "{} {}: {} files, {} bytes in {:?}",
+            layout.name(),
+            partitioning.name(),
+            files.len(),
+            bytes,
+            write
+        );
+
+        if !config.keep_files {
+            std::fs::remove_dir_all(&dir)
+                .io_context(|| format!("Failed to remove {}", dir.display()))?;
+        }
+        results.push(LayoutResult {
+            layout,
+            partitioning,
+            files: files.len(),
+            rows: batch.num_rows(),
+            bytes,
+            write,
+        });
+    }
+    if !config.keep_files {
+        // Only goes if nothing else is in there
+        let _ = std::fs::remove_dir(&config.work_dir);
+    }
+    Ok((results, pivot))
+}
+
+// Columns of a tall batch by name, downcast
+fn tall_column<'a, T: 'static>(batch: &'a RecordBatch, name: &str) -> Result<&'a T> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<T>())
+        .ok_or_else(|| TelemetryError::config(format!("Expected a tall run with a {name} column")))
+}
+
+fn pivot_wide(tall: &RecordBatch) -> Result<RecordBatch> {
+    let sensor_types = tall_column::<StringArray>(tall, "sensor_type")?;
+    let since_launch = tall_column::<UInt64Array>(tall, "time_since_launch_ms")?;
+    let timestamps = tall_column::<TimestampMicrosecondArray>(tall, "timestamp")?;
+    let floats = tall_column::<Float64Array>(tall, "value")
+        .or_else(|_| tall_column::<Float64Array>(tall, "value_float"))
+        .ok();
+    let ints = tall_column::<Int64Array>(tall, "value_int").ok();
+
+    // Row per millisecond, earliest timestamp of the readings in it
+    let mut rows: BTreeMap<u64, i64> = BTreeMap::new();
+    for row in 0..tall.num_rows() {
+        let timestamp = timestamps.value(row);
+        rows.entry(since_launch.value(row))
+            .and_modify(|t| *t = (*t).min(timestamp))
+            .or_insert(timestamp);
+    }
+    let row_of: BTreeMap<u64, usize> = rows.keys().enumerate().map(|(i, ms)| (*ms, i)).collect();
+
+    let mut columns: BTreeMap<&str, Vec<Option<f64>>> = BTreeMap::new();
+    for row in 0..tall.num_rows() {
+        let value = match (floats, ints) {
+            (Some(floats), _) if floats.is_valid(row) => floats.value(row),
+            (_, Some(ints)) if ints.is_valid(row) => ints.value(row) as f64,
+            _ => continue,
+        };
+        let column = columns
+            .entry(sensor_types.value(row))
+            .or_insert_with(|| vec![None; rows.len()]);
+        column[row_of[&since_launch.value(row)]] = Some(value);
+    }
+
+    let mut fields = vec![
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Microsecond, None),
+            false,
+        ),
+        Field::new("time_since_launch_ms", DataType::UInt64, false),
+    ];
+    let mut arrays: Vec<ArrayRef> = vec![
+        Arc::new(TimestampMicrosecondArray::from_iter_values(
+            rows.values().copied(),
+        )),
+        Arc::new(UInt64Array::from_iter_values(rows.keys().copied())),
+    ];
+    for (name, values) in columns {
+        fields.push(Field::new(name, DataType::Float64, true));
+        arrays.push(Arc::new(Float64Array::from(values)));
+    }
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+        .export_context(SINK, || "Failed to build wide record batch")
+}
+
+// Writes the batch under `dir`, one file per partition. Returns the files written
+fn write_partitioned(
+    batch: &RecordBatch,
+    partitioning: Partitioning,
+    config: &LayoutBenchConfig,
+    key_values: &Option<Vec<KeyValue>>,
+    dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let keys: Vec<String> = match partitioning {
+        Partitioning::Single => {
+            let file = dir.join("part-0.parquet");
+            write_file(batch, config.codec, key_values, &file)?;
+            return Ok(vec![file]);
+        }
+        Partitioning::BySensor => tall_column::<StringArray>(batch, "sensor_type")?
+            .iter()
+            .map(|sensor_type| format!("sensor_type={}", sensor_type.unwrap_or_default()))
+            .collect(),
+        Partitioning::ByTime => tall_column::<UInt64Array>(batch, "time_since_launch_ms")?
+            .values()
+            .iter()
+            .map(|ms| format!("window={:05}", ms / config.partition_window_ms))
+            .collect(),
+    };
+
+    // Sort by partition so each one is a contiguous slice
+    let keys = StringArray::from(keys);
+    let indices = sort_to_indices(&keys, None, None)
+        .export_context(SINK, || "Failed to sort rows into partitions")?;
+    let sorted = take_record_batch(batch, &indices)
+        .export_context(SINK, || "Failed to sort rows into partitions")?;
+    let sorted_keys: Vec<&str> = indices
+        .values()
+        .iter()
+        .map(|idx| keys.value(*idx as usize))
+        .collect();
+
+    let mut files = Vec::new();
+    let mut start = 0;
+    while start < sorted_keys.len() {
+        let key = sorted_keys[start];
+        let end = start
+            + sorted_keys[start..]
+                .iter()
+                .take_while(|other| **other == key)
+                .count();
+        let file = dir.join(key).join("part-0.parquet");
+        write_file(
+            &sorted.slice(start, end - start),
+            config.codec,
+            key_values,
+            &file,
+        )?;
+        files.push(file);
+        start = end;
+    }
+    Ok(files)
+}
+
+fn write_file(
+    batch: &RecordBatch,
+    codec: Codec,
+    key_values: &Option<Vec<KeyValue>>,
+    path: &Path,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .io_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let file = File::create(path).io_context(|| format!("Failed to create {}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(codec.compression())
+        .set_key_value_metadata(key_values.clone())
+        .build();
+    let schema: SchemaRef = batch.schema();
+    let mut writer = ArrowWriter::try_new(file, schema, Some(props))
+        .export_context(SINK, || "Failed to create arrow writer")?;
+    writer
+        .write(batch)
+        .export_context(SINK, || "Failed to write record batch")?;
+    writer
+        .close()
+        .export_context(SINK, || "Failed to close Parquet writer")?;
+    Ok(())
+}
+
+pub fn print_layout_table(results: &[LayoutResult], pivot: Duration) {
+    println!(
+        "{:<6}  {:<10}  {:>7}  {:>13}  {:>15}  {:>10}",
+        "layout", "partitions", "files", "rows", "bytes", "write_ms"
+    );
+    for result in results {
+        println!(
+            "{:<6}  {:<10}  {:>7}  {:>13}  {:>15}  {:>10.1}",
+            result.layout.name(),
+            result.partitioning.name(),
+            result.files,
+            result.rows.to_formatted_string(&Locale::en),
+            result.bytes.to_formatted_string(&Locale::en),
+            result.write.as_secs_f64() * 1000.0
+        );
+    }
+    println!(
+        "Pivoting to wide took {:.1} ms on top of the wide write times",
+        pivot.as_secs_f64() * 1000.0
+    );
+}
+
+pub fn write_layout_report(input: &Path, results: &[LayoutResult], report: &Path) -> Result<()> {
+    let mut csv = String::from("input,layout,partitions,files,rows,bytes,write_ms\n");
+    for result in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{:.3}",
+            quote(&input.display().to_string()),
+            result.layout.name(),
+            result.partitioning.name(),
+            result.files,
+            result.rows,
+            result.bytes,
+            result.write.as_secs_f64() * 1000.0
+        );
+    }
+    std::fs::write(report, csv).io_context(|| format!("Failed to write {}", report.display()))?;
+    info!("Layout report written to {}", report.display());
+    Ok(())
+}
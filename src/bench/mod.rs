@@ -0,0 +1,83 @@
+mod codecs;
+mod layouts;
+mod read;
+#[cfg(feature = "query")]
+mod read_datafusion;
+
+pub use codecs::*;
+pub use layouts::*;
+pub use read::*;
+#[cfg(feature = "query")]
+pub use read_datafusion::*;
+
+use crate::error::{ExportContext, IoContext, Result};
+use arrow::record_batch::RecordBatch;
+use arrow_schema::SchemaRef;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::metadata::KeyValue;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+// Sink name reported in export errors
+const SINK: &str = "benchmark parquet";
+
+// A run read fully into memory, so every variant is written from identical data
+pub struct LoadedDataset {
+    pub schema: SchemaRef,
+    pub key_values: Option<Vec<KeyValue>>,
+    pub batches: Vec<RecordBatch>,
+}
+
+impl LoadedDataset {
+    pub fn read(path: &Path) -> Result<Self> {
+        let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+            .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+        let key_values = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .cloned();
+        let schema = builder.schema().clone();
+        let batches = builder
+            .build()
+            .export_context(SINK, || format!("Failed to read {}", path.display()))?
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+        Ok(Self {
+            schema,
+            key_values,
+            batches,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    // Size of the decoded columns, what the compression ratio is measured against
+    pub fn memory_bytes(&self) -> usize {
+        self.batches
+            .iter()
+            .map(|batch| batch.get_array_memory_size())
+            .sum()
+    }
+}
+
+// Time to decode every row of the file, the read side of each comparison
+fn time_full_read(path: &Path) -> Result<Duration> {
+    let start = Instant::now();
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|builder| builder.build())
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+    for batch in reader {
+        batch.export_context(SINK, || format!("Failed to read {}", path.display()))?;
+    }
+    Ok(start.elapsed())
+}
+
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
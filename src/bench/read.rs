@@ -0,0 +1,402 @@
+use super::{SINK, quote};
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use arrow::array::{Array, ArrayRef, BooleanArray, StringArray, UInt64Array};
+use arrow::compute::and;
+use arrow::compute::kernels::cmp::{eq, gt_eq, lt};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use num_format::{Locale, ToFormattedString};
+use parquet::arrow::ProjectionMask;
+use parquet::arrow::arrow_reader::statistics::StatisticsConverter;
+use parquet::arrow::arrow_reader::{ArrowPredicateFn, ParquetRecordBatchReaderBuilder, RowFilter};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// Queries a consumer of the data typically runs. Each prunes row groups on their statistics
+// and filters rows while decoding, like a query engine pushing the predicate down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReadQuery {
+    // Every column of every row
+    FullScan,
+    // All readings of one sensor_type
+    Sensor(String),
+    // Readings with time since launch in [start, end)
+    TimeSlice { start_ms: u64, end_ms: u64 },
+}
+
+impl ReadQuery {
+    pub fn description(&self) -> String {
+        match self {
+            ReadQuery::FullScan => "full scan".to_string(),
+            ReadQuery::Sensor(sensor) => format!("sensor_type = {sensor}"),
+            ReadQuery::TimeSlice { start_ms, end_ms } => format!("time {start_ms}..{end_ms}ms"),
+        }
+    }
+}
+
+// What ran the queries
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadEngine {
+    // The parquet reader with its own pruning and row filters
+    Parquet,
+    // SQL on a DataFusion session, in builds with the query feature
+    DataFusion,
+}
+
+impl ReadEngine {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReadEngine::Parquet => "parquet",
+            ReadEngine::DataFusion => "datafusion",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReadBenchConfig {
+    // Sensor for the filter query. Defaults to the first one in the data
+    pub sensor: Option<String>,
+    // Slice for the time range query. Defaults to the middle tenth of the run
+    pub time_slice_ms: Option<(u64, u64)>,
+    // Times each query runs over every file
+    pub repeat: usize,
+}
+
+// How one query did over a dataset
+#[derive(Debug)]
+pub struct ReadResult {
+    pub dataset: PathBuf,
+    pub engine: ReadEngine,
+    pub query: ReadQuery,
+    pub files: usize,
+    pub rows: usize,
+    pub row_groups_read: usize,
+    pub row_groups_total: usize,
+    pub latencies: Vec<Duration>,
+}
+
+impl ReadResult {
+    fn latency_ms(&self, quantile: f64) -> f64 {
+        let mut latencies = self.latencies.clone();
+        latencies.sort();
+        let idx = ((latencies.len() - 1) as f64 * quantile).round() as usize;
+        latencies[idx].as_secs_f64() * 1000.0
+    }
+}
+
+// What a query touched in one file
+#[derive(Debug, Default)]
+struct ScanStats {
+    rows: usize,
+    row_groups_read: usize,
+    row_groups_total: usize,
+}
+
+// Runs the queries against each dataset, a parquet file or a directory of them like the
+// partitioned output of bench-layouts. Files are read one after the other on this thread
+pub fn bench_read(datasets: &[PathBuf], config: &ReadBenchConfig) -> Result<Vec<ReadResult>> {
+    let mut results = Vec::new();
+    for dataset in datasets {
+        let files = parquet_files(dataset)?;
+        for query in dataset_queries(&files, dataset, config)? {
+            let mut latencies = Vec::with_capacity(config.repeat);
+            let mut total = ScanStats::default();
+            for _ in 0..config.repeat.max(1) {
+                let start = Instant::now();
+                total = ScanStats::default();
+                for file in &files {
+                    let stats = run_query(file, &query)?;
+                    total.rows += stats.rows;
+                    total.row_groups_read += stats.row_groups_read;
+                    total.row_groups_total += stats.row_groups_total;
+                }
+                latencies.push(start.elapsed());
+            }
+            info!(
+                "{} on {}: {} rows in {:?}",
+                query.description(),
+                dataset.display(),
+                total.rows,
+                latencies.iter().min()
+            );
+            results.push(ReadResult {
+                dataset: dataset.clone(),
+                engine: ReadEngine::Parquet,
+                query,
+                files: files.len(),
+                rows: total.rows,
+                row_groups_read: total.row_groups_read,
+                row_groups_total: total.row_groups_total,
+                latencies,
+            });
+        }
+    }
+    Ok(results)
+}
+
+// The queries to run on a dataset's files, filling in the sensor and slice from the data when
+// they weren't given
+pub(super) fn dataset_queries(
+    files: &[PathBuf],
+    dataset: &Path,
+    config: &ReadBenchConfig,
+) -> Result<[ReadQuery; 3]> {
+    if files.is_empty() {
+        return Err(TelemetryError::config(format!(
+            "No parquet files in {}",
+            dataset.display()
+        )));
+    }
+    let sensor = match &config.sensor {
+        Some(sensor) => sensor.clone(),
+        None => first_sensor(&files[0])?,
+    };
+    let (start_ms, end_ms) = match config.time_slice_ms {
+        Some(slice) => slice,
+        None => {
+            let last_ms = last_time_since_launch(files)?;
+            (last_ms * 45 / 100, last_ms * 55 / 100)
+        }
+    };
+    Ok([
+        ReadQuery::FullScan,
+        ReadQuery::Sensor(sensor),
+        ReadQuery::TimeSlice { start_ms, end_ms },
+    ])
+}
+
+// Row groups over all the files, what pruning is measured against
+#[cfg(feature = "query")]
+pub(super) fn row_group_count(files: &[PathBuf]) -> Result<usize> {
+    files.iter().try_fold(0, |count, file| {
+        Ok(count + open(file)?.metadata().num_row_groups())
+    })
+}
+
+// The file itself, or every .parquet file under the directory
+pub(super) fn parquet_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let entries =
+        std::fs::read_dir(path).io_context(|| format!("Failed to read {}", path.display()))?;
+    for entry in entries {
+        let entry = entry.io_context(|| format!("Failed to read {}", path.display()))?;
+        let entry = entry.path();
+        if entry.is_dir() {
+            files.extend(parquet_files(&entry)?);
+        } else if entry.extension().is_some_and(|e| e == "parquet") {
+            files.push(entry);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn open(path: &Path) -> Result<ParquetRecordBatchReaderBuilder<File>> {
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    ParquetRecordBatchReaderBuilder::try_new(file)
+        .export_context(SINK, || format!("Failed to read {}", path.display()))
+}
+
+fn first_sensor(path: &Path) -> Result<String> {
+    let builder = open(path)?;
+    let mask = ProjectionMask::columns(builder.parquet_schema(), ["sensor_type"]);
+    let first = builder
+        .with_projection(mask)
+        .with_batch_size(1)
+        .build()
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?
+        .next()
+        .transpose()
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+    first
+        .as_ref()
+        .and_then(|batch| batch.column_by_name("sensor_type"))
+        .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+        .filter(|sensor_types| !sensor_types.is_empty())
+        .map(|sensor_types| sensor_types.value(0).to_string())
+        .ok_or_else(|| {
+            TelemetryError::config(format!(
+                "{} has no sensor_type readings. Pass --sensor",
+                path.display()
+            ))
+        })
+}
+
+// Largest time since launch from the row group statistics, without reading any data
+fn last_time_since_launch(files: &[PathBuf]) -> Result<u64> {
+    let mut last = 0;
+    for file in files {
+        let builder = open(file)?;
+        let (_, maxes) = column_bounds(&builder, "time_since_launch_ms")?;
+        if let Some(maxes) = maxes.as_any().downcast_ref::<UInt64Array>() {
+            last = maxes.iter().flatten().fold(last, u64::max);
+        }
+    }
+    Ok(last)
+}
+
+// Per row group min and max of the column
+fn column_bounds(
+    builder: &ParquetRecordBatchReaderBuilder<File>,
+    column: &str,
+) -> Result<(ArrayRef, ArrayRef)> {
+    let converter =
+        StatisticsConverter::try_new(column, builder.schema(), builder.parquet_schema())
+            .export_context(SINK, || format!("No {column} column to filter on"))?;
+    let row_groups = builder.metadata().row_groups();
+    let mins = converter
+        .row_group_mins(row_groups.iter())
+        .export_context(SINK, || format!("Failed to read {column} statistics"))?;
+    let maxes = converter
+        .row_group_maxes(row_groups.iter())
+        .export_context(SINK, || format!("Failed to read {column} statistics"))?;
+    Ok((mins, maxes))
+}
+
+// Row groups whose statistics say they might hold matches. Missing statistics keep the group
+fn candidate_row_groups(
+    mins: &ArrayRef,
+    maxes: &ArrayRef,
+    might_match: impl Fn(&ArrayRef, &ArrayRef, usize) -> bool,
+) -> Vec<usize> {
+    (0..mins.len())
+        .filter(|idx| mins.is_null(*idx) || maxes.is_null(*idx) || might_match(mins, maxes, *idx))
+        .collect()
+}
+
+fn run_query(path: &Path, query: &ReadQuery) -> Result<ScanStats> {
+    let builder = open(path)?;
+    let row_groups_total = builder.metadata().num_row_groups();
+
+    let (builder, row_groups_read) = match query {
+        ReadQuery::FullScan => (builder, row_groups_total),
+        ReadQuery::Sensor(sensor) => {
+            let (mins, maxes) = column_bounds(&builder, "sensor_type")?;
+            let row_groups = candidate_row_groups(&mins, &maxes, |mins, maxes, idx| {
+                match (
+                    mins.as_any().downcast_ref::<StringArray>(),
+                    maxes.as_any().downcast_ref::<StringArray>(),
+                ) {
+                    (Some(mins), Some(maxes)) => {
+                        mins.value(idx) <= sensor.as_str() && sensor.as_str() <= maxes.value(idx)
+                    }
+                    _ => true,
+                }
+            });
+            let mask = ProjectionMask::columns(builder.parquet_schema(), ["sensor_type"]);
+            let sensor = StringArray::new_scalar(sensor.clone());
+            let predicate =
+                ArrowPredicateFn::new(mask, move |batch: RecordBatch| eq(batch.column(0), &sensor));
+            let read = row_groups.len();
+            let builder = builder
+                .with_row_groups(row_groups)
+                .with_row_filter(RowFilter::new(vec![Box::new(predicate)]));
+            (builder, read)
+        }
+        ReadQuery::TimeSlice { start_ms, end_ms } => {
+            let (start_ms, end_ms) = (*start_ms, *end_ms);
+            let (mins, maxes) = column_bounds(&builder, "time_since_launch_ms")?;
+            let row_groups = candidate_row_groups(&mins, &maxes, |mins, maxes, idx| {
+                match (
+                    mins.as_any().downcast_ref::<UInt64Array>(),
+                    maxes.as_any().downcast_ref::<UInt64Array>(),
+                ) {
+                    (Some(mins), Some(maxes)) => {
+                        mins.value(idx) < end_ms && maxes.value(idx) >= start_ms
+                    }
+                    _ => true,
+                }
+            });
+            let mask = ProjectionMask::columns(builder.parquet_schema(), ["time_since_launch_ms"]);
+            let start = UInt64Array::new_scalar(start_ms);
+            let end = UInt64Array::new_scalar(end_ms);
+            let predicate = ArrowPredicateFn::new(
+                mask,
+                move |batch: RecordBatch| -> std::result::Result<BooleanArray, ArrowError> {
+                    let since_launch = batch.column(0);
+                    and(&gt_eq(since_launch, &start)?, &lt(since_launch, &end)?)
+                },
+            );
+            let read = row_groups.len();
+            let builder = builder
+                .with_row_groups(row_groups)
+                .with_row_filter(RowFilter::new(vec![Box::new(predicate)]));
+            (builder, read)
+        }
+    };
+
+    let reader = builder
+        .build()
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+    let mut rows = 0;
+    for batch in reader {
+        rows += batch
+            .export_context(SINK, || format!("Failed to read {}", path.display()))?
+            .num_rows();
+    }
+    Ok(ScanStats {
+        rows,
+        row_groups_read,
+        row_groups_total,
+    })
+}
+
+pub fn print_read_table(results: &[ReadResult]) {
+    println!(
+        "{:<40}  {:<10}  {:<24}  {:>5}  {:>13}  {:>10}  {:>9}  {:>9}  {:>9}",
+        "dataset",
+        "engine",
+        "query",
+        "files",
+        "rows",
+        "row_groups",
+        "min_ms",
+        "median_ms",
+        "max_ms"
+    );
+    for result in results {
+        println!(
+            "{:<40}  {:<10}  {:<24}  {:>5}  {:>13}  {:>10}  {:>9.1}  {:>9.1}  {:>9.1}",
+            result.dataset.display(),
+            result.engine.name(),
+            result.query.description(),
+            result.files,
+            result.rows.to_formatted_string(&Locale::en),
+            format!("{}/{}", result.row_groups_read, result.row_groups_total),
+            result.latency_ms(0.0),
+            result.latency_ms(0.5),
+            result.latency_ms(1.0)
+        );
+    }
+}
+
+pub fn write_read_report(results: &[ReadResult], report: &Path) -> Result<()> {
+    let mut csv = String::from(
+        "dataset,engine,query,files,rows,row_groups_read,row_groups_total,min_ms,median_ms,max_ms\n",
+    );
+    for result in results {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{:.3},{:.3},{:.3}",
+            quote(&result.dataset.display().to_string()),
+            result.engine.name(),
+            quote(&result.query.description()),
+            result.files,
+            result.rows,
+            result.row_groups_read,
+            result.row_groups_total,
+            result.latency_ms(0.0),
+            result.latency_ms(0.5),
+            result.latency_ms(1.0)
+        );
+    }
+    std::fs::write(report, csv).io_context(|| format!("Failed to write {}", report.display()))?;
+    info!("Read report written to {}", report.display());
+    Ok(())
+}
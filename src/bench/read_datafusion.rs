@@ -0,0 +1,159 @@
+use super::SINK;
+use super::read::{
+    ReadBenchConfig, ReadEngine, ReadQuery, ReadResult, dataset_queries, parquet_files,
+    row_group_count,
+};
+use crate::error::{ExportContext, Result, TelemetryError};
+use datafusion::physical_plan::{ExecutionPlan, collect};
+use datafusion::prelude::{ParquetReadOptions, SessionContext};
+use std::path::PathBuf;
+use std::time::Instant;
+use tracing::info;
+
+// Table each dataset is registered as
+const TABLE: &str = "readings";
+
+// DataFusion's counts of row groups its statistics and bloom filters ruled out
+const PRUNED_METRICS: [&str; 2] = [
+    "row_groups_pruned_statistics",
+    "row_groups_pruned_bloom_filter",
+];
+
+// The bench-read queries as SQL on a DataFusion session, each dataset's files as one table. Row
+// groups read are the ones its pruning left, so the two engines line up in the report
+pub async fn bench_read_datafusion(
+    datasets: &[PathBuf],
+    config: &ReadBenchConfig,
+) -> Result<Vec<ReadResult>> {
+    let mut results = Vec::new();
+    for dataset in datasets {
+        let files = parquet_files(dataset)?;
+        let queries = dataset_queries(&files, dataset, config)?;
+        let row_groups_total = row_group_count(&files)?;
+
+        let context = SessionContext::new();
+        let paths: Vec<String> = files
+            .iter()
+            .map(|file| file.to_string_lossy().into_owned())
+            .collect();
+        let table = context
+            .read_parquet(paths, ParquetReadOptions::default())
+            .await
+            .export_context(SINK, || format!("Failed to read {}", dataset.display()))?;
+        context
+            .register_table(TABLE, table.into_view())
+            .export_context(SINK, || format!("Failed to read {}", dataset.display()))?;
+
+        for query in queries {
+            let sql = query_sql(&query);
+            let mut latencies = Vec::with_capacity(config.repeat);
+            let mut rows = 0;
+            let mut row_groups_pruned = 0;
+            for _ in 0..config.repeat.max(1) {
+                let start = Instant::now();
+                let plan = context
+                    .sql(&sql)
+                    .await
+                    .map_err(|e| TelemetryError::config(e.to_string()))?
+                    .create_physical_plan()
+                    .await
+                    .export_context(SINK, || format!("Failed to plan {sql}"))?;
+                let batches = collect(plan.clone(), context.task_ctx())
+                    .await
+                    .export_context(SINK, || format!("Failed to run {sql}"))?;
+                latencies.push(start.elapsed());
+                rows = batches.iter().map(|batch| batch.num_rows()).sum();
+                row_groups_pruned = pruned_row_groups(plan.as_ref());
+            }
+            info!(
+                "{} on {} with DataFusion: {} rows in {:?}",
+                query.description(),
+                dataset.display(),
+                rows,
+                latencies.iter().min()
+            );
+            results.push(ReadResult {
+                dataset: dataset.clone(),
+                engine: ReadEngine::DataFusion,
+                query,
+                files: files.len(),
+                rows,
+                row_groups_read: row_groups_total.saturating_sub(row_groups_pruned),
+                row_groups_total,
+                latencies,
+            });
+        }
+    }
+    Ok(results)
+}
+
+// Every column of the matching rows, like the parquet reader returns them
+fn query_sql(query: &ReadQuery) -> String {
+    match query {
+        ReadQuery::FullScan => format!("SELECT * FROM {TABLE}"),
+        ReadQuery::Sensor(sensor) => format!(
+            "SELECT * FROM {TABLE} WHERE sensor_type = '{}'",
+            sensor.replace('\'', "''")
+        ),
+        ReadQuery::TimeSlice { start_ms, end_ms } => format!(
+            "SELECT * FROM {TABLE} WHERE time_since_launch_ms >= {start_ms} \
+            AND time_since_launch_ms < {end_ms}"
+        ),
+    }
+}
+
+// Row groups pruned anywhere in the executed plan
+fn pruned_row_groups(plan: &dyn ExecutionPlan) -> usize {
+    let pruned: usize = plan.metrics().map_or(0, |metrics| {
+        PRUNED_METRICS
+            .iter()
+            .filter_map(|name| metrics.sum_by_name(name))
+            .map(|value| value.as_usize())
+            .sum()
+    });
+    pruned
+        + plan
+            .children()
+            .into_iter()
+            .map(|child| pruned_row_groups(child.as_ref()))
+            .sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::bench_read;
+    use crate::exporters::{ParquetConfig, ParquetExporter};
+    use crate::generators::TelemetryGenerator;
+    use crate::models::{SampleRate, TelemetryConfig};
+
+    #[tokio::test]
+    async fn both_engines_find_the_same_rows() {
+        let config = TelemetryConfig {
+            duration: 4,
+            sample_rate_hz: SampleRate::from_hz(10),
+            ..TelemetryConfig::default()
+        };
+        let dataset = TelemetryGenerator::new(config).generate(true).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        ParquetExporter::new(ParquetConfig::default())
+            .export(&dataset, dir.path(), "run")
+            .unwrap();
+
+        let datasets = [dir.path().join("run.parquet")];
+        let config = ReadBenchConfig {
+            sensor: None,
+            time_slice_ms: None,
+            repeat: 1,
+        };
+        let parquet = bench_read(&datasets, &config).unwrap();
+        let datafusion = bench_read_datafusion(&datasets, &config).await.unwrap();
+        assert_eq!(parquet.len(), datafusion.len());
+        for (parquet, datafusion) in parquet.iter().zip(&datafusion) {
+            assert_eq!(parquet.query, datafusion.query);
+            assert_eq!(parquet.rows, datafusion.rows);
+            assert_eq!(parquet.row_groups_total, datafusion.row_groups_total);
+        }
+        assert_eq!(datafusion[0].rows, dataset.readings.len());
+    }
+}
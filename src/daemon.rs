@@ -0,0 +1,439 @@
+use chrono::{DateTime, Utc};
+use num_format::{Locale, ToFormattedString};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use telemetry_generator::error::{IoContext, Result, TelemetryError};
+use tracing::{info, warn};
+
+// `start` runs the stream command and the HTTP API in the background. The daemon keeps its pid in
+// `{run_dir}/daemon.pid` while it's up and rewrites `{run_dir}/daemon.json` every
+// `STATE_INTERVAL` for `status` to read. `stop` signals the pid and waits for it to go
+pub const PID_FILE: &str = "daemon.pid";
+pub const STATE_FILE: &str = "daemon.json";
+// Where the background process logs to
+pub const LOG_FILE: &str = "daemon.log";
+
+pub const STATE_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long `start` waits for the daemon to write its pid before giving up on it
+const START_TIMEOUT: Duration = Duration::from_secs(5);
+// How long `stop` waits for the daemon to close its sink and exit
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Counters the streaming side bumps as it goes, read back into the state file
+#[derive(Debug, Default)]
+pub struct LiveStats {
+    readings: AtomicU64,
+    flights: AtomicU64,
+    requests: AtomicU64,
+    jobs_queued: AtomicUsize,
+    jobs_running: AtomicUsize,
+    jobs_finished: AtomicU64,
+    flight: Mutex<String>,
+    // Last sink failure, cleared by the next successful write
+    sink_error: Mutex<Option<String>>,
+}
+
+impl LiveStats {
+    pub fn launched(&self, launch_id: &str) {
+        self.flights.fetch_add(1, Ordering::Relaxed);
+        *self.flight.lock().unwrap_or_else(|e| e.into_inner()) = launch_id.to_string();
+    }
+
+    pub fn sent(&self, readings: usize) {
+        self.readings.fetch_add(readings as u64, Ordering::Relaxed);
+        let mut sink_error = self.sink_error.lock().unwrap_or_else(|e| e.into_inner());
+        if sink_error.is_some() {
+            info!("Sink recovered");
+            *sink_error = None;
+        }
+    }
+
+    // An API request came in
+    pub fn served(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn job_queued(&self) {
+        self.jobs_queued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn job_started(&self) {
+        self.jobs_queued.fetch_sub(1, Ordering::Relaxed);
+        self.jobs_running.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn job_finished(&self) {
+        self.jobs_running.fetch_sub(1, Ordering::Relaxed);
+        self.jobs_finished.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self, error: String) {
+        *self.sink_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+    }
+
+    // The counters as they stand, for `GET /status`
+    pub fn snapshot(&self) -> LiveSnapshot {
+        LiveSnapshot {
+            flight: self
+                .flight
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+            flights: self.flights.load(Ordering::Relaxed),
+            readings_sent: self.readings.load(Ordering::Relaxed),
+            api_requests: self.requests.load(Ordering::Relaxed),
+            jobs_queued: self.jobs_queued.load(Ordering::Relaxed),
+            jobs_running: self.jobs_running.load(Ordering::Relaxed),
+            jobs_finished: self.jobs_finished.load(Ordering::Relaxed),
+            sink_error: self
+                .sink_error
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct LiveSnapshot {
+    pub flight: String,
+    pub flights: u64,
+    pub readings_sent: u64,
+    pub api_requests: u64,
+    pub jobs_queued: usize,
+    pub jobs_running: usize,
+    pub jobs_finished: u64,
+    pub sink_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DaemonState {
+    pub pid: u32,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Set once the daemon has shut down
+    pub stopped_at: Option<DateTime<Utc>>,
+    // Sink being streamed to, if any
+    pub target: Option<String>,
+    // Address the API is served on, if any
+    pub listen: Option<String>,
+    pub api_requests: u64,
+    // Jobs from `POST /jobs`, see `jobs.rs`
+    #[serde(default)]
+    pub jobs_queued: usize,
+    #[serde(default)]
+    pub jobs_running: usize,
+    #[serde(default)]
+    pub jobs_finished: u64,
+    // Launch id of the flight being streamed
+    pub flight: String,
+    pub flights: u64,
+    pub readings_sent: u64,
+    // Over the last update
+    pub readings_per_sec: f64,
+    pub sink_error: Option<String>,
+}
+
+impl DaemonState {
+    pub fn new(target: Option<String>, listen: Option<String>) -> Self {
+        let now = Utc::now();
+        Self {
+            pid: std::process::id(),
+            started_at: now,
+            updated_at: now,
+            stopped_at: None,
+            target,
+            listen,
+            api_requests: 0,
+            jobs_queued: 0,
+            jobs_running: 0,
+            jobs_finished: 0,
+            flight: String::new(),
+            flights: 0,
+            readings_sent: 0,
+            readings_per_sec: 0.0,
+            sink_error: None,
+        }
+    }
+
+    pub fn update(&mut self, stats: &LiveStats) {
+        let now = Utc::now();
+        let readings = stats.readings.load(Ordering::Relaxed);
+        let elapsed_s = (now - self.updated_at).num_milliseconds() as f64 / 1000.0;
+        if elapsed_s > 0.0 {
+            self.readings_per_sec = (readings - self.readings_sent) as f64 / elapsed_s;
+        }
+        self.updated_at = now;
+        self.readings_sent = readings;
+        self.flights = stats.flights.load(Ordering::Relaxed);
+        self.api_requests = stats.requests.load(Ordering::Relaxed);
+        self.jobs_queued = stats.jobs_queued.load(Ordering::Relaxed);
+        self.jobs_running = stats.jobs_running.load(Ordering::Relaxed);
+        self.jobs_finished = stats.jobs_finished.load(Ordering::Relaxed);
+        self.flight = stats
+            .flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        self.sink_error = stats
+            .sink_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+    }
+
+    // Written to a temporary file and renamed so `status` never reads half of it
+    pub fn write(&self, run_dir: &Path) -> Result<()> {
+        let path = run_dir.join(STATE_FILE);
+        let json = serde_json::to_string_pretty(self).map_err(|e| {
+            TelemetryError::config(format!("Failed to serialize daemon state: {e}"))
+        })?;
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, json)
+            .and_then(|()| std::fs::rename(&temp, &path))
+            .io_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn load(run_dir: &Path) -> Result<Option<Self>> {
+        let path = run_dir.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json = std::fs::read_to_string(&path)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| TelemetryError::config(format!("{}: {e}", path.display())))
+    }
+}
+
+// Held by the running daemon, removing the pid file when it goes
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    // Refuses while another daemon is running from the same directory. A pid file left behind
+    // by one that died is replaced
+    pub fn claim(run_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(run_dir)
+            .io_context(|| format!("Failed to create {}", run_dir.display()))?;
+        if let Some(pid) = running_pid(run_dir)? {
+            return Err(TelemetryError::config(format!(
+                "A daemon is already running from {} as pid {pid}",
+                run_dir.display()
+            )));
+        }
+        let path = run_dir.join(PID_FILE);
+        std::fs::write(&path, format!("{}\n", std::process::id()))
+            .io_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(Self { path })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove {}: {e}", self.path.display());
+        }
+    }
+}
+
+// Start this executable again with `args` and `--foreground`, detached from the terminal and
+// logging to `daemon.log`. Returns its pid once it has claimed the pid file
+pub fn spawn(run_dir: &Path, args: &[String]) -> Result<u32> {
+    std::fs::create_dir_all(run_dir)
+        .io_context(|| format!("Failed to create {}", run_dir.display()))?;
+    if let Some(pid) = running_pid(run_dir)? {
+        return Err(TelemetryError::config(format!(
+            "A daemon is already running from {} as pid {pid}",
+            run_dir.display()
+        )));
+    }
+    let log_path = run_dir.join(LOG_FILE);
+    let log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+        .io_context(|| format!("Failed to open {}", log_path.display()))?;
+    let exe = std::env::current_exe().io_context(|| "Failed to find the executable")?;
+
+    let mut command = Command::new(exe);
+    command
+        .args(args)
+        .arg("--foreground")
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().io_context(|| "Failed to share the log")?)
+        .stderr(log);
+    detach(&mut command);
+    let mut child = command
+        .spawn()
+        .io_context(|| "Failed to start the daemon")?;
+
+    let started = Instant::now();
+    while started.elapsed() < START_TIMEOUT {
+        if let Some(status) = child
+            .try_wait()
+            .io_context(|| "Failed to check on the daemon")?
+        {
+            return Err(TelemetryError::config(format!(
+                "The daemon exited with {status}, see {}",
+                log_path.display()
+            )));
+        }
+        if read_pid(run_dir)? == Some(child.id()) {
+            return Ok(child.id());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Err(TelemetryError::config(format!(
+        "The daemon didn't start within {START_TIMEOUT:?}, see {}",
+        log_path.display()
+    )))
+}
+
+// Ask the daemon to finish and wait for it to
+pub fn stop(run_dir: &Path) -> Result<u32> {
+    let Some(pid) = running_pid(run_dir)? else {
+        return Err(TelemetryError::config(format!(
+            "No daemon is running from {}",
+            run_dir.display()
+        )));
+    };
+    terminate(pid).io_context(|| format!("Failed to signal pid {pid}"))?;
+    let started = Instant::now();
+    while started.elapsed() < STOP_TIMEOUT {
+        if !alive(pid) {
+            return Ok(pid);
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Err(TelemetryError::config(format!(
+        "Pid {pid} is still running after {STOP_TIMEOUT:?}"
+    )))
+}
+
+pub fn status(run_dir: &Path) -> Result<()> {
+    let pid = running_pid(run_dir)?;
+    let state = DaemonState::load(run_dir)?;
+    let Some(state) = state else {
+        println!("not running, no daemon has run from {}", run_dir.display());
+        return Ok(());
+    };
+
+    match pid {
+        Some(pid) => {
+            let uptime = (Utc::now() - state.started_at).num_seconds().max(0);
+            println!("running   pid {pid}, up {}", format_uptime(uptime));
+        }
+        None => {
+            let stopped = state.stopped_at.unwrap_or(state.updated_at);
+            println!("stopped   {}", stopped.format("%Y-%m-%d %H:%M:%S"));
+        }
+    }
+    if let Some(listen) = &state.listen {
+        println!(
+            "api       http://{listen}, {} requests",
+            state.api_requests.to_formatted_string(&Locale::en)
+        );
+        println!(
+            "jobs      {} queued, {} running, {} finished",
+            state.jobs_queued, state.jobs_running, state.jobs_finished
+        );
+    }
+    if let Some(target) = &state.target {
+        println!("target    {target}");
+        println!("flight    {} ({} flown)", state.flight, state.flights);
+        println!(
+            "readings  {} sent, {:.0}/s",
+            state.readings_sent.to_formatted_string(&Locale::en),
+            state.readings_per_sec
+        );
+        match &state.sink_error {
+            Some(error) => println!("sink      failing: {error}"),
+            None => println!("sink      healthy"),
+        }
+    }
+    if pid.is_some() {
+        let age = (Utc::now() - state.updated_at).num_milliseconds() as f64 / 1000.0;
+        println!("updated   {age:.1}s ago");
+    }
+    Ok(())
+}
+
+// Pid in the pid file, if that process is still alive
+fn running_pid(run_dir: &Path) -> Result<Option<u32>> {
+    Ok(read_pid(run_dir)?.filter(|pid| alive(*pid)))
+}
+
+fn read_pid(run_dir: &Path) -> Result<Option<u32>> {
+    let path = run_dir.join(PID_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(pid) => {
+            pid.trim().parse().map(Some).map_err(|_| {
+                TelemetryError::config(format!("{} doesn't hold a pid", path.display()))
+            })
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e).io_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+fn format_uptime(seconds: i64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m {seconds}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+// In its own process group so Ctrl-C in the starting terminal doesn't reach it
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0);
+}
+
+#[cfg(not(unix))]
+fn detach(_command: &mut Command) {}
+
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks the process exists and we may signal it
+    let signalled = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    signalled || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+// Without signals there's no checking, so trust the pid file
+#[cfg(not(unix))]
+fn alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> io::Result<()> {
+    // SAFETY: sending SIGTERM has no memory safety requirements
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn terminate(_pid: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "stop needs unix signals",
+    ))
+}
@@ -0,0 +1,78 @@
+use std::fmt::Display;
+use thiserror::Error;
+
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+pub type Result<T, E = TelemetryError> = std::result::Result<T, E>;
+
+// Failure categories for the generator, exporters and models, so callers can match on what went
+// wrong instead of parsing messages. The CLI wraps these in anyhow at the top level
+#[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)] // Reads better at match sites than Config, Io, ...
+pub enum TelemetryError {
+    // Bad settings, scripts or arguments
+    #[error("{0}")]
+    ConfigError(String),
+
+    // The simulation itself went wrong
+    #[error("{0}")]
+    GenerationError(String),
+
+    // Writing to a sink failed, e.g. parquet, influxdb or a plugin
+    #[error("{sink} export failed: {message}")]
+    ExportError {
+        sink: String,
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+
+    #[error("{message}")]
+    IoError {
+        message: String,
+        #[source]
+        source: std::io::Error,
+    },
+}
+
+impl TelemetryError {
+    pub fn config(message: impl Display) -> Self {
+        TelemetryError::ConfigError(message.to_string())
+    }
+
+    pub fn export(sink: impl Display, message: impl Display) -> Self {
+        TelemetryError::ExportError {
+            sink: sink.to_string(),
+            message: message.to_string(),
+            source: None,
+        }
+    }
+}
+
+// Like anyhow's `Context`, but producing the matching `TelemetryError` variant
+pub trait IoContext<T> {
+    fn io_context<M: Display>(self, message: impl FnOnce() -> M) -> Result<T>;
+}
+
+impl<T> IoContext<T> for std::result::Result<T, std::io::Error> {
+    fn io_context<M: Display>(self, message: impl FnOnce() -> M) -> Result<T> {
+        self.map_err(|source| TelemetryError::IoError {
+            message: message().to_string(),
+            source,
+        })
+    }
+}
+
+pub trait ExportContext<T> {
+    fn export_context<M: Display>(self, sink: &str, message: impl FnOnce() -> M) -> Result<T>;
+}
+
+impl<T, E: Into<BoxError>> ExportContext<T> for std::result::Result<T, E> {
+    fn export_context<M: Display>(self, sink: &str, message: impl FnOnce() -> M) -> Result<T> {
+        self.map_err(|source| TelemetryError::ExportError {
+            sink: sink.to_string(),
+            message: message().to_string(),
+            source: Some(source.into()),
+        })
+    }
+}
@@ -0,0 +1,184 @@
+mod montecarlo;
+mod plan;
+
+pub use montecarlo::*;
+pub use plan::*;
+
+use crate::history::{RunRecord, record_run};
+use crate::{Failure, GenerateReport, generate_to_parquet};
+use anyhow::{Result, anyhow};
+use num_format::{Locale, ToFormattedString};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use telemetry_generator::error::IoContext;
+use tracing::{error, info, info_span};
+
+// How one run of an experiment went
+#[derive(Debug)]
+struct RunOutcome {
+    name: String,
+    elapsed: Duration,
+    result: Result<GenerateReport>,
+}
+
+// Runs every run of the plan, `parallel` at a time, then logs a summary and writes it to
+// `{output_dir}/{plan name}/experiment.csv`. A failed run doesn't stop the others
+pub fn run_experiment(plan: ExperimentPlan, output_dir: &Path) -> Result<()> {
+    let ExperimentPlan {
+        name,
+        parallel,
+        runs,
+    } = plan;
+    let start_time = Instant::now();
+    let outcomes = run_all(&name, parallel, runs, output_dir);
+
+    log_summary(&outcomes);
+    let report_dir = output_dir.join(&name);
+    write_report(&outcomes, &report_dir)?;
+
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    info!(
+        "Experiment {} finished in {:.2?}s",
+        name,
+        start_time.elapsed().as_secs_f64()
+    );
+    if failed > 0 {
+        return Err(anyhow!(
+            "{failed} of {} runs in experiment {name} failed. See {}",
+            outcomes.len(),
+            report_dir.join("experiment.csv").display()
+        ));
+    }
+    Ok(())
+}
+
+// Execute the runs `parallel` at a time, returning their outcomes in run order
+fn run_all(
+    name: &str,
+    parallel: usize,
+    mut runs: Vec<PlannedRun>,
+    output_dir: &Path,
+) -> Vec<RunOutcome> {
+    let workers = parallel.min(runs.len());
+    info!(
+        "Running experiment {} with {} runs, {} at a time",
+        name,
+        runs.len(),
+        workers
+    );
+    if workers > 1 {
+        // Progress bars from parallel runs would draw over each other
+        for run in &mut runs {
+            run.args.disable_progress = true;
+        }
+    }
+
+    let next_run = AtomicUsize::new(0);
+    let mut outcomes: Vec<(usize, RunOutcome)> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut outcomes = Vec::new();
+                    loop {
+                        let idx = next_run.fetch_add(1, Ordering::Relaxed);
+                        let Some(run) = runs.get(idx) else {
+                            break outcomes;
+                        };
+                        outcomes.push((idx, execute(name, run, output_dir)));
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("Experiment worker panicked"))
+            .collect()
+    });
+    outcomes.sort_by_key(|(idx, _)| *idx);
+    outcomes.into_iter().map(|(_, outcome)| outcome).collect()
+}
+
+fn execute(experiment: &str, run: &PlannedRun, output_dir: &Path) -> RunOutcome {
+    let span = info_span!("run", name = %run.name);
+    let _entered = span.enter();
+    info!("Starting run {}", run.name);
+
+    let start_time = Instant::now();
+    let result = generate_to_parquet(&run.args, None);
+    match &result {
+        Ok(report) => {
+            let mut command = vec!["generate".to_string()];
+            command.extend(run.flags.iter().cloned());
+            let experiment = Some(format!("{experiment}/{}", run.name));
+            let record =
+                RunRecord::new(&run.args, command, experiment, report, start_time.elapsed());
+            record_run(output_dir, &record);
+        }
+        Err(e) => error!("Run {} failed: {:#}", run.name, e),
+    }
+    RunOutcome {
+        name: run.name.clone(),
+        elapsed: start_time.elapsed(),
+        result,
+    }
+}
+
+fn log_summary(outcomes: &[RunOutcome]) {
+    for outcome in outcomes {
+        match &outcome.result {
+            Ok(report) => info!(
+                "{:<24} ok      {:>15} readings in {:>8.2}s  {}",
+                outcome.name,
+                report.readings.to_formatted_string(&Locale::en),
+                outcome.elapsed.as_secs_f64(),
+                report.parquet_file().display()
+            ),
+            Err(e) => error!(
+                "{:<24} failed  exit code {} after {:.2}s: {:#}",
+                outcome.name,
+                Failure::of(e) as u8,
+                outcome.elapsed.as_secs_f64(),
+                e
+            ),
+        }
+    }
+}
+
+fn write_report(outcomes: &[RunOutcome], report_dir: &Path) -> Result<()> {
+    let mut csv = String::from("run,status,exit_code,readings,elapsed_s,output_file,error\n");
+    for outcome in outcomes {
+        let elapsed_s = outcome.elapsed.as_secs_f64();
+        let _ = match &outcome.result {
+            Ok(report) => writeln!(
+                csv,
+                "{},ok,0,{},{:.3},{},",
+                outcome.name,
+                report.readings,
+                elapsed_s,
+                quote(&report.parquet_file().display().to_string())
+            ),
+            Err(e) => writeln!(
+                csv,
+                "{},failed,{},,{:.3},,{}",
+                outcome.name,
+                Failure::of(e) as u8,
+                elapsed_s,
+                quote(&format!("{e:#}"))
+            ),
+        };
+    }
+
+    std::fs::create_dir_all(report_dir)
+        .io_context(|| format!("Failed to create {}", report_dir.display()))?;
+    let report_file = report_dir.join("experiment.csv");
+    std::fs::write(&report_file, csv)
+        .io_context(|| format!("Failed to write {}", report_file.display()))?;
+    info!("Experiment report written to {}", report_file.display());
+    Ok(())
+}
+
+fn quote(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
@@ -0,0 +1,250 @@
+use crate::{Failure, GenerateReport};
+use anyhow::{Result, anyhow};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt::Write as _;
+use std::path::Path;
+use std::time::Instant;
+use telemetry_generator::error::{IoContext, TelemetryError};
+use tracing::{error, info};
+
+use super::plan::{PlannedRun, check_name};
+use super::{RunOutcome, quote, run_all};
+
+// Mixed into each run's seed so the dispersions don't line up with the run's own draws
+const MONTECARLO_SEED_SALT: u64 = 0x3C4B_10D1;
+
+// Dispersion ranges for the wind each mission flies through
+const SURFACE_WIND_MPS: (f64, f64) = (0.0, 12.0);
+const JET_ALTITUDE_M: (f64, f64) = (9_000.0, 14_000.0);
+const JET_WIND_MPS: (f64, f64) = (10.0, 80.0);
+// Jet streams over the Cape blow from the west give or take
+const JET_FROM_DEG: (f64, f64) = (230.0, 310.0);
+const UPPER_WIND_MPS: (f64, f64) = (5.0, 20.0);
+const GUST_INTENSITY_MPS: (f64, f64) = (0.0, 6.0);
+
+// A campaign of dispersed missions flown from one set of generate flags. Each run gets its own
+// seed counting up from `base_seed`, and from it a wind profile, an engine off nominal and fuzzed
+// phase timings, noise and faults. The dispersions are passed as ordinary generate flags, so
+// any run can be flown again on its own from the run history
+#[derive(Debug)]
+pub struct Campaign {
+    pub name: String,
+    pub runs: usize,
+    pub base_seed: u64,
+    pub parallel: usize,
+    // Chance a mission gets faults injected at all
+    pub fault_probability: f64,
+    pub max_faults: usize,
+    // Generate flags every run starts from. The dispersion flags come after and win
+    pub base_flags: Vec<String>,
+}
+
+// What one run was dispersed with
+#[derive(Debug)]
+struct Dispersion {
+    seed: u64,
+    surface_wind_mps: f64,
+    jet_wind_mps: f64,
+    gust_intensity_mps: f64,
+    max_faults: usize,
+}
+
+impl Dispersion {
+    fn draw(campaign: &Campaign, seed: u64) -> (Self, Vec<String>) {
+        let mut rng = StdRng::seed_from_u64(seed ^ MONTECARLO_SEED_SALT);
+        let mut between = |(low, high): (f64, f64)| rng.gen_range(low..=high);
+
+        let surface_wind_mps = between(SURFACE_WIND_MPS);
+        let surface_from = between((0.0, 360.0));
+        let jet_altitude_m = between(JET_ALTITUDE_M);
+        let jet_wind_mps = between(JET_WIND_MPS);
+        let jet_from = between(JET_FROM_DEG);
+        let upper_wind_mps = between(UPPER_WIND_MPS);
+        let gust_intensity_mps = between(GUST_INTENSITY_MPS);
+        let max_faults = if rng.gen_bool(campaign.fault_probability) {
+            campaign.max_faults
+        } else {
+            0
+        };
+
+        let flags = [
+            "--seed".to_string(),
+            seed.to_string(),
+            "--engine-variation".to_string(),
+            "--fuzz".to_string(),
+            "--fuzz-max-faults".to_string(),
+            max_faults.to_string(),
+            "--wind".to_string(),
+            format!("0:{surface_wind_mps:.1}:{surface_from:.0}"),
+            "--wind".to_string(),
+            format!("{jet_altitude_m:.0}:{jet_wind_mps:.1}:{jet_from:.0}"),
+            "--wind".to_string(),
+            format!("25000:{upper_wind_mps:.1}:{jet_from:.0}"),
+            "--gust-intensity".to_string(),
+            format!("{gust_intensity_mps:.2}"),
+        ];
+        let dispersion = Self {
+            seed,
+            surface_wind_mps,
+            jet_wind_mps,
+            gust_intensity_mps,
+            max_faults,
+        };
+        (dispersion, flags.to_vec())
+    }
+}
+
+// Fly every mission of the campaign, `parallel` at a time, then log the spread of outcomes and
+// write them to `{output_dir}/{name}/montecarlo.csv`. A failed run doesn't stop the others
+pub fn run_campaign(campaign: Campaign, output_dir: &Path) -> Result<()> {
+    check_name("Campaign", &campaign.name)?;
+    if campaign.runs == 0 {
+        return Err(TelemetryError::config("A campaign needs at least one run").into());
+    }
+    if !(0.0..=1.0).contains(&campaign.fault_probability) {
+        return Err(TelemetryError::config("Fault probability must be between 0 and 1").into());
+    }
+
+    // Every run's flags are checked up front, same as an experiment plan
+    let mut dispersions = Vec::with_capacity(campaign.runs);
+    let mut runs = Vec::with_capacity(campaign.runs);
+    for idx in 0..campaign.runs {
+        let seed = campaign.base_seed.wrapping_add(idx as u64);
+        let (dispersion, dispersion_flags) = Dispersion::draw(&campaign, seed);
+        let mut flags = campaign.base_flags.clone();
+        flags.extend(dispersion_flags);
+        let name = format!("run-{:04}", idx + 1);
+        runs.push(PlannedRun::new(&campaign.name, name, flags, output_dir)?);
+        dispersions.push(dispersion);
+    }
+
+    let start_time = Instant::now();
+    let outcomes = run_all(&campaign.name, campaign.parallel, runs, output_dir);
+
+    log_summary(&outcomes);
+    let report_dir = output_dir.join(&campaign.name);
+    write_report(&outcomes, &dispersions, &report_dir)?;
+
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    info!(
+        "Campaign {} finished in {:.2?}s",
+        campaign.name,
+        start_time.elapsed().as_secs_f64()
+    );
+    if failed > 0 {
+        return Err(anyhow!(
+            "{failed} of {} runs in campaign {} failed. See {}",
+            outcomes.len(),
+            campaign.name,
+            report_dir.join("montecarlo.csv").display()
+        ));
+    }
+    Ok(())
+}
+
+// Min, mean and max of each outcome over the runs that finished
+fn log_summary(outcomes: &[RunOutcome]) {
+    let reports: Vec<&GenerateReport> = outcomes
+        .iter()
+        .filter_map(|outcome| outcome.result.as_ref().ok())
+        .collect();
+    for outcome in outcomes {
+        if let Err(e) = &outcome.result {
+            error!(
+                "{:<12} failed  exit code {}: {:#}",
+                outcome.name,
+                Failure::of(e) as u8,
+                e
+            );
+        }
+    }
+    if reports.is_empty() {
+        return;
+    }
+
+    let spread = |label: &str, unit: &str, value: fn(&GenerateReport) -> f64| {
+        let values: Vec<f64> = reports.iter().map(|report| value(report)).collect();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mean = values.iter().sum::<f64>() / values.len() as f64;
+        info!("{label:<18} min {min:>12.1}  mean {mean:>12.1}  max {max:>12.1} {unit}");
+    };
+    spread("Max Q", "Pa", |report| report.flight.max_q_pa);
+    spread("Max Q at", "s", |report| report.flight.max_q_time_s);
+    spread("Burnout velocity", "m/s", |report| {
+        report.flight.burnout_velocity_mps
+    });
+    spread("Max altitude", "m", |report| report.flight.max_altitude_m);
+
+    let with_anomalies = reports
+        .iter()
+        .filter(|report| report.flight.anomalies > 0)
+        .count();
+    let anomalies: usize = reports.iter().map(|report| report.flight.anomalies).sum();
+    info!(
+        "{} of {} missions had anomalies, {} in total",
+        with_anomalies,
+        reports.len(),
+        anomalies
+    );
+}
+
+fn write_report(
+    outcomes: &[RunOutcome],
+    dispersions: &[Dispersion],
+    report_dir: &Path,
+) -> Result<()> {
+    let mut csv = String::from(
+        "run,seed,status,exit_code,max_q_pa,max_q_time_s,burnout_velocity_mps,max_altitude_m,\
+        anomalies,surface_wind_mps,jet_wind_mps,gust_intensity_mps,max_faults,elapsed_s,\
+        output_file,error\n",
+    );
+    for (outcome, dispersion) in outcomes.iter().zip(dispersions) {
+        let dispersed = format!(
+            "{:.1},{:.1},{:.2},{}",
+            dispersion.surface_wind_mps,
+            dispersion.jet_wind_mps,
+            dispersion.gust_intensity_mps,
+            dispersion.max_faults
+        );
+        let elapsed_s = outcome.elapsed.as_secs_f64();
+        let _ = match &outcome.result {
+            Ok(report) => {
+                let flight = &report.flight;
+                writeln!(
+                    csv,
+                    "{},{},ok,0,{:.1},{:.3},{:.2},{:.1},{},{},{:.3},{},",
+                    outcome.name,
+                    dispersion.seed,
+                    flight.max_q_pa,
+                    flight.max_q_time_s,
+                    flight.burnout_velocity_mps,
+                    flight.max_altitude_m,
+                    flight.anomalies,
+                    dispersed,
+                    elapsed_s,
+                    quote(&report.parquet_file().display().to_string())
+                )
+            }
+            Err(e) => writeln!(
+                csv,
+                "{},{},failed,{},,,,,,{},{:.3},,{}",
+                outcome.name,
+                dispersion.seed,
+                Failure::of(e) as u8,
+                dispersed,
+                elapsed_s,
+                quote(&format!("{e:#}"))
+            ),
+        };
+    }
+
+    std::fs::create_dir_all(report_dir)
+        .io_context(|| format!("Failed to create {}", report_dir.display()))?;
+    let report_file = report_dir.join("montecarlo.csv");
+    std::fs::write(&report_file, csv)
+        .io_context(|| format!("Failed to write {}", report_file.display()))?;
+    info!("Campaign summary written to {}", report_file.display());
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use crate::GenerateArgs;
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use telemetry_generator::error::{IoContext, Result, TelemetryError};
+use telemetry_generator::scenario::expand_scenario;
+use tracing::info;
+
+// A named set of `generate` runs. Written in YAML, or JSON which is valid YAML too:
+//
+//   name: compression-sweep
+//   parallel: 2
+//   defaults: --hz 100 -d 600 --seeded-launch-time
+//   runs:
+//     - name: baseline
+//     - name: split
+//       args: --value-columns split --bloom-filter
+//     - name: fuel-bias
+//       args: [--script, scripts/fuel_bias.txt, --plugin, ./my_plugin.sh]
+//
+// Args are the usual generate flags, as one string or a list. A run's args come after the
+// defaults and win over them. Every run writes to `{output_dir}/{plan name}/{run name}`
+#[derive(Debug)]
+pub struct ExperimentPlan {
+    pub name: String,
+    pub parallel: usize,
+    pub runs: Vec<PlannedRun>,
+}
+
+#[derive(Debug)]
+pub struct PlannedRun {
+    pub name: String,
+    // Generate flags as given, recorded in the run history
+    pub flags: Vec<String>,
+    pub args: Box<GenerateArgs>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPlan {
+    name: String,
+    #[serde(default = "default_parallel")]
+    parallel: usize,
+    #[serde(default)]
+    defaults: Value,
+    runs: Vec<RawRun>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRun {
+    name: String,
+    #[serde(default)]
+    args: Value,
+}
+
+fn default_parallel() -> usize {
+    1
+}
+
+// Generate flags on top of the clap definition. Later flags override earlier ones, so runs
+// can change the defaults and the output dir set for them
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true, args_override_self = true)]
+struct RunCommand {
+    #[command(flatten)]
+    args: GenerateArgs,
+}
+
+impl ExperimentPlan {
+    pub fn from_file(path: &Path, output_dir: &Path) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read experiment plan {}", path.display()))?;
+        Self::parse(&source, output_dir).map_err(|e| {
+            TelemetryError::config(format!("Invalid experiment plan {}: {e}", path.display()))
+        })
+    }
+
+    // Every run's args are checked up front so a typo in the last run doesn't surface hours in
+    pub fn parse(source: &str, output_dir: &Path) -> Result<Self> {
+        let raw: RawPlan =
+            serde_yaml::from_str(source).map_err(|e| TelemetryError::config(e.to_string()))?;
+
+        check_name("Experiment", &raw.name)?;
+        if raw.parallel == 0 {
+            return Err(TelemetryError::config("parallel must be at least 1"));
+        }
+        if raw.runs.is_empty() {
+            return Err(TelemetryError::config("Plan has no runs"));
+        }
+
+        let defaults = flags("defaults", raw.defaults)?;
+        let mut names = HashSet::new();
+        let mut runs = Vec::with_capacity(raw.runs.len());
+        for run in raw.runs {
+            check_name("Run", &run.name)?;
+            if !names.insert(run.name.clone()) {
+                return Err(TelemetryError::config(format!(
+                    "Run name {} is used twice",
+                    run.name
+                )));
+            }
+
+            let mut args = defaults.clone();
+            args.extend(flags(&run.name, run.args)?);
+            runs.push(PlannedRun::new(&raw.name, run.name, args, output_dir)?);
+        }
+
+        info!(
+            "Parsed experiment plan {} with {} runs",
+            raw.name,
+            runs.len()
+        );
+        Ok(Self {
+            name: raw.name,
+            parallel: raw.parallel,
+            runs,
+        })
+    }
+}
+
+impl PlannedRun {
+    // Run writing to `{output_dir}/{plan}/{name}` with the given generate flags
+    pub fn new(plan: &str, name: String, flags: Vec<String>, output_dir: &Path) -> Result<Self> {
+        let run_dir = output_dir.join(plan).join(&name);
+        let mut args = vec!["--output-dir".to_string(), run_dir.display().to_string()];
+        args.extend(flags);
+
+        let args = expand_scenario(args);
+        let command = RunCommand::try_parse_from(&args).map_err(|e| {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            let message = message.trim_start_matches("error: ");
+            TelemetryError::config(format!("Run {name}: {message}"))
+        })?;
+        Ok(Self {
+            name,
+            flags: args,
+            args: Box::new(command.args),
+        })
+    }
+}
+
+// Names end up as directories, so keep them tame
+pub(super) fn check_name(what: &str, name: &str) -> Result<()> {
+    let tame = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if name.is_empty() || !tame {
+        return Err(TelemetryError::config(format!(
+            "{what} name `{name}` may only use letters, digits, - and _"
+        )));
+    }
+    Ok(())
+}
+
+// Flags given as one whitespace separated string or a list of scalars
+fn flags(owner: &str, value: Value) -> Result<Vec<String>> {
+    match value {
+        Value::Null => Ok(Vec::new()),
+        Value::String(line) => Ok(line.split_whitespace().map(str::to_string).collect()),
+        Value::Array(items) => items
+            .into_iter()
+            .map(|item| match item {
+                Value::String(flag) => Ok(flag),
+                Value::Number(_) | Value::Bool(_) => Ok(item.to_string()),
+                other => Err(TelemetryError::config(format!(
+                    "{owner}: args must be plain values, not {other}"
+                ))),
+            })
+            .collect(),
+        other => Err(TelemetryError::config(format!(
+            "{owner}: args should be a string or a list, not {other}"
+        ))),
+    }
+}
@@ -0,0 +1,39 @@
+use super::output_files::prepare_output_file;
+use crate::error::{IoContext, Result};
+use crate::generators::LimitAlert;
+use crate::models::sensor_label;
+use std::fmt::Write as _;
+use std::path::Path;
+use tracing::info;
+
+// Lists every limit exceedance as `{output_name}.alerts.csv`, the alarms a monitoring system
+// watching the run should raise. `end_ms` is empty for limits still exceeded when the run ended
+pub struct AlertExporter;
+
+impl AlertExporter {
+    pub fn export(alerts: &[LimitAlert], output_dir: &Path, output_name: &str) -> Result<()> {
+        let mut csv = String::from("sensor,limit,threshold,start_ms,end_ms,peak\n");
+        for alert in alerts {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                sensor_label(alert.sensor.field_name(), alert.instance, alert.side),
+                alert.level.name(),
+                alert.limit,
+                alert.start_ms,
+                alert.end_ms.map(|ms| ms.to_string()).unwrap_or_default(),
+                alert.peak
+            );
+        }
+
+        let alerts_file = prepare_output_file(output_dir, output_name, "alerts.csv")?;
+        std::fs::write(&alerts_file, csv)
+            .io_context(|| format!("Failed to write {}", alerts_file.display()))?;
+        info!(
+            "{} limit alerts written to {}",
+            alerts.len(),
+            alerts_file.display()
+        );
+        Ok(())
+    }
+}
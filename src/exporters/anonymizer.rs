@@ -0,0 +1,468 @@
+use super::output_files::{OverwritePolicy, output_file_path, resolve_output_name};
+use super::parquet_exporter::UNITS_KEY;
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use crate::generators::parse_time_ms;
+use crate::models::{SensorEnum, UnitSystem};
+use arrow::array::{Array, ArrayRef, Float64Array, StringArray, TimestampMicrosecondArray};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use clap::ValueEnum;
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::properties::WriterProperties;
+use rand::Rng;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+// Sink name reported in export errors
+const SINK: &str = "anonymized parquet";
+
+// Format the metadata CSV writes launch times in
+const LAUNCH_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f UTC";
+
+// How a run is disguised before it's shared outside the team
+#[derive(Debug, Clone)]
+pub struct AnonymizeConfig {
+    // Added to every absolute timestamp. Times since launch are left alone
+    pub time_shift_ms: i64,
+    pub latitude_offset_deg: f64,
+    pub longitude_offset_deg: f64,
+    // CSV of `launch_id,shared_id`. Launch ids missing from it get a random shared id added
+    pub launch_id_map: PathBuf,
+    // Launch id of the run when there is no metadata CSV to read it from
+    pub launch_id: Option<String>,
+}
+
+// Copies a run's parquet file and sidecars with shifted timestamps, offset positions and the
+// launch id swapped for its shared id. The mapping back to the real ids stays in the launch id
+// map, which is created readable by its owner only
+pub struct DatasetAnonymizer {
+    config: AnonymizeConfig,
+}
+
+impl DatasetAnonymizer {
+    pub fn new(config: AnonymizeConfig) -> Self {
+        Self { config }
+    }
+
+    // Anonymize the run written to `parquet_file` into `output_dir`. Returns the new parquet file
+    pub fn anonymize(
+        &self,
+        parquet_file: &Path,
+        output_dir: &Path,
+        policy: OverwritePolicy,
+    ) -> Result<PathBuf> {
+        let input_dir = parquet_file.parent().unwrap_or(Path::new("."));
+        let input_name = parquet_file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(".parquet"))
+            .ok_or_else(|| {
+                TelemetryError::config(format!("{} is not a .parquet file", parquet_file.display()))
+            })?;
+
+        let metadata_csv = output_file_path(input_dir, input_name, "metadata.csv");
+        let launch_id = match (&self.config.launch_id, metadata_csv.exists()) {
+            (Some(launch_id), _) => launch_id.clone(),
+            (None, true) => read_launch_id(&metadata_csv)?,
+            (None, false) => {
+                return Err(TelemetryError::config(format!(
+                    "No {} to read the launch id from. Pass it with --launch-id",
+                    metadata_csv.display()
+                )));
+            }
+        };
+
+        let mut launch_ids = LaunchIdMap::load(&self.config.launch_id_map)?;
+        let shared_id = launch_ids.shared_id(&launch_id)?;
+        let output_name = resolve_output_name(
+            output_dir,
+            &input_name.replace(&launch_id, &shared_id),
+            policy,
+        )?;
+        std::fs::create_dir_all(output_dir)
+            .io_context(|| format!("Failed to create {}", output_dir.display()))?;
+
+        let positions = self.position_offsets(input_dir, input_name);
+        let output_file = output_file_path(output_dir, &output_name, "parquet");
+        self.anonymize_parquet(parquet_file, &output_file, &positions)?;
+
+        if metadata_csv.exists() {
+            self.anonymize_metadata(
+                &metadata_csv,
+                &output_file_path(output_dir, &output_name, "metadata.csv"),
+                &shared_id,
+            )?;
+        }
+        // None of these sidecars say anything identifying
+        for extension in ["sensors.csv", "calibration.json", "windows.csv"] {
+            let sidecar = output_file_path(input_dir, input_name, extension);
+            if sidecar.exists() {
+                let target = output_file_path(output_dir, &output_name, extension);
+                std::fs::copy(&sidecar, &target)
+                    .io_context(|| format!("Failed to copy {}", sidecar.display()))?;
+            }
+        }
+        let manifest = output_file_path(input_dir, input_name, "evolution.json");
+        if manifest.exists() {
+            anonymize_manifest(
+                &manifest,
+                &output_file_path(output_dir, &output_name, "evolution.json"),
+                &shared_id,
+            )?;
+        }
+
+        info!(
+            "Anonymized {} as {}",
+            parquet_file.display(),
+            output_file.display()
+        );
+        Ok(output_file)
+    }
+
+    // Offset per sensor_type for the latitude and longitude channels, in the file's units.
+    // Names come from the sensor catalog when there is one, so renames and instances are found
+    fn position_offsets(&self, input_dir: &Path, input_name: &str) -> PositionOffsets {
+        let mut sensors: HashMap<String, SensorEnum> = HashMap::new();
+        let catalog = output_file_path(input_dir, input_name, "sensors.csv");
+        if let Ok(catalog) = std::fs::read_to_string(&catalog) {
+            for line in catalog.lines().skip(1) {
+                let mut columns = line.splitn(3, ',');
+                if let (Some(sensor_type), Some(name)) = (columns.next(), columns.next())
+                    && let Some(sensor) = SensorEnum::from_name(name)
+                {
+                    sensors.insert(sensor_type.to_string(), sensor);
+                }
+            }
+        }
+
+        PositionOffsets {
+            sensors,
+            latitude_deg: self.config.latitude_offset_deg,
+            longitude_deg: self.config.longitude_offset_deg,
+            units: UnitSystem::default(),
+        }
+    }
+
+    fn anonymize_parquet(
+        &self,
+        input: &Path,
+        output: &Path,
+        positions: &PositionOffsets,
+    ) -> Result<()> {
+        let file =
+            File::open(input).io_context(|| format!("Failed to open {}", input.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+            .export_context(SINK, || format!("Failed to read {}", input.display()))?;
+
+        // Keep the footer so readers still know the layout and units
+        let key_values = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .cloned();
+        let units = key_values
+            .iter()
+            .flatten()
+            .find(|kv| kv.key == UNITS_KEY)
+            .and_then(|kv| kv.value.as_deref())
+            .and_then(|units| UnitSystem::from_str(units, true).ok())
+            .unwrap_or_default();
+        let positions = PositionOffsets {
+            units,
+            ..positions.clone()
+        };
+
+        let schema = builder.schema().clone();
+        let reader = builder
+            .build()
+            .export_context(SINK, || format!("Failed to read {}", input.display()))?;
+
+        let output_file =
+            File::create(output).io_context(|| format!("Failed to create {}", output.display()))?;
+        let props = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .set_key_value_metadata(key_values)
+            .build();
+        let mut writer = ArrowWriter::try_new(output_file, schema, Some(props))
+            .export_context(SINK, || "Failed to create arrow writer")?;
+
+        let mut rows: usize = 0;
+        for batch in reader {
+            let batch = batch.export_context(SINK, || "Failed to read record batch")?;
+            let batch = self.anonymize_batch(&batch, &positions)?;
+            rows += batch.num_rows();
+            writer
+                .write(&batch)
+                .export_context(SINK, || "Failed to write record batch")?;
+        }
+        writer
+            .close()
+            .export_context(SINK, || "Failed to close Parquet writer")?;
+
+        info!("Anonymized {} rows", rows);
+        Ok(())
+    }
+
+    fn anonymize_batch(
+        &self,
+        batch: &RecordBatch,
+        positions: &PositionOffsets,
+    ) -> Result<RecordBatch> {
+        let schema = batch.schema();
+        let sensor_types = batch
+            .column_by_name("sensor_type")
+            .and_then(|column| column.as_any().downcast_ref::<StringArray>());
+        let time_shift_us = self.config.time_shift_ms * 1_000;
+
+        let columns = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, column)| -> ArrayRef {
+                match field.name().as_str() {
+                    "timestamp" => {
+                        match column.as_any().downcast_ref::<TimestampMicrosecondArray>() {
+                            Some(timestamps) => Arc::new(
+                                timestamps.unary::<_, arrow::datatypes::TimestampMicrosecondType>(
+                                    |t| t + time_shift_us,
+                                ),
+                            ),
+                            None => column.clone(),
+                        }
+                    }
+                    "value" | "value_float" => {
+                        match (column.as_any().downcast_ref::<Float64Array>(), sensor_types) {
+                            (Some(values), Some(sensor_types)) => {
+                                Arc::new(positions.apply(values, sensor_types))
+                            }
+                            _ => column.clone(),
+                        }
+                    }
+                    _ => column.clone(),
+                }
+            })
+            .collect();
+
+        RecordBatch::try_new(schema, columns)
+            .export_context(SINK, || "Failed to build anonymized record batch")
+    }
+
+    fn anonymize_metadata(&self, input: &Path, output: &Path, shared_id: &str) -> Result<()> {
+        let metadata = std::fs::read_to_string(input)
+            .io_context(|| format!("Failed to read {}", input.display()))?;
+        let mut lines = metadata.lines();
+        let mut anonymized = String::new();
+        if let Some(header) = lines.next() {
+            anonymized.push_str(header);
+            anonymized.push('\n');
+        }
+        for line in lines {
+            let mut fields: Vec<String> = line.split(',').map(str::to_string).collect();
+            if let Some(launch_id) = fields.get_mut(0) {
+                *launch_id = shared_id.to_string();
+            }
+            if let Some(launch_time) = fields.get_mut(1) {
+                *launch_time = self.shift_launch_time(launch_time)?;
+            }
+            anonymized.push_str(&fields.join(","));
+            anonymized.push('\n');
+        }
+        std::fs::write(output, anonymized)
+            .io_context(|| format!("Failed to write {}", output.display()))
+    }
+
+    fn shift_launch_time(&self, launch_time: &str) -> Result<String> {
+        let parsed = NaiveDateTime::parse_from_str(launch_time, LAUNCH_TIME_FORMAT)
+            .map_err(|e| TelemetryError::config(format!("Invalid launch time {launch_time}: {e}")))?
+            .and_utc();
+        let shifted: DateTime<Utc> =
+            parsed + chrono::Duration::milliseconds(self.config.time_shift_ms);
+        Ok(shifted.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PositionOffsets {
+    // Exported sensor_type to sensor from the catalog
+    sensors: HashMap<String, SensorEnum>,
+    latitude_deg: f64,
+    longitude_deg: f64,
+    units: UnitSystem,
+}
+
+impl PositionOffsets {
+    fn apply(&self, values: &Float64Array, sensor_types: &StringArray) -> Float64Array {
+        let latitude = self.units.convert(SensorEnum::Latitude, self.latitude_deg);
+        let longitude = self
+            .units
+            .convert(SensorEnum::Longitude, self.longitude_deg);
+        let mut offsets: HashMap<&str, f64> = HashMap::new();
+
+        values
+            .iter()
+            .zip(sensor_types.iter())
+            .map(|(value, sensor_type)| {
+                let (Some(value), Some(sensor_type)) = (value, sensor_type) else {
+                    return value;
+                };
+                let offset =
+                    *offsets
+                        .entry(sensor_type)
+                        .or_insert_with(|| match self.sensor(sensor_type) {
+                            Some(SensorEnum::Latitude) => latitude,
+                            Some(SensorEnum::Longitude) => longitude,
+                            _ => 0.0,
+                        });
+                Some(value + offset)
+            })
+            .collect()
+    }
+
+    // Catalog first, then any naming convention with an optional `_001` instance suffix
+    fn sensor(&self, sensor_type: &str) -> Option<SensorEnum> {
+        if let Some(sensor) = self.sensors.get(sensor_type) {
+            return Some(*sensor);
+        }
+        SensorEnum::from_name(sensor_type).or_else(|| {
+            let (name, instance) = sensor_type.rsplit_once('_')?;
+            let is_instance = instance.len() == 3 && instance.bytes().all(|b| b.is_ascii_digit());
+            is_instance.then(|| SensorEnum::from_name(name)).flatten()
+        })
+    }
+}
+
+// Real launch ids to the ids they are shared as
+struct LaunchIdMap {
+    path: PathBuf,
+    shared_ids: HashMap<String, String>,
+}
+
+impl LaunchIdMap {
+    fn load(path: &Path) -> Result<Self> {
+        let mut shared_ids = HashMap::new();
+        if path.exists() {
+            warn_if_readable_by_others(path);
+            let map = std::fs::read_to_string(path)
+                .io_context(|| format!("Failed to read launch id map {}", path.display()))?;
+            for (idx, line) in map.lines().enumerate().skip(1) {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let (launch_id, shared_id) = line.split_once(',').ok_or_else(|| {
+                    TelemetryError::config(format!(
+                        "{} line {}: expected launch_id,shared_id",
+                        path.display(),
+                        idx + 1
+                    ))
+                })?;
+                shared_ids.insert(launch_id.trim().to_string(), shared_id.trim().to_string());
+            }
+        }
+        Ok(Self {
+            path: path.to_path_buf(),
+            shared_ids,
+        })
+    }
+
+    // Shared id for the launch, making one up and saving it if the map doesn't have it yet
+    fn shared_id(&mut self, launch_id: &str) -> Result<String> {
+        if let Some(shared_id) = self.shared_ids.get(launch_id) {
+            return Ok(shared_id.clone());
+        }
+
+        let shared_id = format!("ANON-{:08X}", rand::thread_rng().r#gen::<u32>());
+        let is_new = !self.path.exists();
+        let mut file = restricted_append(&self.path)?;
+        if is_new {
+            writeln!(file, "launch_id,shared_id")
+                .io_context(|| format!("Failed to write {}", self.path.display()))?;
+        }
+        writeln!(file, "{launch_id},{shared_id}")
+            .io_context(|| format!("Failed to write {}", self.path.display()))?;
+
+        info!(
+            "Added shared id for a new launch to {}",
+            self.path.display()
+        );
+        self.shared_ids
+            .insert(launch_id.to_string(), shared_id.clone());
+        Ok(shared_id)
+    }
+}
+
+fn read_launch_id(metadata_csv: &Path) -> Result<String> {
+    let metadata = std::fs::read_to_string(metadata_csv)
+        .io_context(|| format!("Failed to read {}", metadata_csv.display()))?;
+    metadata
+        .lines()
+        .nth(1)
+        .and_then(|row| row.split(',').next())
+        .map(str::to_string)
+        .ok_or_else(|| {
+            TelemetryError::config(format!(
+                "No launch id in {}. Pass it with --launch-id",
+                metadata_csv.display()
+            ))
+        })
+}
+
+fn anonymize_manifest(input: &Path, output: &Path, shared_id: &str) -> Result<()> {
+    let manifest = std::fs::read_to_string(input)
+        .io_context(|| format!("Failed to read {}", input.display()))?;
+    let mut manifest: serde_json::Value = serde_json::from_str(&manifest)
+        .map_err(|e| TelemetryError::config(format!("Invalid {}: {e}", input.display())))?;
+    manifest["launch_id"] = shared_id.into();
+    std::fs::write(output, manifest.to_string())
+        .io_context(|| format!("Failed to write {}", output.display()))
+}
+
+// The map undoes the anonymization, so only its owner gets to read it
+#[cfg(unix)]
+fn restricted_append(path: &Path) -> Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)
+        .io_context(|| format!("Failed to open launch id map {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn restricted_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .io_context(|| format!("Failed to open launch id map {}", path.display()))
+}
+
+#[cfg(unix)]
+fn warn_if_readable_by_others(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+    if let Ok(metadata) = std::fs::metadata(path)
+        && metadata.permissions().mode() & 0o077 != 0
+    {
+        warn!(
+            "Launch id map {} can be read by other users. Consider chmod 600",
+            path.display()
+        );
+    }
+}
+
+#[cfg(not(unix))]
+fn warn_if_readable_by_others(_path: &Path) {}
+
+// Signed times like `-30d` or `6h` in milliseconds
+pub fn parse_time_shift(shift: &str) -> Result<i64> {
+    let (sign, time) = match shift.strip_prefix('-') {
+        Some(time) => (-1, time),
+        None => (1, shift.strip_prefix('+').unwrap_or(shift)),
+    };
+    Ok(sign * parse_time_ms(time)? as i64)
+}
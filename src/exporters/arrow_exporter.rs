@@ -0,0 +1,148 @@
+use super::output_files::prepare_output_file;
+use super::{ParquetConfig, ParquetExporter};
+use crate::error::{ExportContext, IoContext, Result};
+use crate::models::{TelemetryConfig, TelemetryDataset};
+use arrow::ipc::writer::{FileWriter, StreamWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Sink name reported in export errors
+const SINK: &str = "arrow";
+
+// Arrow IPC has a file layout with a footer indexing the batches, and a stream layout without
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrowFormat {
+    // `{output_name}.arrow`, the Feather v2 file notebooks can memory map
+    File,
+    // `{output_name}.arrows`, for readers that take batches as they come
+    Stream,
+}
+
+impl ArrowFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ArrowFormat::File => "arrow",
+            ArrowFormat::Stream => "arrows",
+        }
+    }
+}
+
+// The readings as uncompressed Arrow IPC, so they can be memory mapped rather than decoded.
+// Batches and schema are the parquet exporter's, in the same layout and value columns with the
+// same key values as schema metadata. Its parquet only settings like bloom filters don't apply
+pub struct ArrowExporter {
+    batches: ParquetExporter,
+    format: ArrowFormat,
+}
+
+impl ArrowExporter {
+    pub fn new(config: ParquetConfig, format: ArrowFormat) -> Self {
+        Self {
+            batches: ParquetExporter::new(config),
+            format,
+        }
+    }
+
+    pub fn export(
+        &self,
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
+        if dataset.readings.is_empty() {
+            warn!("No readings to export. Exiting export.");
+            return Ok(());
+        }
+        let mut writer = self.create_writer(&dataset.config, output_dir, output_name)?;
+        writer.write_chunk(dataset)?;
+        writer.close()
+    }
+
+    // Open an IPC file that chunks of readings can be streamed into, a record batch each
+    pub fn create_writer(
+        &self,
+        config: &TelemetryConfig,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<ArrowStreamWriter<'_>> {
+        let arrow_file = prepare_output_file(output_dir, output_name, self.format.extension())?;
+        let output_file = File::create(&arrow_file)
+            .io_context(|| format!("Failed to create output file at {}", arrow_file.display()))?;
+        let output_file = BufWriter::new(output_file);
+        let schema = self.batches.schema(config);
+        let writer = match self.format {
+            ArrowFormat::File => IpcWriter::File(
+                FileWriter::try_new(output_file, &schema)
+                    .export_context(SINK, || "Failed to create Arrow IPC file writer")?,
+            ),
+            ArrowFormat::Stream => IpcWriter::Stream(
+                StreamWriter::try_new(output_file, &schema)
+                    .export_context(SINK, || "Failed to create Arrow IPC stream writer")?,
+            ),
+        };
+        Ok(ArrowStreamWriter {
+            exporter: self,
+            writer,
+            arrow_file,
+            rows_written: 0,
+            readings_seen: 0,
+        })
+    }
+}
+
+enum IpcWriter {
+    File(FileWriter<BufWriter<File>>),
+    Stream(StreamWriter<BufWriter<File>>),
+}
+
+// Streams chunks of readings into a single Arrow IPC file as record batches
+pub struct ArrowStreamWriter<'a> {
+    exporter: &'a ArrowExporter,
+    writer: IpcWriter,
+    arrow_file: PathBuf,
+    rows_written: usize,
+    // Readings of the run so far, for the ids
+    readings_seen: usize,
+}
+
+impl ArrowStreamWriter<'_> {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        if chunk.readings.is_empty() {
+            return Ok(());
+        }
+        let batch = self
+            .exporter
+            .batches
+            .record_batch(chunk, self.readings_seen)?;
+        self.readings_seen += chunk.readings.len();
+        match &mut self.writer {
+            IpcWriter::File(writer) => writer.write(&batch),
+            IpcWriter::Stream(writer) => writer.write(&batch),
+        }
+        .export_context(SINK, || "Failed to write record batch to Arrow IPC")?;
+        self.rows_written += batch.num_rows();
+        Ok(())
+    }
+
+    // Bytes written so far, less what's still buffered
+    pub fn bytes_written(&self) -> u64 {
+        std::fs::metadata(&self.arrow_file).map_or(0, |metadata| metadata.len())
+    }
+
+    pub fn close(self) -> Result<()> {
+        // Finishing writes the footer or end of stream marker and flushes the buffer
+        match self.writer {
+            IpcWriter::File(writer) => writer.into_inner().map(drop),
+            IpcWriter::Stream(writer) => writer.into_inner().map(drop),
+        }
+        .export_context(SINK, || "Failed to close Arrow IPC writer")?;
+        info!(
+            "Exported {} rows to Arrow IPC file at {}",
+            self.rows_written,
+            self.arrow_file.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,316 @@
+use super::output_files::prepare_output_file;
+use crate::error::{IoContext, Result};
+use crate::models::{MissionPhase, SensorEnum, SensorValue, TelemetryConfig, TelemetryDataset};
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Avro object container files, written by hand as there's no Avro crate among the dependencies.
+// A header with the schema and codec, then blocks of records each followed by the file's sync
+// marker. See https://avro.apache.org/docs/1.11.1/specification/#object-container-files
+
+const MAGIC: &[u8; 4] = b"Obj\x01";
+
+// Records a block is flushed at once its encoded size passes, unless configured otherwise
+pub const DEFAULT_AVRO_BLOCK_BYTES: usize = 64 * 1024;
+
+// Compression of each block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AvroCodec {
+    Null,
+    Deflate,
+    Snappy,
+}
+
+impl AvroCodec {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AvroCodec::Null => "null",
+            AvroCodec::Deflate => "deflate",
+            AvroCodec::Snappy => "snappy",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AvroConfig {
+    pub codec: AvroCodec,
+    // Uncompressed bytes of records per block. Bigger blocks compress better, smaller ones let
+    // readers split the file finer
+    pub block_bytes: usize,
+}
+
+impl Default for AvroConfig {
+    fn default() -> Self {
+        Self {
+            codec: AvroCodec::Deflate,
+            block_bytes: DEFAULT_AVRO_BLOCK_BYTES,
+        }
+    }
+}
+
+// The readings as `{output_name}.avro`, a record per reading for Kafka Connect and other Avro
+// consumers. The schema is embedded in the file. `sensor` and `mission_phase` are enums of every
+// sensor and phase, `sensor_type` the exported name with instance and side, and `value` a union
+// of double, long and string like the split parquet columns. The launch id and units go in the
+// file's metadata as `telemetry.launch_id` and `telemetry.units`
+pub struct AvroExporter {
+    config: AvroConfig,
+}
+
+impl AvroExporter {
+    pub fn new(config: AvroConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn export(
+        &self,
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
+        if dataset.readings.is_empty() {
+            warn!("No readings to export. Exiting export.");
+            return Ok(());
+        }
+        let mut writer = self.create_writer(&dataset.config, output_dir, output_name)?;
+        writer.write_chunk(dataset)?;
+        writer.close()
+    }
+
+    // Open a container file and write its header. Chunks streamed in are split into blocks
+    pub fn create_writer(
+        &self,
+        config: &TelemetryConfig,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<AvroStreamWriter> {
+        let avro_file = prepare_output_file(output_dir, output_name, "avro")?;
+        let output_file = File::create(&avro_file)
+            .io_context(|| format!("Failed to create output file at {}", avro_file.display()))?;
+        // From the seed, so the same run writes the same bytes
+        let sync: [u8; 16] = StdRng::seed_from_u64(config.seed).r#gen();
+
+        let mut header = MAGIC.to_vec();
+        let metadata = [
+            ("avro.schema", schema().to_string()),
+            ("avro.codec", self.config.codec.name().to_string()),
+            ("telemetry.launch_id", config.launch_id.clone()),
+            ("telemetry.units", config.units.name().to_string()),
+        ];
+        write_long(&mut header, metadata.len() as i64);
+        for (key, value) in &metadata {
+            write_bytes(&mut header, key.as_bytes());
+            write_bytes(&mut header, value.as_bytes());
+        }
+        write_long(&mut header, 0);
+        header.extend_from_slice(&sync);
+
+        let mut writer = AvroStreamWriter {
+            config: self.config,
+            output: BufWriter::new(output_file),
+            avro_file,
+            sync,
+            block: Vec::with_capacity(self.config.block_bytes + 1024),
+            block_records: 0,
+            bytes_written: 0,
+            records_written: 0,
+        };
+        writer.write_out(&header)?;
+        Ok(writer)
+    }
+}
+
+// Streams chunks of readings into a single Avro container file
+pub struct AvroStreamWriter {
+    config: AvroConfig,
+    output: BufWriter<File>,
+    avro_file: PathBuf,
+    sync: [u8; 16],
+    // Encoded records waiting for the block to fill
+    block: Vec<u8>,
+    block_records: usize,
+    bytes_written: u64,
+    records_written: usize,
+}
+
+impl AvroStreamWriter {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        let config = &chunk.config;
+        for reading in &chunk.readings {
+            let record = &mut self.block;
+            let timestamp_micros = reading.timestamp.timestamp_micros();
+            write_long(record, timestamp_micros);
+            write_long(record, reading.time_since_launch_ms as i64);
+            write_long(record, sensor_symbol(reading.sensor) as i64);
+            write_bytes(record, reading.sensor_label(config).as_bytes());
+            // Union branches in schema order: null, double, long, string
+            match &reading.value {
+                SensorValue::Float(f) => {
+                    write_long(record, 1);
+                    let value = config.export_float(reading.sensor, *f);
+                    record.extend_from_slice(&value.to_le_bytes());
+                }
+                SensorValue::Int(i) => {
+                    write_long(record, 2);
+                    write_long(record, *i);
+                }
+                SensorValue::UnsignedInt(u) => {
+                    write_long(record, 2);
+                    write_long(record, *u as i64);
+                }
+                SensorValue::String(s) => {
+                    write_long(record, 3);
+                    write_bytes(record, s.as_bytes());
+                }
+                SensorValue::State(state) => {
+                    write_long(record, 3);
+                    write_bytes(record, state.name().as_bytes());
+                }
+            }
+            write_bytes(record, config.units.unit(reading.sensor).as_bytes());
+            match config
+                .point_ids
+                .then(|| config.point_id(self.records_written + self.block_records))
+            {
+                Some(id) => {
+                    write_long(record, 1);
+                    write_bytes(record, id.as_bytes());
+                }
+                None => write_long(record, 0),
+            }
+            write_long(record, phase_symbol(reading.phase) as i64);
+            self.block_records += 1;
+
+            if self.block.len() >= self.config.block_bytes {
+                self.flush_block()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Bytes written to the file so far, less the block still being filled
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn close(mut self) -> Result<()> {
+        self.flush_block()?;
+        self.output
+            .flush()
+            .io_context(|| format!("Failed to write {}", self.avro_file.display()))?;
+        info!(
+            "Exported {} readings to Avro file at {}",
+            self.records_written,
+            self.avro_file.display()
+        );
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> Result<()> {
+        if self.block_records == 0 {
+            return Ok(());
+        }
+        let data = match self.config.codec {
+            AvroCodec::Null => std::mem::take(&mut self.block),
+            // Raw deflate, without the zlib header and checksum
+            AvroCodec::Deflate => {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder
+                    .write_all(&self.block)
+                    .and_then(|()| encoder.finish())
+                    .io_context(|| "Failed to deflate an Avro block")?
+            }
+            // Followed by the big endian CRC32 of the uncompressed block
+            AvroCodec::Snappy => {
+                let mut data = snap::raw::Encoder::new()
+                    .compress_vec(&self.block)
+                    .map_err(std::io::Error::other)
+                    .io_context(|| "Failed to compress an Avro block")?;
+                data.extend_from_slice(&crc32fast::hash(&self.block).to_be_bytes());
+                data
+            }
+        };
+        let mut block = Vec::with_capacity(data.len() + 36);
+        write_long(&mut block, self.block_records as i64);
+        write_long(&mut block, data.len() as i64);
+        block.extend_from_slice(&data);
+        block.extend_from_slice(&self.sync);
+        self.write_out(&block)?;
+
+        self.records_written += self.block_records;
+        self.block_records = 0;
+        self.block.clear();
+        Ok(())
+    }
+
+    fn write_out(&mut self, bytes: &[u8]) -> Result<()> {
+        self.output
+            .write_all(bytes)
+            .io_context(|| format!("Failed to write {}", self.avro_file.display()))?;
+        self.bytes_written += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+// Record schema, with the enum symbols in catalog order so their indexes are stable
+fn schema() -> serde_json::Value {
+    let sensors: Vec<&str> = SensorEnum::get_all_sensor_enums()
+        .iter()
+        .map(SensorEnum::field_name)
+        .collect();
+    let phases: Vec<&str> = MissionPhase::ALL.iter().map(MissionPhase::name).collect();
+    serde_json::json!({
+        "type": "record",
+        "name": "TelemetryReading",
+        "namespace": "telemetry_generator",
+        "fields": [
+            {"name": "timestamp", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+            {"name": "time_since_launch_ms", "type": "long"},
+            {"name": "sensor", "type": {"type": "enum", "name": "Sensor", "symbols": sensors}},
+            {"name": "sensor_type", "type": "string"},
+            {"name": "value", "type": ["null", "double", "long", "string"]},
+            {"name": "unit", "type": "string"},
+            {"name": "id", "type": ["null", "string"], "default": null},
+            {
+                "name": "mission_phase",
+                "type": {"type": "enum", "name": "MissionPhase", "symbols": phases}
+            },
+        ],
+    })
+}
+
+fn sensor_symbol(sensor: SensorEnum) -> usize {
+    SensorEnum::get_all_sensor_enums()
+        .iter()
+        .position(|other| *other == sensor)
+        .expect("Every sensor is in the catalog")
+}
+
+fn phase_symbol(phase: MissionPhase) -> usize {
+    MissionPhase::ALL
+        .iter()
+        .position(|other| *other == phase)
+        .expect("Every phase is in ALL")
+}
+
+// Zig-zag varint, how Avro writes ints and longs
+fn write_long(out: &mut Vec<u8>, value: i64) {
+    let mut n = ((value << 1) ^ (value >> 63)) as u64;
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+// Length prefixed, for bytes and strings
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_long(out, bytes.len() as i64);
+    out.extend_from_slice(bytes);
+}
@@ -0,0 +1,42 @@
+use crate::error::Result;
+use crate::generators::ChunkObserver;
+use crate::models::TelemetryDataset;
+use arrow::record_batch::RecordBatch;
+use std::ops::ControlFlow;
+
+use super::ParquetExporter;
+
+// Chunk observer for Arrow consumers. Each chunk is converted to the record batch `exporter`
+// would write for it, so the columns match the parquet files
+pub struct RecordBatchObserver<F> {
+    exporter: ParquetExporter,
+    on_batch: F,
+    readings_seen: usize,
+}
+
+impl<F> RecordBatchObserver<F>
+where
+    F: FnMut(RecordBatch) -> Result<ControlFlow<()>> + Send,
+{
+    pub fn new(exporter: ParquetExporter, on_batch: F) -> Self {
+        Self {
+            exporter,
+            on_batch,
+            readings_seen: 0,
+        }
+    }
+}
+
+impl<F> ChunkObserver for RecordBatchObserver<F>
+where
+    F: FnMut(RecordBatch) -> Result<ControlFlow<()>> + Send,
+{
+    fn on_chunk(&mut self, chunk: &TelemetryDataset) -> Result<ControlFlow<()>> {
+        if chunk.readings.is_empty() {
+            return Ok(ControlFlow::Continue(()));
+        }
+        let batch = self.exporter.record_batch(chunk, self.readings_seen)?;
+        self.readings_seen += chunk.readings.len();
+        (self.on_batch)(batch)
+    }
+}
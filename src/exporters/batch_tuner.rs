@@ -0,0 +1,130 @@
+use std::time::Duration;
+use tracing::{debug, warn};
+
+// Growth per step while throughput keeps improving, and the shrink when it stops
+const GROW_FACTOR: f64 = 1.25;
+const SHRINK_FACTOR: f64 = 0.8;
+
+// A step has to lose this much throughput before the tuner turns around, so noise
+// doesn't flip it every batch
+const THROUGHPUT_TOLERANCE: f64 = 0.95;
+
+// Bounds and targets for adaptive batching
+#[derive(Debug, Clone)]
+pub struct BatchTuning {
+    pub min_batch_size: usize,
+    pub max_batch_size: usize,
+    // Writes slower than this shrink the batch whatever the throughput
+    pub target_latency: Duration,
+    // A write taking longer than this counts as the server being overloaded
+    pub write_timeout: Duration,
+    // Overloaded writes are retried this many times, backing off in between
+    pub max_retries: u32,
+}
+
+impl Default for BatchTuning {
+    fn default() -> Self {
+        Self {
+            min_batch_size: 500,
+            max_batch_size: 100_000,
+            target_latency: Duration::from_secs(1),
+            write_timeout: Duration::from_secs(10),
+            max_retries: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Grow,
+    Shrink,
+}
+
+// Hill climbs the batch size on observed throughput. It keeps stepping the same way while
+// points per second go up and turns around when they drop. Slow writes always shrink it and
+// overloaded ones (429, 503 or a timeout) halve it
+#[derive(Debug)]
+pub struct BatchTuner {
+    tuning: BatchTuning,
+    size: usize,
+    direction: Direction,
+    last_throughput: Option<f64>,
+    best: Option<(usize, f64)>,
+}
+
+impl BatchTuner {
+    pub fn new(start_size: usize, tuning: BatchTuning) -> Self {
+        let size = start_size.clamp(tuning.min_batch_size, tuning.max_batch_size);
+        Self {
+            tuning,
+            size,
+            direction: Direction::Grow,
+            last_throughput: None,
+            best: None,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn tuning(&self) -> &BatchTuning {
+        &self.tuning
+    }
+
+    // Best batch size seen so far with its points per second
+    pub fn best(&self) -> Option<(usize, f64)> {
+        self.best
+    }
+
+    pub fn record_success(&mut self, points: usize, latency: Duration) {
+        let throughput = points as f64 / latency.as_secs_f64().max(1e-6);
+        if self.best.is_none_or(|(_, best)| throughput > best) {
+            self.best = Some((points, throughput));
+        }
+
+        if latency > self.tuning.target_latency {
+            self.direction = Direction::Shrink;
+        } else if let Some(last) = self.last_throughput
+            && throughput < last * THROUGHPUT_TOLERANCE
+        {
+            self.direction = match self.direction {
+                Direction::Grow => Direction::Shrink,
+                Direction::Shrink => Direction::Grow,
+            };
+        }
+        self.last_throughput = Some(throughput);
+
+        let factor = match self.direction {
+            Direction::Grow => GROW_FACTOR,
+            Direction::Shrink => SHRINK_FACTOR,
+        };
+        self.resize((self.size as f64 * factor) as usize);
+        debug!(
+            points,
+            latency_ms = latency.as_millis() as u64,
+            throughput = throughput as u64,
+            next_batch_size = self.size,
+            "Tuned batch size"
+        );
+    }
+
+    pub fn record_overload(&mut self, reason: &str) {
+        self.direction = Direction::Shrink;
+        self.last_throughput = None;
+        self.resize(self.size / 2);
+        warn!(
+            "InfluxDB is overloaded ({reason}), dropping the batch size to {}",
+            self.size
+        );
+    }
+
+    // Wait before retry `attempt`, doubling from half a second
+    pub fn backoff(attempt: u32) -> Duration {
+        Duration::from_millis(500) * 2u32.pow(attempt.min(6))
+    }
+
+    fn resize(&mut self, size: usize) {
+        self.size = size.clamp(self.tuning.min_batch_size, self.tuning.max_batch_size);
+    }
+}
@@ -0,0 +1,63 @@
+use super::output_files::prepare_output_file;
+use crate::error::{ExportContext, IoContext, Result};
+use crate::models::{CalibrationModel, SensorEnum, TelemetryConfig};
+use serde::Serialize;
+use std::path::Path;
+use tracing::info;
+
+// Sink name reported in export errors
+const SINK: &str = "calibration sidecar";
+
+#[derive(Debug, Serialize)]
+struct CalibrationSidecar<'a> {
+    // Unit system calibrated values are converted into after the curve
+    units: &'a str,
+    sensors: Vec<SensorCalibration<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SensorCalibration<'a> {
+    // Every name the sensor is exported under
+    sensor_types: Vec<&'a str>,
+    model: &'a CalibrationModel,
+    // The curve as a polynomial, lowest order first, whatever the model
+    coefficients: Vec<f64>,
+    unit: &'static str,
+}
+
+// Records the calibration curves in effect as `{output_name}.calibration.json`, so calibration
+// pipelines can be checked against the raw_value column
+pub struct CalibrationExporter;
+
+impl CalibrationExporter {
+    pub fn export(config: &TelemetryConfig, output_dir: &Path, output_name: &str) -> Result<()> {
+        if config.calibrations.is_empty() {
+            return Ok(());
+        }
+
+        let sensors = SensorEnum::get_all_sensor_enums()
+            .into_iter()
+            .filter_map(|sensor| {
+                config.calibration(sensor).map(|model| SensorCalibration {
+                    sensor_types: config.exported_names(sensor),
+                    model,
+                    coefficients: model.coefficients(),
+                    unit: config.units.unit(sensor),
+                })
+            })
+            .collect();
+        let sidecar = CalibrationSidecar {
+            units: config.units.name(),
+            sensors,
+        };
+
+        let sidecar_file = prepare_output_file(output_dir, output_name, "calibration.json")?;
+        let json = serde_json::to_vec_pretty(&sidecar)
+            .export_context(SINK, || "Failed to serialize the calibration curves")?;
+        std::fs::write(&sidecar_file, json)
+            .io_context(|| format!("Failed to write {}", sidecar_file.display()))?;
+
+        info!("Calibration curves written to {}", sidecar_file.display());
+        Ok(())
+    }
+}
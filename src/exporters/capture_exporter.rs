@@ -0,0 +1,48 @@
+use super::output_files::prepare_output_file;
+use crate::error::{IoContext, Result};
+use crate::models::{CaptureWindow, CaptureWindows};
+use std::fmt::Write as _;
+use std::path::Path;
+use tracing::info;
+
+// Lists the windows of a run as `{output_name}.windows.csv`, so review tooling can jump between
+// them without scanning for the gaps. `exported` windows are the ones the file was cut down to,
+// the rest were marked by hooks while generating and are only flagged
+pub struct CaptureExporter;
+
+impl CaptureExporter {
+    pub fn export(
+        exported: Option<&CaptureWindows>,
+        marked: &[CaptureWindow],
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
+        let mut rows: Vec<(&CaptureWindow, bool)> = exported
+            .map(|windows| windows.windows().iter().map(|w| (w, true)).collect())
+            .unwrap_or_default();
+        rows.extend(marked.iter().map(|window| (window, false)));
+        rows.sort_by_key(|(window, _)| (window.start_ms, window.end_ms));
+
+        let mut csv = String::from("start_ms,end_ms,exported,events\n");
+        for (window, exported) in &rows {
+            let _ = writeln!(
+                csv,
+                "{},{},{},\"{}\"",
+                window.start_ms,
+                window.end_ms,
+                exported,
+                window.label.replace('"', "\"\"")
+            );
+        }
+
+        let windows_file = prepare_output_file(output_dir, output_name, "windows.csv")?;
+        std::fs::write(&windows_file, csv)
+            .io_context(|| format!("Failed to write {}", windows_file.display()))?;
+        info!(
+            "{} capture windows written to {}",
+            rows.len(),
+            windows_file.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,58 @@
+use super::output_files::prepare_output_file;
+use crate::error::{IoContext, Result};
+use crate::models::{PairSide, SensorEnum, SensorNaming, TelemetryConfig, sensor_label};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+
+// Sensor catalog written next to every run as `{output_name}.sensors.csv`. One row per exported
+// sensor_type with its unit and a description, so long format readers can join units on
+pub struct SensorCatalogExporter;
+
+impl SensorCatalogExporter {
+    pub fn export(config: &TelemetryConfig, output_dir: &Path, output_name: &str) -> Result<()> {
+        let catalog_file = prepare_output_file(output_dir, output_name, "sensors.csv")?;
+        let file = File::create(&catalog_file)
+            .io_context(|| format!("Failed to create {}", catalog_file.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        let instances: Vec<Option<u16>> = match config.sensor_instances {
+            0 | 1 => vec![None],
+            n => (1..=n).map(Some).collect(),
+        };
+
+        writeln!(writer, "sensor_type,name,unit,description")
+            .io_context(|| format!("Failed to write {}", catalog_file.display()))?;
+        for sensor in SensorEnum::get_all_sensor_enums() {
+            let sides: &[Option<PairSide>] = if config.is_redundant(sensor) {
+                &[Some(PairSide::A), Some(PairSide::B)]
+            } else {
+                &[None]
+            };
+            for ((name, instance), side) in config
+                .exported_names(sensor)
+                .into_iter()
+                .flat_map(|name| instances.iter().map(move |instance| (name, instance)))
+                .flat_map(|named| sides.iter().map(move |side| (named, side)))
+            {
+                // Descriptions have commas in them so they get quoted
+                writeln!(
+                    writer,
+                    "{},{},{},\"{}\"",
+                    sensor_label(name, *instance, *side),
+                    sensor.name(SensorNaming::Full),
+                    config.units.unit(sensor),
+                    sensor.description(),
+                )
+                .io_context(|| format!("Failed to write {}", catalog_file.display()))?;
+            }
+        }
+        writer
+            .flush()
+            .io_context(|| format!("Failed to write {}", catalog_file.display()))?;
+
+        info!("Sensor catalog written to {}", catalog_file.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use super::output_files::prepare_output_file;
+use super::stream_sink::{Frame, FrameEncoder, FrameValue, SinkTarget, StreamExporter};
+use super::stream_sink::{FrameFormat, TextCompression};
+use crate::error::{IoContext, Result};
+use crate::models::{MissionPhase, PairSide, SensorEnum, SensorNaming};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use tracing::info;
+
+// CCSDS space packets, the downlink framing ground stations decode. A packet per reading with the
+// 6 byte primary header, a 7 byte time secondary header and the reading as user data, all big
+// endian. See CCSDS 133.0-B-2 for the packet and 301.0-B-4 for the time code
+
+// APIDs are handed out in catalog order from here, leaving the low ones free for housekeeping
+pub const CCSDS_APID_BASE: u16 = 0x100;
+
+// Sequence counts are 14 bits and wrap
+const SEQUENCE_COUNT_MASK: u16 = 0x3FFF;
+
+// Version 0, telemetry, secondary header present
+const PACKET_ID_FLAGS: u16 = 1 << 11;
+
+// Unsegmented, each packet stands alone
+const SEQUENCE_FLAGS: u16 = 0b11 << 14;
+
+// Type of the value in the user data, its first byte
+const VALUE_FLOAT: u8 = 0;
+const VALUE_INT: u8 = 1;
+const VALUE_UNSIGNED: u8 = 2;
+const VALUE_STRING: u8 = 3;
+
+// Packet of a sensor, the same for every run
+pub fn ccsds_apid(sensor: SensorEnum) -> u16 {
+    let position = SensorEnum::get_all_sensor_enums()
+        .iter()
+        .position(|other| *other == sensor)
+        .expect("Every sensor is in the catalog");
+    CCSDS_APID_BASE + position as u16
+}
+
+// The readings as `{output_name}.ccsds`, back to back space packets with no other framing, and the
+// APID of each sensor as `{output_name}.apids.csv`. Over tcp or udp the same packets come from
+// `--format ccsds`, a datagram each on udp
+pub struct CcsdsExporter;
+
+impl CcsdsExporter {
+    pub fn stream_exporter(output_dir: &Path, output_name: &str) -> Result<StreamExporter> {
+        Self::export_apids(output_dir, output_name)?;
+        Ok(StreamExporter {
+            target: SinkTarget::File(
+                prepare_output_file(output_dir, output_name, FrameFormat::Ccsds.extension())?,
+                TextCompression::None,
+            ),
+            format: FrameFormat::Ccsds,
+        })
+    }
+
+    // One row per APID for setting up the decoder
+    fn export_apids(output_dir: &Path, output_name: &str) -> Result<()> {
+        let apids_file = prepare_output_file(output_dir, output_name, "apids.csv")?;
+        let file = File::create(&apids_file)
+            .io_context(|| format!("Failed to create {}", apids_file.display()))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "apid,sensor,name")
+            .io_context(|| format!("Failed to write {}", apids_file.display()))?;
+        for sensor in SensorEnum::get_all_sensor_enums() {
+            writeln!(
+                writer,
+                "{},{},{}",
+                ccsds_apid(sensor),
+                sensor.field_name(),
+                sensor.name(SensorNaming::Full)
+            )
+            .io_context(|| format!("Failed to write {}", apids_file.display()))?;
+        }
+        writer
+            .flush()
+            .io_context(|| format!("Failed to write {}", apids_file.display()))?;
+        info!("CCSDS APIDs written to {}", apids_file.display());
+        Ok(())
+    }
+}
+
+// Encodes reading frames as space packets. Start and end frames have no packet
+#[derive(Default)]
+pub(super) struct CcsdsEncoder {
+    // Next sequence count of each APID
+    sequence_counts: Vec<u16>,
+}
+
+impl FrameEncoder for CcsdsEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        let Frame::Reading(frame) = frame else {
+            return Ok(());
+        };
+        let reading = frame.reading;
+        let apid = ccsds_apid(reading.sensor);
+        let slot = usize::from(apid - CCSDS_APID_BASE);
+        if self.sequence_counts.len() <= slot {
+            self.sequence_counts.resize(slot + 1, 0);
+        }
+        let sequence_count = self.sequence_counts[slot];
+        self.sequence_counts[slot] = (sequence_count + 1) & SEQUENCE_COUNT_MASK;
+
+        // Length goes in once the data field is written
+        let start = out.len();
+        out.extend_from_slice(&(PACKET_ID_FLAGS | apid).to_be_bytes());
+        out.extend_from_slice(&(SEQUENCE_FLAGS | sequence_count).to_be_bytes());
+        out.extend_from_slice(&[0, 0]);
+
+        // Secondary header, a CUC time of 4 bytes of seconds and 3 bytes of 2^-24 second
+        // fractions since 1970-01-01 UTC, the P-field left implicit
+        let seconds = reading.timestamp.timestamp() as u32;
+        let fraction =
+            ((u64::from(reading.timestamp.timestamp_subsec_nanos()) << 24) / 1_000_000_000) as u32;
+        out.extend_from_slice(&seconds.to_be_bytes());
+        out.extend_from_slice(&fraction.to_be_bytes()[1..]);
+
+        // User data: value type, phase, instance (0 unless fanned out), side (0 none, 1 a, 2 b),
+        // then the value. Strings run to the end of the packet
+        let value_type = match frame.value {
+            FrameValue::Float(_) => VALUE_FLOAT,
+            FrameValue::Int(_) => VALUE_INT,
+            FrameValue::UnsignedInt(_) => VALUE_UNSIGNED,
+            FrameValue::String(_) => VALUE_STRING,
+        };
+        let phase = MissionPhase::ALL
+            .iter()
+            .position(|other| *other == reading.phase)
+            .expect("Every phase is in ALL") as u8;
+        let side = match reading.side {
+            None => 0u8,
+            Some(PairSide::A) => 1,
+            Some(PairSide::B) => 2,
+        };
+        out.extend_from_slice(&[value_type, phase]);
+        out.extend_from_slice(&reading.instance.unwrap_or(0).to_be_bytes());
+        out.push(side);
+        match frame.value {
+            FrameValue::Float(f) => out.extend_from_slice(&f.to_be_bytes()),
+            FrameValue::Int(i) => out.extend_from_slice(&i.to_be_bytes()),
+            FrameValue::UnsignedInt(u) => out.extend_from_slice(&u.to_be_bytes()),
+            FrameValue::String(s) => out.extend_from_slice(s.as_bytes()),
+        }
+
+        // Bytes after the primary header, less one
+        let data_length = (out.len() - start - 7) as u16;
+        out[start + 4..start + 6].copy_from_slice(&data_length.to_be_bytes());
+        Ok(())
+    }
+}
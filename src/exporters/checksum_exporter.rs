@@ -0,0 +1,343 @@
+use super::output_files::{output_file_path, run_output_files};
+use crate::error::{IoContext, Result};
+use crate::models::{SensorValue, TelemetryReading};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::info;
+
+// Extension of the checksum sidecar, left out of the files it covers
+pub const CHECKSUM_EXTENSION: &str = "sha256";
+
+// Bytes read from disk at a time while hashing
+const READ_BUFFER_BYTES: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+pub struct FileChecksum {
+    pub path: PathBuf,
+    // Lowercase hex SHA-256
+    pub sha256: String,
+}
+
+// Hashes every file a run produced, so copies moved between machines and object stores can be
+// verified. The sidecar `{output_name}.sha256` is in `sha256sum` format, next to the files:
+//
+//   cd output && sha256sum -c SIM-001_1000hz_120s.sha256
+pub struct ChecksumExporter;
+
+impl ChecksumExporter {
+    pub fn export(
+        output_dir: &Path,
+        output_name: &str,
+        sidecar: bool,
+    ) -> Result<Vec<FileChecksum>> {
+        let start_time = Instant::now();
+        let sidecar_file = output_file_path(output_dir, output_name, CHECKSUM_EXTENSION);
+        let mut checksums = Vec::new();
+        for path in run_output_files(output_dir, output_name) {
+            if path == sidecar_file {
+                continue;
+            }
+            let sha256 = sha256_file(&path)?;
+            checksums.push(FileChecksum { path, sha256 });
+        }
+        info!(
+            "Hashed {} output files in {:.2?}s",
+            checksums.len(),
+            start_time.elapsed().as_secs_f64()
+        );
+
+        if sidecar {
+            let mut lines = String::new();
+            for checksum in &checksums {
+                let file_name = checksum.path.file_name().unwrap_or_default();
+                let _ = writeln!(
+                    lines,
+                    "{}  {}",
+                    checksum.sha256,
+                    file_name.to_string_lossy()
+                );
+            }
+            std::fs::write(&sidecar_file, lines)
+                .io_context(|| format!("Failed to write {}", sidecar_file.display()))?;
+            info!("Checksums written to {}", sidecar_file.display());
+        }
+        Ok(checksums)
+    }
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; READ_BUFFER_BYTES];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .io_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finish_hex())
+}
+
+// SHA-256 over the readings of a run themselves, fed a chunk at a time. Covers everything a
+// reading holds, with floats by their bits, so two runs only match if every value matches
+// exactly. Independent of how the run was chunked and of any output format
+pub struct ReadingDigest {
+    hasher: Sha256,
+    readings: usize,
+    buffer: Vec<u8>,
+}
+
+impl Default for ReadingDigest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReadingDigest {
+    pub fn new() -> Self {
+        Self {
+            hasher: Sha256::new(),
+            readings: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, readings: &[TelemetryReading]) {
+        let buffer = &mut self.buffer;
+        for reading in readings {
+            buffer.clear();
+            buffer.extend_from_slice(&reading.timestamp.timestamp().to_le_bytes());
+            buffer.extend_from_slice(&reading.timestamp.timestamp_subsec_nanos().to_le_bytes());
+            buffer.extend_from_slice(&reading.time_since_launch_ms.to_le_bytes());
+            buffer.extend_from_slice(reading.phase.name().as_bytes());
+            buffer.push(0);
+            buffer.extend_from_slice(reading.sensor.field_name().as_bytes());
+            buffer.push(0);
+            buffer.extend_from_slice(
+                &reading
+                    .instance
+                    .map_or(0, |instance| instance as u32 + 1)
+                    .to_le_bytes(),
+            );
+            buffer.push(reading.side.map_or(0, |side| side.suffix().as_bytes()[0]));
+            // Tagged so an int and a float with the same bits differ
+            match &reading.value {
+                SensorValue::Float(f) => {
+                    buffer.push(b'f');
+                    buffer.extend_from_slice(&f.to_bits().to_le_bytes());
+                }
+                SensorValue::Int(i) => {
+                    buffer.push(b'i');
+                    buffer.extend_from_slice(&i.to_le_bytes());
+                }
+                SensorValue::UnsignedInt(u) => {
+                    buffer.push(b'u');
+                    buffer.extend_from_slice(&u.to_le_bytes());
+                }
+                SensorValue::String(s) => {
+                    buffer.push(b's');
+                    buffer.extend_from_slice(&(s.len() as u64).to_le_bytes());
+                    buffer.extend_from_slice(s.as_bytes());
+                }
+                SensorValue::State(state) => {
+                    buffer.push(b'e');
+                    buffer.extend_from_slice(state.name().as_bytes());
+                    buffer.push(0);
+                }
+            }
+            self.hasher.update(buffer);
+        }
+        self.readings += readings.len();
+    }
+
+    pub fn readings(&self) -> usize {
+        self.readings
+    }
+
+    // Lowercase hex SHA-256
+    pub fn finish_hex(self) -> String {
+        self.hasher.finish_hex()
+    }
+}
+
+// FIPS 180-4 SHA-256. Hand rolled as no hashing crate is a dependency
+struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    total_bytes: u64,
+}
+
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+impl Sha256 {
+    fn new() -> Self {
+        Self {
+            state: INITIAL_STATE,
+            block: [0; 64],
+            block_len: 0,
+            total_bytes: 0,
+        }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        self.total_bytes += data.len() as u64;
+        // Top up a partial block first
+        if self.block_len > 0 {
+            let take = (64 - self.block_len).min(data.len());
+            self.block[self.block_len..self.block_len + take].copy_from_slice(&data[..take]);
+            self.block_len += take;
+            data = &data[take..];
+            if self.block_len < 64 {
+                return;
+            }
+            let block = self.block;
+            self.compress(&block);
+            self.block_len = 0;
+        }
+        let mut blocks = data.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block.try_into().expect("Chunks are 64 bytes"));
+        }
+        let rest = blocks.remainder();
+        self.block[..rest.len()].copy_from_slice(rest);
+        self.block_len = rest.len();
+    }
+
+    fn finish_hex(mut self) -> String {
+        let bit_length = self.total_bytes.wrapping_mul(8);
+        // A one bit, zeros up to 56 bytes into a block, then the length
+        let padding_len = if self.block_len < 56 {
+            56 - self.block_len
+        } else {
+            120 - self.block_len
+        };
+        let mut padding = [0u8; 72];
+        padding[0] = 0x80;
+        padding[padding_len..padding_len + 8].copy_from_slice(&bit_length.to_be_bytes());
+        let total_bytes = self.total_bytes;
+        self.update(&padding[..padding_len + 8]);
+        debug_assert_eq!(self.block_len, 0);
+        self.total_bytes = total_bytes;
+
+        self.state
+            .iter()
+            .fold(String::with_capacity(64), |mut hex, word| {
+                let _ = write!(hex, "{word:08x}");
+                hex
+            })
+    }
+
+    fn compress(&mut self, block: &[u8; 64]) {
+        let mut schedule = [0u32; 64];
+        for (word, bytes) in schedule.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes(bytes.try_into().expect("Chunks are 4 bytes"));
+        }
+        for t in 16..64 {
+            let s0 = schedule[t - 15].rotate_right(7)
+                ^ schedule[t - 15].rotate_right(18)
+                ^ (schedule[t - 15] >> 3);
+            let s1 = schedule[t - 2].rotate_right(17)
+                ^ schedule[t - 2].rotate_right(19)
+                ^ (schedule[t - 2] >> 10);
+            schedule[t] = schedule[t - 16]
+                .wrapping_add(s0)
+                .wrapping_add(schedule[t - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for t in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(ROUND_CONSTANTS[t])
+                .wrapping_add(schedule[t]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+        for (word, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::TelemetryError;
+    use crate::generators::{ChaosLevel, TelemetryGenerator};
+    use crate::models::{SampleRate, TelemetryConfig};
+    use chrono::DateTime;
+    use std::ops::ControlFlow;
+
+    // Readings and digest of a run cut into chunks of `chunk_readings`
+    fn digest(config: &TelemetryConfig, chunk_readings: usize) -> (usize, String) {
+        let mut generator = TelemetryGenerator::new(config.clone()).with_chaos(ChaosLevel::Medium);
+        let mut digest = ReadingDigest::new();
+        generator
+            .generate_chunked(true, chunk_readings, |chunk| {
+                digest.update(&chunk.readings);
+                Ok::<_, TelemetryError>(ControlFlow::Continue(()))
+            })
+            .unwrap();
+        (digest.readings(), digest.finish_hex())
+    }
+
+    #[test]
+    fn digest_is_the_same_for_any_chunk_size() {
+        let config = TelemetryConfig {
+            duration: 10,
+            sample_rate_hz: SampleRate::from_hz(100),
+            batch_noise: true,
+            sensor_instances: 2,
+            launch_time: DateTime::from_timestamp(1_700_000_000, 0),
+            ..TelemetryConfig::default()
+        };
+        let whole = digest(&config, usize::MAX);
+        assert!(whole.0 > 0);
+        for chunk_readings in [config.readings_per_step(), 1_000, 4_099] {
+            assert_eq!(
+                digest(&config, chunk_readings),
+                whole,
+                "{chunk_readings} per chunk"
+            );
+        }
+
+        let reseeded = TelemetryConfig {
+            seed: config.seed + 1,
+            ..config
+        };
+        assert_ne!(digest(&reseeded, usize::MAX).1, whole.1);
+    }
+}
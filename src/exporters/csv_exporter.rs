@@ -1,7 +1,10 @@
+use super::output_files::prepare_output_file;
+use super::stream_sink::{FrameFormat, SinkTarget, StreamExporter, TextCompression};
+use crate::error::{IoContext, Result};
 use crate::models::TelemetryDataset;
-use anyhow::{Context, Result};
 use std::fs::File;
 use std::io::Write;
+use std::path::Path;
 use tracing::info;
 
 pub struct CsvMetadataExporter;
@@ -9,36 +12,68 @@ pub struct CsvMetadataExporter;
 impl CsvMetadataExporter {
     // Export telemetry meta data around run
 
-    pub fn export(dataset: &TelemetryDataset, output_name: &str) -> Result<()> {
+    pub fn export(dataset: &TelemetryDataset, output_dir: &Path, output_name: &str) -> Result<()> {
         info!("Inside export csv function");
 
         // Create the file first
-        let csv_file = format!("output/{output_name}.metadata.csv");
-        info!("Writing file to: {}", csv_file);
+        let csv_file = prepare_output_file(output_dir, output_name, "metadata.csv")?;
+        info!("Writing file to: {}", csv_file.display());
         let mut output_file: File = File::create(&csv_file)
-            .with_context(|| format!("Failed to create the file yo! {}", &csv_file))?;
+            .io_context(|| format!("Failed to create the file yo! {}", csv_file.display()))?;
 
         // Write the header
         writeln!(
             output_file,
-            "launch_id,launch_time,time_since_launch_ms,vehicle_type,engine_type,sample_rate_hz"
-        )?;
+            "launch_id,launch_time,time_since_launch_ms,vehicle_type,engine_type,sample_rate_hz,units"
+        )
+        .io_context(|| format!("Failed to write {}", csv_file.display()))?;
 
         // Only 1 row to write
         if let Some(first) = dataset.readings.first() {
             writeln!(
                 output_file,
-                "{},{},{},{},{},{}",
-                "id_1",
+                "{},{},{},Kerbal,Narwhal,{},{}",
+                dataset.config.launch_id,
                 dataset.launch_time,
                 first.time_since_launch_ms,
-                "Kerbal",
-                "Narwhal",
-                "todo:pass_me_in_sir",
-            )?;
+                dataset.config.sample_rate_hz,
+                dataset.config.units.name(),
+            )
+            .io_context(|| format!("Failed to write {}", csv_file.display()))?;
         }
 
-        info!("Csv file write completed to {}", csv_file);
+        info!("Csv file write completed to {}", csv_file.display());
         Ok(())
     }
 }
+
+// Every reading as `{output_name}.csv`, or `.csv.gz` and `.csv.zst` when compressed. The data
+// file of `--format csv`, with the same columns and encoder as `--text-output csv`
+pub struct CsvDataExporter;
+
+impl CsvDataExporter {
+    // File sink for the readings, for runs that stream them chunk by chunk
+    pub fn stream_exporter(
+        output_dir: &Path,
+        output_name: &str,
+        compression: TextCompression,
+    ) -> Result<StreamExporter> {
+        let extension = format!("{}{}", FrameFormat::Csv.extension(), compression.suffix());
+        Ok(StreamExporter {
+            target: SinkTarget::File(
+                prepare_output_file(output_dir, output_name, &extension)?,
+                compression,
+            ),
+            format: FrameFormat::Csv,
+        })
+    }
+
+    pub fn export(
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+        compression: TextCompression,
+    ) -> Result<()> {
+        Self::stream_exporter(output_dir, output_name, compression)?.export(dataset, output_name)
+    }
+}
@@ -0,0 +1,164 @@
+use crate::error::{IoContext, Result, TelemetryError};
+use crate::models::MIN_CHUNK_READINGS;
+use num_format::{Locale, ToFormattedString};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Rough compressed parquet bytes per reading, measured on 1 kHz runs with the default settings
+const ESTIMATED_BYTES_PER_READING: u64 = 16;
+
+// Chunk size to stream in when only the output size is capped, so the cap is checked
+// between chunks instead of after the whole run
+pub const WATCHED_CHUNK_READINGS: usize = 1_000_000;
+
+// Aim for this many chunks under the output cap so a run stops reasonably close to it
+const CHUNKS_PER_OUTPUT_CAP: u64 = 8;
+
+// Space to leave free on the disk so the footer, metadata and everyone else still fit
+const DISK_HEADROOM_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogVerdict {
+    Continue,
+    // The next chunk would push the output past --max-output-bytes
+    OutputCapReached,
+    // The next chunk may not fit on the disk
+    DiskLow { available_bytes: u64 },
+}
+
+// Keeps a long run from dying to a full disk at 95% completion.
+// Checks free space up front against the estimated output size, then again between chunks.
+pub struct DiskWatchdog {
+    output_dir: PathBuf,
+    max_output_bytes: Option<u64>,
+}
+
+impl DiskWatchdog {
+    pub fn new(output_dir: &Path, max_output_bytes: Option<u64>) -> Self {
+        Self {
+            output_dir: output_dir.to_path_buf(),
+            max_output_bytes,
+        }
+    }
+
+    pub fn estimate_output_bytes(readings: usize) -> u64 {
+        readings as u64 * ESTIMATED_BYTES_PER_READING
+    }
+
+    // Shrink chunks so the output cap is checked well before it can be passed
+    pub fn chunk_readings(&self, chunk_readings: usize) -> usize {
+        match self.max_output_bytes {
+            Some(max_output_bytes) => {
+                let capped_readings =
+                    max_output_bytes / CHUNKS_PER_OUTPUT_CAP / ESTIMATED_BYTES_PER_READING;
+                chunk_readings.min((capped_readings as usize).max(MIN_CHUNK_READINGS))
+            }
+            None => chunk_readings,
+        }
+    }
+
+    // Fail fast before generating anything if the output can't fit
+    pub fn preflight(&self, estimated_bytes: u64) -> Result<()> {
+        let mut needed_bytes = estimated_bytes;
+        if let Some(max_output_bytes) = self.max_output_bytes
+            && estimated_bytes > max_output_bytes
+        {
+            warn!(
+                "Estimated output of {} bytes exceeds the max output size of {} bytes. The run will stop early",
+                fmt_bytes(estimated_bytes),
+                fmt_bytes(max_output_bytes)
+            );
+            needed_bytes = max_output_bytes;
+        }
+
+        let Some(available_bytes) = available_space(&self.output_dir)? else {
+            warn!("Can't check free disk space on this platform. Skipping the check");
+            return Ok(());
+        };
+        info!(
+            "Estimated output is {} bytes with {} bytes free in {}",
+            fmt_bytes(estimated_bytes),
+            fmt_bytes(available_bytes),
+            self.output_dir.display()
+        );
+
+        if needed_bytes + DISK_HEADROOM_BYTES > available_bytes {
+            return Err(TelemetryError::IoError {
+                message: format!(
+                    "Not enough disk space in {}. The output needs about {} bytes but only {} are free. \
+                    Free up space, shorten the run or cap it with --max-output-bytes",
+                    self.output_dir.display(),
+                    fmt_bytes(needed_bytes + DISK_HEADROOM_BYTES),
+                    fmt_bytes(available_bytes)
+                ),
+                source: io::ErrorKind::StorageFull.into(),
+            });
+        }
+        Ok(())
+    }
+
+    // Between chunks, check the next one still fits under the cap and on the disk.
+    // Sizes the next chunk from the bytes per reading written so far
+    pub fn check(
+        &self,
+        bytes_written: u64,
+        readings_written: usize,
+        next_chunk_readings: usize,
+    ) -> Result<WatchdogVerdict> {
+        let bytes_per_reading = if readings_written == 0 {
+            ESTIMATED_BYTES_PER_READING
+        } else {
+            bytes_written.div_ceil(readings_written as u64)
+        };
+        let next_chunk_bytes = bytes_per_reading * next_chunk_readings as u64;
+
+        if let Some(max_output_bytes) = self.max_output_bytes
+            && bytes_written + next_chunk_bytes > max_output_bytes
+        {
+            return Ok(WatchdogVerdict::OutputCapReached);
+        }
+
+        if let Some(available_bytes) = available_space(&self.output_dir)?
+            && next_chunk_bytes + DISK_HEADROOM_BYTES > available_bytes
+        {
+            return Ok(WatchdogVerdict::DiskLow { available_bytes });
+        }
+
+        Ok(WatchdogVerdict::Continue)
+    }
+}
+
+fn fmt_bytes(bytes: u64) -> String {
+    bytes.to_formatted_string(&Locale::en)
+}
+
+// Bytes available to unprivileged users on the disk holding `dir`.
+// The output directory may not be created yet, so check its closest existing parent
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Result<Option<u64>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = dir
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .unwrap_or(Path::new("."));
+    let c_path = CString::new(dir.as_os_str().as_bytes())
+        .map_err(io::Error::from)
+        .io_context(|| format!("Invalid output path {}", dir.display()))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: c_path is a valid nul terminated string and stat is a valid out pointer
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error())
+            .io_context(|| format!("Failed to check free disk space in {}", dir.display()));
+    }
+
+    #[allow(clippy::unnecessary_cast)] // The field types differ between platforms
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Result<Option<u64>> {
+    Ok(None)
+}
@@ -0,0 +1,43 @@
+use super::output_files::prepare_output_file;
+use crate::error::{ExportContext, IoContext, Result};
+use crate::models::{SchemaChange, TelemetryConfig};
+use serde::Serialize;
+use std::path::Path;
+use tracing::info;
+
+// Sink name reported in export errors
+const SINK: &str = "evolution manifest";
+
+#[derive(Debug, Serialize)]
+struct EvolutionManifest<'a> {
+    launch_id: &'a str,
+    changes: &'a [SchemaChange],
+}
+
+// Records the schema evolution plan as `{output_name}.evolution.json`, so tests of downstream
+// readers know which sensors should appear or be renamed and when
+pub struct EvolutionManifestExporter;
+
+impl EvolutionManifestExporter {
+    pub fn export(config: &TelemetryConfig, output_dir: &Path, output_name: &str) -> Result<()> {
+        if config.schema_evolution.is_empty() {
+            return Ok(());
+        }
+
+        let manifest_file = prepare_output_file(output_dir, output_name, "evolution.json")?;
+        let manifest = EvolutionManifest {
+            launch_id: &config.launch_id,
+            changes: &config.schema_evolution,
+        };
+        let json = serde_json::to_vec_pretty(&manifest)
+            .export_context(SINK, || "Failed to serialize the schema evolution plan")?;
+        std::fs::write(&manifest_file, json)
+            .io_context(|| format!("Failed to write {}", manifest_file.display()))?;
+
+        info!(
+            "Schema evolution plan written to {}",
+            manifest_file.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use crate::models::TelemetryReading;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+// Sink name reported in export errors
+const SINK: &str = "journal";
+
+// Progress of a streaming export, saved after every confirmed batch to
+// `{output_dir}/{launch_id}.{sink}.journal.json`. It's what tells you what landed when an
+// export dies half way, and what `--resume-export` continues from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportJournal {
+    #[serde(flatten)]
+    pub run: ExportRun,
+    pub launch_time: DateTime<Utc>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub attempts: u32,
+    pub status: ExportStatus,
+    pub confirmed_batches: usize,
+    pub confirmed_readings: usize,
+    pub last_time_since_launch_ms: Option<u64>,
+    pub last_timestamp: Option<DateTime<Utc>>,
+    // Why the last attempt failed
+    pub error: Option<String>,
+}
+
+// What was exported where. A resume has to match it exactly, or the regenerated readings
+// wouldn't line up with the ones already sent
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportRun {
+    pub sink: String,
+    // Where the sink writes, e.g. the url, org and bucket
+    pub target: String,
+    pub launch_id: String,
+    pub seed: u64,
+    pub sample_rate_hz: String,
+    pub duration_s: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportStatus {
+    Running,
+    Failed,
+    Complete,
+}
+
+impl ExportJournal {
+    pub fn new(run: ExportRun, launch_time: DateTime<Utc>) -> Self {
+        let now = Utc::now();
+        Self {
+            run,
+            launch_time,
+            started_at: now,
+            updated_at: now,
+            attempts: 0,
+            status: ExportStatus::Running,
+            confirmed_batches: 0,
+            confirmed_readings: 0,
+            last_time_since_launch_ms: None,
+            last_timestamp: None,
+            error: None,
+        }
+    }
+
+    pub fn path(output_dir: &Path, run: &ExportRun) -> PathBuf {
+        output_dir.join(format!("{}.{}.journal.json", run.launch_id, run.sink))
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read export journal {}", path.display()))?;
+        serde_json::from_str(&text).map(Some).map_err(|e| {
+            TelemetryError::config(format!(
+                "Export journal {} is unreadable: {e}",
+                path.display()
+            ))
+        })
+    }
+
+    // Check a previous attempt can be picked up by `run`
+    pub fn check_resumable(&self, run: &ExportRun) -> Result<()> {
+        if self.status == ExportStatus::Complete {
+            return Err(TelemetryError::config(format!(
+                "The {} export of {} already completed with {} readings. Nothing to resume",
+                self.run.sink, self.run.launch_id, self.confirmed_readings
+            )));
+        }
+        let (was, now) = (&self.run, run);
+        let mut differences = Vec::new();
+        if was.target != now.target {
+            differences.push(format!("target {} is now {}", was.target, now.target));
+        }
+        if was.seed != now.seed {
+            differences.push(format!("seed {} is now {}", was.seed, now.seed));
+        }
+        if was.sample_rate_hz != now.sample_rate_hz {
+            differences.push(format!(
+                "rate {} Hz is now {} Hz",
+                was.sample_rate_hz, now.sample_rate_hz
+            ));
+        }
+        if was.duration_s != now.duration_s {
+            differences.push(format!(
+                "duration {}s is now {}s",
+                was.duration_s, now.duration_s
+            ));
+        }
+        if !differences.is_empty() {
+            return Err(TelemetryError::config(format!(
+                "Can't resume with different settings, the regenerated readings wouldn't line \
+                up: {}",
+                differences.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    // Start an attempt, counting it
+    pub fn begin(&mut self, path: &Path) -> Result<()> {
+        self.attempts += 1;
+        self.status = ExportStatus::Running;
+        self.error = None;
+        self.save(path)
+    }
+
+    // A batch landed in the sink
+    pub fn confirm(&mut self, path: &Path, batch: &[TelemetryReading]) -> Result<()> {
+        self.confirmed_batches += 1;
+        self.confirmed_readings += batch.len();
+        if let Some(last) = batch.iter().max_by_key(|r| r.time_since_launch_ms) {
+            self.last_time_since_launch_ms = Some(last.time_since_launch_ms);
+            self.last_timestamp = Some(last.timestamp);
+        }
+        self.save(path)
+    }
+
+    pub fn fail(&mut self, path: &Path, error: String) -> Result<()> {
+        self.status = ExportStatus::Failed;
+        self.error = Some(error);
+        self.save(path)
+    }
+
+    pub fn complete(&mut self, path: &Path) -> Result<()> {
+        self.status = ExportStatus::Complete;
+        self.save(path)
+    }
+
+    // Written next to the journal and renamed over it so a crash never leaves half a file
+    fn save(&mut self, path: &Path) -> Result<()> {
+        self.updated_at = Utc::now();
+        let json = serde_json::to_string_pretty(self)
+            .export_context(SINK, || "Failed to serialize the export journal")?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json)
+            .io_context(|| format!("Failed to write export journal {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .io_context(|| format!("Failed to write export journal {}", path.display()))
+    }
+}
@@ -0,0 +1,297 @@
+use super::output_files::prepare_output_file;
+use crate::error::{ExportContext, Result, TelemetryError};
+use crate::models::{SensorValue, TelemetryDataset};
+use chrono::{DateTime, SecondsFormat, Utc};
+use hdf5::types::VarLenUnicode;
+use hdf5::{Dataset, File, Group, H5Type, Location};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Sink name reported in export errors
+const SINK: &str = "hdf5";
+
+// Rows HDF5 grows a channel's datasets by at a time
+const CHUNK_ROWS: usize = 4096;
+
+// The readings as HDF5 for flight test analysis tools, `/{launch_id}/{sensor}/time` and `value`
+// per sensor. Times are seconds since launch, jitter included, and values are in the exported
+// units with strings and discrete states by name. The run group has the run's settings as
+// attributes, and each sensor group its unit and description
+pub struct Hdf5Exporter;
+
+impl Hdf5Exporter {
+    pub fn export(dataset: &TelemetryDataset, output_dir: &Path, output_name: &str) -> Result<()> {
+        if dataset.readings.is_empty() {
+            warn!("No readings to export. Exiting export.");
+            return Ok(());
+        }
+        let mut writer = Self::create_writer(dataset, output_dir, output_name)?;
+        writer.write_chunk(dataset)?;
+        writer.close()
+    }
+
+    // Create the file and the run group with its attributes from the first chunk. Sensor groups
+    // are added as their first reading comes
+    pub fn create_writer(
+        first: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<Hdf5StreamWriter> {
+        let hdf5_file = prepare_output_file(output_dir, output_name, "h5")?;
+        let file = File::create(&hdf5_file)
+            .export_context(SINK, || format!("Failed to create {}", hdf5_file.display()))?;
+        let config = &first.config;
+        let run = file
+            .create_group(&config.launch_id)
+            .export_context(SINK, || "Failed to create the run group")?;
+        write_text_attr(&run, "launch_id", &config.launch_id)?;
+        write_text_attr(
+            &run,
+            "launch_time",
+            &first
+                .launch_time
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+        )?;
+        write_attr(
+            &run,
+            "sample_rate_hz",
+            1.0 / config.sample_rate_hz.period_s(),
+        )?;
+        write_attr(&run, "duration_s", config.duration)?;
+        write_attr(&run, "seed", config.seed)?;
+        write_text_attr(&run, "units", config.units.name())?;
+
+        Ok(Hdf5StreamWriter {
+            file,
+            run,
+            hdf5_file,
+            launch_time: first.launch_time,
+            channels: HashMap::new(),
+            readings_written: 0,
+        })
+    }
+}
+
+// Streams chunks of readings into a single HDF5 file, growing each sensor's datasets
+pub struct Hdf5StreamWriter {
+    file: File,
+    run: Group,
+    hdf5_file: PathBuf,
+    launch_time: DateTime<Utc>,
+    channels: HashMap<String, Channel>,
+    readings_written: usize,
+}
+
+struct Channel {
+    time: Dataset,
+    value: Dataset,
+    rows: usize,
+}
+
+// A chunk's readings of one sensor, typed by its first reading
+enum ChannelValues {
+    Float(Vec<f64>),
+    Int(Vec<i64>),
+    UnsignedInt(Vec<u64>),
+    Text(Vec<VarLenUnicode>),
+}
+
+impl Hdf5StreamWriter {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        let config = &chunk.config;
+        let mut columns: HashMap<String, (Vec<f64>, ChannelValues)> = HashMap::new();
+        for reading in &chunk.readings {
+            let label = reading.sensor_label(config);
+            if !self.channels.contains_key(label.as_ref()) {
+                let channel = self.create_channel(
+                    &label,
+                    &reading.value,
+                    config.units.unit(reading.sensor),
+                    reading.sensor.description(),
+                )?;
+                self.channels.insert(label.to_string(), channel);
+            }
+            let (times, values) = columns
+                .entry(label.into_owned())
+                .or_insert_with(|| (Vec::new(), ChannelValues::new(&reading.value)));
+            let since_launch = reading.timestamp - self.launch_time;
+            times.push(since_launch.num_microseconds().unwrap_or(i64::MAX) as f64 / 1e6);
+            match (values, &reading.value) {
+                (ChannelValues::Float(values), SensorValue::Float(f)) => {
+                    values.push(config.export_float(reading.sensor, *f))
+                }
+                (ChannelValues::Int(values), SensorValue::Int(i)) => values.push(*i),
+                (ChannelValues::UnsignedInt(values), SensorValue::UnsignedInt(u)) => {
+                    values.push(*u)
+                }
+                (ChannelValues::Text(values), SensorValue::String(s)) => values.push(text(s)?),
+                (ChannelValues::Text(values), SensorValue::State(state)) => {
+                    values.push(text(state.name())?)
+                }
+                (_, value) => {
+                    return Err(TelemetryError::ExportError {
+                        sink: SINK.to_string(),
+                        message: format!("{value:?} doesn't match the type of the channel"),
+                        source: None,
+                    });
+                }
+            }
+        }
+
+        for (label, (times, values)) in columns {
+            let channel = self
+                .channels
+                .get_mut(&label)
+                .expect("Channels are created before their readings are collected");
+            let start = channel.rows;
+            let end = start + times.len();
+            append(&channel.time, &times, start, end)?;
+            match &values {
+                ChannelValues::Float(values) => append(&channel.value, values, start, end),
+                ChannelValues::Int(values) => append(&channel.value, values, start, end),
+                ChannelValues::UnsignedInt(values) => append(&channel.value, values, start, end),
+                ChannelValues::Text(values) => append(&channel.value, values, start, end),
+            }?;
+            channel.rows = end;
+        }
+        self.readings_written += chunk.readings.len();
+        Ok(())
+    }
+
+    fn create_channel(
+        &self,
+        label: &str,
+        value: &SensorValue,
+        unit: &str,
+        description: &str,
+    ) -> Result<Channel> {
+        let group = self
+            .run
+            .create_group(label)
+            .export_context(SINK, || format!("Failed to create the {label} group"))?;
+        write_text_attr(&group, "unit", unit)?;
+        write_text_attr(&group, "description", description)?;
+        let time = create_dataset::<f64>(&group, "time")?;
+        write_text_attr(&time, "unit", "s")?;
+        let value = match ChannelValues::new(value) {
+            ChannelValues::Float(_) => create_dataset::<f64>(&group, "value"),
+            ChannelValues::Int(_) => create_dataset::<i64>(&group, "value"),
+            ChannelValues::UnsignedInt(_) => create_dataset::<u64>(&group, "value"),
+            ChannelValues::Text(_) => create_dataset::<VarLenUnicode>(&group, "value"),
+        }?;
+        write_text_attr(&value, "unit", unit)?;
+        Ok(Channel {
+            time,
+            value,
+            rows: 0,
+        })
+    }
+
+    // Bytes written so far, less what the library still holds
+    pub fn bytes_written(&self) -> u64 {
+        std::fs::metadata(&self.hdf5_file).map_or(0, |metadata| metadata.len())
+    }
+
+    pub fn close(self) -> Result<()> {
+        self.file.flush().export_context(SINK, || {
+            format!("Failed to finish {}", self.hdf5_file.display())
+        })?;
+        info!(
+            "Exported {} readings in {} sensors to HDF5 file at {}",
+            self.readings_written,
+            self.channels.len(),
+            self.hdf5_file.display()
+        );
+        Ok(())
+    }
+}
+
+impl ChannelValues {
+    fn new(value: &SensorValue) -> Self {
+        match value {
+            SensorValue::Float(_) => ChannelValues::Float(Vec::new()),
+            SensorValue::Int(_) => ChannelValues::Int(Vec::new()),
+            SensorValue::UnsignedInt(_) => ChannelValues::UnsignedInt(Vec::new()),
+            SensorValue::String(_) | SensorValue::State(_) => ChannelValues::Text(Vec::new()),
+        }
+    }
+}
+
+// One dimensional and empty, grown as readings come
+fn create_dataset<T: H5Type>(group: &Group, name: &str) -> Result<Dataset> {
+    group
+        .new_dataset::<T>()
+        .chunk(CHUNK_ROWS)
+        .shape(0..)
+        .create(name)
+        .export_context(SINK, || format!("Failed to create the {name} dataset"))
+}
+
+fn append<T: H5Type>(dataset: &Dataset, values: &[T], start: usize, end: usize) -> Result<()> {
+    dataset
+        .resize(end)
+        .and_then(|_| dataset.write_slice(values, start..end))
+        .export_context(SINK, || format!("Failed to write to {}", dataset.name()))
+}
+
+fn text(value: &str) -> Result<VarLenUnicode> {
+    value
+        .parse::<VarLenUnicode>()
+        .map_err(|e| e.to_string())
+        .export_context(SINK, || format!("{value:?} can't be stored as HDF5 text"))
+}
+
+fn write_attr<T: H5Type>(location: &Location, name: &str, value: T) -> Result<()> {
+    location
+        .new_attr::<T>()
+        .shape(())
+        .create(name)
+        .and_then(|attr| attr.write_scalar(&value))
+        .export_context(SINK, || format!("Failed to write the {name} attribute"))
+}
+
+fn write_text_attr(location: &Location, name: &str, value: &str) -> Result<()> {
+    write_attr(location, name, text(value)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::TelemetryGenerator;
+    use crate::models::{SampleRate, TelemetryConfig};
+
+    #[test]
+    fn channels_read_back_with_their_units() {
+        let config = TelemetryConfig {
+            duration: 2,
+            sample_rate_hz: SampleRate::from_hz(10),
+            ..TelemetryConfig::default()
+        };
+        let dataset = TelemetryGenerator::new(config).generate(true).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        Hdf5Exporter::export(&dataset, dir.path(), "run").unwrap();
+
+        let file = File::open(dir.path().join("run.h5")).unwrap();
+        let run = file.group(&dataset.config.launch_id).unwrap();
+        let launch_id: VarLenUnicode = run.attr("launch_id").unwrap().read_scalar().unwrap();
+        assert_eq!(launch_id.as_str(), dataset.config.launch_id);
+
+        let reading = &dataset.readings[0];
+        let label = reading.sensor_label(&dataset.config);
+        let channel = run.group(&label).unwrap();
+        let unit: VarLenUnicode = channel.attr("unit").unwrap().read_scalar().unwrap();
+        assert_eq!(unit.as_str(), dataset.config.units.unit(reading.sensor));
+        let rows: usize = run
+            .groups()
+            .unwrap()
+            .iter()
+            .map(|channel| channel.dataset("time").unwrap().size())
+            .sum();
+        assert_eq!(rows, dataset.readings.len());
+        assert_eq!(
+            channel.dataset("value").unwrap().size(),
+            channel.dataset("time").unwrap().size()
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use super::output_files::prepare_output_file;
+use crate::error::{IoContext, Result};
+use crate::generators::HealthTransition;
+use std::fmt::Write as _;
+use std::path::Path;
+use tracing::info;
+
+// Lists every health status change as `{output_name}.health.csv`, the known transitions an
+// alerting pipeline under test should raise
+pub struct HealthExporter;
+
+impl HealthExporter {
+    pub fn export(
+        transitions: &[HealthTransition],
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
+        let mut csv = String::from("time_since_launch_ms,from,to,cause\n");
+        for transition in transitions {
+            let _ = writeln!(
+                csv,
+                "{},{},{},\"{}\"",
+                transition.time_since_launch_ms,
+                transition.from.name(),
+                transition.to.name(),
+                transition.cause.replace('"', "\"\"")
+            );
+        }
+
+        let health_file = prepare_output_file(output_dir, output_name, "health.csv")?;
+        std::fs::write(&health_file, csv)
+            .io_context(|| format!("Failed to write {}", health_file.display()))?;
+        info!(
+            "{} health transitions written to {}",
+            transitions.len(),
+            health_file.display()
+        );
+        Ok(())
+    }
+}
@@ -1,10 +1,34 @@
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
-use influxdb2::Client;
-use serde::{Deserialize, Serialize};
+use crate::error::{Result, TelemetryError};
+use influxdb2::{Client, RequestError};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::Receiver;
 use tracing::{error, info, warn};
 
-use crate::models::TelemetryDataset;
+use super::{BatchTuner, BatchTuning, ExportJournal, StreamProgress};
+use crate::generators::{FlightEvent, FlightEventKind, LimitAlert};
+use crate::models::{
+    MissionPhase, SensorNaming, TelemetryReading, line_protocol_nanos, push_escaped, sensor_label,
+};
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+
+// Measurement every reading is written under
+pub(super) const MEASUREMENT: &str = "rocket_telemetry";
+
+// Measurement limit alerts are written under as events, one point per exceedance at its start
+const ALERT_MEASUREMENT: &str = "rocket_alerts";
+
+// Measurement mission events are written under as they're reached, with the `title`, `text` and
+// `tags` fields Grafana annotation queries look for
+const EVENT_MEASUREMENT: &str = "rocket_events";
+
+// Chunks a streaming generator can get ahead of the server by
+pub const STREAM_QUEUE_CHUNKS: usize = 8;
+
+// Sink name reported in export errors
+const SINK: &str = "influxdb";
 
 #[derive(Debug, Clone)]
 pub struct InfluxDBConfig {
@@ -13,6 +37,12 @@ pub struct InfluxDBConfig {
     pub org: String,
     pub bucket: String,
     pub batch_size: usize,
+    // Tagged on every point so runs sharing a bucket can be told apart
+    pub launch_id: String,
+    // Convention the `sensor` tag is named by
+    pub naming: SensorNaming,
+    // Tune the batch size from write latency and overload responses, starting at `batch_size`
+    pub auto_batch: Option<BatchTuning>,
 }
 
 impl Default for InfluxDBConfig {
@@ -23,6 +53,9 @@ impl Default for InfluxDBConfig {
             org: "my_org".to_string(),
             bucket: "my_bucket".to_string(),
             batch_size: 5000,
+            launch_id: "eg_launch".to_string(),
+            naming: SensorNaming::Short,
+            auto_batch: None,
         }
     }
 }
@@ -31,76 +64,379 @@ impl Default for InfluxDBConfig {
 pub struct InfluxDBExporter {
     client: Client,
     config: InfluxDBConfig,
+    annotations: Option<MissionAnnotations>,
 }
 
 impl InfluxDBExporter {
     pub fn new(config: InfluxDBConfig) -> Self {
         let client = Client::new(&config.url, &config.org, &config.token);
-        Self { client, config }
-    }
-
-    pub async fn export(&self, dataset: &TelemetryDataset) -> Result<()> {
-        info!("inside export influx db function");
-
-        if dataset.readings.is_empty() {
-            warn!("No data detected to export!");
-            return Ok(());
+        Self {
+            client,
+            config,
+            annotations: None,
         }
+    }
 
-        let total_readings = dataset.readings.len();
-        let batch_count = (total_readings + self.config.batch_size - 1) / self.config.batch_size;
-
-        let pb = ProgressBar::new(batch_count as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:50.cyan/blue}] {pos}/{len} batches ({percent}%) {msg}")?
-                .progress_chars("#>-"),
-        );
-        pb.set_message(format!(
-            "Sending data to Influx with BS: {}",
-            self.config.batch_size
-        ));
+    // Also write mission events to `rocket_events` as the readings reach them, so dashboards mark
+    // liftoff, max Q, MECO and the rest on their graphs. Phase changes come from the readings,
+    // staging and faults from `events`, the generator's `events()`
+    pub fn with_annotations(mut self, events: Vec<FlightEvent>) -> Self {
+        self.annotations = Some(MissionAnnotations::new(events));
+        self
+    }
 
-        for (batch_idx, chunk) in dataset.readings.chunks(self.config.batch_size).enumerate() {
-            let mut line_data = String::new();
+    // Send chunks as they come off a generator running alongside, so the run never has to fit
+    // in memory. Every batch the server confirms is recorded in the journal. Returns the number
+    // of readings sent once the generator hangs up
+    pub async fn stream(
+        &mut self,
+        mut chunks: Receiver<Vec<TelemetryReading>>,
+        progress: &StreamProgress,
+        journal: &mut ExportJournal,
+        journal_path: &Path,
+    ) -> Result<usize> {
+        let mut tuner = self
+            .config
+            .auto_batch
+            .clone()
+            .map(|tuning| BatchTuner::new(self.config.batch_size, tuning));
+        let mut pending: Vec<TelemetryReading> = Vec::new();
+        let mut generator_done = false;
+        let mut line_capacity: usize = 0;
+        // Numbered across attempts so errors match the journal
+        let mut batch_idx: usize = journal.confirmed_batches;
+        let mut sent_readings: usize = 0;
+        let mut overloads: u32 = 0;
 
-            for reading in chunk {
-                let line = reading.to_line_protocol("rocket_telemetry");
-                line_data.push_str(&line);
-                line_data.push('\n');
+        loop {
+            let batch_size = tuner
+                .as_ref()
+                .map_or(self.config.batch_size, BatchTuner::size);
+            while !generator_done && pending.len() < batch_size {
+                match chunks.recv().await {
+                    Some(mut readings) => pending.append(&mut readings),
+                    None => generator_done = true,
+                }
+            }
+            if pending.is_empty() {
+                break;
             }
 
-            // Write the batch to the server
-            let write_result = self
-                .client
-                .write_line_protocol(&self.config.org, &self.config.bucket, line_data)
-                .await;
-
-            match write_result {
-                Ok(_) => {
-                    pb.set_position(batch_idx as u64 + 1);
-                    pb.set_message(format!(
-                        "Sent batch {}/{} ({} readings)",
-                        batch_idx + 1,
-                        batch_count,
-                        chunk.len()
-                    ));
+            // Batches stay queued until they're written, so an overloaded one is retried
+            // at the smaller size
+            let batch = &pending[..batch_size.min(pending.len())];
+            let line_data = self.line_protocol(batch, &mut line_capacity);
+            let write_timeout = tuner.as_ref().map(|t| t.tuning().write_timeout);
+            let started = Instant::now();
+            match (self.write(line_data, write_timeout).await, &mut tuner) {
+                (Ok(()), tuner) => {
+                    if let Some(tuner) = tuner {
+                        tuner.record_success(batch.len(), started.elapsed());
+                    }
+                    journal.confirm(journal_path, batch)?;
+                    self.write_annotations(batch).await?;
+                    overloads = 0;
+                    batch_idx += 1;
+                    sent_readings += batch.len();
+                    let sent_ms = batch.iter().map(|r| r.time_since_launch_ms).max();
+                    progress.sent(batch.len(), sent_ms.unwrap_or_default());
+                    let written = batch.len();
+                    pending.drain(..written);
                 }
-                Err(e) => {
+                (Err(e), Some(tuner))
+                    if overloads < tuner.tuning().max_retries
+                        && let Some(reason) = e.overload_reason() =>
+                {
+                    tuner.record_overload(&reason);
+                    overloads += 1;
+                    tokio::time::sleep(BatchTuner::backoff(overloads)).await;
+                }
+                (Err(e), _) => {
                     error!(error = %e, batch_idx, "Failed to send batch to Influx");
-                    return Err(anyhow::anyhow!("Influx DB write error!: {}", e));
+                    return Err(TelemetryError::ExportError {
+                        sink: SINK.to_string(),
+                        message: format!("Influx DB write error on batch {batch_idx}!"),
+                        source: Some(e.into()),
+                    });
                 }
             }
         }
 
-        pb.finish_with_message(format!(
-            "Successfully exported to InfluxDB: {total_readings}"
-        ));
         info!(
-            total_readings,
-            batch_count, "Successfully exported data to influxdb"
+            sent_readings,
+            batch_idx, "Successfully streamed data to influxdb"
         );
+        if let Some((batch_size, throughput)) = tuner.as_ref().and_then(BatchTuner::best) {
+            info!(
+                "Best throughput was {:.0} points/s with batches of {batch_size}",
+                throughput
+            );
+        }
+        Ok(sent_readings)
+    }
+
+    // Batch as line protocol. `line_capacity` sizes the buffer off the biggest batch so far so
+    // later ones rarely need to grow
+    fn line_protocol(&self, batch: &[TelemetryReading], line_capacity: &mut usize) -> String {
+        let mut line_data = String::with_capacity(*line_capacity);
+        let mut skipped = 0usize;
+        for reading in batch {
+            if reading.write_line_protocol(
+                MEASUREMENT,
+                self.config.naming,
+                &self.config.launch_id,
+                &mut line_data,
+            ) {
+                line_data.push('\n');
+            } else {
+                skipped += 1;
+            }
+        }
+        if skipped > 0 {
+            warn!("Skipped {skipped} NaN or infinite readings line protocol can't carry");
+        }
+        *line_capacity = (*line_capacity).max(line_data.len());
+        line_data
+    }
+
+    // Write readings as one batch straight away, for live streams that can't wait on a full one.
+    // Annotations they reach go in the same write
+    pub async fn write_readings(&mut self, readings: &[TelemetryReading]) -> Result<()> {
+        if readings.is_empty() {
+            return Ok(());
+        }
+        let mut line_data = self.line_protocol(readings, &mut 0);
+        if let Some(annotations) = &mut self.annotations {
+            annotations.annotate(readings, &self.config.launch_id, &mut line_data);
+        }
+        self.write(line_data, None).await.map_err(|e| {
+            error!(error = %e, "Failed to send readings to Influx");
+            TelemetryError::ExportError {
+                sink: SINK.to_string(),
+                message: format!("Influx DB write error on {} readings!", readings.len()),
+                source: Some(e.into()),
+            }
+        })
+    }
+
+    // Write the run's limit alerts once the readings are in, so they can be annotated on or
+    // joined against them
+    pub async fn write_alerts(&self, alerts: &[LimitAlert]) -> Result<()> {
+        if alerts.is_empty() {
+            return Ok(());
+        }
+        let mut line_data = String::new();
+        for alert in alerts {
+            push_escaped(&mut line_data, ALERT_MEASUREMENT, &[',', ' ']);
+            line_data.push_str(",sensor=");
+            let sensor = sensor_label(alert.sensor.field_name(), alert.instance, alert.side);
+            push_escaped(&mut line_data, &sensor, &[',', '=', ' ']);
+            write!(line_data, ",limit={},launch_id=", alert.level.name())
+                .expect("Writing to a String can't fail");
+            push_escaped(&mut line_data, &self.config.launch_id, &[',', '=', ' ']);
+            write!(
+                line_data,
+                " threshold={},peak={},start_ms={}u",
+                alert.limit, alert.peak, alert.start_ms
+            )
+            .expect("Writing to a String can't fail");
+            if let Some(end_ms) = alert.end_ms {
+                write!(line_data, ",end_ms={end_ms}u").expect("Writing to a String can't fail");
+            }
+            writeln!(line_data, " {}", line_protocol_nanos(alert.start))
+                .expect("Writing to a String can't fail");
+        }
 
+        self.write(line_data, None).await.map_err(|e| {
+            error!(error = %e, "Failed to send alerts to Influx");
+            TelemetryError::ExportError {
+                sink: SINK.to_string(),
+                message: format!("Influx DB write error on {} alerts!", alerts.len()),
+                source: Some(e.into()),
+            }
+        })?;
+        info!("Wrote {} limit alerts to influxdb", alerts.len());
         Ok(())
     }
+
+    // Annotations for a batch the server has confirmed. Written on their own so a batch retried
+    // after an overload doesn't annotate twice
+    async fn write_annotations(&mut self, batch: &[TelemetryReading]) -> Result<()> {
+        let Some(annotations) = &mut self.annotations else {
+            return Ok(());
+        };
+        let mut line_data = String::new();
+        if annotations.annotate(batch, &self.config.launch_id, &mut line_data) == 0 {
+            return Ok(());
+        }
+        self.write(line_data, None).await.map_err(|e| {
+            error!(error = %e, "Failed to send annotations to Influx");
+            TelemetryError::ExportError {
+                sink: SINK.to_string(),
+                message: "Influx DB write error on mission event annotations!".to_string(),
+                source: Some(e.into()),
+            }
+        })
+    }
+
+    // Write a batch to the server, giving up after `timeout` when one is set
+    async fn write(
+        &self,
+        line_data: String,
+        timeout: Option<Duration>,
+    ) -> std::result::Result<(), WriteError> {
+        let write =
+            self.client
+                .write_line_protocol(&self.config.org, &self.config.bucket, line_data);
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, write)
+                .await
+                .map_err(|_| WriteError::Timeout(timeout))?
+                .map_err(WriteError::Request),
+            None => write.await.map_err(WriteError::Request),
+        }
+    }
+}
+
+// Mission events still to come in a run being sent, picked off as the readings reach them
+#[derive(Debug)]
+struct MissionAnnotations {
+    // Phase of the last reading sent, None before the first
+    phase: Option<MissionPhase>,
+    events: VecDeque<FlightEvent>,
+}
+
+impl MissionAnnotations {
+    fn new(mut events: Vec<FlightEvent>) -> Self {
+        events.sort_by_key(|event| event.time_since_launch_ms);
+        Self {
+            phase: None,
+            events: events.into(),
+        }
+    }
+
+    // Append a point per event `readings` reach, in time order. Returns how many
+    fn annotate(
+        &mut self,
+        readings: &[TelemetryReading],
+        launch_id: &str,
+        line_data: &mut String,
+    ) -> usize {
+        let mut written = 0;
+        for reading in readings {
+            let previous = self.phase.replace(reading.phase);
+            if previous.is_none() {
+                // A resumed export starts part way, with the events before it already written
+                self.events
+                    .retain(|event| event.time_since_launch_ms >= reading.time_since_launch_ms);
+            }
+            while let Some(event) = self
+                .events
+                .pop_front_if(|event| event.time_since_launch_ms <= reading.time_since_launch_ms)
+            {
+                // Readings land on the sample grid, the event may fall between them
+                let early_ms = reading.time_since_launch_ms - event.time_since_launch_ms;
+                let timestamp = reading.timestamp - chrono::Duration::milliseconds(early_ms as i64);
+                let (kind, title) = match event.kind {
+                    FlightEventKind::Staging => ("staging", "Stage separation".to_string()),
+                    FlightEventKind::Anomaly => ("anomaly", format!("Anomaly: {}", event.label)),
+                };
+                push_annotation(
+                    line_data,
+                    launch_id,
+                    kind,
+                    &title,
+                    event.time_since_launch_ms,
+                    timestamp,
+                );
+                written += 1;
+            }
+            // The first phase only counts from launch, not part way through a resumed export
+            let entered = match previous {
+                Some(previous) => previous != reading.phase,
+                None => reading.time_since_launch_ms == 0,
+            };
+            if entered {
+                push_annotation(
+                    line_data,
+                    launch_id,
+                    "phase",
+                    phase_title(previous, reading.phase),
+                    reading.time_since_launch_ms,
+                    reading.timestamp,
+                );
+                written += 1;
+            }
+        }
+        written
+    }
+}
+
+// What entering `phase` looks like on a dashboard
+fn phase_title(previous: Option<MissionPhase>, phase: MissionPhase) -> &'static str {
+    match (previous, phase) {
+        (_, MissionPhase::Prelaunch) => "Prelaunch",
+        (None | Some(MissionPhase::Prelaunch), _) => "Liftoff",
+        (_, MissionPhase::MaxQ) => "Max Q",
+        (_, MissionPhase::Ascent) => "Through max Q",
+        (_, MissionPhase::Staging) => "MECO",
+        (_, MissionPhase::Insertion) => "Second stage ignition",
+        (_, MissionPhase::Recovery) => "Recovery",
+    }
+}
+
+fn push_annotation(
+    line_data: &mut String,
+    launch_id: &str,
+    kind: &str,
+    title: &str,
+    time_since_launch_ms: u64,
+    timestamp: DateTime<Utc>,
+) {
+    line_data.push_str(EVENT_MEASUREMENT);
+    line_data.push_str(",launch_id=");
+    push_escaped(line_data, launch_id, &[',', '=', ' ']);
+    write!(line_data, ",kind={kind} title=\"").expect("Writing to a String can't fail");
+    push_escaped(line_data, title, &['"']);
+    write!(
+        line_data,
+        "\",text=\"T+{:.1}s\",tags=\"",
+        time_since_launch_ms as f64 / 1000.0
+    )
+    .expect("Writing to a String can't fail");
+    push_escaped(line_data, launch_id, &['"']);
+    writeln!(
+        line_data,
+        ",{kind}\",time_since_launch_ms={time_since_launch_ms}u {}",
+        line_protocol_nanos(timestamp)
+    )
+    .expect("Writing to a String can't fail");
+}
+
+#[derive(Debug, thiserror::Error)]
+enum WriteError {
+    #[error("Write timed out after {0:?}")]
+    Timeout(Duration),
+    #[error(transparent)]
+    Request(RequestError),
+}
+
+impl WriteError {
+    // Why the server looks overloaded, None for errors retrying won't fix
+    fn overload_reason(&self) -> Option<String> {
+        match self {
+            WriteError::Timeout(timeout) => Some(format!("timed out after {timeout:?}")),
+            WriteError::Request(RequestError::Http { status, .. })
+                if status.as_u16() == 429 || status.as_u16() == 503 =>
+            {
+                Some(format!("HTTP {status}"))
+            }
+            WriteError::Request(RequestError::ReqwestProcessing { source })
+                if source.is_timeout() =>
+            {
+                Some("request timed out".to_string())
+            }
+            WriteError::Request(_) => None,
+        }
+    }
 }
@@ -0,0 +1,82 @@
+use super::output_files::prepare_output_file;
+use super::stream_sink::{FrameFormat, SinkTarget, StreamExporter, TextCompression};
+use crate::error::{ExportContext, Result};
+use crate::models::TelemetryDataset;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::path::Path;
+use tracing::info;
+
+const SINK: &str = "json";
+
+// Readings written between progress bar updates
+const PROGRESS_READINGS: usize = 10_000;
+
+// How the readings are laid out in the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum JsonLayout {
+    // `{output_name}.json`, one document with the run details and a `readings` array
+    Document,
+    // `{output_name}.ndjson`, a reading object per line for Elasticsearch and Logstash
+    Lines,
+}
+
+impl JsonLayout {
+    fn format(&self) -> FrameFormat {
+        match self {
+            JsonLayout::Document => FrameFormat::JsonDocument,
+            JsonLayout::Lines => FrameFormat::Ndjson,
+        }
+    }
+}
+
+// Every reading as JSON, written as it goes so large runs never sit in memory as one document.
+// Compression adds .zst or .gz like the other text files
+pub struct JsonExporter;
+
+impl JsonExporter {
+    // File sink for the readings, for runs that stream them chunk by chunk
+    pub fn stream_exporter(
+        output_dir: &Path,
+        output_name: &str,
+        layout: JsonLayout,
+        compression: TextCompression,
+    ) -> Result<StreamExporter> {
+        let format = layout.format();
+        let extension = format!("{}{}", format.extension(), compression.suffix());
+        Ok(StreamExporter {
+            target: SinkTarget::File(
+                prepare_output_file(output_dir, output_name, &extension)?,
+                compression,
+            ),
+            format,
+        })
+    }
+
+    pub fn export(
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+        layout: JsonLayout,
+        compression: TextCompression,
+    ) -> Result<()> {
+        info!("Writing {} readings as JSON", dataset.readings.len());
+        let pb = ProgressBar::new(dataset.readings.len() as u64);
+        pb.set_style(
+            ProgressStyle::default_bar()
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:50.cyan/blue}] {pos:>7}/{len:7} readings ({percent}%) {msg} ({eta})")
+                .export_context(SINK, || "Invalid progress bar template")?
+                .progress_chars("#>-"),
+        );
+
+        let mut sink = Self::stream_exporter(output_dir, output_name, layout, compression)?
+            .start(dataset, output_name)?;
+        for readings in dataset.readings.chunks(PROGRESS_READINGS) {
+            sink.write_readings(readings, &dataset.config)?;
+            pb.inc(readings.len() as u64);
+        }
+        sink.close()?;
+
+        pb.finish_with_message("JSON write complete");
+        Ok(())
+    }
+}
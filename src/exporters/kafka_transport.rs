@@ -0,0 +1,122 @@
+use crate::error::{ExportContext, Result};
+use rdkafka::ClientContext;
+use rdkafka::config::ClientConfig;
+use rdkafka::error::KafkaError;
+use rdkafka::producer::{BaseProducer, BaseRecord, DeliveryResult, Producer, ProducerContext};
+use rdkafka::types::RDKafkaErrorCode;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::stream_sink::Transport;
+
+// Kafka producer, one message per frame. Messages are keyed by the run's output name so a run
+// stays on one partition and its frames arrive in order. Text encodings lose the newline each
+// frame ends with, like over WebSocket
+
+// Longest close waits on the brokers to take what's still queued
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Wait for room when the producer's queue is full
+const QUEUE_FULL_WAIT: Duration = Duration::from_millis(100);
+
+pub(super) struct KafkaTransport {
+    brokers: String,
+    topic: String,
+    key: String,
+    binary: bool,
+    producer: BaseProducer<DeliveryContext>,
+}
+
+// Keeps the first delivery failure, reported on the next send, flush or close
+#[derive(Default)]
+struct DeliveryContext {
+    failed: Mutex<Option<KafkaError>>,
+}
+
+impl ClientContext for DeliveryContext {}
+
+impl ProducerContext for DeliveryContext {
+    type DeliveryOpaque = ();
+
+    fn delivery(&self, result: &DeliveryResult<'_>, _: Self::DeliveryOpaque) {
+        if let Err((e, _)) = result {
+            self.failed
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get_or_insert_with(|| e.clone());
+        }
+    }
+}
+
+impl KafkaTransport {
+    pub(super) fn connect(brokers: &str, topic: &str, key: &str, binary: bool) -> Result<Self> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("client.id", "telemetry-generator")
+            .create_with_context(DeliveryContext::default())
+            .export_context("kafka", || format!("Failed to connect to {brokers}"))?;
+        Ok(Self {
+            brokers: brokers.to_string(),
+            topic: topic.to_string(),
+            key: key.to_string(),
+            binary,
+            producer,
+        })
+    }
+
+    // Serve delivery reports and fail on the first message the brokers didn't take
+    fn check_deliveries(&self) -> Result<()> {
+        self.producer.poll(Duration::ZERO);
+        let failed = self
+            .producer
+            .context()
+            .failed
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        failed.map_or(Ok(()), Err).export_context("kafka", || {
+            format!("{} didn't take a message for {}", self.brokers, self.topic)
+        })
+    }
+}
+
+impl Transport for KafkaTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        let payload = if self.binary {
+            message
+        } else {
+            message.strip_suffix(b"\n").unwrap_or(message)
+        };
+        loop {
+            let record = BaseRecord::<str, [u8]>::to(&self.topic)
+                .key(&self.key)
+                .payload(payload);
+            match self.producer.send(record) {
+                Ok(()) => break,
+                Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                    self.producer.poll(QUEUE_FULL_WAIT);
+                }
+                Err((e, _)) => {
+                    return Err(e).export_context("kafka", || {
+                        format!("Failed to send to {} on {}", self.topic, self.brokers)
+                    });
+                }
+            }
+        }
+        self.check_deliveries()
+    }
+
+    // librdkafka sends on its own thread within linger.ms, so there's nothing to push
+    fn flush(&mut self) -> Result<()> {
+        self.check_deliveries()
+    }
+
+    fn close(self: Box<Self>) -> Result<()> {
+        self.producer
+            .flush(FLUSH_TIMEOUT)
+            .export_context("kafka", || {
+                format!("Failed to flush to {} on {}", self.topic, self.brokers)
+            })?;
+        self.check_deliveries()
+    }
+}
@@ -0,0 +1,311 @@
+use super::output_files::{
+    OverwritePolicy, output_file_path, prepare_output_file, resolve_output_name,
+};
+use super::parquet_exporter::UNITS_KEY;
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use arrow::array::{Array, ArrayRef, StringArray, TimestampMicrosecondArray, UInt64Array};
+use arrow::compute::{concat_batches, sort_to_indices, take_record_batch};
+use arrow::datatypes::TimestampMicrosecondType;
+use arrow::record_batch::RecordBatch;
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::WriterProperties;
+use std::collections::HashSet;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+// Sink name reported in export errors
+const SINK: &str = "merged parquet";
+
+// Column telling the merged runs apart
+pub const RUN_COLUMN: &str = "run";
+
+// How the runs' timelines are lined up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum MergeAlignment {
+    // Interleave readings by their absolute timestamp, as they happened
+    Absolute,
+    // Pretend every run launched with the first one. Timestamps are shifted to match and rows
+    // are ordered by time since launch
+    Launch,
+}
+
+#[derive(Debug, Clone)]
+pub struct MergeConfig {
+    pub alignment: MergeAlignment,
+    // Value of the run column per input. Defaults to the input file name
+    pub tags: Vec<String>,
+}
+
+// Combines several runs into one parquet file with a `run` column, for multi vehicle fixtures.
+// All runs are held in memory, so this is for fixture sized runs, not day long ones
+pub struct RunMerger {
+    config: MergeConfig,
+}
+
+// One input run read into memory
+struct LoadedRun {
+    tag: String,
+    path: PathBuf,
+    key_values: Option<Vec<KeyValue>>,
+    batch: RecordBatch,
+}
+
+impl RunMerger {
+    pub fn new(config: MergeConfig) -> Self {
+        Self { config }
+    }
+
+    // Merge the runs into `{output_dir}/{output_name}.parquet`. Returns the file and rows written
+    pub fn merge(
+        &self,
+        inputs: &[PathBuf],
+        output_dir: &Path,
+        output_name: &str,
+        policy: OverwritePolicy,
+    ) -> Result<(PathBuf, usize)> {
+        let tags = self.tags(inputs)?;
+        let runs = inputs
+            .iter()
+            .zip(tags)
+            .map(|(path, tag)| load_run(path, tag))
+            .collect::<Result<Vec<_>>>()?;
+        check_compatible(&runs)?;
+
+        let first = &runs[0];
+        let launch_us = launch_time_us(&first.batch);
+        let schema = merged_schema(&first.batch.schema());
+        let batches = runs
+            .iter()
+            .map(|run| {
+                let shift_us = match self.config.alignment {
+                    MergeAlignment::Absolute => 0,
+                    MergeAlignment::Launch => launch_us - launch_time_us(&run.batch),
+                };
+                tag_run(&schema, run, shift_us)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let merged = concat_batches(&schema, &batches)
+            .export_context(SINK, || "Failed to combine the runs")?;
+
+        let sort_column = match self.config.alignment {
+            MergeAlignment::Absolute => "timestamp",
+            MergeAlignment::Launch => "time_since_launch_ms",
+        };
+        let column = merged
+            .column_by_name(sort_column)
+            .ok_or_else(|| TelemetryError::config(format!("Runs have no {sort_column} column")))?;
+        let indices = sort_to_indices(column, None, None)
+            .export_context(SINK, || format!("Failed to sort by {sort_column}"))?;
+        let merged = take_record_batch(&merged, &indices)
+            .export_context(SINK, || format!("Failed to sort by {sort_column}"))?;
+
+        let output_name = resolve_output_name(output_dir, output_name, policy)?;
+        let output_file = prepare_output_file(output_dir, &output_name, "parquet")?;
+        let file = File::create(&output_file)
+            .io_context(|| format!("Failed to create {}", output_file.display()))?;
+        let props = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .set_key_value_metadata(first.key_values.clone())
+            .build();
+        let mut writer = ArrowWriter::try_new(file, schema, Some(props))
+            .export_context(SINK, || "Failed to create arrow writer")?;
+        writer
+            .write(&merged)
+            .export_context(SINK, || "Failed to write record batch")?;
+        writer
+            .close()
+            .export_context(SINK, || "Failed to close Parquet writer")?;
+
+        write_metadata(
+            &runs,
+            &output_file_path(output_dir, &output_name, "metadata.csv"),
+        )?;
+
+        info!(
+            "Merged {} runs into {} rows in {}",
+            runs.len(),
+            merged.num_rows(),
+            output_file.display()
+        );
+        Ok((output_file, merged.num_rows()))
+    }
+
+    fn tags(&self, inputs: &[PathBuf]) -> Result<Vec<String>> {
+        if inputs.len() < 2 {
+            return Err(TelemetryError::config("Merge needs at least two runs"));
+        }
+        let tags: Vec<String> = if self.config.tags.is_empty() {
+            inputs.iter().map(|path| run_name(path)).collect()
+        } else if self.config.tags.len() == inputs.len() {
+            self.config.tags.clone()
+        } else {
+            return Err(TelemetryError::config(format!(
+                "Got {} tags for {} runs. Pass one --tag per run",
+                self.config.tags.len(),
+                inputs.len()
+            )));
+        };
+
+        let mut seen = HashSet::new();
+        if let Some(tag) = tags.iter().find(|tag| !seen.insert(*tag)) {
+            return Err(TelemetryError::config(format!(
+                "Two runs are tagged {tag}. Pass one --tag per run to tell them apart"
+            )));
+        }
+        Ok(tags)
+    }
+}
+
+// File name without the .parquet extension
+fn run_name(path: &Path) -> String {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.strip_suffix(".parquet").unwrap_or(name).to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn load_run(path: &Path, tag: String) -> Result<LoadedRun> {
+    let file = File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+    let key_values = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .cloned();
+    let schema = builder.schema().clone();
+    let batches = builder
+        .build()
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+    let batch = concat_batches(&schema, &batches)
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+
+    info!(
+        "Read {} rows of run {} from {}",
+        batch.num_rows(),
+        tag,
+        path.display()
+    );
+    Ok(LoadedRun {
+        tag,
+        path: path.to_path_buf(),
+        key_values,
+        batch,
+    })
+}
+
+// Runs have to share a value layout and units to end up in one file
+fn check_compatible(runs: &[LoadedRun]) -> Result<()> {
+    let first = &runs[0];
+    let units = |run: &LoadedRun| {
+        run.key_values
+            .iter()
+            .flatten()
+            .find(|kv| kv.key == UNITS_KEY)
+            .and_then(|kv| kv.value.clone())
+    };
+    for run in &runs[1..] {
+        if run.batch.schema().fields() != first.batch.schema().fields() {
+            return Err(TelemetryError::config(format!(
+                "{} and {} have different columns. Merge runs written with the same --value-columns",
+                first.path.display(),
+                run.path.display()
+            )));
+        }
+        if units(run) != units(first) {
+            return Err(TelemetryError::config(format!(
+                "{} and {} use different units. Merge runs written with the same --units",
+                first.path.display(),
+                run.path.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn merged_schema(schema: &SchemaRef) -> SchemaRef {
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(RUN_COLUMN, DataType::Utf8, false));
+    Arc::new(Schema::new(fields))
+}
+
+// Launch time of the run as the earliest timestamp minus its time since launch
+pub(super) fn launch_time_us(batch: &RecordBatch) -> i64 {
+    let timestamps = batch
+        .column_by_name("timestamp")
+        .and_then(|c| c.as_any().downcast_ref::<TimestampMicrosecondArray>());
+    let since_launch = batch
+        .column_by_name("time_since_launch_ms")
+        .and_then(|c| c.as_any().downcast_ref::<UInt64Array>());
+    let (Some(timestamps), Some(since_launch)) = (timestamps, since_launch) else {
+        return 0;
+    };
+    timestamps
+        .iter()
+        .zip(since_launch.iter())
+        .filter_map(|(t, since)| Some(t? - since? as i64 * 1_000))
+        .min()
+        .unwrap_or(0)
+}
+
+// The run's rows with its timestamps shifted and its tag in the run column
+fn tag_run(schema: &SchemaRef, run: &LoadedRun, shift_us: i64) -> Result<RecordBatch> {
+    let batch_schema = run.batch.schema();
+    let mut columns: Vec<ArrayRef> = batch_schema
+        .fields()
+        .iter()
+        .zip(run.batch.columns())
+        .map(
+            |(field, column)| match column.as_any().downcast_ref::<TimestampMicrosecondArray>() {
+                Some(timestamps) if field.name() == "timestamp" && shift_us != 0 => {
+                    Arc::new(timestamps.unary::<_, TimestampMicrosecondType>(|t| t + shift_us))
+                        as ArrayRef
+                }
+                _ => column.clone(),
+            },
+        )
+        .collect();
+    columns.push(Arc::new(StringArray::from(vec![
+        run.tag.as_str();
+        run.batch.num_rows()
+    ])));
+
+    RecordBatch::try_new(schema.clone(), columns)
+        .export_context(SINK, || format!("Failed to tag run {}", run.tag))
+}
+
+// Metadata rows of every run that has a metadata CSV, with the run tag up front
+fn write_metadata(runs: &[LoadedRun], output: &Path) -> Result<()> {
+    let mut header = None;
+    let mut rows = Vec::new();
+    for run in runs {
+        let input_dir = run.path.parent().unwrap_or(Path::new("."));
+        let metadata_csv = output_file_path(input_dir, &run_name(&run.path), "metadata.csv");
+        let Ok(metadata) = std::fs::read_to_string(&metadata_csv) else {
+            continue;
+        };
+        let mut lines = metadata.lines();
+        if let Some(first) = lines.next() {
+            header.get_or_insert_with(|| format!("{RUN_COLUMN},{first}"));
+        }
+        rows.extend(lines.map(|line| format!("{},{line}", run.tag)));
+    }
+
+    let Some(header) = header else {
+        return Ok(());
+    };
+    let mut csv = header;
+    csv.push('\n');
+    for row in rows {
+        csv.push_str(&row);
+        csv.push('\n');
+    }
+    std::fs::write(output, csv).io_context(|| format!("Failed to write {}", output.display()))
+}
@@ -1,7 +1,76 @@
+mod alert_exporter;
+mod anonymizer;
+mod arrow_exporter;
+mod avro_exporter;
+mod batch_observer;
+mod batch_tuner;
+mod calibration_exporter;
+mod capture_exporter;
+mod catalog_exporter;
+mod ccsds_exporter;
+mod checksum_exporter;
 mod csv_exporter;
+mod disk_watchdog;
+mod evolution_exporter;
+mod export_journal;
+#[cfg(feature = "hdf5")]
+mod hdf5_exporter;
+mod health_exporter;
 mod influxdb_exporter;
+mod json_exporter;
+#[cfg(feature = "kafka")]
+mod kafka_transport;
+mod merger;
+mod modbus_exporter;
+mod mqtt_exporter;
+#[cfg(feature = "opcua")]
+mod opcua_exporter;
+#[cfg(feature = "orc")]
+mod orc_exporter;
+mod output_files;
 mod parquet_exporter;
+mod plugin_exporter;
+mod plugin_schema;
+mod protobuf_encoder;
+mod resampler;
+mod sqlite_exporter;
+mod stream_progress;
+mod stream_sink;
+mod xlsx_exporter;
 
+pub use alert_exporter::*;
+pub use anonymizer::*;
+pub use arrow_exporter::*;
+pub use avro_exporter::*;
+pub use batch_observer::RecordBatchObserver;
+pub use batch_tuner::*;
+pub use calibration_exporter::*;
+pub use capture_exporter::*;
+pub use catalog_exporter::*;
+pub use ccsds_exporter::*;
+pub use checksum_exporter::*;
 pub use csv_exporter::*;
+pub use disk_watchdog::*;
+pub use evolution_exporter::*;
+pub use export_journal::*;
+#[cfg(feature = "hdf5")]
+pub use hdf5_exporter::*;
+pub use health_exporter::*;
 pub use influxdb_exporter::*;
+pub use json_exporter::*;
+pub use merger::*;
+pub use modbus_exporter::*;
+pub use mqtt_exporter::*;
+#[cfg(feature = "opcua")]
+pub use opcua_exporter::*;
+#[cfg(feature = "orc")]
+pub use orc_exporter::*;
+pub use output_files::*;
 pub use parquet_exporter::*;
+pub use plugin_exporter::*;
+pub use plugin_schema::*;
+pub use resampler::*;
+pub use sqlite_exporter::*;
+pub use stream_progress::*;
+pub use stream_sink::*;
+pub use xlsx_exporter::*;
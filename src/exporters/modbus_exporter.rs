@@ -0,0 +1,233 @@
+use crate::error::{Result, TelemetryError};
+use crate::generators::CancelToken;
+use crate::models::{SensorEnum, SensorValue, TelemetryConfig, TelemetryReading};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+// Modbus TCP slave for legacy pollers. Mapped channels are held as 32 bit floats over two
+// holding registers each, high word first, and updated as the readings go out. Function codes
+// 3 and 4 read them, anything else gets an illegal function exception. Unit ids are ignored
+
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Most registers one read may ask for, from the Modbus spec
+const MAX_READ_REGISTERS: u16 = 125;
+
+// Unit id and function code, then up to 252 bytes of data
+const MAX_PDU_BYTES: usize = 254;
+
+const READ_HOLDING_REGISTERS: u8 = 3;
+const READ_INPUT_REGISTERS: u8 = 4;
+
+const ILLEGAL_FUNCTION: u8 = 1;
+const ILLEGAL_DATA_ADDRESS: u8 = 2;
+const ILLEGAL_DATA_VALUE: u8 = 3;
+
+// A channel served at `address` and the register after it, e.g. `alt=0`. Addresses count from 0
+// like on the wire, so 40001 in a poller's notation is 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModbusRegister {
+    pub sensor: SensorEnum,
+    pub address: u16,
+}
+
+impl FromStr for ModbusRegister {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (sensor, address) = s.split_once('=').ok_or_else(|| {
+            TelemetryError::config(format!("Register {s} should look like <sensor>=<address>"))
+        })?;
+        let sensor = SensorEnum::from_name(sensor.trim())
+            .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {}", sensor.trim())))?;
+        let address = address
+            .trim()
+            .parse::<u16>()
+            .ok()
+            .filter(|address| *address < u16::MAX)
+            .ok_or_else(|| {
+                TelemetryError::config(format!(
+                    "Invalid register address {}, it takes two registers from 0 to 65534",
+                    address.trim()
+                ))
+            })?;
+        Ok(Self { sensor, address })
+    }
+}
+
+// Clones share the registers, so a flight can write to the server the stream command bound
+#[derive(Debug, Clone)]
+pub struct ModbusServer {
+    registers: Arc<RwLock<Vec<u16>>>,
+    addresses: HashMap<SensorEnum, u16>,
+    local_addr: SocketAddr,
+}
+
+impl ModbusServer {
+    // Listen on `addr` until `shutdown` is cancelled. The registers read 0 until the first
+    // readings arrive
+    pub async fn bind(
+        addr: SocketAddr,
+        registers: &[ModbusRegister],
+        shutdown: CancelToken,
+    ) -> Result<Self> {
+        if registers.is_empty() {
+            return Err(TelemetryError::config(
+                "Map at least one sensor to a register for the Modbus server",
+            ));
+        }
+        let mut addresses = HashMap::new();
+        let mut taken: HashMap<u16, SensorEnum> = HashMap::new();
+        for register in registers {
+            for address in [register.address, register.address + 1] {
+                if let Some(other) = taken.insert(address, register.sensor) {
+                    return Err(TelemetryError::config(format!(
+                        "Register {address} is mapped to both {} and {}",
+                        other.field_name(),
+                        register.sensor.field_name()
+                    )));
+                }
+            }
+            if addresses
+                .insert(register.sensor, register.address)
+                .is_some()
+            {
+                return Err(TelemetryError::config(format!(
+                    "{} is mapped to more than one register",
+                    register.sensor.field_name()
+                )));
+            }
+        }
+        let size = taken.keys().max().map_or(0, |max| *max as usize + 1);
+
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| TelemetryError::config(format!("Failed to listen on {addr}: {e}")))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| TelemetryError::config(format!("Failed to listen on {addr}: {e}")))?;
+        let registers = Arc::new(RwLock::new(vec![0; size]));
+        tokio::spawn(accept(listener, registers.clone(), shutdown));
+        info!(
+            "Serving {} channels over Modbus TCP on {local_addr}",
+            addresses.len()
+        );
+        Ok(Self {
+            registers,
+            addresses,
+            local_addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // Update the mapped channels from a timestep's readings. Values are in the exported units,
+    // discrete states as their code. Strings and other channels are skipped
+    pub fn write_readings(&self, readings: &[TelemetryReading], config: &TelemetryConfig) {
+        let mut registers = self.registers.write().unwrap_or_else(|e| e.into_inner());
+        for reading in readings {
+            let Some(address) = self.addresses.get(&reading.sensor) else {
+                continue;
+            };
+            let value = match &reading.value {
+                SensorValue::Float(f) => config.export_float(reading.sensor, *f),
+                SensorValue::Int(i) => *i as f64,
+                SensorValue::UnsignedInt(u) => *u as f64,
+                SensorValue::State(state) => state.code() as f64,
+                SensorValue::String(_) => continue,
+            };
+            let bits = (value as f32).to_bits();
+            let address = *address as usize;
+            registers[address] = (bits >> 16) as u16;
+            registers[address + 1] = bits as u16;
+        }
+    }
+}
+
+// Take connections until shutdown. Dropping the set on the way out closes them
+async fn accept(listener: TcpListener, registers: Arc<RwLock<Vec<u16>>>, shutdown: CancelToken) {
+    let mut connections = JoinSet::new();
+    while !shutdown.is_cancelled() {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, peer)) => {
+                    debug!("Modbus poller connected from {peer}");
+                    connections.spawn(serve(stream, registers.clone()));
+                }
+                Err(e) => warn!("Failed to accept a Modbus connection: {e}"),
+            },
+            _ = tokio::time::sleep(SHUTDOWN_CHECK_INTERVAL) => {}
+        }
+        // Reap the pollers that hung up
+        while connections.try_join_next().is_some() {}
+    }
+}
+
+// Answer requests on one connection until the poller hangs up or sends something that isn't
+// Modbus TCP
+async fn serve(mut stream: TcpStream, registers: Arc<RwLock<Vec<u16>>>) {
+    let mut header = [0u8; 7];
+    let mut pdu = [0u8; MAX_PDU_BYTES];
+    loop {
+        if stream.read_exact(&mut header).await.is_err() {
+            return;
+        }
+        let protocol = u16::from_be_bytes([header[2], header[3]]);
+        // Counts the unit id, already read with the header
+        let length = u16::from_be_bytes([header[4], header[5]]) as usize;
+        if protocol != 0 || !(2..=MAX_PDU_BYTES).contains(&length) {
+            debug!("Dropping a Modbus connection that sent a bad header");
+            return;
+        }
+        let pdu = &mut pdu[..length - 1];
+        if stream.read_exact(pdu).await.is_err() {
+            return;
+        }
+        let response = respond(pdu, &registers);
+        let mut frame = Vec::with_capacity(7 + response.len());
+        frame.extend_from_slice(&header[..4]);
+        frame.extend_from_slice(&(response.len() as u16 + 1).to_be_bytes());
+        frame.push(header[6]);
+        frame.extend_from_slice(&response);
+        if stream.write_all(&frame).await.is_err() {
+            return;
+        }
+    }
+}
+
+// Response PDU for a request PDU, an exception for anything but a valid read
+fn respond(pdu: &[u8], registers: &RwLock<Vec<u16>>) -> Vec<u8> {
+    let function = pdu[0];
+    let exception = |code: u8| vec![function | 0x80, code];
+    if function != READ_HOLDING_REGISTERS && function != READ_INPUT_REGISTERS {
+        return exception(ILLEGAL_FUNCTION);
+    }
+    let [_, start_hi, start_lo, count_hi, count_lo] = pdu else {
+        return exception(ILLEGAL_DATA_VALUE);
+    };
+    let start = u16::from_be_bytes([*start_hi, *start_lo]) as usize;
+    let count = u16::from_be_bytes([*count_hi, *count_lo]);
+    if !(1..=MAX_READ_REGISTERS).contains(&count) {
+        return exception(ILLEGAL_DATA_VALUE);
+    }
+    let registers = registers.read().unwrap_or_else(|e| e.into_inner());
+    let Some(values) = registers.get(start..start + count as usize) else {
+        return exception(ILLEGAL_DATA_ADDRESS);
+    };
+    let mut response = Vec::with_capacity(2 + values.len() * 2);
+    response.push(function);
+    response.push((values.len() * 2) as u8);
+    for value in values {
+        response.extend_from_slice(&value.to_be_bytes());
+    }
+    response
+}
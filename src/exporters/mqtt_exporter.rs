@@ -0,0 +1,284 @@
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use crate::models::{TelemetryConfig, TelemetryReading};
+use chrono::SecondsFormat;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Outgoing, QoS, Transport};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use super::{FrameValue, ReadingFrame};
+
+// Sink name reported in export errors
+const SINK: &str = "mqtt";
+
+// Topic every reading goes to unless told otherwise, a topic per sensor
+pub const DEFAULT_MQTT_TOPIC: &str = "telemetry/{launch_id}/{sensor}";
+
+// Publishes the client can queue up before `write_readings` waits on the connection
+const REQUEST_QUEUE: usize = 1024;
+
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+// Broker to publish to, `mqtt://host:port` or `mqtts://host:port` for TLS. The port defaults to
+// 1883 and 8883
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MqttBroker {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+}
+
+impl FromStr for MqttBroker {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (tls, address) = if let Some(address) = s.strip_prefix("mqtt://") {
+            (false, address)
+        } else if let Some(address) = s.strip_prefix("mqtts://") {
+            (true, address)
+        } else {
+            return Err(TelemetryError::config(format!(
+                "Unknown broker {s}. Use mqtt://host:port or mqtts://host:port"
+            )));
+        };
+        let default_port = if tls { 8883 } else { 1883 };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|_| TelemetryError::config(format!("Invalid broker port in {s}")))?;
+                (host, port)
+            }
+            None => (address, default_port),
+        };
+        if host.is_empty() {
+            return Err(TelemetryError::config(format!(
+                "Missing broker host in {s}"
+            )));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            tls,
+        })
+    }
+}
+
+impl std::fmt::Display for MqttBroker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scheme = if self.tls { "mqtts" } else { "mqtt" };
+        write!(f, "{scheme}://{}:{}", self.host, self.port)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub broker: MqttBroker,
+    pub client_id: String,
+    // `{launch_id}` and `{sensor}` are filled in per reading. Leave `{sensor}` out to send every
+    // reading to the one topic
+    pub topic: String,
+    // 0, 1 or 2
+    pub qos: u8,
+    // Brokers keep the last reading of each topic for subscribers that join later
+    pub retain: bool,
+    // PEM CA certificate to trust for TLS instead of the system's roots
+    pub ca_file: Option<PathBuf>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            broker: MqttBroker {
+                host: "localhost".to_string(),
+                port: 1883,
+                tls: false,
+            },
+            client_id: "telemetry-generator".to_string(),
+            topic: DEFAULT_MQTT_TOPIC.to_string(),
+            qos: 0,
+            retain: false,
+            ca_file: None,
+            username: None,
+            password: None,
+        }
+    }
+}
+
+// Publishes each reading as a JSON message, the same object as an NDJSON line, like a flight
+// computer would to an edge gateway. The connection is driven by a task on the current tokio
+// runtime, so connect from inside one
+pub struct MqttExporter {
+    client: AsyncClient,
+    connection: JoinHandle<()>,
+    // Why the connection dropped, reported by the next publish
+    failure: Arc<Mutex<Option<String>>>,
+    config: MqttConfig,
+    qos: QoS,
+    // Rendered topics per sensor label
+    topics: HashMap<String, String>,
+    readings_sent: usize,
+}
+
+impl MqttExporter {
+    pub fn connect(config: MqttConfig) -> Result<Self> {
+        let qos = match config.qos {
+            0 => QoS::AtMostOnce,
+            1 => QoS::AtLeastOnce,
+            2 => QoS::ExactlyOnce,
+            other => {
+                return Err(TelemetryError::config(format!(
+                    "MQTT QoS is 0, 1 or 2, not {other}"
+                )));
+            }
+        };
+        let mut options = MqttOptions::new(
+            config.client_id.clone(),
+            config.broker.host.clone(),
+            config.broker.port,
+        );
+        options.set_keep_alive(KEEP_ALIVE);
+        if let Some(username) = &config.username {
+            options.set_credentials(
+                username.clone(),
+                config.password.clone().unwrap_or_default(),
+            );
+        }
+        match (&config.ca_file, config.broker.tls) {
+            (Some(ca_file), true) => {
+                let ca = std::fs::read(ca_file)
+                    .io_context(|| format!("Failed to read {}", ca_file.display()))?;
+                options.set_transport(Transport::tls(ca, None, None));
+            }
+            (None, true) => {
+                options.set_transport(Transport::tls_with_default_config());
+            }
+            (Some(_), false) => {
+                return Err(TelemetryError::config(
+                    "A CA file only applies to TLS brokers, use mqtts://",
+                ));
+            }
+            (None, false) => {}
+        }
+
+        info!("Publishing to {} on {}", config.topic, config.broker);
+        let (client, event_loop) = AsyncClient::new(options, REQUEST_QUEUE);
+        let failure = Arc::new(Mutex::new(None));
+        let connection = tokio::spawn(drive(event_loop, failure.clone()));
+        Ok(Self {
+            client,
+            connection,
+            failure,
+            config,
+            qos,
+            topics: HashMap::new(),
+            readings_sent: 0,
+        })
+    }
+
+    // Publish every reading as a message of its own. Waits while the client's queue is full
+    pub async fn write_readings(
+        &mut self,
+        readings: &[TelemetryReading],
+        config: &TelemetryConfig,
+    ) -> Result<()> {
+        for reading in readings {
+            let label = reading.sensor_label(config);
+            let topic = match self.topics.get(label.as_ref()) {
+                Some(topic) => topic.clone(),
+                None => {
+                    let topic = self
+                        .config
+                        .topic
+                        .replace("{launch_id}", &config.launch_id)
+                        .replace("{sensor}", &label);
+                    self.topics.insert(label.to_string(), topic.clone());
+                    topic
+                }
+            };
+            let payload = serde_json::to_vec(&ReadingFrame {
+                timestamp: reading
+                    .timestamp
+                    .to_rfc3339_opts(SecondsFormat::Micros, true),
+                time_since_launch_ms: reading.time_since_launch_ms,
+                sensor_type: label,
+                value: FrameValue::new(reading, config),
+                unit: config.units.unit(reading.sensor),
+                id: None,
+                mission_phase: reading.phase.name(),
+                reading,
+            })
+            .export_context(SINK, || "Failed to serialize reading")?;
+            if let Err(e) = self
+                .client
+                .publish(topic, self.qos, self.config.retain, payload)
+                .await
+            {
+                return Err(self.publish_error(e));
+            }
+        }
+        self.readings_sent += readings.len();
+        Ok(())
+    }
+
+    // Disconnect once everything queued has gone out
+    pub async fn close(self) -> Result<()> {
+        if let Err(e) = self.client.disconnect().await {
+            return Err(self.publish_error(e));
+        }
+        let _ = self.connection.await;
+        if let Some(failure) = self
+            .failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+        {
+            return Err(TelemetryError::export(SINK, failure));
+        }
+        info!(
+            "Published {} readings to {}",
+            self.readings_sent, self.config.broker
+        );
+        Ok(())
+    }
+
+    // The connection's own error says more than the closed queue the client sees
+    fn publish_error(&self, error: rumqttc::ClientError) -> TelemetryError {
+        let failure = self
+            .failure
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        match failure {
+            Some(failure) => TelemetryError::export(SINK, failure),
+            None => TelemetryError::ExportError {
+                sink: SINK.to_string(),
+                message: format!("Failed to publish to {}", self.config.broker),
+                source: Some(error.into()),
+            },
+        }
+    }
+}
+
+// Poll the connection until it's disconnected or fails. Failing closes the client's queue so the
+// next publish errors out rather than reconnecting behind the caller's back
+async fn drive(mut event_loop: EventLoop, failure: Arc<Mutex<Option<String>>>) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => {}
+            Err(e) => {
+                warn!("MQTT connection failed: {e}");
+                *failure.lock().unwrap_or_else(|e| e.into_inner()) =
+                    Some(format!("Connection to the broker failed: {e}"));
+                return;
+            }
+        }
+    }
+}
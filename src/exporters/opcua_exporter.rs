@@ -0,0 +1,115 @@
+use crate::error::{Result, TelemetryError};
+use crate::generators::CancelToken;
+use crate::models::{SensorValue, TelemetryConfig, TelemetryReading};
+use opcua::server::prelude::*;
+use opcua::sync::RwLock;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+// OPC UA server for SCADA and historian products that speak nothing else. Each channel is a
+// variable in the Telemetry folder, its node id the sensor label as a string in the
+// `urn:telemetry-generator` namespace, e.g. `s=F_pa`. Variables are added on their first
+// reading and updated as the readings go out, with the reading's timestamp as the source time.
+// Anonymous access without security, so it's for test benches only, like the Modbus server
+
+const APPLICATION_NAME: &str = "Telemetry Generator";
+const APPLICATION_URI: &str = "urn:telemetry-generator";
+
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Clones share the address space, so a flight can write to the server the stream command bound
+#[derive(Clone)]
+pub struct OpcUaServer {
+    address_space: Arc<RwLock<AddressSpace>>,
+    namespace: u16,
+    folder: NodeId,
+    local_addr: SocketAddr,
+}
+
+impl OpcUaServer {
+    // Serve on `addr` until `shutdown` is cancelled. The server's self signed certificate is
+    // made in `pki_dir` the first time
+    pub fn bind(addr: SocketAddr, pki_dir: &Path, shutdown: CancelToken) -> Result<Self> {
+        // The server binds on its own thread and only logs a failure, so check the port first
+        drop(
+            std::net::TcpListener::bind(addr)
+                .map_err(|e| TelemetryError::config(format!("Failed to listen on {addr}: {e}")))?,
+        );
+        let server = ServerBuilder::new_anonymous(APPLICATION_NAME)
+            .application_uri(APPLICATION_URI)
+            .product_uri(APPLICATION_URI)
+            .host_and_port(addr.ip().to_string(), addr.port())
+            .pki_dir(pki_dir)
+            .create_sample_keypair(true)
+            .server()
+            .ok_or_else(|| {
+                TelemetryError::config(format!("Failed to set up the OPC UA server on {addr}"))
+            })?;
+
+        let address_space = server.address_space();
+        let (namespace, folder) = {
+            let mut address_space = address_space.write();
+            let namespace = address_space
+                .register_namespace(APPLICATION_URI)
+                .map_err(|()| TelemetryError::config("Failed to register the OPC UA namespace"))?;
+            let folder = address_space
+                .add_folder("Telemetry", "Telemetry", &NodeId::objects_folder_id())
+                .map_err(|()| TelemetryError::config("Failed to add the OPC UA folder"))?;
+            (namespace, folder)
+        };
+
+        let server = Arc::new(RwLock::new(server));
+        let running = server.clone();
+        std::thread::Builder::new()
+            .name("opcua".to_string())
+            .spawn(move || Server::run_server(running))
+            .map_err(|e| {
+                TelemetryError::config(format!("Failed to start the OPC UA server: {e}"))
+            })?;
+        tokio::spawn(async move {
+            while !shutdown.is_cancelled() {
+                tokio::time::sleep(SHUTDOWN_CHECK_INTERVAL).await;
+            }
+            server.write().abort();
+        });
+        info!("Serving channels over OPC UA on opc.tcp://{addr}");
+        Ok(Self {
+            address_space,
+            namespace,
+            folder,
+            local_addr: addr,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    // Update the channels from a timestep's readings. Values are in the exported units, discrete
+    // states by name
+    pub fn write_readings(&self, readings: &[TelemetryReading], config: &TelemetryConfig) {
+        let now = DateTime::now();
+        let mut address_space = self.address_space.write();
+        for reading in readings {
+            let label = reading.sensor_label(config);
+            let node_id = NodeId::new(self.namespace, label.to_string());
+            let value = match &reading.value {
+                SensorValue::Float(f) => Variant::from(config.export_float(reading.sensor, *f)),
+                SensorValue::Int(i) => Variant::from(*i),
+                SensorValue::UnsignedInt(u) => Variant::from(*u),
+                SensorValue::String(s) => Variant::String(UAString::from(s.as_str())),
+                SensorValue::State(state) => Variant::String(UAString::from(state.name())),
+            };
+            if address_space.find_node(&node_id).is_none() {
+                let variable =
+                    Variable::new(&node_id, label.as_ref(), label.as_ref(), value.clone());
+                address_space.add_variables(vec![variable], &self.folder);
+            }
+            let source_time = DateTime::from(reading.timestamp);
+            address_space.set_variable_value(node_id, value, &source_time, &now);
+        }
+    }
+}
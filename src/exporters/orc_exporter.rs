@@ -0,0 +1,180 @@
+use super::output_files::prepare_output_file;
+use super::{ParquetConfig, ParquetExporter};
+use crate::error::{ExportContext, IoContext, Result};
+use crate::models::{TelemetryConfig, TelemetryDataset};
+use arrow::compute::cast;
+use arrow::datatypes::DataType;
+use arrow::record_batch::RecordBatch;
+use arrow_schema::{Field, Schema, SchemaRef};
+use orc_rust::{ArrowWriter, ArrowWriterBuilder};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+
+// Sink name reported in export errors
+const SINK: &str = "orc";
+
+// The readings as ORC for Hive era readers. Batches are the parquet exporter's, in the same layout
+// and value columns, cast to what ORC holds. Its parquet only settings and key values don't apply
+pub struct OrcExporter {
+    batches: ParquetExporter,
+}
+
+impl OrcExporter {
+    pub fn new(config: ParquetConfig) -> Self {
+        Self {
+            batches: ParquetExporter::new(config),
+        }
+    }
+
+    pub fn export(
+        &self,
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
+        if dataset.readings.is_empty() {
+            warn!("No readings to export. Exiting export.");
+            return Ok(());
+        }
+        let mut writer = self.create_writer(&dataset.config, output_dir, output_name)?;
+        writer.write_chunk(dataset)?;
+        writer.close()
+    }
+
+    // Open an ORC file that chunks of readings can be streamed into, a record batch each
+    pub fn create_writer(
+        &self,
+        config: &TelemetryConfig,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<OrcStreamWriter<'_>> {
+        let orc_file = prepare_output_file(output_dir, output_name, "orc")?;
+        let output_file = File::create(&orc_file)
+            .io_context(|| format!("Failed to create output file at {}", orc_file.display()))?;
+        let schema = orc_schema(&self.batches.schema(config));
+        let writer = ArrowWriterBuilder::new(BufWriter::new(output_file), schema.clone())
+            .try_build()
+            .export_context(SINK, || "Failed to create ORC writer")?;
+        Ok(OrcStreamWriter {
+            exporter: self,
+            writer,
+            schema,
+            orc_file,
+            rows_written: 0,
+            readings_seen: 0,
+        })
+    }
+}
+
+// ORC has no unsigned integers and orc-rust doesn't write timestamps yet, so both go in as Int64,
+// the timestamp as microseconds since the epoch like the parquet column holds it
+fn orc_schema(schema: &Schema) -> SchemaRef {
+    let fields: Vec<Field> = schema
+        .fields()
+        .iter()
+        .map(|field| match field.data_type() {
+            DataType::UInt64 | DataType::Timestamp(_, _) => {
+                field.as_ref().clone().with_data_type(DataType::Int64)
+            }
+            _ => field.as_ref().clone(),
+        })
+        .collect();
+    Arc::new(Schema::new(fields))
+}
+
+// Streams chunks of readings into a single ORC file, a stripe filling up over several batches
+pub struct OrcStreamWriter<'a> {
+    exporter: &'a OrcExporter,
+    writer: ArrowWriter<BufWriter<File>>,
+    schema: SchemaRef,
+    orc_file: PathBuf,
+    rows_written: usize,
+    // Readings of the run so far, for the ids
+    readings_seen: usize,
+}
+
+impl OrcStreamWriter<'_> {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        if chunk.readings.is_empty() {
+            return Ok(());
+        }
+        let batch = self
+            .exporter
+            .batches
+            .record_batch(chunk, self.readings_seen)?;
+        self.readings_seen += chunk.readings.len();
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(self.schema.fields())
+            .map(|(column, field)| cast(column, field.data_type()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .export_context(SINK, || "Failed to convert the readings for ORC")?;
+        let batch = RecordBatch::try_new(self.schema.clone(), columns)
+            .export_context(SINK, || "Failed to convert the readings for ORC")?;
+        self.writer
+            .write(&batch)
+            .export_context(SINK, || "Failed to write record batch to ORC")?;
+        self.rows_written += batch.num_rows();
+        Ok(())
+    }
+
+    // Bytes written so far, less the stripe still being built
+    pub fn bytes_written(&self) -> u64 {
+        std::fs::metadata(&self.orc_file).map_or(0, |metadata| metadata.len())
+    }
+
+    pub fn close(self) -> Result<()> {
+        // Closing writes the last stripe and the file footer
+        self.writer
+            .close()
+            .export_context(SINK, || "Failed to close ORC writer")?;
+        info!(
+            "Exported {} rows to ORC file at {}",
+            self.rows_written,
+            self.orc_file.display()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::TelemetryGenerator;
+    use crate::models::{SampleRate, TelemetryConfig};
+    use orc_rust::ArrowReaderBuilder;
+
+    #[test]
+    fn readings_read_back_from_the_orc_file() {
+        let config = TelemetryConfig {
+            duration: 2,
+            sample_rate_hz: SampleRate::from_hz(10),
+            point_ids: true,
+            ..TelemetryConfig::default()
+        };
+        let dataset = TelemetryGenerator::new(config).generate(true).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        OrcExporter::new(ParquetConfig::default())
+            .export(&dataset, dir.path(), "run")
+            .unwrap();
+
+        let file = File::open(dir.path().join("run.orc")).unwrap();
+        let batches = ArrowReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+        assert_eq!(rows, dataset.readings.len());
+        let schema = batches[0].schema();
+        assert_eq!(
+            schema.field_with_name("timestamp").unwrap().data_type(),
+            &DataType::Int64
+        );
+        assert!(schema.field_with_name("id").is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+use crate::error::{IoContext, Result, TelemetryError};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Files written per run as `{output_name}.{extension}`
+pub const OUTPUT_EXTENSIONS: [&str; 27] = [
+    "parquet",
+    "metadata.csv",
+    "sensors.csv",
+    "evolution.json",
+    "calibration.json",
+    "windows.csv",
+    "health.csv",
+    "alerts.csv",
+    "ndjson",
+    "ndjson.zst",
+    "ndjson.gz",
+    "csv",
+    "csv.zst",
+    "csv.gz",
+    "json",
+    "json.zst",
+    "json.gz",
+    "sqlite",
+    "arrow",
+    "arrows",
+    "avro",
+    "orc",
+    "h5",
+    "ccsds",
+    "apids.csv",
+    "summary.xlsx",
+    "sha256",
+];
+
+// File the readings themselves are written to. The text formats are compressed with
+// --text-compression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum DataFormat {
+    Parquet,
+    // `{output_name}.csv`
+    Csv,
+    // `{output_name}.json`, a single document
+    Json,
+    // `{output_name}.ndjson`, a reading per line
+    Ndjson,
+    // `{output_name}.sqlite`, a readings table and a runs table. Never compressed
+    Sqlite,
+    // `{output_name}.arrow`, Arrow IPC ready to memory map. Never compressed
+    Arrow,
+    // `{output_name}.arrows`, the Arrow IPC stream layout
+    ArrowStream,
+    // `{output_name}.avro`, an Avro object container file with the schema embedded
+    Avro,
+    // `{output_name}.orc`, for Hive era readers. Only in builds with the orc feature
+    Orc,
+    // `{output_name}.ccsds`, CCSDS space packets with the APIDs in `{output_name}.apids.csv`.
+    // Never compressed
+    Ccsds,
+    // `{output_name}.h5`, times and values per sensor for flight test tools. Only in builds with
+    // the hdf5 feature
+    Hdf5,
+}
+
+impl DataFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DataFormat::Parquet => "parquet",
+            DataFormat::Csv => "csv",
+            DataFormat::Json => "json",
+            DataFormat::Ndjson => "ndjson",
+            DataFormat::Sqlite => "sqlite",
+            DataFormat::Arrow => "arrow",
+            DataFormat::ArrowStream => "arrows",
+            DataFormat::Avro => "avro",
+            DataFormat::Orc => "orc",
+            DataFormat::Ccsds => "ccsds",
+            DataFormat::Hdf5 => "h5",
+        }
+    }
+
+    // Written as text through a file sink and compressed with --text-compression
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self,
+            DataFormat::Csv | DataFormat::Json | DataFormat::Ndjson
+        )
+    }
+}
+
+// Give up looking for a free suffix after this many tries
+const MAX_SUFFIX: u32 = 10_000;
+
+// What to do when a run's output files already exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    // Refuse to run so baseline runs don't get clobbered. The default
+    Error,
+    Overwrite,
+    // Pick the first free `{output_name}_{n}`
+    AppendSuffix,
+}
+
+// Output name to use for the run under the policy
+pub fn resolve_output_name(
+    output_dir: &Path,
+    output_name: &str,
+    policy: OverwritePolicy,
+) -> Result<String> {
+    let existing = existing_outputs(output_dir, output_name);
+    if existing.is_empty() {
+        return Ok(output_name.to_string());
+    }
+
+    match policy {
+        OverwritePolicy::Error => Err(TelemetryError::config(format!(
+            "Output already exists: {}. Use --force to overwrite it or --append-suffix to write next to it",
+            existing.join(", ")
+        ))),
+        OverwritePolicy::Overwrite => {
+            warn!("Overwriting existing output: {}", existing.join(", "));
+            Ok(output_name.to_string())
+        }
+        OverwritePolicy::AppendSuffix => {
+            for suffix in 1..=MAX_SUFFIX {
+                let candidate = format!("{output_name}_{suffix}");
+                if existing_outputs(output_dir, &candidate).is_empty() {
+                    info!(
+                        "Output {} exists. Writing to {} instead",
+                        output_name, candidate
+                    );
+                    return Ok(candidate);
+                }
+            }
+            Err(TelemetryError::config(format!(
+                "No free output name found for {output_name} after {MAX_SUFFIX} tries"
+            )))
+        }
+    }
+}
+
+fn existing_outputs(output_dir: &Path, output_name: &str) -> Vec<String> {
+    run_output_files(output_dir, output_name)
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+// Files a run with this output name has left in the directory
+pub fn run_output_files(output_dir: &Path, output_name: &str) -> Vec<PathBuf> {
+    OUTPUT_EXTENSIONS
+        .iter()
+        .map(|extension| output_file_path(output_dir, output_name, extension))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+pub fn output_file_path(output_dir: &Path, output_name: &str, extension: &str) -> PathBuf {
+    output_dir.join(format!("{output_name}.{extension}"))
+}
+
+// Path for one of the run's files, creating the output directory first if it's missing
+pub fn prepare_output_file(
+    output_dir: &Path,
+    output_name: &str,
+    extension: &str,
+) -> Result<PathBuf> {
+    std::fs::create_dir_all(output_dir).io_context(|| {
+        format!(
+            "Failed to create output directory {}. Check it's writable or pick another with --output-dir",
+            output_dir.display()
+        )
+    })?;
+    Ok(output_file_path(output_dir, output_name, extension))
+}
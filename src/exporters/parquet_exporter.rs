@@ -1,23 +1,118 @@
-use crate::models::{SensorValue, TelemetryDataset};
-use anyhow::{Context, Result};
-use arrow::array::{ArrayRef, Float64Array, StringArray, TimestampMicrosecondArray};
+use super::output_files::prepare_output_file;
+use crate::error::{ExportContext, IoContext, Result};
+use crate::models::{
+    PairSide, SensorEnum, SensorNaming, SensorValue, TelemetryConfig, TelemetryDataset,
+    sensor_label,
+};
+use arrow::array::{ArrayRef, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray};
+use arrow::compute::{SortOptions, sort_to_indices, take_record_batch};
 use arrow::record_batch::RecordBatch;
 use arrow_array::UInt64Array;
 use arrow_schema::{DataType, Field, Schema};
 use indicatif::{ProgressBar, ProgressStyle};
 use parquet::arrow::arrow_writer::ArrowWriter;
-use parquet::file::properties::WriterProperties;
+use parquet::file::metadata::KeyValue;
+use parquet::file::properties::{EnabledStatistics, WriterProperties};
+use parquet::format::SortingColumn;
+use parquet::schema::types::ColumnPath;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::{fs::File, sync::Arc};
 use tracing::{info, warn};
 
-pub struct ParquetExporter;
+// Sink name reported in export errors
+const SINK: &str = "parquet";
+
+// Key in the parquet footer recording which value layout the file was written with
+pub const SCHEMA_VERSION_KEY: &str = "telemetry_schema_version";
+
+// Key recording the unit system values were converted into
+pub const UNITS_KEY: &str = "telemetry_units";
+
+// Key holding a JSON object of sensor_type to unit, e.g. {"F_pa":"Pa"}
+pub const SENSOR_UNITS_KEY: &str = "telemetry_sensor_units";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ValueColumns {
+    // v1: one non-null Float64 `value` column. Ints are cast and strings dropped
+    Single,
+    // v2: nullable value_float, value_int and value_str columns
+    Split,
+}
+
+impl ValueColumns {
+    pub fn schema_version(&self) -> &'static str {
+        match self {
+            ValueColumns::Single => "1",
+            ValueColumns::Split => "2",
+        }
+    }
+}
+
+// How readings map onto rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum RowLayout {
+    // A row per reading with a sensor_type column
+    #[default]
+    Long,
+    // A row per timestep with a nullable Float64 column per sensor, for ML training
+    Wide,
+}
+
+#[derive(Debug, Clone)]
+pub struct ParquetConfig {
+    // Sort rows by timestamp before writing and record it in the file metadata.
+    // Off by default since jittered, out of order rows are more realistic
+    pub sort_by_timestamp: bool,
+    // Write a bloom filter for the sensor_type column to speed up point lookups
+    pub bloom_filter: bool,
+    pub bloom_filter_fpp: f64,
+    // Distinct sensor_type values the bloom filter is sized for
+    pub bloom_filter_ndv: u64,
+    // Page level statistics which produce the column and offset indexes
+    pub column_index: bool,
+    // Smaller pages make the column index more selective at the cost of size
+    pub data_page_row_count_limit: Option<usize>,
+    pub value_columns: ValueColumns,
+    // Add a nullable raw_value column with calibrated sensors' values before calibration
+    pub raw_values: bool,
+    // Value columns, raw values and the bloom filter only apply to the long layout
+    pub layout: RowLayout,
+}
+
+impl Default for ParquetConfig {
+    fn default() -> Self {
+        Self {
+            sort_by_timestamp: false,
+            bloom_filter: false,
+            bloom_filter_fpp: 0.05,
+            bloom_filter_ndv: SensorEnum::number_of_sensors() as u64,
+            column_index: true,
+            data_page_row_count_limit: None,
+            value_columns: ValueColumns::Single,
+            raw_values: false,
+            layout: RowLayout::Long,
+        }
+    }
+}
+
+pub struct ParquetExporter {
+    config: ParquetConfig,
+}
 
 impl ParquetExporter {
-    // pub fn new() -> Self {
-    //     ParquetExporter {}
-    // }
+    pub fn new(config: ParquetConfig) -> Self {
+        Self { config }
+    }
 
-    pub fn export(dataset: &TelemetryDataset, output_name: &str) -> Result<()> {
+    pub fn export(
+        &self,
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
         info!("Inside export parquet");
 
         // Don't write anything out...
@@ -26,42 +121,172 @@ impl ParquetExporter {
             return Ok(()); // todo return something else.
         }
 
-        let schema: Schema = Self::create_schema();
-        let parquet_file = format!("output/{output_name}.parquet");
+        let mut writer = self.create_writer(&dataset.config, output_dir, output_name)?;
+        writer.write_chunk(dataset)?;
+        writer.close()
+    }
+
+    // The dataset as a parquet file written to `writer`, e.g. a buffer for an HTTP response.
+    // Returns the writer once the footer is in
+    pub fn write_to<W: Write + Send>(&self, dataset: &TelemetryDataset, writer: W) -> Result<W> {
+        let batch = self.record_batch(dataset, 0)?;
+        let props = self.writer_properties(&dataset.config);
+        let mut writer = ArrowWriter::try_new(writer, batch.schema(), Some(props))
+            .export_context(SINK, || "Failed to create arrow writer")?;
+        writer
+            .write(&batch)
+            .export_context(SINK, || "Failed to write record batch to Parquet")?;
+        writer
+            .into_inner()
+            .export_context(SINK, || "Failed to finish the Parquet file")
+    }
+
+    // A chunk as the record batch it would be written as, in the configured layout and order.
+    // `first_reading` is the index in the run of the chunk's first reading, for its id
+    pub fn record_batch(
+        &self,
+        chunk: &TelemetryDataset,
+        first_reading: usize,
+    ) -> Result<RecordBatch> {
+        let batch = match self.config.layout {
+            RowLayout::Long => self.convert_to_record_batch(
+                chunk,
+                self.create_schema(&chunk.config),
+                first_reading,
+            )?,
+            RowLayout::Wide => {
+                let columns = WideColumns::new(&chunk.config);
+                columns.convert_to_record_batch(chunk, columns.schema())?
+            }
+        };
+        if self.config.sort_by_timestamp {
+            Self::sort_by_timestamp(&batch)
+        } else {
+            Ok(batch)
+        }
+    }
+
+    // Open a parquet file that chunks of readings can be streamed into. Each chunk becomes a row group
+    pub fn create_writer(
+        &self,
+        config: &TelemetryConfig,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<ParquetStreamWriter<'_>> {
+        let wide_columns =
+            (self.config.layout == RowLayout::Wide).then(|| WideColumns::new(config));
+        let schema: Schema = match &wide_columns {
+            Some(columns) => columns.schema(),
+            None => self.create_schema(config),
+        };
+        let parquet_file = prepare_output_file(output_dir, output_name, "parquet")?;
         let output_file: File = File::create(&parquet_file)
-            .with_context(|| format!("Failed to create output file at {output_name}"))?;
+            .io_context(|| format!("Failed to create output file at {}", parquet_file.display()))?;
 
         // Create arrow writer
-        let props = WriterProperties::builder()
-            .set_compression(parquet::basic::Compression::SNAPPY)
-            .build();
-        let mut writer: ArrowWriter<File> =
+        let props = self.writer_properties(config);
+        let writer: ArrowWriter<File> =
             ArrowWriter::try_new(output_file, Arc::new(schema.clone()), Some(props))
-                .context("Failed to create arrow writer")?;
+                .export_context(SINK, || "Failed to create arrow writer")?;
 
-        let batch: RecordBatch = Self::convert_to_record_batch(dataset, schema)?;
+        Ok(ParquetStreamWriter {
+            exporter: self,
+            writer,
+            schema,
+            wide_columns,
+            parquet_file,
+            rows_written: 0,
+            readings_seen: 0,
+        })
+    }
 
-        // Write to file
-        writer
-            .write(&batch)
-            .with_context(|| "Failed to write record batch to Parquet")?;
+    // Schema of the record batches in the configured layout, with the footer's key values as
+    // its metadata. For writers of other formats built on the same batches
+    pub fn schema(&self, config: &TelemetryConfig) -> Schema {
+        let schema = match self.config.layout {
+            RowLayout::Long => self.create_schema(config),
+            RowLayout::Wide => WideColumns::new(config).schema(),
+        };
+        let metadata = self
+            .key_values(config)
+            .into_iter()
+            .filter_map(|key_value| Some((key_value.key, key_value.value?)))
+            .collect();
+        schema.with_metadata(metadata)
+    }
 
-        writer
-            .close()
-            .with_context(|| "Failed to close Parquet writer")?;
+    fn key_values(&self, config: &TelemetryConfig) -> Vec<KeyValue> {
+        // The schema version describes the long value columns, it doesn't apply to wide files
+        let mut key_values = vec![
+            KeyValue::new(UNITS_KEY.to_string(), config.units.name().to_string()),
+            KeyValue::new(SENSOR_UNITS_KEY.to_string(), Self::sensor_units(config)),
+        ];
+        if self.config.layout != RowLayout::Wide {
+            key_values.insert(
+                0,
+                KeyValue::new(
+                    SCHEMA_VERSION_KEY.to_string(),
+                    self.config.value_columns.schema_version().to_string(),
+                ),
+            );
+        }
+        key_values
+    }
 
-        // Implement Parquet export logic here
-        info!(
-            "Exporting {} readings to Parquet file at {}",
-            batch.num_rows(),
-            parquet_file
-        );
+    fn writer_properties(&self, config: &TelemetryConfig) -> WriterProperties {
+        let wide = self.config.layout == RowLayout::Wide;
+        let mut props = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .set_key_value_metadata(Some(self.key_values(config)));
 
-        Ok(())
+        if self.config.sort_by_timestamp {
+            // Let query engines know every row group is ordered by timestamp
+            props = props.set_sorting_columns(Some(vec![SortingColumn::new(0, false, false)]));
+        }
+
+        if self.config.bloom_filter && !wide {
+            let sensor_column = ColumnPath::from("sensor_type");
+            info!(
+                "Writing bloom filter for {} with fpp {}",
+                sensor_column, self.config.bloom_filter_fpp
+            );
+            props = props
+                .set_column_bloom_filter_enabled(sensor_column.clone(), true)
+                .set_column_bloom_filter_fpp(sensor_column.clone(), self.config.bloom_filter_fpp)
+                .set_column_bloom_filter_ndv(sensor_column, self.config.bloom_filter_ndv);
+        }
+
+        // Column index needs page level stats. Otherwise only keep row group stats
+        props = if self.config.column_index {
+            props.set_statistics_enabled(EnabledStatistics::Page)
+        } else {
+            props.set_statistics_enabled(EnabledStatistics::Chunk)
+        };
+
+        if let Some(limit) = self.config.data_page_row_count_limit {
+            props = props.set_data_page_row_count_limit(limit);
+        }
+
+        props.build()
+    }
+
+    // Units are the same for every instance of a sensor so only base names are listed
+    fn sensor_units(config: &TelemetryConfig) -> String {
+        let sensor_units: serde_json::Map<String, serde_json::Value> =
+            SensorEnum::get_all_sensor_enums()
+                .into_iter()
+                .flat_map(|sensor| {
+                    config
+                        .exported_names(sensor)
+                        .into_iter()
+                        .map(move |name| (name.to_string(), config.units.unit(sensor).into()))
+                })
+                .collect();
+        serde_json::Value::Object(sensor_units).to_string()
     }
 
-    fn create_schema() -> Schema {
-        Schema::new(vec![
+    fn create_schema(&self, config: &TelemetryConfig) -> Schema {
+        let mut fields = vec![
             Field::new(
                 "timestamp",
                 DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None), // todo is Nano second possible?
@@ -69,27 +294,62 @@ impl ParquetExporter {
             ),
             Field::new("time_since_launch_ms", DataType::UInt64, false),
             Field::new("sensor_type", DataType::Utf8, false),
-            Field::new("value", DataType::Float64, false), // was 3 columns for Float, I64, U64
-        ])
+        ];
+
+        match self.config.value_columns {
+            ValueColumns::Single => {
+                fields.push(Field::new("value", DataType::Float64, false));
+            }
+            ValueColumns::Split => {
+                // Only the column matching the reading's type is filled, the rest are null
+                fields.push(Field::new("value_float", DataType::Float64, true));
+                fields.push(Field::new("value_int", DataType::Int64, true));
+                fields.push(Field::new("value_str", DataType::Utf8, true));
+            }
+        }
+        if self.config.raw_values {
+            fields.push(Field::new("raw_value", DataType::Float64, true));
+        }
+        // Last so column positions of earlier files still line up
+        fields.push(Field::new("mission_phase", DataType::Utf8, false));
+        if config.point_ids {
+            fields.push(Field::new("id", DataType::Utf8, false));
+        }
+
+        Schema::new(fields)
     }
 
     // Convert telemetry record to arrow record batch
-    fn convert_to_record_batch(dataset: &TelemetryDataset, schema: Schema) -> Result<RecordBatch> {
+    fn convert_to_record_batch(
+        &self,
+        dataset: &TelemetryDataset,
+        schema: Schema,
+        first_reading: usize,
+    ) -> Result<RecordBatch> {
         info!("Inside convert to record batch");
         let total_readings = dataset.readings.len();
         // todo currently no choice on the PB
         let pb = ProgressBar::new(total_readings as u64);
         pb.set_style(
             ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:50.cyan/blue}] {pos:>7}/{len:7} readings ({percent}%) {msg} ({eta})")?
+                .template("{spinner:.green} [{elapsed_precise}] [{bar:50.cyan/blue}] {pos:>7}/{len:7} readings ({percent}%) {msg} ({eta})")
+                .export_context(SINK, || "Invalid progress bar template")?
                 .progress_chars("#>-"),
         );
 
         // prepare arrays
         let mut timestamps = Vec::with_capacity(total_readings);
         let mut time_since_launch_ms = Vec::with_capacity(total_readings);
-        let mut sensor_types = Vec::with_capacity(total_readings);
+        let mut sensor_types: Vec<Cow<str>> = Vec::with_capacity(total_readings);
         let mut values = Vec::with_capacity(total_readings);
+        let mut value_floats: Vec<Option<f64>> = Vec::new();
+        let mut value_ints: Vec<Option<i64>> = Vec::new();
+        let mut value_strs: Vec<Option<&str>> = Vec::new();
+        let mut raw_values: Vec<Option<f64>> = Vec::new();
+        let mut phases = Vec::with_capacity(total_readings);
+        let mut ids: Vec<String> = Vec::new();
+        let mut dropped_readings: usize = 0;
+        let config = &dataset.config;
 
         // Fill arrays from readings
         for (i, reading) in dataset.readings.iter().enumerate() {
@@ -97,35 +357,306 @@ impl ParquetExporter {
                 pb.set_position(i as u64);
             }
 
+            match self.config.value_columns {
+                ValueColumns::Single => {
+                    // Single float column can't hold strings so those readings get dropped
+                    values.push(match &reading.value {
+                        SensorValue::Float(v) => config.export_float(reading.sensor, *v),
+                        SensorValue::Int(v) => *v as f64,
+                        SensorValue::UnsignedInt(v) => *v as f64,
+                        SensorValue::State(state) => state.code() as f64,
+                        SensorValue::String(_) => {
+                            dropped_readings += 1;
+                            continue;
+                        }
+                    });
+                }
+                ValueColumns::Split => {
+                    let (float, int, string) = match &reading.value {
+                        SensorValue::Float(v) => {
+                            (Some(config.export_float(reading.sensor, *v)), None, None)
+                        }
+                        SensorValue::Int(v) => (None, Some(*v), None),
+                        // Counters never get near i64::MAX in practice
+                        SensorValue::UnsignedInt(v) => {
+                            (None, Some(i64::try_from(*v).unwrap_or(i64::MAX)), None)
+                        }
+                        SensorValue::String(v) => (None, None, Some(v.as_str())),
+                        // Code for filtering plus the name for reading
+                        SensorValue::State(state) => (None, Some(state.code()), Some(state.name())),
+                    };
+                    value_floats.push(float);
+                    value_ints.push(int);
+                    value_strs.push(string);
+                }
+            }
+
+            if self.config.raw_values {
+                raw_values.push(match &reading.value {
+                    SensorValue::Float(v) if config.calibration(reading.sensor).is_some() => {
+                        Some(*v)
+                    }
+                    _ => None,
+                });
+            }
             timestamps.push(reading.timestamp.timestamp_micros());
             time_since_launch_ms.push(reading.time_since_launch_ms);
-            sensor_types.push(reading.sensor.field_name().to_string());
-
-            values.push(match &reading.value {
-                SensorValue::Float(v) => *v, // as f64,
-                // SensorValue::Int(v) => *v as f64,
-                // SensorValue::UnsignedInt(v) => *v as f64,
-                SensorValue::String(v) => todo!("Can't pass a string here: {v}. need to refactor"),
-                // SensorValue::State(v) => todo!(),
-                // SensorValue::Status(v) => todo!(),
-            });
+            sensor_types.push(reading.sensor_label(&dataset.config));
+            phases.push(reading.phase.name());
+            if config.point_ids {
+                ids.push(config.point_id(first_reading + i));
+            }
         }
 
         pb.finish_with_message("Arrow conversion complete");
 
+        if dropped_readings > 0 {
+            warn!(
+                "Dropped {} string readings that don't fit the single value column. Use the split value columns to keep them",
+                dropped_readings
+            );
+        }
+
         // Create Arrays from collected values
-        let arrays: Vec<ArrayRef> = vec![
+        let mut arrays: Vec<ArrayRef> = vec![
             Arc::new(TimestampMicrosecondArray::from(timestamps)),
             Arc::new(UInt64Array::from(time_since_launch_ms)),
-            Arc::new(StringArray::from(sensor_types)),
-            Arc::new(Float64Array::from(values)),
-            // value ints, uInts
+            Arc::new(StringArray::from_iter_values(sensor_types)),
         ];
+        match self.config.value_columns {
+            ValueColumns::Single => arrays.push(Arc::new(Float64Array::from(values))),
+            ValueColumns::Split => {
+                arrays.push(Arc::new(Float64Array::from(value_floats)));
+                arrays.push(Arc::new(Int64Array::from(value_ints)));
+                arrays.push(Arc::new(StringArray::from(value_strs)));
+            }
+        }
+        if self.config.raw_values {
+            arrays.push(Arc::new(Float64Array::from(raw_values)));
+        }
+        arrays.push(Arc::new(StringArray::from(phases)));
+        if config.point_ids {
+            arrays.push(Arc::new(StringArray::from(ids)));
+        }
 
         let batch = RecordBatch::try_new(Arc::new(schema), arrays)
-            .with_context(|| "Failed to create RecordBatch from arrays")?;
+            .export_context(SINK, || "Failed to create RecordBatch from arrays")?;
         info!("Successfully created Arrow RecordBatch");
 
         Ok(batch)
     }
+
+    // Sort the whole batch by timestamp so each row group written from it is sorted too
+    fn sort_by_timestamp(batch: &RecordBatch) -> Result<RecordBatch> {
+        info!("Sorting {} rows by timestamp", batch.num_rows());
+        let indices = sort_to_indices(batch.column(0), Some(SortOptions::default()), None)
+            .export_context(SINK, || "Failed to sort readings by timestamp")?;
+
+        take_record_batch(batch, &indices).export_context(SINK, || "Failed to reorder record batch")
+    }
+}
+
+// Streams chunks of readings into a single parquet file as row groups
+pub struct ParquetStreamWriter<'a> {
+    exporter: &'a ParquetExporter,
+    writer: ArrowWriter<File>,
+    schema: Schema,
+    // Set for the wide layout
+    wide_columns: Option<WideColumns>,
+    parquet_file: PathBuf,
+    rows_written: usize,
+    // Readings of the run so far, including any the value columns dropped
+    readings_seen: usize,
+}
+
+impl ParquetStreamWriter<'_> {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        if chunk.readings.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch: RecordBatch = match &self.wide_columns {
+            Some(columns) => columns.convert_to_record_batch(chunk, self.schema.clone())?,
+            None => self.exporter.convert_to_record_batch(
+                chunk,
+                self.schema.clone(),
+                self.readings_seen,
+            )?,
+        };
+        self.readings_seen += chunk.readings.len();
+        if self.exporter.config.sort_by_timestamp {
+            batch = ParquetExporter::sort_by_timestamp(&batch)?;
+        }
+
+        // Write to file and close out the row group so the chunk can be dropped
+        self.writer
+            .write(&batch)
+            .export_context(SINK, || "Failed to write record batch to Parquet")?;
+        self.writer
+            .flush()
+            .export_context(SINK, || "Failed to flush row group to Parquet")?;
+        self.rows_written += batch.num_rows();
+
+        Ok(())
+    }
+
+    // Bytes flushed to the file so far
+    pub fn bytes_written(&self) -> u64 {
+        self.writer.bytes_written() as u64
+    }
+
+    pub fn close(self) -> Result<()> {
+        self.writer
+            .close()
+            .export_context(SINK, || "Failed to close Parquet writer")?;
+
+        info!(
+            "Exported {} {} to Parquet file at {}",
+            self.rows_written,
+            if self.wide_columns.is_some() {
+                "rows"
+            } else {
+                "readings"
+            },
+            self.parquet_file.display()
+        );
+
+        Ok(())
+    }
+}
+
+// Column per sensor, instance and redundant side, named from the full sensor names, e.g.
+// `ChamberPressure_003_a`. Renames from schema evolution don't apply, a column keeps its name
+struct WideColumns {
+    index: HashMap<(SensorEnum, Option<u16>, Option<PairSide>), usize>,
+    names: Vec<String>,
+}
+
+impl WideColumns {
+    fn new(config: &TelemetryConfig) -> Self {
+        let instances: Vec<Option<u16>> = match config.sensor_instances {
+            0 | 1 => vec![None],
+            n => (1..=n).map(Some).collect(),
+        };
+        let mut index = HashMap::new();
+        let mut names = Vec::new();
+        for sensor in SensorEnum::get_all_sensor_enums() {
+            // A single command link rather than one per instance, and no columns without it
+            let instances: &[Option<u16>] = match sensor.is_event_driven() {
+                true if !config.reports_commands() => continue,
+                true => &[None],
+                false => &instances,
+            };
+            let sides: &[Option<PairSide>] = if config.is_redundant(sensor) {
+                &[Some(PairSide::A), Some(PairSide::B)]
+            } else {
+                &[None]
+            };
+            for &instance in instances {
+                for &side in sides {
+                    index.insert((sensor, instance, side), names.len());
+                    names.push(
+                        sensor_label(sensor.name(SensorNaming::PascalCase), instance, side)
+                            .into_owned(),
+                    );
+                }
+            }
+        }
+        Self { index, names }
+    }
+
+    fn schema(&self) -> Schema {
+        let mut fields = vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(arrow::datatypes::TimeUnit::Microsecond, None),
+                false,
+            ),
+            Field::new("time_since_launch_ms", DataType::UInt64, false),
+        ];
+        fields.extend(
+            self.names
+                .iter()
+                .map(|name| Field::new(name, DataType::Float64, true)),
+        );
+        fields.push(Field::new("mission_phase", DataType::Utf8, false));
+        Schema::new(fields)
+    }
+
+    // A new row starts whenever time moves on or a sensor reports twice, so steps that share a
+    // millisecond at high rates still get a row each. Rows take the earliest timestamp in them.
+    // States are written as their codes and strings are dropped
+    fn convert_to_record_batch(
+        &self,
+        dataset: &TelemetryDataset,
+        schema: Schema,
+    ) -> Result<RecordBatch> {
+        let config = &dataset.config;
+        let mut timestamps: Vec<i64> = Vec::new();
+        let mut time_since_launch_ms: Vec<u64> = Vec::new();
+        let mut phases: Vec<&str> = Vec::new();
+        let mut columns: Vec<Vec<Option<f64>>> = vec![Vec::new(); self.names.len()];
+        let mut dropped_readings: usize = 0;
+
+        for reading in &dataset.readings {
+            let Some(&column) = self
+                .index
+                .get(&(reading.sensor, reading.instance, reading.side))
+            else {
+                continue;
+            };
+            let value = match &reading.value {
+                SensorValue::Float(v) => config.export_float(reading.sensor, *v),
+                SensorValue::Int(v) => *v as f64,
+                SensorValue::UnsignedInt(v) => *v as f64,
+                SensorValue::State(state) => state.code() as f64,
+                SensorValue::String(_) => {
+                    dropped_readings += 1;
+                    continue;
+                }
+            };
+
+            let timestamp = reading.timestamp.timestamp_micros();
+            let same_row = time_since_launch_ms.last() == Some(&reading.time_since_launch_ms)
+                && columns[column].len() < time_since_launch_ms.len();
+            if same_row {
+                if let Some(row_timestamp) = timestamps.last_mut() {
+                    *row_timestamp = (*row_timestamp).min(timestamp);
+                }
+            } else {
+                // Close the previous row, leaving sensors that didn't report in it null
+                for values in &mut columns {
+                    values.resize(time_since_launch_ms.len(), None);
+                }
+                timestamps.push(timestamp);
+                time_since_launch_ms.push(reading.time_since_launch_ms);
+                phases.push(reading.phase.name());
+            }
+            columns[column].push(Some(value));
+        }
+        for values in &mut columns {
+            values.resize(time_since_launch_ms.len(), None);
+        }
+
+        if dropped_readings > 0 {
+            warn!(
+                "Dropped {} string readings that don't fit the wide float columns",
+                dropped_readings
+            );
+        }
+
+        let mut arrays: Vec<ArrayRef> = vec![
+            Arc::new(TimestampMicrosecondArray::from(timestamps)),
+            Arc::new(UInt64Array::from(time_since_launch_ms)),
+        ];
+        arrays.extend(
+            columns
+                .into_iter()
+                .map(|values| Arc::new(Float64Array::from(values)) as ArrayRef),
+        );
+        arrays.push(Arc::new(StringArray::from(phases)));
+
+        RecordBatch::try_new(Arc::new(schema), arrays)
+            .export_context(SINK, || "Failed to build wide record batch")
+    }
 }
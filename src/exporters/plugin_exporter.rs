@@ -0,0 +1,95 @@
+use crate::error::{ExportContext, Result, TelemetryError};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use tracing::info;
+
+use super::stream_sink::Transport;
+
+// Sink name reported in export errors
+const SINK: &str = "plugin";
+
+// Version of the NDJSON protocol spoken to plugins. Bump on breaking changes
+pub const PLUGIN_PROTOCOL_VERSION: u32 = 1;
+
+// Plugins are any executable that reads NDJSON on stdin, so teams can add their own sinks
+// without forking. The plugin is started with the output name as its only argument and gets:
+//
+//   {"type":"start","protocol_version":1,"launch_id":"SIM-001","launch_time":"...","sample_rate_hz":"1000","units":"raw"}
+//   {"type":"reading","timestamp":"...","time_since_launch_ms":0,"sensor_type":"F_pa","value":101325.0,"unit":"Pa","mission_phase":"prelaunch"}
+//   ...
+//   {"type":"end","readings":1620000}
+//
+// Idempotent runs add a stable `"id"` to each reading, the same on every rerun. With
+// --plugin-format the same frames come in another encoding instead.
+//
+// Exiting non zero fails the run.
+pub struct PluginTransport {
+    path: PathBuf,
+    child: Child,
+    stdin: BufWriter<ChildStdin>,
+}
+
+impl PluginTransport {
+    pub fn spawn(path: &Path, output_name: &str) -> Result<Self> {
+        info!("Starting exporter plugin {}", path.display());
+        let mut child = Command::new(path)
+            .arg(output_name)
+            .stdin(Stdio::piped())
+            .spawn()
+            .export_context(SINK, || {
+                format!("Failed to start plugin {}", path.display())
+            })?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| TelemetryError::export(SINK, "Plugin stdin was not piped"))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            child,
+            stdin: BufWriter::new(stdin),
+        })
+    }
+}
+
+impl Transport for PluginTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.stdin.write_all(message).export_context(SINK, || {
+            format!("Plugin {} stopped reading input", self.path.display())
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stdin.flush().export_context(SINK, || {
+            format!("Plugin {} stopped reading input", self.path.display())
+        })
+    }
+
+    // Close stdin and wait for the plugin to finish
+    fn close(self: Box<Self>) -> Result<()> {
+        let PluginTransport {
+            path,
+            mut child,
+            stdin,
+        } = *self;
+        drop(
+            stdin
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .export_context(SINK, || {
+                    format!("Failed to flush readings to plugin {}", path.display())
+                })?,
+        );
+
+        let status = child.wait().export_context(SINK, || {
+            format!("Failed waiting on plugin {}", path.display())
+        })?;
+        if !status.success() {
+            return Err(TelemetryError::export(
+                SINK,
+                format!("Plugin {} exited with {}", path.display(), status),
+            ));
+        }
+        Ok(())
+    }
+}
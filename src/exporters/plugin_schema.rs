@@ -0,0 +1,292 @@
+use crate::error::{IoContext, Result};
+use crate::models::MissionPhase;
+use serde_json::{Map, Value, json};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use super::plugin_exporter::PLUGIN_PROTOCOL_VERSION;
+
+// Stop listing problems after this many, a broken stream usually breaks every line the same way
+const MAX_VIOLATIONS: usize = 20;
+
+// JSON Schema of one NDJSON line of the plugin protocol, for partners to codegen their consumers
+// from. Keep it in step with `Frame`
+pub fn plugin_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("https://github.com/jaswged/telemetry-generator/schemas/plugin-v{PLUGIN_PROTOCOL_VERSION}.json"),
+        "title": "Telemetry generator plugin frame",
+        "description": "One line of the NDJSON stream sent to exporter plugins. A run sends one start frame, a reading frame per reading and one end frame",
+        "oneOf": [
+            { "$ref": "#/$defs/start" },
+            { "$ref": "#/$defs/reading" },
+            { "$ref": "#/$defs/end" }
+        ],
+        "$defs": {
+            "start": {
+                "type": "object",
+                "required": ["type", "protocol_version", "launch_id", "launch_time", "sample_rate_hz", "units"],
+                "additionalProperties": false,
+                "properties": {
+                    "type": { "const": "start" },
+                    "protocol_version": { "const": PLUGIN_PROTOCOL_VERSION },
+                    "launch_id": { "type": "string" },
+                    "launch_time": { "type": "string", "format": "date-time" },
+                    "sample_rate_hz": {
+                        "type": "string",
+                        "description": "Exact decimal rate, e.g. 1000 or 333.3"
+                    },
+                    "units": { "enum": ["raw", "si", "imperial"] }
+                }
+            },
+            "reading": {
+                "type": "object",
+                "required": ["type", "timestamp", "time_since_launch_ms", "sensor_type", "value", "unit", "mission_phase"],
+                "additionalProperties": false,
+                "properties": {
+                    "type": { "const": "reading" },
+                    "timestamp": { "type": "string", "format": "date-time" },
+                    "time_since_launch_ms": { "type": "integer", "minimum": 0 },
+                    "sensor_type": {
+                        "type": "string",
+                        "description": "Sensor name in the run's naming convention"
+                    },
+                    "value": {
+                        "type": ["number", "string"],
+                        "description": "Numbers after calibration and unit conversion. Discrete channels send their state name"
+                    },
+                    "unit": { "type": "string" },
+                    "id": {
+                        "type": "string",
+                        "description": "Stable across reruns of an idempotent run"
+                    },
+                    "mission_phase": {
+                        "enum": MissionPhase::ALL.map(|phase| phase.name()),
+                        "description": "Phase of flight the reading was taken in"
+                    }
+                }
+            },
+            "end": {
+                "type": "object",
+                "required": ["type", "readings"],
+                "additionalProperties": false,
+                "properties": {
+                    "type": { "const": "end" },
+                    "readings": { "type": "integer", "minimum": 0 }
+                }
+            }
+        }
+    })
+}
+
+// Check every line of a captured plugin stream against the schema. Returns the problems found,
+// each with its line number, and the number of lines checked
+pub fn validate_plugin_stream(path: &Path) -> Result<(usize, Vec<String>)> {
+    let schema = plugin_schema();
+    let file =
+        std::fs::File::open(path).io_context(|| format!("Failed to open {}", path.display()))?;
+
+    let mut lines = 0;
+    let mut violations = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.io_context(|| format!("Failed to read {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        lines += 1;
+        let problem = match serde_json::from_str::<Value>(&line) {
+            Ok(frame) => check(&schema, &frame, &schema, "").err(),
+            Err(e) => Some(format!("not JSON: {e}")),
+        };
+        if let Some(problem) = problem {
+            violations.push(format!("Line {}: {problem}", idx + 1));
+            if violations.len() >= MAX_VIOLATIONS {
+                break;
+            }
+        }
+    }
+    Ok((lines, violations))
+}
+
+// Just the keywords `plugin_schema` uses. `format` and descriptions are left to real validators
+fn check(schema: &Value, value: &Value, root: &Value, at: &str) -> std::result::Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        return Ok(());
+    };
+    if let Some(Value::String(reference)) = schema.get("$ref") {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer))
+            .ok_or_else(|| format!("schema has a dangling $ref {reference}"))?;
+        return check(target, value, root, at);
+    }
+    let here = if at.is_empty() { "frame" } else { at };
+
+    if let Some(Value::Array(options)) = schema.get("oneOf") {
+        // Report against the option with a matching `type` const, that's the one the frame meant
+        let errors: Vec<String> = options
+            .iter()
+            .filter_map(|option| check(option, value, root, at).err())
+            .collect();
+        return match options.len() - errors.len() {
+            1 => Ok(()),
+            0 => Err(errors
+                .into_iter()
+                .find(|error| !error.starts_with("type: expected"))
+                .unwrap_or_else(|| format!("{here} is not a start, reading or end frame"))),
+            _ => Err(format!("{here} matches more than one frame")),
+        };
+    }
+    if let Some(expected) = schema.get("const")
+        && value != expected
+    {
+        return Err(format!("{here}: expected {expected}, found {value}"));
+    }
+    if let Some(Value::Array(allowed)) = schema.get("enum")
+        && !allowed.contains(value)
+    {
+        return Err(format!(
+            "{here}: {value} is not one of {}",
+            Value::from(allowed.clone())
+        ));
+    }
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::String(name) => vec![name],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.iter().any(|name| is_type(value, name)) {
+            return Err(format!(
+                "{here}: expected {}, found {value}",
+                types.join(" or ")
+            ));
+        }
+    }
+    if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64)
+        && value.as_f64().is_some_and(|number| number < minimum)
+    {
+        return Err(format!("{here}: {value} is below the minimum of {minimum}"));
+    }
+    if let Some(object) = value.as_object() {
+        check_object(schema, object, root, at)?;
+    }
+    Ok(())
+}
+
+fn check_object(
+    schema: &Map<String, Value>,
+    object: &Map<String, Value>,
+    root: &Value,
+    at: &str,
+) -> std::result::Result<(), String> {
+    let properties = schema.get("properties").and_then(Value::as_object);
+    // Check the type tag first so a wrong frame kind isn't reported as a missing field
+    let mut names: Vec<&String> = object.keys().collect();
+    names.sort_by_key(|name| *name != "type");
+    for name in names {
+        let path = if at.is_empty() {
+            name.clone()
+        } else {
+            format!("{at}.{name}")
+        };
+        match properties.and_then(|properties| properties.get(name)) {
+            Some(property) => check(property, &object[name], root, &path)?,
+            None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                return Err(format!("unexpected field {path}"));
+            }
+            None => {}
+        }
+    }
+    if let Some(Value::Array(required)) = schema.get("required") {
+        for name in required.iter().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                return Err(format!("missing field {name}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn is_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::{FrameFormat, StreamExporter};
+    use crate::generators::TelemetryGenerator;
+    use crate::models::{SampleRate, SensorNaming, TelemetryConfig, UnitSystem};
+    use std::io::Write;
+
+    // Problems found in `lines` written out as a captured stream
+    fn validate(lines: &[u8]) -> (usize, Vec<String>) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(lines).unwrap();
+        validate_plugin_stream(file.path()).unwrap()
+    }
+
+    #[test]
+    fn generated_frames_match_the_schema() {
+        for (units, naming, point_ids) in [
+            (UnitSystem::Raw, SensorNaming::Short, false),
+            (UnitSystem::Imperial, SensorNaming::SnakeCase, true),
+        ] {
+            let config = TelemetryConfig {
+                duration: 2,
+                sample_rate_hz: SampleRate::from_hz(10),
+                units,
+                naming,
+                point_ids,
+                ..TelemetryConfig::default()
+            };
+            let dataset = TelemetryGenerator::new(config).generate(true).unwrap();
+            let stream = StreamExporter::encode(FrameFormat::Json, &dataset).unwrap();
+            let (lines, violations) = validate(&stream);
+            assert_eq!(lines, dataset.readings.len() + 2);
+            assert!(
+                violations.is_empty(),
+                "{units:?} {naming:?}: {violations:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn broken_frames_are_reported_by_line() {
+        let stream = br#"{"type":"end","readings":3}
+{"type":"end"}
+{"type":"end","readings":-1}
+{"type":"end","readings":3,"extra":true}
+{"type":"reading","timestamp":"2024-01-01T00:00:00Z","time_since_launch_ms":0,"sensor_type":"F_pa","value":1.5,"unit":"Pa","mission_phase":"lunch"}
+{"type":"middle"}
+not json
+"#;
+        let (lines, violations) = validate(stream);
+        assert_eq!(lines, 7);
+        let expected = [
+            "Line 2: missing field readings",
+            "Line 3: readings: -1 is below the minimum of 0",
+            "Line 4: unexpected field extra",
+            "Line 5: mission_phase: \"lunch\" is not one of",
+            "Line 6: frame is not a start, reading or end frame",
+            "Line 7: not JSON",
+        ];
+        assert_eq!(violations.len(), expected.len(), "{violations:?}");
+        for (violation, expected) in violations.iter().zip(expected) {
+            assert!(
+                violation.starts_with(expected),
+                "{violation} for {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use super::stream_sink::{Frame, FrameEncoder, FrameValue};
+use crate::error::{ExportContext, Result};
+use prost::Message;
+
+// Frames as length delimited protobuf messages, a varint length then the message, so a stream
+// splits back into frames. The messages are declared by hand to save a build step. Keep them in
+// step with proto/telemetry_frame.proto, which consumers generate their code from
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoFrame {
+    #[prost(oneof = "ProtoKind", tags = "1, 2, 3")]
+    kind: Option<ProtoKind>,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum ProtoKind {
+    #[prost(message, tag = "1")]
+    Start(ProtoStart),
+    #[prost(message, tag = "2")]
+    Reading(ProtoReading),
+    #[prost(message, tag = "3")]
+    End(ProtoEnd),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoStart {
+    #[prost(uint32, tag = "1")]
+    protocol_version: u32,
+    #[prost(string, tag = "2")]
+    launch_id: String,
+    #[prost(string, tag = "3")]
+    launch_time: String,
+    #[prost(string, tag = "4")]
+    sample_rate_hz: String,
+    #[prost(string, tag = "5")]
+    units: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoReading {
+    #[prost(string, tag = "1")]
+    timestamp: String,
+    #[prost(uint64, tag = "2")]
+    time_since_launch_ms: u64,
+    #[prost(string, tag = "3")]
+    sensor_type: String,
+    #[prost(oneof = "ProtoValue", tags = "4, 5, 6, 7")]
+    value: Option<ProtoValue>,
+    #[prost(string, tag = "8")]
+    unit: String,
+    #[prost(string, optional, tag = "9")]
+    id: Option<String>,
+    #[prost(string, tag = "10")]
+    mission_phase: String,
+}
+
+#[derive(Clone, PartialEq, prost::Oneof)]
+enum ProtoValue {
+    #[prost(double, tag = "4")]
+    Float(f64),
+    #[prost(sint64, tag = "5")]
+    Int(i64),
+    #[prost(uint64, tag = "6")]
+    Unsigned(u64),
+    #[prost(string, tag = "7")]
+    String(String),
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoEnd {
+    #[prost(uint64, tag = "1")]
+    readings: u64,
+}
+
+pub(super) struct ProtobufEncoder;
+
+impl FrameEncoder for ProtobufEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        let kind = match frame {
+            Frame::Start {
+                protocol_version,
+                launch_id,
+                launch_time,
+                sample_rate_hz,
+                units,
+            } => ProtoKind::Start(ProtoStart {
+                protocol_version: *protocol_version,
+                launch_id: launch_id.to_string(),
+                launch_time: launch_time.clone(),
+                sample_rate_hz: sample_rate_hz.clone(),
+                units: units.to_string(),
+            }),
+            Frame::Reading(reading) => ProtoKind::Reading(ProtoReading {
+                timestamp: reading.timestamp.clone(),
+                time_since_launch_ms: reading.time_since_launch_ms,
+                sensor_type: reading.sensor_type.to_string(),
+                value: Some(match reading.value {
+                    FrameValue::Float(f) => ProtoValue::Float(f),
+                    FrameValue::Int(i) => ProtoValue::Int(i),
+                    FrameValue::UnsignedInt(u) => ProtoValue::Unsigned(u),
+                    FrameValue::String(s) => ProtoValue::String(s.to_string()),
+                }),
+                unit: reading.unit.to_string(),
+                id: reading.id.clone(),
+                mission_phase: reading.mission_phase.to_string(),
+            }),
+            Frame::End { readings } => ProtoKind::End(ProtoEnd {
+                readings: *readings as u64,
+            }),
+        };
+        ProtoFrame { kind: Some(kind) }
+            .encode_length_delimited(out)
+            .export_context("protobuf", || "Failed to encode frame")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exporters::{FrameFormat, StreamExporter};
+    use crate::generators::TelemetryGenerator;
+    use crate::models::{SampleRate, TelemetryConfig};
+
+    #[test]
+    fn stream_splits_back_into_frames() {
+        let config = TelemetryConfig {
+            duration: 2,
+            sample_rate_hz: SampleRate::from_hz(10),
+            point_ids: true,
+            ..TelemetryConfig::default()
+        };
+        let dataset = TelemetryGenerator::new(config).generate(true).unwrap();
+        let stream = StreamExporter::encode(FrameFormat::Protobuf, &dataset).unwrap();
+        let mut rest = stream.as_slice();
+        let mut frames = Vec::new();
+        while !rest.is_empty() {
+            frames.push(ProtoFrame::decode_length_delimited(&mut rest).unwrap());
+        }
+        assert_eq!(frames.len(), dataset.readings.len() + 2);
+        let Some(ProtoKind::Start(start)) = &frames[0].kind else {
+            panic!("first frame should be the start");
+        };
+        assert_eq!(start.launch_id, dataset.config.launch_id);
+        let Some(ProtoKind::Reading(reading)) = &frames[1].kind else {
+            panic!("second frame should be a reading");
+        };
+        assert_eq!(
+            reading.time_since_launch_ms,
+            dataset.readings[0].time_since_launch_ms
+        );
+        assert_eq!(reading.id.as_deref(), Some(&*dataset.config.point_id(0)));
+        assert!(reading.value.is_some());
+        assert_eq!(
+            frames.last().unwrap().kind,
+            Some(ProtoKind::End(ProtoEnd {
+                readings: dataset.readings.len() as u64
+            }))
+        );
+    }
+}
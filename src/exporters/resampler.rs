@@ -0,0 +1,284 @@
+use super::merger::launch_time_us;
+use super::output_files::{OverwritePolicy, prepare_output_file, resolve_output_name};
+use super::parquet_exporter::{SENSOR_UNITS_KEY, UNITS_KEY};
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use crate::models::SampleRate;
+use arrow::array::{
+    Array, ArrayRef, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray, UInt64Array,
+};
+use arrow::record_batch::RecordBatch;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::file::properties::WriterProperties;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+// Sink name reported in export errors
+const SINK: &str = "resampled parquet";
+
+// Grid rows per record batch written
+const GRID_BATCH_ROWS: usize = 65_536;
+
+// How a channel's value at a grid point is worked out from the readings around it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Interpolation {
+    // Straight line between the readings either side
+    Linear,
+    // Last reading at or before the grid point, like a sample and hold
+    Previous,
+    // Whichever reading is closer in time
+    Nearest,
+}
+
+// What to put in grid points that fall in a gap longer than the max gap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum GapPolicy {
+    // Leave the channel null
+    Null,
+    // Carry the last reading through the gap
+    Hold,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResampleConfig {
+    pub rate: SampleRate,
+    pub interpolation: Interpolation,
+    // Readings further apart than this are a gap. None interpolates across anything
+    pub max_gap_ms: Option<u64>,
+    pub gap_policy: GapPolicy,
+}
+
+// Turns a long format run into a wide one on a uniform time grid: `timestamp`,
+// `time_since_launch_ms` and a nullable Float64 column per sensor_type. The grid starts at launch
+// and runs to the last reading. String readings and NaN dropouts are skipped. Every reading is
+// held in memory, so very long high rate runs want a lot of it
+pub struct Resampler {
+    config: ResampleConfig,
+}
+
+// Readings of one channel as (ms since launch, value), sorted by time
+type Channel = Vec<(f64, f64)>;
+
+impl Resampler {
+    pub fn new(config: ResampleConfig) -> Self {
+        Self { config }
+    }
+
+    // Resample the run into `{output_dir}/{output_name}.parquet`. Returns the file and grid rows
+    pub fn resample(
+        &self,
+        input: &Path,
+        output_dir: &Path,
+        output_name: &str,
+        policy: OverwritePolicy,
+    ) -> Result<(PathBuf, usize)> {
+        let file =
+            File::open(input).io_context(|| format!("Failed to open {}", input.display()))?;
+        let builder = ParquetRecordBatchReaderBuilder::try_new(file)
+            .export_context(SINK, || format!("Failed to read {}", input.display()))?;
+
+        // Units still apply to the channel columns. The schema version doesn't, it's a new layout
+        let key_values: Vec<_> = builder
+            .metadata()
+            .file_metadata()
+            .key_value_metadata()
+            .into_iter()
+            .flatten()
+            .filter(|kv| kv.key == UNITS_KEY || kv.key == SENSOR_UNITS_KEY)
+            .cloned()
+            .collect();
+        let reader = builder
+            .build()
+            .export_context(SINK, || format!("Failed to read {}", input.display()))?;
+
+        let mut channels: BTreeMap<String, Channel> = BTreeMap::new();
+        let mut launch_us = i64::MAX;
+        let mut readings: usize = 0;
+        for batch in reader {
+            let batch = batch.export_context(SINK, || "Failed to read record batch")?;
+            if batch.num_rows() > 0 {
+                launch_us = launch_us.min(launch_time_us(&batch));
+            }
+            readings += collect_readings(&batch, &mut channels)?;
+        }
+        if channels.is_empty() {
+            return Err(TelemetryError::config(format!(
+                "{} has no numeric readings to resample",
+                input.display()
+            )));
+        }
+        for channel in channels.values_mut() {
+            channel.sort_by(|a, b| a.0.total_cmp(&b.0));
+        }
+
+        let last_ms = channels
+            .values()
+            .filter_map(|channel| channel.last())
+            .map(|(t, _)| *t)
+            .fold(0.0, f64::max);
+        let grid: Vec<u64> = (0..)
+            .map(|idx| self.config.rate.offset_nanos(idx))
+            .take_while(|nanos| *nanos as f64 / 1e6 <= last_ms)
+            .collect();
+        info!(
+            "Resampling {} readings of {} channels onto {} grid points at {} Hz",
+            readings,
+            channels.len(),
+            grid.len(),
+            self.config.rate
+        );
+
+        let mut fields = vec![
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+            Field::new("time_since_launch_ms", DataType::Float64, false),
+        ];
+        fields.extend(
+            channels
+                .keys()
+                .map(|name| Field::new(name, DataType::Float64, true)),
+        );
+        let schema = Arc::new(Schema::new(fields));
+
+        let output_name = resolve_output_name(output_dir, output_name, policy)?;
+        let output_file = prepare_output_file(output_dir, &output_name, "parquet")?;
+        let file = File::create(&output_file)
+            .io_context(|| format!("Failed to create {}", output_file.display()))?;
+        let props = WriterProperties::builder()
+            .set_compression(parquet::basic::Compression::SNAPPY)
+            .set_key_value_metadata(Some(key_values))
+            .build();
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(props))
+            .export_context(SINK, || "Failed to create arrow writer")?;
+
+        // Each channel keeps a cursor so the whole grid is one pass over its readings
+        let mut cursors = vec![0; channels.len()];
+        for chunk in grid.chunks(GRID_BATCH_ROWS) {
+            let times_ms: Vec<f64> = chunk.iter().map(|nanos| *nanos as f64 / 1e6).collect();
+            let mut columns: Vec<ArrayRef> = vec![
+                Arc::new(TimestampMicrosecondArray::from_iter_values(
+                    chunk
+                        .iter()
+                        .map(|nanos| launch_us + (*nanos / 1_000) as i64),
+                )),
+                Arc::new(Float64Array::from(times_ms.clone())),
+            ];
+            for (channel, cursor) in channels.values().zip(cursors.iter_mut()) {
+                let values: Float64Array = times_ms
+                    .iter()
+                    .map(|t| self.value_at(channel, cursor, *t))
+                    .collect();
+                columns.push(Arc::new(values));
+            }
+
+            let batch = RecordBatch::try_new(schema.clone(), columns)
+                .export_context(SINK, || "Failed to build grid record batch")?;
+            writer
+                .write(&batch)
+                .export_context(SINK, || "Failed to write record batch")?;
+        }
+        writer
+            .close()
+            .export_context(SINK, || "Failed to close Parquet writer")?;
+
+        info!("Wrote resampled grid to {}", output_file.display());
+        Ok((output_file, grid.len()))
+    }
+
+    // Value of the channel at `t` ms. `cursor` is the index of the first reading after the
+    // previous grid point and only moves forward
+    fn value_at(&self, channel: &Channel, cursor: &mut usize, t: f64) -> Option<f64> {
+        while *cursor < channel.len() && channel[*cursor].0 <= t {
+            *cursor += 1;
+        }
+        let previous = cursor.checked_sub(1).map(|idx| channel[idx]);
+        let next = channel.get(*cursor).copied();
+        if let Some((time, value)) = previous
+            && time == t
+        {
+            return Some(value);
+        }
+
+        let within = |from: f64, to: f64| {
+            self.config
+                .max_gap_ms
+                .is_none_or(|max_gap| to - from <= max_gap as f64)
+        };
+        match (previous, next) {
+            (Some(previous), Some(next)) if !within(previous.0, next.0) => {
+                match self.config.gap_policy {
+                    GapPolicy::Null => None,
+                    GapPolicy::Hold => Some(previous.1),
+                }
+            }
+            (Some(previous), Some(next)) => Some(match self.config.interpolation {
+                Interpolation::Linear => {
+                    let fraction = (t - previous.0) / (next.0 - previous.0);
+                    previous.1 + fraction * (next.1 - previous.1)
+                }
+                Interpolation::Previous => previous.1,
+                Interpolation::Nearest if t - previous.0 <= next.0 - t => previous.1,
+                Interpolation::Nearest => next.1,
+            }),
+            // Past the last reading only sample and hold carries on, and only as far as a gap
+            (Some(previous), None) => (self.config.interpolation == Interpolation::Previous
+                && within(previous.0, t))
+            .then_some(previous.1),
+            (None, Some(next)) => (self.config.interpolation == Interpolation::Nearest
+                && within(t, next.0))
+            .then_some(next.1),
+            (None, None) => None,
+        }
+    }
+}
+
+// Add the batch's numeric readings to their channels. Returns how many were added
+fn collect_readings(
+    batch: &RecordBatch,
+    channels: &mut BTreeMap<String, Channel>,
+) -> Result<usize> {
+    let column = |name: &str| batch.column_by_name(name).map(|c| c.as_any());
+    let sensor_types = column("sensor_type").and_then(|c| c.downcast_ref::<StringArray>());
+    let since_launch = column("time_since_launch_ms").and_then(|c| c.downcast_ref::<UInt64Array>());
+    let (Some(sensor_types), Some(since_launch)) = (sensor_types, since_launch) else {
+        return Err(TelemetryError::config(
+            "Expected a long format run with sensor_type and time_since_launch_ms columns",
+        ));
+    };
+    // v1 has one value column. v2 splits floats and ints, strings can't be interpolated
+    let floats = column("value")
+        .or_else(|| column("value_float"))
+        .and_then(|c| c.downcast_ref::<Float64Array>());
+    let ints = column("value_int").and_then(|c| c.downcast_ref::<Int64Array>());
+
+    let mut added = 0;
+    for row in 0..batch.num_rows() {
+        if sensor_types.is_null(row) || since_launch.is_null(row) {
+            continue;
+        }
+        let value = match (floats, ints) {
+            (Some(floats), _) if floats.is_valid(row) => floats.value(row),
+            (_, Some(ints)) if ints.is_valid(row) => ints.value(row) as f64,
+            _ => continue,
+        };
+        if !value.is_finite() {
+            continue;
+        }
+        let reading = (since_launch.value(row) as f64, value);
+        match channels.get_mut(sensor_types.value(row)) {
+            Some(channel) => channel.push(reading),
+            None => {
+                channels.insert(sensor_types.value(row).to_string(), vec![reading]);
+            }
+        }
+        added += 1;
+    }
+    Ok(added)
+}
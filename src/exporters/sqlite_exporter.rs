@@ -0,0 +1,198 @@
+use super::output_files::prepare_output_file;
+use crate::error::{ExportContext, IoContext, Result};
+use crate::models::{SensorValue, TelemetryDataset};
+use chrono::SecondsFormat;
+use rusqlite::{Connection, params};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+// Sink name reported in export errors
+const SINK: &str = "sqlite";
+
+// A run row the readings point back to. One per file today, but it leaves room to append runs
+const SCHEMA: &str = "
+CREATE TABLE runs (
+    id INTEGER PRIMARY KEY,
+    launch_id TEXT NOT NULL,
+    launch_time TEXT NOT NULL,
+    sample_rate_hz REAL NOT NULL,
+    duration_s INTEGER NOT NULL,
+    seed INTEGER NOT NULL,
+    units TEXT NOT NULL,
+    readings INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE readings (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    timestamp TEXT NOT NULL,
+    time_since_launch_ms INTEGER NOT NULL,
+    sensor_type TEXT NOT NULL,
+    value REAL,
+    value_text TEXT,
+    unit TEXT NOT NULL,
+    id TEXT,
+    mission_phase TEXT NOT NULL
+);
+";
+
+// Every reading and the run's settings in `{output_name}.sqlite`, for DB Browser, DuckDB and
+// anything else that opens SQLite without a parquet toolchain. Numbers go in `value` in the
+// exported units, strings and discrete states by name in `value_text`. Meant for small runs,
+// it's many times the size of the parquet and slower to write
+pub struct SqliteExporter;
+
+impl SqliteExporter {
+    pub fn export(dataset: &TelemetryDataset, output_dir: &Path, output_name: &str) -> Result<()> {
+        if dataset.readings.is_empty() {
+            warn!("No readings to export. Exiting export.");
+            return Ok(());
+        }
+        let mut writer = Self::create_writer(dataset, output_dir, output_name)?;
+        writer.write_chunk(dataset)?;
+        writer.close()
+    }
+
+    // Create the file with its tables and the run row from the first chunk. Chunks written after
+    // go in a transaction each
+    pub fn create_writer(
+        first: &TelemetryDataset,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<SqliteStreamWriter> {
+        let sqlite_file = prepare_output_file(output_dir, output_name, "sqlite")?;
+        // A file left by an overwritten run would already have the tables
+        if sqlite_file.exists() {
+            std::fs::remove_file(&sqlite_file)
+                .io_context(|| format!("Failed to replace {}", sqlite_file.display()))?;
+        }
+        let connection = Connection::open(&sqlite_file).export_context(SINK, || {
+            format!("Failed to create {}", sqlite_file.display())
+        })?;
+        // Nothing to recover if a run dies part way, it gets generated again
+        connection
+            .execute_batch("PRAGMA journal_mode = OFF; PRAGMA synchronous = OFF;")
+            .export_context(SINK, || "Failed to set up the database")?;
+        connection
+            .execute_batch(SCHEMA)
+            .export_context(SINK, || "Failed to create the tables")?;
+
+        let config = &first.config;
+        connection
+            .execute(
+                "INSERT INTO runs (launch_id, launch_time, sample_rate_hz, duration_s, seed, units) \
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    config.launch_id,
+                    first
+                        .launch_time
+                        .to_rfc3339_opts(SecondsFormat::Micros, true),
+                    1.0 / config.sample_rate_hz.period_s(),
+                    config.duration as i64,
+                    // Stored as its bits, SQLite integers are signed
+                    config.seed as i64,
+                    config.units.name(),
+                ],
+            )
+            .export_context(SINK, || "Failed to write the run")?;
+        let run_id = connection.last_insert_rowid();
+
+        Ok(SqliteStreamWriter {
+            connection,
+            sqlite_file,
+            run_id,
+            readings_written: 0,
+        })
+    }
+}
+
+// Streams chunks of readings into the `readings` table of a single SQLite file
+pub struct SqliteStreamWriter {
+    connection: Connection,
+    sqlite_file: PathBuf,
+    run_id: i64,
+    readings_written: usize,
+}
+
+impl SqliteStreamWriter {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        if chunk.readings.is_empty() {
+            return Ok(());
+        }
+        let config = &chunk.config;
+        let transaction = self
+            .connection
+            .transaction()
+            .export_context(SINK, || "Failed to start a transaction")?;
+        {
+            let mut insert = transaction
+                .prepare_cached(
+                    "INSERT INTO readings (run_id, timestamp, time_since_launch_ms, sensor_type, \
+                    value, value_text, unit, id, mission_phase) \
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                )
+                .export_context(SINK, || "Failed to prepare the insert")?;
+            for (idx, reading) in chunk.readings.iter().enumerate() {
+                let (value, value_text) = match &reading.value {
+                    SensorValue::Float(f) => (Some(config.export_float(reading.sensor, *f)), None),
+                    SensorValue::Int(i) => (Some(*i as f64), None),
+                    SensorValue::UnsignedInt(u) => (Some(*u as f64), None),
+                    SensorValue::String(s) => (None, Some(s.as_str())),
+                    SensorValue::State(state) => (Some(state.code() as f64), Some(state.name())),
+                };
+                let id = config
+                    .point_ids
+                    .then(|| config.point_id(self.readings_written + idx));
+                insert
+                    .execute(params![
+                        self.run_id,
+                        reading
+                            .timestamp
+                            .to_rfc3339_opts(SecondsFormat::Micros, true),
+                        reading.time_since_launch_ms as i64,
+                        reading.sensor_label(config),
+                        value,
+                        value_text,
+                        config.units.unit(reading.sensor),
+                        id,
+                        reading.phase.name(),
+                    ])
+                    .export_context(SINK, || "Failed to insert a reading")?;
+            }
+        }
+        transaction
+            .commit()
+            .export_context(SINK, || "Failed to commit readings")?;
+        self.readings_written += chunk.readings.len();
+        Ok(())
+    }
+
+    // Bytes in the file so far
+    pub fn bytes_written(&self) -> u64 {
+        std::fs::metadata(&self.sqlite_file).map_or(0, |metadata| metadata.len())
+    }
+
+    // Record the reading count on the run and index the readings for lookups by sensor and time
+    pub fn close(self) -> Result<()> {
+        self.connection
+            .execute(
+                "UPDATE runs SET readings = ?1 WHERE id = ?2",
+                params![self.readings_written as i64, self.run_id],
+            )
+            .export_context(SINK, || "Failed to update the run")?;
+        self.connection
+            .execute_batch(
+                "CREATE INDEX readings_by_sensor ON readings (run_id, sensor_type, \
+                time_since_launch_ms);",
+            )
+            .export_context(SINK, || "Failed to index the readings")?;
+        self.connection
+            .close()
+            .map_err(|(_, e)| e)
+            .export_context(SINK, || "Failed to close the database")?;
+        info!(
+            "Exported {} readings to SQLite file at {}",
+            self.readings_written,
+            self.sqlite_file.display()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,106 @@
+use crate::error::{ExportContext, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+use num_format::{Locale, ToFormattedString};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Sink name reported in export errors
+const SINK: &str = "progress";
+
+// One progress line for a run that's generated and streamed at the same time. The bar tracks
+// points the sink has taken, the message shows how far the simulation got and how far behind
+// it the sink is. Cheap to clone, the generator thread and the sender share one
+#[derive(Clone)]
+pub struct StreamProgress {
+    bar: Option<ProgressBar>,
+    counters: Arc<Counters>,
+    mission_ms: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    generated_points: AtomicU64,
+    generated_ms: AtomicU64,
+    sent_points: AtomicU64,
+    sent_ms: AtomicU64,
+}
+
+impl StreamProgress {
+    pub fn new(total_points: usize, mission_ms: u64, disable_progress: bool) -> Result<Self> {
+        let bar = if disable_progress {
+            None
+        } else {
+            let pb = ProgressBar::new(total_points as u64);
+            pb.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {percent}% {msg} ({eta})")
+                    .export_context(SINK, || "Invalid progress bar template")?
+                    .progress_chars("#>-"),
+            );
+            Some(pb)
+        };
+        Ok(Self {
+            bar,
+            counters: Arc::default(),
+            mission_ms,
+        })
+    }
+
+    // A chunk left the generator, ending at `time_since_launch_ms`
+    pub fn generated(&self, points: usize, time_since_launch_ms: u64) {
+        self.counters
+            .generated_points
+            .fetch_add(points as u64, Ordering::Relaxed);
+        self.counters
+            .generated_ms
+            .fetch_max(time_since_launch_ms, Ordering::Relaxed);
+        self.refresh();
+    }
+
+    // A batch made it to the sink, ending at `time_since_launch_ms`
+    pub fn sent(&self, points: usize, time_since_launch_ms: u64) {
+        self.counters
+            .sent_points
+            .fetch_add(points as u64, Ordering::Relaxed);
+        self.counters
+            .sent_ms
+            .fetch_max(time_since_launch_ms, Ordering::Relaxed);
+        self.refresh();
+    }
+
+    pub fn sent_points(&self) -> u64 {
+        self.counters.sent_points.load(Ordering::Relaxed)
+    }
+
+    // Summary line, also what the bar finishes on
+    pub fn summary(&self) -> String {
+        let generated_points = self.counters.generated_points.load(Ordering::Relaxed);
+        let generated_ms = self.counters.generated_ms.load(Ordering::Relaxed);
+        let sent_points = self.counters.sent_points.load(Ordering::Relaxed);
+        let sent_ms = self.counters.sent_ms.load(Ordering::Relaxed);
+        format!(
+            "T+{:.1}s/{:.0}s | generated {} | sent {} | lag {} pts, {:.1}s",
+            generated_ms as f64 / 1000.0,
+            self.mission_ms as f64 / 1000.0,
+            generated_points.to_formatted_string(&Locale::en),
+            sent_points.to_formatted_string(&Locale::en),
+            generated_points
+                .saturating_sub(sent_points)
+                .to_formatted_string(&Locale::en),
+            generated_ms.saturating_sub(sent_ms) as f64 / 1000.0,
+        )
+    }
+
+    pub fn finish(&self) {
+        if let Some(pb) = &self.bar {
+            pb.finish_with_message(self.summary());
+        }
+    }
+
+    fn refresh(&self) {
+        if let Some(pb) = &self.bar {
+            pb.set_position(self.sent_points());
+            pb.set_message(self.summary());
+        }
+    }
+}
@@ -0,0 +1,788 @@
+use crate::error::{ExportContext, IoContext, Result, TelemetryError};
+use crate::models::{
+    SensorValue, TelemetryConfig, TelemetryDataset, TelemetryReading, line_protocol_nanos,
+    push_escaped,
+};
+use chrono::SecondsFormat;
+use flate2::write::GzEncoder;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+use tungstenite::{Message, WebSocket, stream::MaybeTlsStream};
+
+use super::ccsds_exporter::CcsdsEncoder;
+use super::influxdb_exporter::MEASUREMENT;
+#[cfg(feature = "kafka")]
+use super::kafka_transport::KafkaTransport;
+use super::plugin_exporter::{PLUGIN_PROTOCOL_VERSION, PluginTransport};
+use super::protobuf_encoder::ProtobufEncoder;
+
+// Streaming sinks are an encoding paired with a transport, so any format goes over any transport.
+// A run sends one start frame, a reading frame per reading and one end frame
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Frame<'a> {
+    Start {
+        protocol_version: u32,
+        launch_id: &'a str,
+        launch_time: String,
+        sample_rate_hz: String,
+        units: &'a str,
+    },
+    Reading(ReadingFrame<'a>),
+    End {
+        readings: usize,
+    },
+}
+
+// Fields of a reading frame, also written on their own by the plain JSON encodings
+#[derive(Debug, Serialize)]
+pub struct ReadingFrame<'a> {
+    pub timestamp: String,
+    pub time_since_launch_ms: u64,
+    pub sensor_type: Cow<'a, str>,
+    pub value: FrameValue<'a>,
+    pub unit: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub mission_phase: &'static str,
+    // Reading the fields came from, for the binary encodings
+    #[serde(skip)]
+    pub reading: &'a TelemetryReading,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum FrameValue<'a> {
+    Float(f64),
+    Int(i64),
+    UnsignedInt(u64),
+    String(&'a str),
+}
+
+impl<'a> FrameValue<'a> {
+    pub(super) fn new(reading: &'a TelemetryReading, config: &TelemetryConfig) -> Self {
+        match &reading.value {
+            SensorValue::Float(f) => FrameValue::Float(config.export_float(reading.sensor, *f)),
+            SensorValue::Int(i) => FrameValue::Int(*i),
+            SensorValue::UnsignedInt(u) => FrameValue::UnsignedInt(*u),
+            SensorValue::String(s) => FrameValue::String(s),
+            SensorValue::State(state) => FrameValue::String(state.name()),
+        }
+    }
+}
+
+// Turns frames into bytes. Each call appends exactly one message to `out`, transports that
+// care about message boundaries send it as one
+pub trait FrameEncoder: Send {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()>;
+}
+
+// Moves encoded messages somewhere. Close flushes and reports anything the far end got wrong
+pub trait Transport: Send {
+    fn send(&mut self, message: &[u8]) -> Result<()>;
+    // Push out anything buffered, for live streams that can't wait for the buffer to fill
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+    fn close(self: Box<Self>) -> Result<()>;
+}
+
+// Encodings to pick from on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FrameFormat {
+    // One JSON object per line, the plugin protocol
+    Json,
+    // Header row at the start then a row per reading. Start details and the end count are left out
+    Csv,
+    // One reading object per line with no start or end frames, for Elasticsearch and Logstash
+    Ndjson,
+    // A single JSON document, the start details with a `readings` array and the count at the end
+    JsonDocument,
+    // CCSDS space packets, one per reading. See `CcsdsExporter`
+    Ccsds,
+    // A MessagePack map per frame with the same keys as the JSON frames
+    #[value(name = "msgpack")]
+    MessagePack,
+    // Length delimited protobuf messages, see proto/telemetry_frame.proto
+    Protobuf,
+    // InfluxDB line protocol, a line per reading with no start or end frames, for Telegraf and
+    // anything else that speaks it. Non-finite floats are left out as InfluxDB can't store them
+    LineProtocol,
+}
+
+impl FrameFormat {
+    pub fn encoder(&self) -> Box<dyn FrameEncoder> {
+        match self {
+            FrameFormat::Json => Box::new(JsonEncoder),
+            FrameFormat::Csv => Box::new(CsvEncoder),
+            FrameFormat::Ndjson => Box::new(NdjsonEncoder),
+            FrameFormat::JsonDocument => Box::new(JsonDocumentEncoder { readings: 0 }),
+            FrameFormat::Ccsds => Box::<CcsdsEncoder>::default(),
+            FrameFormat::MessagePack => Box::new(MessagePackEncoder),
+            FrameFormat::Protobuf => Box::new(ProtobufEncoder),
+            FrameFormat::LineProtocol => Box::new(LineProtocolEncoder {
+                launch_id: String::new(),
+                line: String::new(),
+            }),
+        }
+    }
+
+    // File extension when written to disk
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Json => "ndjson",
+            FrameFormat::Csv => "csv",
+            FrameFormat::Ndjson => "ndjson",
+            FrameFormat::JsonDocument => "json",
+            FrameFormat::Ccsds => "ccsds",
+            FrameFormat::MessagePack => "msgpack",
+            FrameFormat::Protobuf => "pb",
+            FrameFormat::LineProtocol => "lp",
+        }
+    }
+
+    // Sent as binary rather than text messages where a transport tells them apart
+    pub fn is_binary(&self) -> bool {
+        matches!(
+            self,
+            FrameFormat::Ccsds | FrameFormat::MessagePack | FrameFormat::Protobuf
+        )
+    }
+}
+
+// Compression for text files, applied on the fly as frames are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TextCompression {
+    None,
+    Zstd,
+    Gzip,
+}
+
+impl TextCompression {
+    // Appended to the format's extension, e.g. `.ndjson.zst`
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            TextCompression::None => "",
+            TextCompression::Zstd => ".zst",
+            TextCompression::Gzip => ".gz",
+        }
+    }
+}
+
+struct JsonEncoder;
+
+impl FrameEncoder for JsonEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        serde_json::to_writer(&mut *out, frame)
+            .export_context("json", || "Failed to serialize frame")?;
+        out.push(b'\n');
+        Ok(())
+    }
+}
+
+struct CsvEncoder;
+
+impl FrameEncoder for CsvEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        match frame {
+            Frame::Start { .. } => {
+                out.extend_from_slice(
+                    b"timestamp,time_since_launch_ms,sensor_type,value,unit,id,mission_phase\n",
+                );
+            }
+            Frame::Reading(ReadingFrame {
+                timestamp,
+                time_since_launch_ms,
+                sensor_type,
+                value,
+                unit,
+                id,
+                mission_phase,
+                ..
+            }) => {
+                let value = match value {
+                    FrameValue::Float(f) => f.to_string(),
+                    FrameValue::Int(i) => i.to_string(),
+                    FrameValue::UnsignedInt(u) => u.to_string(),
+                    FrameValue::String(s) => csv_field(s).into_owned(),
+                };
+                let _ = writeln!(
+                    out,
+                    "{timestamp},{time_since_launch_ms},{},{value},{unit},{},{mission_phase}",
+                    csv_field(sensor_type),
+                    id.as_deref().unwrap_or_default()
+                );
+            }
+            Frame::End { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+struct NdjsonEncoder;
+
+impl FrameEncoder for NdjsonEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        if let Frame::Reading(reading) = frame {
+            serde_json::to_writer(&mut *out, reading)
+                .export_context("ndjson", || "Failed to serialize reading")?;
+            out.push(b'\n');
+        }
+        Ok(())
+    }
+}
+
+// Written a piece per frame so the document never has to be held in memory. Each reading still
+// gets a line of its own
+struct JsonDocumentEncoder {
+    readings: usize,
+}
+
+impl FrameEncoder for JsonDocumentEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        match frame {
+            Frame::Start {
+                launch_id,
+                launch_time,
+                sample_rate_hz,
+                units,
+                ..
+            } => {
+                // Launch ids are free text, so they go through serde to be escaped
+                out.extend_from_slice(b"{\"launch_id\":");
+                serde_json::to_writer(&mut *out, launch_id)
+                    .export_context("json", || "Failed to serialize launch id")?;
+                let _ = write!(
+                    out,
+                    ",\"launch_time\":\"{launch_time}\",\"sample_rate_hz\":\"{sample_rate_hz}\",\"units\":\"{units}\",\"readings\":["
+                );
+            }
+            Frame::Reading(reading) => {
+                out.extend_from_slice(if self.readings == 0 { b"\n" } else { b",\n" });
+                serde_json::to_writer(&mut *out, reading)
+                    .export_context("json", || "Failed to serialize reading")?;
+                self.readings += 1;
+            }
+            Frame::End { readings } => {
+                let _ = writeln!(out, "\n],\"reading_count\":{readings}}}");
+            }
+        }
+        Ok(())
+    }
+}
+
+// Quote a field only when it needs it
+struct MessagePackEncoder;
+
+impl FrameEncoder for MessagePackEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        rmp_serde::encode::write_named(out, frame)
+            .export_context("msgpack", || "Failed to encode frame")
+    }
+}
+
+// Launch id comes from the start frame as every line is tagged with it
+struct LineProtocolEncoder {
+    launch_id: String,
+    line: String,
+}
+
+impl FrameEncoder for LineProtocolEncoder {
+    fn encode(&mut self, frame: &Frame, out: &mut Vec<u8>) -> Result<()> {
+        let reading = match frame {
+            Frame::Start { launch_id, .. } => {
+                self.launch_id = launch_id.to_string();
+                return Ok(());
+            }
+            Frame::Reading(reading) => reading,
+            Frame::End { .. } => return Ok(()),
+        };
+        if let FrameValue::Float(f) = reading.value
+            && !f.is_finite()
+        {
+            return Ok(());
+        }
+        let line = &mut self.line;
+        line.clear();
+        push_escaped(line, MEASUREMENT, &[',', ' ']);
+        line.push_str(",sensor=");
+        push_escaped(line, &reading.sensor_type, &[',', '=', ' ']);
+        line.push_str(",launch_id=");
+        push_escaped(line, &self.launch_id, &[',', '=', ' ']);
+        line.push(' ');
+        push_escaped(
+            line,
+            reading.reading.sensor.field_name_full(),
+            &[',', '=', ' '],
+        );
+        line.push('=');
+        match reading.value {
+            FrameValue::Float(f) => line.push_str(&f.to_string()),
+            FrameValue::Int(i) => line.push_str(&format!("{i}i")),
+            FrameValue::UnsignedInt(u) => line.push_str(&format!("{u}u")),
+            FrameValue::String(s) => {
+                line.push('"');
+                push_escaped(line, s, &['"']);
+                line.push('"');
+            }
+        }
+        line.push_str(&format!(
+            " {}\n",
+            line_protocol_nanos(reading.reading.timestamp)
+        ));
+        out.extend_from_slice(line.as_bytes());
+        Ok(())
+    }
+}
+
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+// Where a sink sends to, e.g. `tcp://localhost:9000`, `udp://10.0.0.5:5140`,
+// `ws://localhost:8080/telemetry` or `kafka://localhost:9092/telemetry`
+#[derive(Debug, Clone)]
+pub enum SinkTarget {
+    // Executable fed on stdin, see `PluginTransport`
+    Plugin(PathBuf),
+    Tcp(String),
+    // Written to stdout, for piping into another tool
+    Stdout,
+    // One datagram per frame, so keep frames under the path MTU
+    Udp(String),
+    // One WebSocket message per frame, text or binary by the encoding. The url keeps its path
+    WebSocket(String),
+    // One Kafka message per frame to a topic, with the brokers comma separated. Needs the kafka
+    // feature
+    Kafka { brokers: String, topic: String },
+    // Text file of the run, e.g. `{output_name}.ndjson.zst`
+    File(PathBuf, TextCompression),
+    // Held in memory, for payloads that go out in one piece. See `StreamExporter::encode`
+    Buffer(Arc<Mutex<Vec<u8>>>),
+}
+
+impl SinkTarget {
+    fn connect(&self, output_name: &str, format: FrameFormat) -> Result<Box<dyn Transport>> {
+        Ok(match self {
+            SinkTarget::Plugin(path) => Box::new(PluginTransport::spawn(path, output_name)?),
+            SinkTarget::Stdout => Box::new(StdoutTransport {
+                stdout: BufWriter::new(std::io::stdout()),
+            }),
+            SinkTarget::Tcp(address) => {
+                let stream = TcpStream::connect(address)
+                    .export_context("tcp", || format!("Failed to connect to {address}"))?;
+                Box::new(TcpTransport {
+                    address: address.clone(),
+                    stream: BufWriter::new(stream),
+                })
+            }
+            SinkTarget::Udp(address) => {
+                let socket = UdpSocket::bind("0.0.0.0:0")
+                    .and_then(|socket| socket.connect(address).map(|_| socket))
+                    .export_context("udp", || format!("Failed to open a socket to {address}"))?;
+                Box::new(UdpTransport {
+                    address: address.clone(),
+                    socket,
+                })
+            }
+            SinkTarget::WebSocket(url) => {
+                let (socket, _) = tungstenite::connect(url)
+                    .export_context("websocket", || format!("Failed to connect to {url}"))?;
+                Box::new(WebSocketTransport {
+                    url: url.clone(),
+                    socket,
+                    binary: format.is_binary(),
+                })
+            }
+            #[cfg(feature = "kafka")]
+            SinkTarget::Kafka { brokers, topic } => Box::new(KafkaTransport::connect(
+                brokers,
+                topic,
+                output_name,
+                format.is_binary(),
+            )?),
+            #[cfg(not(feature = "kafka"))]
+            SinkTarget::Kafka { .. } => {
+                return Err(TelemetryError::config(
+                    "kafka:// needs the kafka feature. Rebuild with --features kafka",
+                ));
+            }
+            SinkTarget::File(path, compression) => {
+                let file = BufWriter::new(
+                    File::create(path)
+                        .io_context(|| format!("Failed to create {}", path.display()))?,
+                );
+                let writer = match compression {
+                    TextCompression::None => FileWriter::Plain(file),
+                    TextCompression::Zstd => {
+                        FileWriter::Zstd(zstd::Encoder::new(file, ZSTD_LEVEL).io_context(|| {
+                            format!("Failed to start zstd for {}", path.display())
+                        })?)
+                    }
+                    TextCompression::Gzip => {
+                        FileWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+                    }
+                };
+                Box::new(FileTransport {
+                    path: path.clone(),
+                    writer,
+                })
+            }
+            SinkTarget::Buffer(buffer) => Box::new(BufferTransport {
+                buffer: buffer.clone(),
+            }),
+        })
+    }
+}
+
+impl std::fmt::Display for SinkTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkTarget::Plugin(path) => write!(f, "plugin {}", path.display()),
+            SinkTarget::Tcp(address) => write!(f, "tcp://{address}"),
+            SinkTarget::Stdout => write!(f, "stdout"),
+            SinkTarget::Udp(address) => write!(f, "udp://{address}"),
+            SinkTarget::WebSocket(url) => write!(f, "{url}"),
+            SinkTarget::Kafka { brokers, topic } => write!(f, "kafka://{brokers}/{topic}"),
+            SinkTarget::File(path, _) => write!(f, "{}", path.display()),
+            SinkTarget::Buffer(_) => write!(f, "memory"),
+        }
+    }
+}
+
+// Parse `tcp://host:port`, `udp://host:port`, `ws://host:port/path`,
+// `kafka://host:port[,host:port]/topic` or `stdout`. Plugins come in through --plugin. MQTT
+// publishes per reading, see `MqttExporter`
+impl FromStr for SinkTarget {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "stdout" || s == "-" {
+            return Ok(SinkTarget::Stdout);
+        }
+        let (scheme, address) = s.split_once("://").ok_or_else(|| {
+            TelemetryError::config(format!(
+                "Sink {s} should look like tcp://host:port, udp://host:port, ws://host:port/path, kafka://host:port/topic or stdout"
+            ))
+        })?;
+        let authority = address
+            .split_once('/')
+            .map_or(address, |(authority, _)| authority);
+        if authority
+            .rsplit_once(':')
+            .is_none_or(|(_, port)| port.parse::<u16>().is_err())
+        {
+            return Err(TelemetryError::config(format!(
+                "Sink {s} is missing a port"
+            )));
+        }
+        match scheme {
+            "tcp" => Ok(SinkTarget::Tcp(address.to_string())),
+            "udp" => Ok(SinkTarget::Udp(address.to_string())),
+            "ws" => Ok(SinkTarget::WebSocket(s.to_string())),
+            "kafka" => match address.split_once('/') {
+                Some((brokers, topic)) if !topic.is_empty() => Ok(SinkTarget::Kafka {
+                    brokers: brokers.to_string(),
+                    topic: topic.to_string(),
+                }),
+                _ => Err(TelemetryError::config(format!(
+                    "Kafka sink {s} is missing a topic, e.g. kafka://localhost:9092/telemetry"
+                ))),
+            },
+            _ => Err(TelemetryError::config(format!(
+                "Unknown sink transport {scheme}. Use tcp, udp, ws or kafka"
+            ))),
+        }
+    }
+}
+
+struct TcpTransport {
+    address: String,
+    stream: BufWriter<TcpStream>,
+}
+
+impl Transport for TcpTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.stream
+            .write_all(message)
+            .export_context("tcp", || format!("Lost the connection to {}", self.address))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stream
+            .flush()
+            .export_context("tcp", || format!("Failed to flush to {}", self.address))
+    }
+
+    fn close(mut self: Box<Self>) -> Result<()> {
+        self.flush()
+    }
+}
+
+struct StdoutTransport {
+    stdout: BufWriter<std::io::Stdout>,
+}
+
+impl Transport for StdoutTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.stdout
+            .write_all(message)
+            .export_context("stdout", || "Failed to write to stdout")
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.stdout
+            .flush()
+            .export_context("stdout", || "Failed to flush stdout")
+    }
+
+    fn close(mut self: Box<Self>) -> Result<()> {
+        self.flush()
+    }
+}
+
+struct UdpTransport {
+    address: String,
+    socket: UdpSocket,
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.socket
+            .send(message)
+            .map(|_| ())
+            .export_context("udp", || format!("Failed to send to {}", self.address))
+    }
+
+    fn close(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct WebSocketTransport {
+    url: String,
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    binary: bool,
+}
+
+impl Transport for WebSocketTransport {
+    // Text encodings end each frame with a newline, which a message boundary makes redundant
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        let message = if self.binary {
+            Message::binary(message.to_vec())
+        } else {
+            let text = message.strip_suffix(b"\n").unwrap_or(message);
+            Message::text(String::from_utf8_lossy(text).into_owned())
+        };
+        self.socket.write(message).export_context("websocket", || {
+            format!("Lost the connection to {}", self.url)
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.socket
+            .flush()
+            .export_context("websocket", || format!("Failed to flush to {}", self.url))
+    }
+
+    fn close(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        self.socket
+            .close(None)
+            .export_context("websocket", || format!("Failed to close {}", self.url))
+    }
+}
+
+struct BufferTransport {
+    buffer: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Transport for BufferTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend_from_slice(message);
+        Ok(())
+    }
+
+    fn close(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+// zstd's own default, a good trade of speed for size on text
+const ZSTD_LEVEL: i32 = 3;
+
+enum FileWriter {
+    Plain(BufWriter<File>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+struct FileTransport {
+    path: PathBuf,
+    writer: FileWriter,
+}
+
+impl Transport for FileTransport {
+    fn send(&mut self, message: &[u8]) -> Result<()> {
+        match &mut self.writer {
+            FileWriter::Plain(writer) => writer.write_all(message),
+            FileWriter::Zstd(writer) => writer.write_all(message),
+            FileWriter::Gzip(writer) => writer.write_all(message),
+        }
+        .io_context(|| format!("Failed to write {}", self.path.display()))
+    }
+
+    // Compressed files are only readable once the encoder writes its last frame
+    fn close(self: Box<Self>) -> Result<()> {
+        let FileTransport { path, writer } = *self;
+        let file = match writer {
+            FileWriter::Plain(writer) => Ok(writer),
+            FileWriter::Zstd(writer) => writer.finish(),
+            FileWriter::Gzip(writer) => writer.finish(),
+        };
+        file.and_then(|mut file| file.flush())
+            .io_context(|| format!("Failed to finish {}", path.display()))
+    }
+}
+
+// A target plus the encoding to send it, started once per run
+#[derive(Debug, Clone)]
+pub struct StreamExporter {
+    pub target: SinkTarget,
+    pub format: FrameFormat,
+}
+
+impl StreamExporter {
+    // Connect and send the start frame. Readings follow chunk by chunk
+    pub fn start(&self, dataset: &TelemetryDataset, output_name: &str) -> Result<StreamSink> {
+        info!("Streaming {:?} to {}", self.format, self.target);
+        let mut sink = StreamSink {
+            target: self.target.clone(),
+            encoder: self.format.encoder(),
+            transport: self.target.connect(output_name, self.format)?,
+            buffer: Vec::new(),
+            readings_sent: 0,
+            bytes_sent: 0,
+        };
+        sink.send(&Frame::Start {
+            protocol_version: PLUGIN_PROTOCOL_VERSION,
+            launch_id: &dataset.config.launch_id,
+            launch_time: dataset
+                .launch_time
+                .to_rfc3339_opts(SecondsFormat::Micros, true),
+            sample_rate_hz: dataset.config.sample_rate_hz.to_string(),
+            units: dataset.config.units.name(),
+        })?;
+        Ok(sink)
+    }
+
+    pub fn export(&self, dataset: &TelemetryDataset, output_name: &str) -> Result<()> {
+        let mut sink = self.start(dataset, output_name)?;
+        sink.write_chunk(dataset)?;
+        sink.close()
+    }
+
+    // The whole dataset in `format`, start and end frames included, e.g. for an HTTP response
+    pub fn encode(format: FrameFormat, dataset: &TelemetryDataset) -> Result<Vec<u8>> {
+        let buffer = Arc::new(Mutex::new(Vec::new()));
+        let exporter = StreamExporter {
+            target: SinkTarget::Buffer(buffer.clone()),
+            format,
+        };
+        exporter.export(dataset, &dataset.config.launch_id)?;
+        Ok(std::mem::take(
+            &mut *buffer.lock().unwrap_or_else(|e| e.into_inner()),
+        ))
+    }
+}
+
+// Running sink being fed readings
+pub struct StreamSink {
+    target: SinkTarget,
+    encoder: Box<dyn FrameEncoder>,
+    transport: Box<dyn Transport>,
+    // Reused between frames
+    buffer: Vec<u8>,
+    readings_sent: usize,
+    // Before compression
+    bytes_sent: u64,
+}
+
+impl StreamSink {
+    pub fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        self.write_readings(&chunk.readings, &chunk.config)
+    }
+
+    pub fn write_readings(
+        &mut self,
+        readings: &[TelemetryReading],
+        config: &TelemetryConfig,
+    ) -> Result<()> {
+        for (idx, reading) in readings.iter().enumerate() {
+            let id = config
+                .point_ids
+                .then(|| config.point_id(self.readings_sent + idx));
+            self.send(&Frame::Reading(ReadingFrame {
+                timestamp: reading
+                    .timestamp
+                    .to_rfc3339_opts(SecondsFormat::Micros, true),
+                time_since_launch_ms: reading.time_since_launch_ms,
+                sensor_type: reading.sensor_label(config),
+                value: FrameValue::new(reading, config),
+                unit: config.units.unit(reading.sensor),
+                id,
+                mission_phase: reading.phase.name(),
+                reading,
+            }))?;
+        }
+        self.readings_sent += readings.len();
+        Ok(())
+    }
+
+    // Push the readings written so far out to the target
+    pub fn flush(&mut self) -> Result<()> {
+        self.transport.flush()
+    }
+
+    // Bytes written so far when the target is a file, before any compression. 0 for the rest
+    pub fn file_bytes(&self) -> u64 {
+        match self.target {
+            SinkTarget::File(..) => self.bytes_sent,
+            _ => 0,
+        }
+    }
+
+    // Send the end frame and close the transport
+    pub fn close(mut self) -> Result<()> {
+        self.send(&Frame::End {
+            readings: self.readings_sent,
+        })?;
+        self.transport.close()?;
+        info!("Sent {} readings to {}", self.readings_sent, self.target);
+        Ok(())
+    }
+
+    fn send(&mut self, frame: &Frame) -> Result<()> {
+        self.buffer.clear();
+        self.encoder.encode(frame, &mut self.buffer)?;
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.bytes_sent += self.buffer.len() as u64;
+        self.transport.send(&self.buffer)
+    }
+}
@@ -0,0 +1,307 @@
+use super::output_files::prepare_output_file;
+use crate::error::{ExportContext, Result};
+use crate::generators::{
+    ChunkObserver, FlightEvent, FlightEventKind, FlightSummary, HealthTransition, LimitAlert,
+};
+use crate::models::{
+    MissionPhase, PairSide, SensorEnum, SensorValue, TelemetryConfig, TelemetryDataset,
+    sensor_label,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rust_xlsxwriter::{Format, Workbook, Worksheet, XlsxError};
+use std::collections::HashMap;
+use std::ops::ControlFlow;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+// Sink name reported in export errors
+const SINK: &str = "xlsx";
+
+// Running statistics per sensor, gathered chunk by chunk as a `ChunkObserver` so runs too big
+// for memory can still be summarized. Clones share the totals, so hand one to the generator and
+// keep the other for the summary
+#[derive(Debug, Clone, Default)]
+pub struct SensorStatistics {
+    totals: Arc<Mutex<RunTotals>>,
+}
+
+#[derive(Debug, Default)]
+struct RunTotals {
+    launch_time: Option<DateTime<Utc>>,
+    readings: u64,
+    channels: HashMap<(SensorEnum, Option<u16>, Option<PairSide>), ChannelStats>,
+    // Each phase the run entered and when
+    phases: Vec<(MissionPhase, u64)>,
+}
+
+// Welford's running mean and variance, stable over billions of readings
+#[derive(Debug, Clone)]
+struct ChannelStats {
+    readings: u64,
+    // Readings with a number, so discrete states and strings only count
+    numeric: u64,
+    min: f64,
+    max: f64,
+    mean: f64,
+    m2: f64,
+    last_ms: u64,
+}
+
+impl ChannelStats {
+    fn new() -> Self {
+        Self {
+            readings: 0,
+            numeric: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            mean: 0.0,
+            m2: 0.0,
+            last_ms: 0,
+        }
+    }
+
+    fn add(&mut self, value: Option<f64>, time_ms: u64) {
+        self.readings += 1;
+        self.last_ms = self.last_ms.max(time_ms);
+        // NaN dropouts from chaos count as readings but not towards the numbers
+        let Some(value) = value.filter(|value| value.is_finite()) else {
+            return;
+        };
+        self.numeric += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = value - self.mean;
+        self.mean += delta / self.numeric as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    fn std_dev(&self) -> f64 {
+        if self.numeric < 2 {
+            0.0
+        } else {
+            (self.m2 / (self.numeric - 1) as f64).sqrt()
+        }
+    }
+}
+
+impl ChunkObserver for SensorStatistics {
+    fn on_chunk(&mut self, chunk: &TelemetryDataset) -> Result<ControlFlow<()>> {
+        let mut totals = self.totals.lock().unwrap_or_else(|e| e.into_inner());
+        totals.launch_time.get_or_insert(chunk.launch_time);
+        totals.readings += chunk.readings.len() as u64;
+        for reading in &chunk.readings {
+            if totals.phases.last().map(|(phase, _)| *phase) != Some(reading.phase) {
+                totals
+                    .phases
+                    .push((reading.phase, reading.time_since_launch_ms));
+            }
+            // Summarized in the exported units, like the files hold them
+            let value = match &reading.value {
+                SensorValue::Float(f) => Some(chunk.config.export_float(reading.sensor, *f)),
+                SensorValue::Int(i) => Some(*i as f64),
+                SensorValue::UnsignedInt(u) => Some(*u as f64),
+                SensorValue::String(_) | SensorValue::State(_) => None,
+            };
+            totals
+                .channels
+                .entry((reading.sensor, reading.instance, reading.side))
+                .or_insert_with(ChannelStats::new)
+                .add(value, reading.time_since_launch_ms);
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+// Everything the workbook covers besides the sensor statistics
+#[derive(Debug, Clone, Copy)]
+pub struct RunOverview<'a> {
+    pub config: &'a TelemetryConfig,
+    pub flight: &'a FlightSummary,
+    pub events: &'a [FlightEvent],
+    pub health: &'a [HealthTransition],
+    pub alerts: &'a [LimitAlert],
+}
+
+// Small workbook for people who won't open a parquet file, `{output_name}.summary.xlsx`. A Run
+// sheet with the settings and flight summary, a Sensors sheet with statistics per sensor and a
+// Timeline sheet with phases, events, health changes and alerts in order. No raw readings
+pub struct XlsxSummaryExporter;
+
+impl XlsxSummaryExporter {
+    pub fn export(
+        overview: &RunOverview,
+        statistics: &SensorStatistics,
+        output_dir: &Path,
+        output_name: &str,
+    ) -> Result<()> {
+        let totals = statistics.totals.lock().unwrap_or_else(|e| e.into_inner());
+        let mut workbook = Workbook::new();
+        let header = Format::new().set_bold();
+
+        Self::run_sheet(workbook.add_worksheet(), overview, &totals, &header)
+            .export_context(SINK, || "Failed to write the Run sheet")?;
+        Self::sensor_sheet(workbook.add_worksheet(), overview.config, &totals, &header)
+            .export_context(SINK, || "Failed to write the Sensors sheet")?;
+        Self::timeline_sheet(workbook.add_worksheet(), overview, &totals, &header)
+            .export_context(SINK, || "Failed to write the Timeline sheet")?;
+
+        let xlsx_file = prepare_output_file(output_dir, output_name, "summary.xlsx")?;
+        workbook
+            .save(&xlsx_file)
+            .export_context(SINK, || format!("Failed to save {}", xlsx_file.display()))?;
+        info!("Run summary written to {}", xlsx_file.display());
+        Ok(())
+    }
+
+    fn run_sheet(
+        sheet: &mut Worksheet,
+        overview: &RunOverview,
+        totals: &RunTotals,
+        header: &Format,
+    ) -> Result<(), XlsxError> {
+        let config = overview.config;
+        let flight = overview.flight;
+        sheet.set_name("Run")?;
+        sheet.write_string_with_format(0, 0, "Setting", header)?;
+        sheet.write_string_with_format(0, 1, "Value", header)?;
+        let launch_time = totals
+            .launch_time
+            .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true))
+            .unwrap_or_default();
+        let text_rows = [
+            ("Launch id", config.launch_id.clone()),
+            ("Launch time", launch_time),
+            ("Sample rate (Hz)", config.sample_rate_hz.to_string()),
+            ("Units", config.units.name().to_string()),
+            ("Seed", config.seed.to_string()),
+        ];
+        let number_rows = [
+            ("Duration (s)", config.duration as f64),
+            ("Readings", totals.readings as f64),
+            ("Sensors", totals.channels.len() as f64),
+            ("Max Q (Pa)", flight.max_q_pa),
+            ("Max Q at (s)", flight.max_q_time_s),
+            ("Burnout velocity (m/s)", flight.burnout_velocity_mps),
+            ("Max altitude (m)", flight.max_altitude_m),
+            ("Anomalies", flight.anomalies as f64),
+        ];
+        let mut row = 1;
+        for (name, value) in text_rows {
+            sheet.write_string(row, 0, name)?;
+            sheet.write_string(row, 1, value)?;
+            row += 1;
+        }
+        for (name, value) in number_rows {
+            sheet.write_string(row, 0, name)?;
+            sheet.write_number(row, 1, value)?;
+            row += 1;
+        }
+        sheet.set_column_width(0, 24)?;
+        sheet.set_column_width(1, 28)?;
+        Ok(())
+    }
+
+    fn sensor_sheet(
+        sheet: &mut Worksheet,
+        config: &TelemetryConfig,
+        totals: &RunTotals,
+        header: &Format,
+    ) -> Result<(), XlsxError> {
+        sheet.set_name("Sensors")?;
+        let columns = [
+            "Sensor", "Name", "Unit", "Readings", "Min", "Max", "Mean", "Std dev", "Last (s)",
+        ];
+        for (col, title) in columns.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, *title, header)?;
+        }
+        // Catalog order, then instance and side
+        let mut channels: Vec<_> = totals.channels.iter().collect();
+        channels.sort_by_key(|((sensor, instance, side), _)| {
+            (*sensor as usize, *instance, side.map(|side| side.suffix()))
+        });
+        for (row, ((sensor, instance, side), stats)) in (1..).zip(channels) {
+            let name = config.sensor_name(*sensor, stats.last_ms);
+            sheet.write_string(row, 0, sensor_label(name, *instance, *side))?;
+            sheet.write_string(row, 1, sensor.description())?;
+            sheet.write_string(row, 2, config.units.unit(*sensor))?;
+            sheet.write_number(row, 3, stats.readings as f64)?;
+            if stats.numeric > 0 {
+                sheet.write_number(row, 4, stats.min)?;
+                sheet.write_number(row, 5, stats.max)?;
+                sheet.write_number(row, 6, stats.mean)?;
+                sheet.write_number(row, 7, stats.std_dev())?;
+            }
+            sheet.write_number(row, 8, stats.last_ms as f64 / 1000.0)?;
+        }
+        sheet.set_column_width(0, 14)?;
+        sheet.set_column_width(1, 44)?;
+        sheet.set_freeze_panes(1, 0)?;
+        Ok(())
+    }
+
+    fn timeline_sheet(
+        sheet: &mut Worksheet,
+        overview: &RunOverview,
+        totals: &RunTotals,
+        header: &Format,
+    ) -> Result<(), XlsxError> {
+        sheet.set_name("Timeline")?;
+        let mut entries: Vec<(u64, &str, String)> = totals
+            .phases
+            .iter()
+            .map(|(phase, ms)| (*ms, "phase", phase.name().to_string()))
+            .collect();
+        entries.extend(overview.events.iter().map(|event| {
+            let kind = match event.kind {
+                FlightEventKind::Staging => "staging",
+                FlightEventKind::Anomaly => "anomaly",
+            };
+            (event.time_since_launch_ms, kind, event.label.clone())
+        }));
+        entries.extend(overview.health.iter().map(|transition| {
+            (
+                transition.time_since_launch_ms,
+                "health",
+                format!(
+                    "{} to {}: {}",
+                    transition.from.name(),
+                    transition.to.name(),
+                    transition.cause
+                ),
+            )
+        }));
+        entries.extend(overview.alerts.iter().map(|alert| {
+            let name = overview.config.sensor_name(alert.sensor, alert.start_ms);
+            let until = match alert.end_ms {
+                Some(end_ms) => format!("until T+{:.1}s", end_ms as f64 / 1000.0),
+                None => "until the end".to_string(),
+            };
+            (
+                alert.start_ms,
+                "alert",
+                format!(
+                    "{} {} past {} peaking at {:.2} {until}",
+                    alert.level.name(),
+                    sensor_label(name, alert.instance, alert.side),
+                    alert.limit,
+                    alert.peak
+                ),
+            )
+        }));
+        // Stable, so phases come before events at the same time
+        entries.sort_by_key(|(ms, _, _)| *ms);
+
+        for (col, title) in ["T+ (s)", "Kind", "Detail"].iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, *title, header)?;
+        }
+        for (row, (ms, kind, detail)) in (1..).zip(entries) {
+            sheet.write_number(row, 0, ms as f64 / 1000.0)?;
+            sheet.write_string(row, 1, kind)?;
+            sheet.write_string(row, 2, detail)?;
+        }
+        sheet.set_column_width(2, 80)?;
+        sheet.set_freeze_panes(1, 0)?;
+        Ok(())
+    }
+}
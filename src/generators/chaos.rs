@@ -0,0 +1,180 @@
+use crate::models::{SampleRate, SensorValue, TelemetryReading};
+use chrono::Duration;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tracing::info;
+
+// Mixed into the run seed so chaos gets its own stream and leaves the sensor noise untouched
+const CHAOS_SEED_SALT: u64 = 0xC4A0_5EED;
+
+// Events per second of simulated time at medium intensity
+const GAPS_PER_S: f64 = 1.0 / 60.0;
+const STUCK_SENSORS_PER_S: f64 = 1.0 / 30.0;
+const SPIKES_PER_S: f64 = 1.0 / 5.0;
+const DROPOUTS_PER_S: f64 = 1.0 / 10.0;
+const DUPLICATES_PER_S: f64 = 1.0 / 5.0;
+const LATE_READINGS_PER_S: f64 = 1.0 / 5.0;
+
+// How messy a chaos run gets. Scales how often each kind of fault happens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ChaosLevel {
+    Low,
+    Medium,
+    High,
+    Extreme,
+}
+
+impl ChaosLevel {
+    fn intensity(&self) -> f64 {
+        match self {
+            ChaosLevel::Low => 0.25,
+            ChaosLevel::Medium => 1.0,
+            ChaosLevel::High => 4.0,
+            ChaosLevel::Extreme => 16.0,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ChaosCounts {
+    gaps: usize,
+    dropped: usize,
+    stuck_sensors: usize,
+    spikes: usize,
+    dropouts: usize,
+    duplicates: usize,
+    late_readings: usize,
+}
+
+#[derive(Debug)]
+struct StuckSensor {
+    // Position of the sensor within a timestep
+    index: usize,
+    value: SensorValue,
+    until_ms: u64,
+}
+
+// Sprinkles faults, gaps, duplicates and out of order readings over a run.
+// Works on the readings of one timestep at a time as they are generated.
+pub struct Chaos {
+    rng: StdRng,
+    level: ChaosLevel,
+    period_s: f64,
+    gap_until_ms: Option<u64>,
+    stuck: Vec<StuckSensor>,
+    counts: ChaosCounts,
+}
+
+impl Chaos {
+    pub fn new(level: ChaosLevel, seed: u64, sample_rate: SampleRate) -> Self {
+        info!("Chaos mode {:?} enabled", level);
+        Self {
+            rng: StdRng::seed_from_u64(seed ^ CHAOS_SEED_SALT),
+            level,
+            period_s: sample_rate.period_s(),
+            gap_until_ms: None,
+            stuck: Vec::new(),
+            counts: ChaosCounts::default(),
+        }
+    }
+
+    // Mess with the readings of the timestep at `now_ms`, which start at `step_start`
+    pub fn apply(&mut self, now_ms: u64, readings: &mut Vec<TelemetryReading>, step_start: usize) {
+        let step_len = readings.len() - step_start;
+        if step_len == 0 {
+            return;
+        }
+
+        // Gaps drop every reading for a while, like a lost downlink
+        if self.gap_until_ms.is_some_and(|until| now_ms >= until) {
+            self.gap_until_ms = None;
+        }
+        if self.gap_until_ms.is_none() && self.happens(GAPS_PER_S) {
+            self.gap_until_ms = Some(now_ms + self.rng.gen_range(100..2_000));
+            self.counts.gaps += 1;
+        }
+        if self.gap_until_ms.is_some() {
+            self.counts.dropped += step_len;
+            readings.truncate(step_start);
+            return;
+        }
+
+        // Stuck sensors keep reporting the value they froze on
+        self.stuck.retain(|stuck| now_ms < stuck.until_ms);
+        if self.happens(STUCK_SENSORS_PER_S) {
+            let index = self.rng.gen_range(0..step_len);
+            self.stuck.push(StuckSensor {
+                index,
+                value: readings[step_start + index].value.clone(),
+                until_ms: now_ms + self.rng.gen_range(1_000..10_000),
+            });
+            self.counts.stuck_sensors += 1;
+        }
+        for stuck in &self.stuck {
+            if let Some(reading) = readings.get_mut(step_start + stuck.index) {
+                reading.value = stuck.value.clone();
+            }
+        }
+
+        if self.happens(SPIKES_PER_S) {
+            let index = step_start + self.rng.gen_range(0..step_len);
+            if let SensorValue::Float(value) = &mut readings[index].value {
+                let sign = if self.rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+                *value *= sign * self.rng.gen_range(5.0..20.0);
+                self.counts.spikes += 1;
+            }
+        }
+
+        if self.happens(DROPOUTS_PER_S) {
+            let index = step_start + self.rng.gen_range(0..step_len);
+            if let SensorValue::Float(value) = &mut readings[index].value {
+                *value = f64::NAN;
+                self.counts.dropouts += 1;
+            }
+        }
+
+        // Late readings are stamped a few timesteps back, so they land out of order
+        if self.happens(LATE_READINGS_PER_S) {
+            let index = step_start + self.rng.gen_range(0..step_len);
+            let steps_late = self.rng.gen_range(1..=20) as f64;
+            let late_by = Duration::microseconds(((steps_late * self.period_s) * 1e6) as i64);
+            let reading = &mut readings[index];
+            reading.timestamp -= late_by;
+            reading.time_since_launch_ms = reading
+                .time_since_launch_ms
+                .saturating_sub(late_by.num_milliseconds() as u64);
+            self.counts.late_readings += 1;
+        }
+
+        if self.happens(DUPLICATES_PER_S) {
+            let index = step_start + self.rng.gen_range(0..step_len);
+            readings.push(readings[index].clone());
+            self.counts.duplicates += 1;
+        }
+    }
+
+    // Sensor faults injected so far, leaving out delivery problems like gaps and duplicates
+    pub fn faults(&self) -> usize {
+        self.counts.stuck_sensors + self.counts.spikes + self.counts.dropouts
+    }
+
+    pub fn log_summary(&self) {
+        let counts = &self.counts;
+        info!(
+            gaps = counts.gaps,
+            dropped_readings = counts.dropped,
+            stuck_sensors = counts.stuck_sensors,
+            spikes = counts.spikes,
+            dropouts = counts.dropouts,
+            duplicates = counts.duplicates,
+            late_readings = counts.late_readings,
+            "Chaos summary"
+        );
+    }
+
+    // Roll for an event that happens `per_s` times a second at medium intensity
+    fn happens(&mut self, per_s: f64) -> bool {
+        let chance = (per_s * self.level.intensity() * self.period_s).min(1.0);
+        self.rng.gen_bool(chance)
+    }
+}
@@ -0,0 +1,149 @@
+use crate::models::{MissionPhase, SensorEnum, SensorValue, TelemetryReading};
+use chrono::{DateTime, Duration, Utc};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::{Bernoulli, Distribution, Exp, LogNormal};
+use tracing::info;
+
+// Mixed into the run seed so the command link gets its own stream and leaves the sensor noise
+// untouched
+const COMMAND_SEED_SALT: u64 = 0x0C0D_ACC5;
+
+// Spread of the ack latency, as the standard deviation of its log
+const ACK_LATENCY_SIGMA: f64 = 0.4;
+
+// Commands lost on the uplink and never acked, for testing timeouts
+const LOST_COMMAND_CHANCE: f64 = 0.01;
+
+// A command waiting on its ack
+#[derive(Debug, Clone, Copy)]
+struct PendingAck {
+    id: u64,
+    sent_us: u64,
+    ack_us: u64,
+}
+
+// Synthetic uplink commanding interleaved with the telemetry, for testing command and response
+// correlation. Commands go out at seeded random times as a `Cmd` reading with their sequence
+// number, and the ack comes back as an `Ack` reading with the same number and an `Ack_s`
+// reading with the latency. Readings are stamped with when they happened. Only sequence numbers,
+// nothing about who sent what
+#[derive(Debug, Clone)]
+pub struct CommandLink {
+    rng: StdRng,
+    interval_us: Exp<f64>,
+    latency_us: LogNormal<f64>,
+    lost: Bernoulli,
+    next_send_us: u64,
+    next_id: u64,
+    pending: Vec<PendingAck>,
+    sent: u64,
+    acked: u64,
+    dropped: u64,
+}
+
+impl CommandLink {
+    pub fn new(rate_per_min: f64, mean_ack_ms: f64, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed ^ COMMAND_SEED_SALT);
+        let interval_us = Exp::new(rate_per_min / 60e6).expect("Command rate is positive");
+        // Log normal with the mean latency asked for
+        let mean_us = mean_ack_ms * 1000.0;
+        let latency_us = LogNormal::new(
+            mean_us.ln() - ACK_LATENCY_SIGMA * ACK_LATENCY_SIGMA / 2.0,
+            ACK_LATENCY_SIGMA,
+        )
+        .expect("Ack latency sigma is positive");
+        let next_send_us = interval_us.sample(&mut rng) as u64;
+        info!("Sending {rate_per_min} commands a minute, acked after {mean_ack_ms} ms on average");
+        Self {
+            rng,
+            interval_us,
+            latency_us,
+            lost: Bernoulli::new(LOST_COMMAND_CHANCE).expect("Loss chance is a probability"),
+            next_send_us,
+            next_id: 1,
+            pending: Vec::new(),
+            sent: 0,
+            acked: 0,
+            dropped: 0,
+        }
+    }
+
+    // Append the commands sent and acks received up to `offset_nanos`, in time order. They take
+    // the phase of the step they're reported in
+    pub(super) fn apply(
+        &mut self,
+        launch_time: DateTime<Utc>,
+        offset_nanos: u64,
+        phase: MissionPhase,
+        readings: &mut Vec<TelemetryReading>,
+    ) {
+        let now_us = offset_nanos / 1000;
+        let mut events: Vec<(u64, SensorEnum, SensorValue)> = Vec::new();
+        while self.next_send_us <= now_us {
+            let sent_us = self.next_send_us;
+            let id = self.next_id;
+            events.push((
+                sent_us,
+                SensorEnum::CommandSent,
+                SensorValue::UnsignedInt(id),
+            ));
+            if self.lost.sample(&mut self.rng) {
+                self.dropped += 1;
+            } else {
+                let latency_us = self.latency_us.sample(&mut self.rng).max(1.0) as u64;
+                self.pending.push(PendingAck {
+                    id,
+                    sent_us,
+                    ack_us: sent_us + latency_us,
+                });
+            }
+            self.sent += 1;
+            self.next_id += 1;
+            // At least a microsecond apart, so every command has its own time
+            self.next_send_us += (self.interval_us.sample(&mut self.rng) as u64).max(1);
+        }
+        self.pending.retain(|pending| {
+            if pending.ack_us > now_us {
+                return true;
+            }
+            events.push((
+                pending.ack_us,
+                SensorEnum::CommandAck,
+                SensorValue::UnsignedInt(pending.id),
+            ));
+            events.push((
+                pending.ack_us,
+                SensorEnum::CommandAckLatency,
+                SensorValue::Float((pending.ack_us - pending.sent_us) as f64 / 1e6),
+            ));
+            false
+        });
+        self.acked += events
+            .iter()
+            .filter(|(_, sensor, _)| *sensor == SensorEnum::CommandAck)
+            .count() as u64;
+
+        // Stable, so an ack keeps its latency right after it
+        events.sort_by_key(|(time_us, _, _)| *time_us);
+        for (time_us, sensor, value) in events {
+            readings.push(TelemetryReading::new(
+                launch_time + Duration::microseconds(time_us as i64),
+                (time_us + 500) / 1000,
+                phase,
+                sensor,
+                value,
+            ));
+        }
+    }
+
+    pub fn log_summary(&self) {
+        info!(
+            sent = self.sent,
+            acked = self.acked,
+            lost = self.dropped,
+            awaiting_ack = self.pending.len(),
+            "Command summary"
+        );
+    }
+}
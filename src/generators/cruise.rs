@@ -0,0 +1,59 @@
+use crate::error::TelemetryError;
+use std::str::FromStr;
+
+use super::generator::SimulationState;
+
+// Steady state run where every sensor hovers around a setpoint with noise. No flight phases,
+// so compression and retention benchmarks get a big boring data shape without dynamics as a
+// confound. Setpoints default to a sustained burn and can be overridden per field.
+#[derive(Debug, Clone, Default)]
+pub struct Cruise {
+    setpoints: Vec<Setpoint>,
+}
+
+// Override for one simulation field, e.g. `chamber_pressure_pa=4500000`
+#[derive(Debug, Clone)]
+pub struct Setpoint {
+    field: String,
+    value: f64,
+}
+
+impl Cruise {
+    pub fn new(setpoints: Vec<Setpoint>) -> Self {
+        Self { setpoints }
+    }
+
+    // State held for the whole run
+    pub(super) fn state(&self) -> SimulationState {
+        let mut state = SimulationState::cruise();
+        for setpoint in &self.setpoints {
+            if let Some(value) = state.field_mut(&setpoint.field) {
+                *value = setpoint.value;
+            }
+        }
+        state
+    }
+}
+
+impl FromStr for Setpoint {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, value) = s.split_once('=').ok_or_else(|| {
+            TelemetryError::config(format!("Setpoint {s} should look like <field>=<value>"))
+        })?;
+        let (field, value) = (field.trim(), value.trim());
+
+        if SimulationState::initialize().field_mut(field).is_none() {
+            return Err(TelemetryError::config(format!("Unknown field {field}")));
+        }
+        let value = value
+            .parse()
+            .map_err(|_| TelemetryError::config(format!("Invalid setpoint value {value}")))?;
+
+        Ok(Self {
+            field: field.to_string(),
+            value,
+        })
+    }
+}
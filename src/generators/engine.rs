@@ -0,0 +1,84 @@
+use crate::error::{Result, TelemetryError};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, Normal};
+use tracing::info;
+
+// Mixed into the run seed so the engine draw leaves the sensor noise stream untouched
+const ENGINE_SEED_SALT: u64 = 0xE761_9E5E;
+
+// Turbopump efficiency of the nominal engine the profile is flown with
+const NOMINAL_PUMP_EFFICIENCY: f64 = 0.70;
+
+// Spread of engine characteristics across a fleet, in percent of nominal. Draws are normal with
+// the spread as two standard deviations and clipped to it
+#[derive(Debug, Clone, Copy)]
+pub struct EngineSpread {
+    pub thrust_pct: f64,
+    pub isp_pct: f64,
+    pub pump_efficiency_pct: f64,
+}
+
+// The engine a run flies with. Scales on the nominal curves, 1 is a nominal engine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EngineUnit {
+    pub thrust_scale: f64,
+    pub isp_scale: f64,
+    pub pump_efficiency: f64,
+}
+
+impl Default for EngineUnit {
+    fn default() -> Self {
+        Self {
+            thrust_scale: 1.0,
+            isp_scale: 1.0,
+            pump_efficiency: NOMINAL_PUMP_EFFICIENCY,
+        }
+    }
+}
+
+impl EngineUnit {
+    // Same seed, same engine, so a fleet of seeds shows unit to unit variation
+    pub fn from_seed(seed: u64, spread: EngineSpread) -> Result<Self> {
+        let mut rng = StdRng::seed_from_u64(seed ^ ENGINE_SEED_SALT);
+        let mut draw = |pct: f64| -> Result<f64> {
+            if !(0.0..100.0).contains(&pct) {
+                return Err(TelemetryError::config(format!(
+                    "Engine spread of {pct}% should be at least 0 and below 100"
+                )));
+            }
+            let fraction = pct / 100.0;
+            let normal = Normal::new(0.0, fraction / 2.0)
+                .map_err(|e| TelemetryError::config(format!("Bad engine spread: {e}")))?;
+            Ok(1.0 + normal.sample(&mut rng).clamp(-fraction, fraction))
+        };
+
+        let engine = Self {
+            thrust_scale: draw(spread.thrust_pct)?,
+            isp_scale: draw(spread.isp_pct)?,
+            pump_efficiency: NOMINAL_PUMP_EFFICIENCY * draw(spread.pump_efficiency_pct)?,
+        };
+        info!(
+            "Engine unit has {:+.2}% thrust, {:+.2}% Isp and {:.1}% pump efficiency",
+            (engine.thrust_scale - 1.0) * 100.0,
+            (engine.isp_scale - 1.0) * 100.0,
+            engine.pump_efficiency * 100.0
+        );
+        Ok(engine)
+    }
+
+    // Chamber pressure goes with thrust
+    pub fn pressure_scale(&self) -> f64 {
+        self.thrust_scale
+    }
+
+    // Mass flow is thrust over Isp, so more thrust costs propellant and more Isp saves it
+    pub fn flow_scale(&self) -> f64 {
+        self.thrust_scale / self.isp_scale
+    }
+
+    // A less efficient pump spins faster to deliver the same flow
+    pub fn pump_rpm_scale(&self) -> f64 {
+        self.flow_scale() * NOMINAL_PUMP_EFFICIENCY / self.pump_efficiency
+    }
+}
@@ -0,0 +1,43 @@
+use crate::models::SensorValue;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use tracing::info;
+
+// Mixed into the run seed so the instance biases leave the sensor noise untouched
+const FANOUT_SEED_SALT: u64 = 0xFA17_0075;
+
+// Instances read a little off from each other, up to half a percent either way
+const MAX_INSTANCE_BIAS: f64 = 0.005;
+
+// Cardinality stress. Every sensor is reported by N synthetic instances, e.g. `F_pa_001` to
+// `F_pa_100`, to load time series databases with lots of series. Each instance gets a fixed
+// seeded bias so they don't all report identical values
+#[derive(Debug, Clone)]
+pub struct SensorFanout {
+    biases: Vec<f64>,
+}
+
+impl SensorFanout {
+    pub fn new(instances: u16, seed: u64) -> Self {
+        info!("Fanning each sensor out into {} instances", instances);
+        let mut rng = StdRng::seed_from_u64(seed ^ FANOUT_SEED_SALT);
+        let biases = (0..instances)
+            .map(|_| rng.gen_range(-MAX_INSTANCE_BIAS..=MAX_INSTANCE_BIAS))
+            .collect();
+        Self { biases }
+    }
+
+    // Instance numbers start at 1 along with the value that instance reports
+    pub(super) fn instances<'a>(
+        &'a self,
+        value: &'a SensorValue,
+    ) -> impl Iterator<Item = (u16, SensorValue)> + 'a {
+        self.biases.iter().zip(1..).map(move |(bias, instance)| {
+            let value = match value {
+                SensorValue::Float(v) => SensorValue::Float(v * (1.0 + bias)),
+                other => other.clone(),
+            };
+            (instance, value)
+        })
+    }
+}
@@ -0,0 +1,105 @@
+// Propellant tank pressures feeding the engine. The tanks pressurize on the pad, sag as
+// propellant drains and the ullage outgrows the pressurant, and ring briefly when valves move.
+// Stage separation hands over to the upper stage's own full tanks
+
+const AMBIENT_PA: f64 = 101_325.0;
+// Regulated flight pressures of full tanks
+const OXIDIZER_TANK_PA: f64 = 350_000.0;
+const FUEL_TANK_PA: f64 = 300_000.0;
+// Flow at full throttle, which sizes the tanks
+const OXIDIZER_FULL_FLOW_KGPS: f64 = 250.0;
+const FUEL_FULL_FLOW_KGPS: f64 = 50.0;
+// Seconds for the regulator to close ~63% of the gap to the target pressure
+const PRESSURIZE_TAU_S: f64 = 0.5;
+// Fraction of the flight pressure lost by the time a tank runs dry
+const DEPLETION_DROP: f64 = 0.3;
+
+// Surges as a fraction of tank pressure. The main valve closing hammers the lines and opening
+// it draws them down, the smaller valves just knock
+const ACTUATION_SURGE: f64 = 0.03;
+const MAIN_VALVE_SURGE: f64 = 0.12;
+// Seconds for a surge to ring down ~63%
+const SURGE_TAU_S: f64 = 0.15;
+
+#[derive(Debug, Clone)]
+pub(super) struct FeedState {
+    stage: u64,
+    oxidizer_used_kg: f64,
+    fuel_used_kg: f64,
+    // Regulated pressures, before any surge
+    oxidizer_pa: f64,
+    fuel_pa: f64,
+    surge: f64,
+}
+
+impl FeedState {
+    pub(super) fn initialize() -> Self {
+        Self {
+            stage: 1,
+            oxidizer_used_kg: 0.0,
+            fuel_used_kg: 0.0,
+            oxidizer_pa: AMBIENT_PA,
+            fuel_pa: AMBIENT_PA,
+            surge: 0.0,
+        }
+    }
+
+    // A small valve actuated
+    pub(super) fn valve_actuated(&mut self) {
+        self.surge += ACTUATION_SURGE;
+    }
+
+    // The main valve opened or closed
+    pub(super) fn main_valve_moved(&mut self, opened: bool) {
+        self.surge += if opened {
+            -MAIN_VALVE_SURGE
+        } else {
+            MAIN_VALVE_SURGE
+        };
+    }
+
+    // Drain the tanks at the current flow rates and return the oxidizer and fuel tank pressures.
+    // Each stage carries enough for `stage_burn_s` at full flow
+    pub(super) fn step(
+        &mut self,
+        stage: u64,
+        oxidizer_flow_kgps: f64,
+        fuel_flow_kgps: f64,
+        stage_burn_s: f64,
+        time_step_s: f64,
+    ) -> (f64, f64) {
+        if stage != self.stage {
+            self.stage = stage;
+            self.oxidizer_used_kg = 0.0;
+            self.fuel_used_kg = 0.0;
+        }
+        self.oxidizer_used_kg += oxidizer_flow_kgps.max(0.0) * time_step_s;
+        self.fuel_used_kg += fuel_flow_kgps.max(0.0) * time_step_s;
+
+        let regulate = 1.0 - (-time_step_s / PRESSURIZE_TAU_S).exp();
+        for (pressure_pa, full_pa, used_kg, capacity_kg) in [
+            (
+                &mut self.oxidizer_pa,
+                OXIDIZER_TANK_PA,
+                self.oxidizer_used_kg,
+                OXIDIZER_FULL_FLOW_KGPS * stage_burn_s,
+            ),
+            (
+                &mut self.fuel_pa,
+                FUEL_TANK_PA,
+                self.fuel_used_kg,
+                FUEL_FULL_FLOW_KGPS * stage_burn_s,
+            ),
+        ] {
+            let used = (used_kg / capacity_kg.max(f64::EPSILON)).min(1.0);
+            let target_pa = full_pa * (1.0 - DEPLETION_DROP * used);
+            *pressure_pa += (target_pa - *pressure_pa) * regulate;
+        }
+
+        self.surge *= (-time_step_s / SURGE_TAU_S).exp();
+        (
+            self.oxidizer_pa * (1.0 + self.surge),
+            self.fuel_pa * (1.0 + self.surge),
+        )
+    }
+}
@@ -0,0 +1,191 @@
+use crate::error::{Result, TelemetryError};
+use rand::Rng;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use tracing::info;
+
+use super::script::ScenarioScript;
+
+// Mixed into the run seed so fuzzing leaves the sensor noise stream untouched
+const FUZZ_SEED_SALT: u64 = 0xF022_5EED;
+
+// Where the mission profile moves from one phase to the next, as a fraction of the run:
+// lift off, max Q, main ascent, staging and orbital insertion
+const NOMINAL_PHASE_ENDS: [f64; 4] = [0.05, 0.15, 0.40, 0.55];
+
+// Faults land between these fractions of the run so they aren't cut off at either end
+const FAULT_WINDOW: (f64, f64) = (0.05, 0.9);
+
+// Fields a fuzzed bias, dropout or sag fault can hit
+const FAULT_FIELDS: [&str; 7] = [
+    "fuel_pressure_pa",
+    "oxidizer_pressure_pa",
+    "chamber_pressure_pa",
+    "chamber_temperature_k",
+    "turbo_pump_rpm",
+    "fuel_flow_rate_kgps",
+    "nozzle_temperature_k",
+];
+
+// Limits the seed derived parameters are picked within
+#[derive(Debug, Clone, Copy)]
+pub struct FuzzBounds {
+    // How far each phase boundary can move, as a fraction of the phase next to it
+    pub phase_jitter: f64,
+    pub max_faults: usize,
+    // Multiples of the standard sensor noise
+    pub noise_scale_min: f64,
+    pub noise_scale_max: f64,
+}
+
+impl Default for FuzzBounds {
+    fn default() -> Self {
+        Self {
+            phase_jitter: 0.25,
+            max_faults: 3,
+            noise_scale_min: 0.5,
+            noise_scale_max: 3.0,
+        }
+    }
+}
+
+// Maps the run's progress onto the nominal mission profile, so phases can start early or late
+// without touching the physics of each phase
+#[derive(Debug, Clone, Copy)]
+pub(super) struct PhaseTiming {
+    phase_ends: [f64; 4],
+}
+
+impl PhaseTiming {
+    // Piecewise linear between the shifted and nominal phase boundaries
+    pub(super) fn nominal_progress(&self, progress: f64) -> f64 {
+        let mut from_start = 0.0;
+        let mut to_start = 0.0;
+        for (from_end, to_end) in self.phase_ends.iter().zip(NOMINAL_PHASE_ENDS) {
+            if progress < *from_end {
+                return to_start
+                    + (progress - from_start) / (from_end - from_start) * (to_end - to_start);
+            }
+            from_start = *from_end;
+            to_start = to_end;
+        }
+        to_start + (progress - from_start) / (1.0 - from_start) * (1.0 - to_start)
+    }
+
+    // Inverse of `nominal_progress`, where in the run a point of the nominal profile lands
+    pub(super) fn actual_progress(&self, nominal: f64) -> f64 {
+        let mut from_start = 0.0;
+        let mut to_start = 0.0;
+        for (from_end, to_end) in self.phase_ends.iter().zip(NOMINAL_PHASE_ENDS) {
+            if nominal < to_end {
+                return from_start
+                    + (nominal - to_start) / (to_end - to_start) * (from_end - from_start);
+            }
+            from_start = *from_end;
+            to_start = to_end;
+        }
+        from_start + (nominal - to_start) / (1.0 - to_start) * (1.0 - from_start)
+    }
+}
+
+// Randomized but reproducible mission for fuzz style pipeline testing. Phase timings, noise
+// levels and injected faults all come from the seed, so each seed flies a different mission that
+// can be generated again
+#[derive(Debug)]
+pub struct Fuzz {
+    pub(super) timing: PhaseTiming,
+    pub(super) noise_scale: f64,
+    pub(super) faults: ScenarioScript,
+}
+
+impl Fuzz {
+    pub fn new(bounds: FuzzBounds, seed: u64, duration_s: usize) -> Result<Self> {
+        if !(0.0..0.5).contains(&bounds.phase_jitter) {
+            return Err(TelemetryError::config(
+                "Fuzz phase jitter must be at least 0 and below 0.5",
+            ));
+        }
+        if bounds.noise_scale_min <= 0.0 || bounds.noise_scale_min > bounds.noise_scale_max {
+            return Err(TelemetryError::config(
+                "Fuzz noise scale needs 0 < min <= max",
+            ));
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed ^ FUZZ_SEED_SALT);
+
+        let mut phase_ends = NOMINAL_PHASE_ENDS;
+        for (idx, end) in phase_ends.iter_mut().enumerate() {
+            let previous = idx.checked_sub(1).map_or(0.0, |i| NOMINAL_PHASE_ENDS[i]);
+            let next = NOMINAL_PHASE_ENDS.get(idx + 1).copied().unwrap_or(1.0);
+            let earliest = *end - bounds.phase_jitter * (*end - previous);
+            let latest = *end + bounds.phase_jitter * (next - *end);
+            *end = rng.gen_range(earliest..=latest);
+        }
+        let timing = PhaseTiming { phase_ends };
+
+        let noise_scale = rng.gen_range(bounds.noise_scale_min..=bounds.noise_scale_max);
+
+        let fault_count = rng.gen_range(0..=bounds.max_faults);
+        let duration_ms = duration_s as f64 * 1000.0;
+        let script: Vec<String> = (0..fault_count)
+            .flat_map(|_| random_fault(&mut rng, duration_ms))
+            .collect();
+        let faults = ScenarioScript::parse(&script.join("\n"))?;
+
+        info!(
+            "Fuzzed phase ends at {} of the run",
+            phase_ends
+                .map(|end| format!("{:.1}%", end * 100.0))
+                .join(", ")
+        );
+        info!("Fuzzed noise at {:.2}x the standard level", noise_scale);
+        for statement in &script {
+            info!("Fuzzed fault: {}", statement);
+        }
+
+        Ok(Self {
+            timing,
+            noise_scale,
+            faults,
+        })
+    }
+}
+
+// Script statements for one fault, a log line labelling it first
+fn random_fault(rng: &mut StdRng, duration_ms: f64) -> Vec<String> {
+    let start_ms = (rng.gen_range(FAULT_WINDOW.0..FAULT_WINDOW.1) * duration_ms) as u64;
+    let length_ms = (rng.gen_range(0.01..0.1) * duration_ms).max(1_000.0) as u64;
+    let window = format!("{start_ms}ms..{}ms", start_ms + length_ms);
+    let field = FAULT_FIELDS.choose(rng).expect("Fault fields aren't empty");
+
+    let (label, adjustment) = match rng.gen_range(0..4) {
+        0 => {
+            let percent = rng.gen_range(2.0..15.0) * if rng.gen_bool(0.5) { 1.0 } else { -1.0 };
+            (
+                format!("{field} bias {percent:+.1}%"),
+                format!("set {field} bias {percent:+.1}%"),
+            )
+        }
+        1 => (format!("{field} dropout"), format!("set {field} value 0")),
+        2 => {
+            let factor = rng.gen_range(0.5..0.9);
+            (
+                format!("{field} sag to {factor:.2}x"),
+                format!("set {field} scale {factor:.2}"),
+            )
+        }
+        _ => {
+            let factor = rng.gen_range(3.0..6.0);
+            (
+                format!("vibration spike {factor:.1}x"),
+                format!("set vibration_z_g scale {factor:.1}"),
+            )
+        }
+    };
+
+    vec![
+        format!("at {start_ms}ms log Fuzz fault: {label}"),
+        format!("at {window} {adjustment}"),
+    ]
+}
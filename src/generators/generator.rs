@@ -1,16 +1,116 @@
+use crate::error::{Result, TelemetryError};
 use crate::models::{
-    SensorEnum, SensorValue, TelemetryConfig, TelemetryDataset, TelemetryReading, TimestampJitter,
+    CaptureWindow, ChannelState, MIN_CHUNK_READINGS, MissionPhase, SensorEnum, SensorValue,
+    TelemetryConfig, TelemetryDataset, TelemetryReading, TimestampJitter,
 };
 use chrono::{DateTime, Duration, Utc};
 use indicatif::{ProgressBar, ProgressStyle};
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
-use rand_distr::{Distribution, Normal};
-use tracing::{error, info, instrument, warn};
+use rand_distr::Normal;
+use std::ops::ControlFlow;
+use tracing::{info, instrument, warn};
+
+use super::cancel::CancelToken;
+use super::chaos::{Chaos, ChaosLevel};
+use super::commanding::CommandLink;
+use super::cruise::Cruise;
+use super::engine::EngineUnit;
+use super::fanout::SensorFanout;
+use super::feed::FeedState;
+use super::fuzz::{Fuzz, PhaseTiming};
+use super::health::{HealthMonitor, HealthTransition};
+use super::imu::{GYRO_NOISE_DPS, ImuErrors, MAGNETOMETER_NOISE_UT};
+use super::limits::{LimitAlert, LimitMonitor};
+use super::noise::NoiseSource;
+use super::observer::ChunkObserver;
+use super::periodic::PeriodicPattern;
+use super::profile::FlightProfile;
+use super::redundancy::RedundantPairs;
+use super::script::{HookEffect, ScenarioScript};
+use super::shape::{SensorShape, ValueShapes};
+use super::summary::{FlightEvent, FlightEventKind, FlightSummary};
+use super::thermal::ThermalState;
+use super::vehicle::{Propulsion, STANDARD_GRAVITY_MPS2, thrust_n};
+use super::wind::{Wind, WindEffect, WindLayer};
+
+// Counter channel rates
+const FRAME_RETRANSMIT_CHANCE: f64 = 0.01;
+const VALVE_ACTUATIONS_PER_S: f64 = 2.0;
+
+// GNSS receiver reporting at 5 Hz
+const GNSS_UPDATE_MS: u64 = 200;
+const GNSS_MAX_SATELLITES: u64 = 12;
+const GNSS_BEST_HDOP: f64 = 0.8;
+// Receivers commonly report 99.99 without a fix
+const GNSS_NO_FIX_HDOP: f64 = 99.99;
+
+// Roll program autopilot. Rate commanded per degree of roll left to go, and how quickly the
+// vehicle picks up the commanded rate
+const ROLL_GAIN_PER_S: f64 = 0.5;
+const ROLL_RATE_TAU_S: f64 = 0.5;
+// Yaw the vehicle picks up per deg/s of roll, and how fast the autopilot settles it back out
+const ROLL_YAW_COUPLING_S: f64 = 0.05;
+const YAW_SETTLE_TAU_S: f64 = 1.0;
+
+// Heater on for 30 seconds out of every 2 minutes
+const HEATER_PERIOD_MS: u64 = 120_000;
+const HEATER_ON_MS: u64 = 30_000;
 
 pub struct TelemetryGenerator {
     config: TelemetryConfig,
     rng: StdRng,
+    noise: NoiseSource,
+    script: Option<ScenarioScript>,
+    chaos: Option<Chaos>,
+    cruise: Option<Cruise>,
+    periodic: Vec<PeriodicPattern>,
+    fanout: Option<SensorFanout>,
+    redundancy: Option<RedundantPairs>,
+    commands: Option<CommandLink>,
+    shapes: Option<ValueShapes>,
+    imu: ImuErrors,
+    engine: EngineUnit,
+    wind: Option<Wind>,
+    health: Option<HealthMonitor>,
+    limits: Option<LimitMonitor>,
+    summary: FlightSummary,
+    // Phases flown, the default rocket unless given a profile file
+    profile: FlightProfile,
+    // Fuzzed phase timings. None flies the nominal profile
+    timing: Option<PhaseTiming>,
+    noise_scale: f64,
+    // Output period set by a `rate` hook. None outputs every step
+    output_period_nanos: Option<u64>,
+    // Windows marked by `capture` hooks during the run
+    marked: Vec<CaptureWindow>,
+    observers: Vec<Box<dyn ChunkObserver>>,
+    cancel: Option<CancelToken>,
+}
+
+// Sensor noise levels
+#[derive(Debug, Clone, Copy)]
+struct NoiseDistributions {
+    pressure: Normal<f64>,
+    temperature: Normal<f64>,
+    flow_rate: Normal<f64>,
+    vibration: Normal<f64>,
+    altitude: Normal<f64>,
+    acceleration: Normal<f64>,
+}
+
+impl NoiseDistributions {
+    // `scale` multiplies every standard deviation. 1 is the standard noise
+    fn new(scale: f64) -> Self {
+        Self {
+            pressure: Normal::new(0.0, 1000.0 * scale).unwrap(),
+            temperature: Normal::new(0.0, 1.0 * scale).unwrap(),
+            flow_rate: Normal::new(0.0, 0.1 * scale).unwrap(),
+            vibration: Normal::new(0.0, 0.01 * scale).unwrap(),
+            altitude: Normal::new(0.0, 0.01 * scale).unwrap(),
+            acceleration: Normal::new(0.0, 0.05 * scale).unwrap(),
+        }
+    }
 }
 
 impl TelemetryGenerator {
@@ -24,25 +124,266 @@ impl TelemetryGenerator {
         info!("Random seed would be: {}", random_seed);
         info!("Seeding RNG with {}", config.seed);
         let rng = StdRng::seed_from_u64(config.seed);
-        Self { config, rng }
+        if config.batch_noise {
+            info!("Sampling noise in batches");
+        }
+        let noise = NoiseSource::new(config.batch_noise);
+        let fanout = (config.sensor_instances > 1)
+            .then(|| SensorFanout::new(config.sensor_instances, config.seed));
+        let redundancy = (!config.redundant_sensors.is_empty()).then(|| {
+            RedundantPairs::new(
+                &config.redundant_sensors,
+                config.redundant_drift_chance,
+                config.duration,
+                config.seed,
+            )
+        });
+        let commands = config.reports_commands().then(|| {
+            CommandLink::new(
+                config.command_rate_per_min,
+                config.command_ack_ms,
+                config.seed,
+            )
+        });
+        Self {
+            config,
+            rng,
+            noise,
+            script: None,
+            chaos: None,
+            cruise: None,
+            periodic: Vec::new(),
+            fanout,
+            redundancy,
+            commands,
+            shapes: None,
+            imu: ImuErrors::default(),
+            engine: EngineUnit::default(),
+            wind: None,
+            health: None,
+            limits: None,
+            summary: FlightSummary::default(),
+            profile: FlightProfile::default(),
+            timing: None,
+            noise_scale: 1.0,
+            output_period_nanos: None,
+            marked: Vec::new(),
+            observers: Vec::new(),
+            cancel: None,
+        }
+    }
+
+    // Adjust reported values and log events over the run. See `ScenarioScript` for the syntax
+    pub fn with_script(mut self, script: ScenarioScript) -> Self {
+        self.script = Some(script).filter(|script| !script.is_empty());
+        self
+    }
+
+    // Show every chunk to `observer` as it's generated, in the order observers were added. Runs
+    // from `generate` are handed over `MIN_CHUNK_READINGS` at a time when observed
+    pub fn with_observer(mut self, observer: impl ChunkObserver + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    // End the run early once `cancel` is cancelled. `generate` returns the readings so far and
+    // `generate_chunked` hands them over as a last chunk, so outputs close cleanly
+    pub fn with_cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    // Whether the run was cut short by its cancel token
+    pub fn cancelled(&self) -> bool {
+        self.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    // Fly the phases of a profile file instead of the default rocket. See `FlightProfile`
+    pub fn with_profile(mut self, profile: FlightProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    // Hold every sensor at a setpoint for the whole run instead of flying the mission
+    pub fn with_cruise(mut self, cruise: Cruise) -> Self {
+        self.cruise = Some(cruise);
+        self
+    }
+
+    // Layer repeating waves like daily thermal cycles onto the reported values
+    pub fn with_periodic(mut self, patterns: Vec<PeriodicPattern>) -> Self {
+        self.periodic.extend(patterns);
+        self
+    }
+
+    // Bias and scale factor errors on the raw gyro and magnetometer channels
+    pub fn with_imu_errors(mut self, imu: ImuErrors) -> Self {
+        self.imu = imu;
+        self
+    }
+
+    // Fly an engine off nominal, scaling the thrust, Isp, pressure, flow and pump readings
+    pub fn with_engine(mut self, engine: EngineUnit) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    // Fly through a wind profile with seeded gusts of `gust_intensity_mps` standard deviation
+    pub fn with_wind(mut self, layers: Vec<WindLayer>, gust_intensity_mps: f64) -> Self {
+        self.wind = Some(Wind::new(layers, gust_intensity_mps, self.config.seed));
+        self
+    }
+
+    // Drive the health status channel from rules over the reported state
+    pub fn with_health(mut self, health: HealthMonitor) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    // Raise alerts whenever reported values cross yellow or red limits
+    pub fn with_limits(mut self, limits: LimitMonitor) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    // Replace the physics of some sensors with fixed data shapes for storage codec testing
+    pub fn with_value_shapes(mut self, shapes: Vec<SensorShape>) -> Self {
+        self.shapes = Some(ValueShapes::new(shapes, self.config.seed)).filter(|s| !s.is_empty());
+        self
+    }
+
+    // Seed derived phase timings, noise level and faults. Call after `with_script`, the faults
+    // run alongside the script
+    pub fn with_fuzz(mut self, fuzz: Fuzz) -> Self {
+        self.timing = Some(fuzz.timing);
+        self.noise_scale = fuzz.noise_scale;
+        match &mut self.script {
+            Some(script) => script.extend(fuzz.faults),
+            None => self.script = Some(fuzz.faults).filter(|script| !script.is_empty()),
+        }
+        self
+    }
+
+    // Sprinkle seeded faults, gaps, duplicates and out of order readings over the run
+    pub fn with_chaos(mut self, level: ChaosLevel) -> Self {
+        self.chaos = Some(Chaos::new(
+            level,
+            self.config.seed,
+            self.config.sample_rate_hz,
+        ));
+        self
+    }
+
+    // Max Q, burnout velocity and anomalies of the run generated so far
+    pub fn flight_summary(&self) -> &FlightSummary {
+        &self.summary
+    }
+
+    // Health status changes of the last run. Empty without health rules
+    pub fn health_transitions(&self) -> &[HealthTransition] {
+        self.health
+            .as_ref()
+            .map_or(&[], |health| health.transitions())
+    }
+
+    // Limit exceedances of the last run. Empty without limits
+    pub fn limit_alerts(&self) -> &[LimitAlert] {
+        self.limits.as_ref().map_or(&[], |limits| limits.alerts())
+    }
+
+    // Windows `capture` hooks marked during the last run
+    pub fn marked_windows(&self) -> &[CaptureWindow] {
+        &self.marked
+    }
+
+    // Staging and fault times of the run about to be generated, in time order
+    pub fn events(&self) -> Vec<FlightEvent> {
+        let mut events = Vec::new();
+        if let Some(staging) = self
+            .profile
+            .staging_progress()
+            .filter(|_| self.cruise.is_none())
+        {
+            let progress = self
+                .timing
+                .map_or(staging, |timing| timing.actual_progress(staging));
+            let step = (progress * self.config.get_total_readings() as f64).ceil() as usize;
+            let offset_nanos = self.config.sample_rate_hz.offset_nanos(step);
+            events.push(FlightEvent {
+                time_since_launch_ms: (offset_nanos + 500_000) / 1_000_000,
+                kind: FlightEventKind::Staging,
+                label: "stage separation".to_string(),
+            });
+        }
+        if let Some(script) = &self.script {
+            events.extend(
+                script
+                    .adjustment_starts()
+                    .into_iter()
+                    .map(|(start_ms, field)| FlightEvent {
+                        time_since_launch_ms: start_ms,
+                        kind: FlightEventKind::Anomaly,
+                        label: format!("{field} fault"),
+                    }),
+            );
+        }
+        events.sort_by_key(|event| event.time_since_launch_ms);
+        events
     }
 
     #[instrument(skip(self), name = "generate")]
-    pub fn generate(&mut self, disable_progress: bool) -> TelemetryDataset {
+    pub fn generate(&mut self, disable_progress: bool) -> Result<TelemetryDataset> {
         info!("Inside generate function");
-        let launch_time = Utc::now();
+        let mut all_readings: Vec<TelemetryReading> =
+            Vec::with_capacity(self.config.get_total_points());
+
+        // Single chunk holding every reading, unless observers want to follow along
+        let chunk_readings = if self.observers.is_empty() {
+            usize::MAX
+        } else {
+            MIN_CHUNK_READINGS
+        };
+        let launch_time = self.generate_chunked(disable_progress, chunk_readings, |chunk| {
+            all_readings.append(&mut chunk.readings);
+            Ok(ControlFlow::Continue(()))
+        })?;
+
+        Ok(TelemetryDataset {
+            readings: all_readings,
+            config: self.config.clone(),
+            launch_time,
+            // base_timestamps,
+        })
+    }
+
+    // Generate readings and hand them to `on_chunk` every `chunk_readings` readings, so the
+    // whole run never has to sit in memory. The chunk buffer is cleared and reused after the
+    // callback, so take the readings out of it to keep them. The callback returns
+    // `ControlFlow::Break` to stop the run early. Observers see each chunk first. The run ends once
+    // `max_rows` readings are out, part way through a timestep if need be. Returns the launch time
+    // of the run
+    #[instrument(skip(self, on_chunk), name = "generate_chunked")]
+    pub fn generate_chunked<F, E>(
+        &mut self,
+        disable_progress: bool,
+        chunk_readings: usize,
+        mut on_chunk: F,
+    ) -> Result<DateTime<Utc>, E>
+    where
+        F: FnMut(&mut TelemetryDataset) -> Result<ControlFlow<()>, E>,
+        E: From<TelemetryError>,
+    {
+        info!("Inside generate chunked function");
+        let launch_time = self.config.launch_time.unwrap_or_else(Utc::now);
+        info!("Launch time is {}", launch_time);
         let total_readings: usize = self.config.get_total_readings();
-        let sensors: usize = SensorEnum::number_of_sensors();
-        let total_points: usize = total_readings * sensors;
+        let sensors: usize = self.config.readings_per_step();
+        let total_points: usize = total_readings.saturating_mul(sensors);
+        let max_rows = self.config.max_rows.unwrap_or(usize::MAX);
 
-        if total_points == 0 {
+        if total_points == 0 || max_rows == 0 {
             warn!("No data points to generate! Check the configuration. Returning empty dataset.");
-            return TelemetryDataset {
-                readings: Vec::new(),
-                config: self.config.clone(),
-                launch_time,
-                // base_timestamps: Vec::new(),
-            };
+            return Ok(launch_time);
         }
 
         // Setup Progress Bar option
@@ -60,11 +401,22 @@ impl TelemetryGenerator {
         };
 
         // Initialize the sim state. todo move to Struct itself. Jason
-        let mut sim_state = SimulationState::initialize();
+        let mut sim_state = match &self.cruise {
+            Some(cruise) => cruise.state(),
+            None => SimulationState::initialize(),
+        };
 
-        // Generate all readings
-        let mut all_readings: Vec<TelemetryReading> = Vec::with_capacity(total_points);
-        let time_step_s = 1.0 / self.config.sample_rate_hz as f64;
+        // Buffer for the current chunk of readings, reused between flushes
+        let chunk_capacity = total_points
+            .min(max_rows)
+            .min(chunk_readings.saturating_add(sensors));
+        let mut chunk = TelemetryDataset {
+            readings: Vec::with_capacity(chunk_capacity),
+            config: self.config.clone(),
+            launch_time,
+        };
+        let mut generated_readings: usize = 0;
+        let time_step_s = self.config.sample_rate_hz.period_s();
         info!(
             "Time step size is: {:6.4} s or {:6.4} ms",
             time_step_s,
@@ -73,110 +425,364 @@ impl TelemetryGenerator {
         info!("\n!Verify if you like the above formating dude!");
 
         // initialize noise distributions
-        let pressure_noise = Normal::new(0.0, 1000.0).unwrap();
-        let temperature_noise = Normal::new(0.0, 1.0).unwrap();
-        let flow_rate_noise = Normal::new(0.0, 0.1).unwrap();
-        let vibration_noise = Normal::new(0.0, 0.01).unwrap();
-        let altitude_noise = Normal::new(0.0, 0.01).unwrap();
+        let noise = NoiseDistributions::new(self.noise_scale);
 
         // Create timestamp jitterer
         let timestamp_jitter = TimestampJitter::new(self.config.timestamp_jitter);
 
+        if self.config.physics_step_ms == Some(0) {
+            return Err(TelemetryError::config("The physics step has to be at least 1ms").into());
+        }
+        // Flight physics on its own clock. Cruise holds the state steady so has nothing to step
+        let mut physics = self
+            .config
+            .physics_step_ms
+            .filter(|_| self.cruise.is_none())
+            .map(|step_ms| {
+                let total_steps = (self.config.duration as u64 * 1000)
+                    .div_ceil(step_ms)
+                    .max(1);
+                info!(
+                    "Integrating physics every {} ms, {} steps over the run",
+                    step_ms, total_steps
+                );
+                PhysicsClock::new(&sim_state, step_ms, total_steps as usize)
+            });
+
+        let mut previous_time_since_launch_ms: u64 = 0;
+        let mut stopped_early = false;
+        let mut reached_max_rows = false;
+        // Offset of the next step to output when a `rate` hook slowed the output down
+        let mut next_output_nanos: u64 = 0;
+
         // Store base timestamps for reference without jitter if needed
         // let mut base_timestamps: Vec<DateTime<Utc>> = Vec::with_capacity(total_points);
 
         // Loop through each sensor reading time
         for i in 0..total_readings {
+            if self.cancelled() {
+                break;
+            }
             // Update progress bar every 1000 readings
-            if let Some(pb) = &progress {
-                if i % 1000 == 0 {
-                    pb.set_position(i as u64);
-                }
+            if let Some(pb) = &progress
+                && i % 1000 == 0
+            {
+                pb.set_position(i as u64);
             }
 
-            // Calculate base timestamp for this data point
-            let base_timestamp_to_jitter: DateTime<Utc> =
-                launch_time + Duration::milliseconds(sim_state.time_since_launch_ms as i64);
+            // Calculate base timestamp for this data point from its exact offset
+            let offset_nanos = self.config.sample_rate_hz.offset_nanos(i);
+            sim_state.time_since_launch_ms = (offset_nanos + 500_000) / 1_000_000;
+            let base_timestamp_to_jitter: DateTime<Utc> = launch_time
+                .checked_add_signed(Duration::nanoseconds(offset_nanos as i64))
+                .ok_or_else(|| {
+                    TelemetryError::GenerationError(format!(
+                        "Timestamp {offset_nanos} ns after a launch at {launch_time} is out of range"
+                    ))
+                })?;
             // base_timestamps.push(base_timestamp);
 
-            // Generate readings for all sensors with jittered timestamps
-            let new_readings: Vec<TelemetryReading> = self.generate_readings_from_sim_state(
-                &mut sim_state,
-                base_timestamp_to_jitter,
-                pressure_noise,
-                temperature_noise,
-                flow_rate_noise,
-                vibration_noise,
-                altitude_noise,
-                &timestamp_jitter,
+            if let Some(physics) = &mut physics {
+                physics.advance_to(offset_nanos, |state, step_s, step, total_steps| {
+                    self.update_simulation_state(state, step_s, step, total_steps)
+                });
+                physics.interpolate(&mut sim_state, offset_nanos);
+            }
+
+            // Hooks look at the true state, before anything changes what's reported
+            let hook_effects = match &mut self.script {
+                Some(script) => script.trigger_hooks(&sim_state, sim_state.time_since_launch_ms),
+                None => Vec::new(),
+            };
+            for effect in hook_effects {
+                match effect {
+                    HookEffect::Rate(rate) => {
+                        let period_nanos = rate.offset_nanos(1);
+                        // Hooks can only slow the output down
+                        self.output_period_nanos = (period_nanos
+                            > self.config.sample_rate_hz.offset_nanos(1))
+                        .then_some(period_nanos);
+                        next_output_nanos = offset_nanos;
+                        info!("Output rate is now {rate} Hz");
+                    }
+                    HookEffect::Capture(window) => self.marked.push(window),
+                }
+            }
+
+            // Scripted adjustments and periodic patterns only change what the sensors report
+            let mut scripted_state = self.script.as_ref().map(|script| {
+                let previous_ms = i.checked_sub(1).map(|_| previous_time_since_launch_ms);
+                script.fire_logs(previous_ms, sim_state.time_since_launch_ms);
+                self.summary.anomalies +=
+                    script.started_adjustments(previous_ms, sim_state.time_since_launch_ms);
+                script.apply(&sim_state, sim_state.time_since_launch_ms)
+            });
+            self.summary.record(
+                sim_state.time_since_launch_ms,
+                sim_state.altitude_m,
+                sim_state.velocity_mps,
+                sim_state.thrust_n,
             );
+            previous_time_since_launch_ms = sim_state.time_since_launch_ms;
+
+            if !self.periodic.is_empty() {
+                let reported_state = scripted_state.get_or_insert_with(|| sim_state.clone());
+                let epoch_s = base_timestamp_to_jitter.timestamp_micros() as f64 / 1e6;
+                for pattern in &self.periodic {
+                    pattern.apply(reported_state, epoch_s);
+                }
+            }
+
+            if let Some(wind) = &mut self.wind {
+                let effect = wind.step(
+                    sim_state.altitude_m,
+                    sim_state.velocity_mps,
+                    [sim_state.roll_deg, sim_state.pitch_deg, sim_state.yaw_deg],
+                    time_step_s,
+                );
+                scripted_state
+                    .get_or_insert_with(|| sim_state.clone())
+                    .apply_wind(&effect);
+            }
+
+            // Health watches what the sensors report, so scripted faults can trip it
+            if let Some(health) = &mut self.health {
+                let level = health.step(
+                    scripted_state.as_ref().unwrap_or(&sim_state),
+                    sim_state.time_since_launch_ms,
+                );
+                sim_state.health = level;
+                if let Some(reported_state) = &mut scripted_state {
+                    reported_state.health = level;
+                }
+            }
+
+            // Steps between outputs still fly, they just aren't reported
+            let output = match self.output_period_nanos {
+                None => true,
+                Some(_) if offset_nanos < next_output_nanos => false,
+                Some(period_nanos) => {
+                    next_output_nanos += period_nanos;
+                    true
+                }
+            };
+
+            // Generate readings for all sensors with jittered timestamps
+            if output {
+                let step_start = chunk.readings.len();
+                self.generate_readings_from_sim_state(
+                    scripted_state.as_mut().unwrap_or(&mut sim_state),
+                    base_timestamp_to_jitter,
+                    &noise,
+                    &timestamp_jitter,
+                    &mut chunk.readings,
+                );
+                if let Some(redundancy) = &mut self.redundancy {
+                    redundancy.apply(
+                        sim_state.time_since_launch_ms,
+                        &mut chunk.readings,
+                        step_start,
+                    );
+                }
+                if let Some(shapes) = &mut self.shapes {
+                    shapes.apply(
+                        sim_state.time_since_launch_ms,
+                        &mut chunk.readings,
+                        step_start,
+                    );
+                }
+                if let Some(commands) = &mut self.commands {
+                    commands.apply(
+                        launch_time,
+                        offset_nanos,
+                        sim_state.phase,
+                        &mut chunk.readings,
+                    );
+                }
+                if let Some(chaos) = &mut self.chaos {
+                    chaos.apply(
+                        sim_state.time_since_launch_ms,
+                        &mut chunk.readings,
+                        step_start,
+                    );
+                }
+                // Checked last so dropouts and spikes raise alerts like they would downstream
+                if let Some(limits) = &mut self.limits {
+                    limits.observe(&chunk.readings[step_start..]);
+                }
+            }
 
-            all_readings.extend(new_readings);
+            // Drop what's past max_rows and finish the run once the flush below is done
+            if generated_readings + chunk.readings.len() >= max_rows {
+                chunk.readings.truncate(max_rows - generated_readings);
+                reached_max_rows = true;
+            }
 
-            // update simulation state for next iteration
-            self.update_simulation_state(&mut sim_state, time_step_s, i, total_readings);
+            // Flush the chunk once it is full
+            if chunk.readings.len() >= chunk_readings {
+                generated_readings += chunk.readings.len();
+                // The callback still gets a chunk an observer stopped on, so nothing is lost
+                let observed = self.observe(&chunk)?;
+                if on_chunk(&mut chunk)?.is_break() || observed.is_break() {
+                    stopped_early = true;
+                    break;
+                }
+                chunk.readings.clear();
+            }
+            if reached_max_rows {
+                break;
+            }
+
+            // update simulation state for next iteration. Cruise holds it steady and the physics
+            // clock steps it on its own
+            if self.cruise.is_none() && physics.is_none() {
+                self.update_simulation_state(&mut sim_state, time_step_s, i, total_readings);
+            }
+            // Counters, discrete states and the thermal model keep going either way
+            self.update_counters(&mut sim_state, time_step_s);
+            Self::update_channel_states(&mut sim_state);
+            sim_state
+                .thermal
+                .step(sim_state.altitude_m, sim_state.velocity_mps, time_step_s);
+            // Tank pressures hold their setpoints in cruise
+            if self.cruise.is_none() {
+                (sim_state.oxidizer_pressure_pa, sim_state.fuel_pressure_pa) = sim_state.feed.step(
+                    sim_state.stage,
+                    sim_state.oxidizer_flow_rate_kgps,
+                    sim_state.fuel_flow_rate_kgps,
+                    self.stage_burn_s(),
+                    time_step_s,
+                );
+            }
+            self.update_gnss(&mut sim_state);
+        }
 
-            // calculate precise millisecond time based on current step
-            sim_state.time_since_launch_ms = (i as f64 * time_step_s * 1000.0).round() as u64;
+        // Flush whatever is left over
+        if !stopped_early && !chunk.readings.is_empty() {
+            generated_readings += chunk.readings.len();
+            // Last chunk so there is nothing left to stop
+            let _ = self.observe(&chunk)?;
+            let _ = on_chunk(&mut chunk)?;
+        }
+        for observer in &mut self.observers {
+            observer.on_finish(generated_readings)?;
         }
 
         // Finalize progress bar
         if let Some(pb) = progress {
-            pb.finish_with_message("Data generation complete");
+            if stopped_early || self.cancelled() {
+                pb.abandon_with_message("Data generation stopped early");
+            } else {
+                pb.finish_with_message("Data generation complete");
+            }
+        }
+        if let Some(commands) = &self.commands {
+            commands.log_summary();
+        }
+        if let Some(chaos) = &self.chaos {
+            chaos.log_summary();
+            self.summary.anomalies += chaos.faults();
+        }
+        if stopped_early {
+            warn!(
+                "Generation stopped early after {} readings",
+                generated_readings
+            );
+        } else if self.cancelled() {
+            warn!("Generation cancelled after {} readings", generated_readings);
+        } else if reached_max_rows {
+            info!("Generation stopped at max rows ({})", max_rows);
         }
 
         info!(
             "Telemetry dataset generated with {} readings",
-            all_readings.len()
+            generated_readings
         );
 
-        TelemetryDataset {
-            readings: all_readings,
-            config: self.config.clone(),
-            launch_time,
-            // base_timestamps,
+        Ok(launch_time)
+    }
+
+    // Hand a chunk to every observer, stopping at the first that wants the run to stop
+    fn observe(&mut self, chunk: &TelemetryDataset) -> Result<ControlFlow<()>> {
+        for observer in &mut self.observers {
+            if observer.on_chunk(chunk)?.is_break() {
+                return Ok(ControlFlow::Break(()));
+            }
         }
+        Ok(ControlFlow::Continue(()))
     }
 
     fn generate_readings_from_sim_state(
         &mut self,
         sim_state: &mut SimulationState,
         base_timestamp: DateTime<Utc>,
-        pressure_noise: Normal<f64>,
-        temperature_noise: Normal<f64>,
-        flow_rate_noise: Normal<f64>,
-        vibration_noise: Normal<f64>,
-        altitude_noise: Normal<f64>,
+        noise: &NoiseDistributions,
         timestamp_jitter: &TimestampJitter,
-    ) -> Vec<TelemetryReading> {
+        readings: &mut Vec<TelemetryReading>,
+    ) {
         // Todo: Too many lines here. Break into methods
         // For this simulation state we need to construct the telemetry records foreach sensor
-        let mut readings: Vec<TelemetryReading> =
-            Vec::with_capacity(SensorEnum::number_of_sensors());
+        // and append them to the chunk buffer
 
         // Pre-sample all noise values, so we only borrow self.rng once
-        let altitude_noise_val = altitude_noise.sample(&mut self.rng);
-        let pressure_noise_val = pressure_noise.sample(&mut self.rng);
-        let temperature_noise_val = temperature_noise.sample(&mut self.rng);
-        let flow_rate_noise_val = flow_rate_noise.sample(&mut self.rng);
-        let vibration_noise_val_x = vibration_noise.sample(&mut self.rng);
-        let vibration_noise_val_y = vibration_noise.sample(&mut self.rng);
-        let vibration_noise_val_z = vibration_noise.sample(&mut self.rng);
-
-        let turbo_pump_rpm_noise = self.rng.gen_range(-50.0..50.0);
-        let thrust_n_noise = self.rng.gen_range(-10.0..100.0);
-        let specific_impulse_noise = self.rng.gen_range(-0.5..0.5);
-        let nozzle_temperature_noise = temperature_noise.sample(&mut self.rng) * 2.0;
-        let roll_angle_noise = self.rng.gen_range(-0.5..0.5);
-        let pitch_angle_noise = self.rng.gen_range(-0.5..0.5);
-        let yaw_angle_noise = self.rng.gen_range(-0.5..0.5);
-        let vibration_freq_noise = self.rng.gen_range(-5.0..5.0);
-
-        // Add readings foreach sensor type
-        let sensor_values = vec![
+        let rng = &mut self.rng;
+        let source = &mut self.noise;
+        let altitude_noise_val = noise.altitude.from_zscore(source.standard_normal(rng));
+        let acceleration_noise_val = noise.acceleration.from_zscore(source.standard_normal(rng));
+        let pressure_noise_val = noise.pressure.from_zscore(source.standard_normal(rng));
+        let temperature_noise_val = noise.temperature.from_zscore(source.standard_normal(rng));
+        let flow_rate_noise_val = noise.flow_rate.from_zscore(source.standard_normal(rng));
+        let vibration_noise_val_x = noise.vibration.from_zscore(source.standard_normal(rng));
+        let vibration_noise_val_y = noise.vibration.from_zscore(source.standard_normal(rng));
+        let vibration_noise_val_z = noise.vibration.from_zscore(source.standard_normal(rng));
+
+        let turbo_pump_rpm_noise = source.uniform(rng, -50.0..50.0);
+        let thrust_n_noise = source.uniform(rng, -10.0..100.0);
+        let specific_impulse_noise = source.uniform(rng, -0.5..0.5);
+        let nozzle_temperature_noise =
+            noise.temperature.from_zscore(source.standard_normal(rng)) * 2.0;
+        let roll_angle_noise = source.uniform(rng, -0.5..0.5);
+        let pitch_angle_noise = source.uniform(rng, -0.5..0.5);
+        let yaw_angle_noise = source.uniform(rng, -0.5..0.5);
+        let vibration_freq_noise = source.uniform(rng, -5.0..5.0);
+        let gyro_noise: [f64; 3] =
+            std::array::from_fn(|_| source.standard_normal(rng) * GYRO_NOISE_DPS);
+        let magnetometer_noise: [f64; 3] =
+            std::array::from_fn(|_| source.standard_normal(rng) * MAGNETOMETER_NOISE_UT);
+        // Each stage's engine has its own sensors, so its own noise, indexed by stage - 1
+        let stage_pressure_noise: [f64; 2] =
+            std::array::from_fn(|_| noise.pressure.from_zscore(source.standard_normal(rng)));
+        let stage_thrust_noise: [f64; 2] =
+            std::array::from_fn(|_| source.uniform(rng, -10.0..100.0));
+        let stage_turbo_pump_rpm_noise: [f64; 2] =
+            std::array::from_fn(|_| source.uniform(rng, -50.0..50.0));
+
+        let angles_deg = [sim_state.roll_deg, sim_state.pitch_deg, sim_state.yaw_deg];
+        let gyro = self.imu.gyro(
+            angles_deg,
+            [
+                sim_state.roll_rate_dps,
+                sim_state.pitch_rate_dps,
+                sim_state.yaw_rate_dps,
+            ],
+        );
+        let magnetometer = self.imu.magnetometer(angles_deg);
+        let engine = self.engine;
+
+        // The engine state belongs to whichever stage is burning, the other stage's engine reads
+        // only its noise
+        let chamber_pressure = sim_state.chamber_pressure_pa * engine.pressure_scale();
+        let thrust = sim_state.thrust_n * engine.thrust_scale;
+        let turbo_pump_rpm = sim_state.turbo_pump_rpm * engine.pump_rpm_scale();
+        let stage_engine = |stage: u64, value: f64| {
+            if sim_state.stage == stage { value } else { 0.0 }
+        };
+
+        // Add readings foreach sensor type. Fixed size array so nothing is allocated per step
+        let sensor_values = [
             (
                 SensorEnum::Acceleration,
-                SensorValue::Float(sim_state.acceleration_mps2),
+                SensorValue::Float(sim_state.acceleration_mps2 + acceleration_noise_val),
             ),
             (
                 SensorEnum::Altitude,
@@ -188,7 +794,7 @@ impl TelemetryGenerator {
             ),
             (
                 SensorEnum::ChamberPressure,
-                SensorValue::Float(sim_state.chamber_pressure_pa + pressure_noise_val * 0.5),
+                SensorValue::Float(chamber_pressure + pressure_noise_val * 0.5),
             ),
             (
                 SensorEnum::ChamberTemperature,
@@ -200,7 +806,9 @@ impl TelemetryGenerator {
             ),
             (
                 SensorEnum::OxidizerFlowRate,
-                SensorValue::Float(sim_state.oxidizer_flow_rate_kgps + flow_rate_noise_val),
+                SensorValue::Float(
+                    sim_state.oxidizer_flow_rate_kgps * engine.flow_scale() + flow_rate_noise_val,
+                ),
             ),
             (
                 SensorEnum::OxidizerTemperature,
@@ -212,7 +820,9 @@ impl TelemetryGenerator {
             ),
             (
                 SensorEnum::FuelFlowRate,
-                SensorValue::Float(sim_state.fuel_flow_rate_kgps + flow_rate_noise_val),
+                SensorValue::Float(
+                    sim_state.fuel_flow_rate_kgps * engine.flow_scale() + flow_rate_noise_val,
+                ),
             ),
             (
                 SensorEnum::FuelTemperature,
@@ -220,20 +830,66 @@ impl TelemetryGenerator {
             ),
             (
                 SensorEnum::TurboPumpRpm,
-                SensorValue::Float(sim_state.turbo_pump_rpm + turbo_pump_rpm_noise),
+                SensorValue::Float(turbo_pump_rpm + turbo_pump_rpm_noise),
             ),
             (
                 SensorEnum::Thrust,
-                SensorValue::Float(sim_state.thrust_n + thrust_n_noise),
+                SensorValue::Float(thrust + thrust_n_noise),
             ),
             (
                 SensorEnum::SpecificImpulse,
-                SensorValue::Float(sim_state.specific_impulse_s + specific_impulse_noise),
+                SensorValue::Float(
+                    sim_state.specific_impulse_s * engine.isp_scale + specific_impulse_noise,
+                ),
             ),
             (
                 SensorEnum::NozzleTemperature,
                 SensorValue::Float(sim_state.nozzle_temperature_k + nozzle_temperature_noise),
             ),
+            (
+                SensorEnum::ActiveStage,
+                SensorValue::UnsignedInt(sim_state.stage),
+            ),
+            (
+                SensorEnum::Stage1ChamberPressure,
+                SensorValue::Float(
+                    stage_engine(1, chamber_pressure) + stage_pressure_noise[0] * 0.5,
+                ),
+            ),
+            (
+                SensorEnum::Stage2ChamberPressure,
+                SensorValue::Float(
+                    stage_engine(2, chamber_pressure) + stage_pressure_noise[1] * 0.5,
+                ),
+            ),
+            (
+                SensorEnum::Stage1Thrust,
+                SensorValue::Float(stage_engine(1, thrust) + stage_thrust_noise[0]),
+            ),
+            (
+                SensorEnum::Stage2Thrust,
+                SensorValue::Float(stage_engine(2, thrust) + stage_thrust_noise[1]),
+            ),
+            (
+                SensorEnum::Stage1TurboPumpRpm,
+                SensorValue::Float(stage_engine(1, turbo_pump_rpm) + stage_turbo_pump_rpm_noise[0]),
+            ),
+            (
+                SensorEnum::Stage2TurboPumpRpm,
+                SensorValue::Float(stage_engine(2, turbo_pump_rpm) + stage_turbo_pump_rpm_noise[1]),
+            ),
+            (
+                SensorEnum::InterstageTemperature,
+                SensorValue::Float(sim_state.thermal.interstage_k + temperature_noise_val * 0.1),
+            ),
+            (
+                SensorEnum::AvionicsBayTemperature,
+                SensorValue::Float(sim_state.thermal.avionics_bay_k + temperature_noise_val * 0.1),
+            ),
+            (
+                SensorEnum::CopvTemperature,
+                SensorValue::Float(sim_state.thermal.copv_k + temperature_noise_val * 0.1),
+            ),
             (
                 SensorEnum::RollAngle,
                 SensorValue::Float(sim_state.roll_deg + roll_angle_noise),
@@ -266,6 +922,18 @@ impl TelemetryGenerator {
                 SensorEnum::Longitude,
                 SensorValue::Float(sim_state.longitude_deg + roll_angle_noise),
             ),
+            (
+                SensorEnum::GnssSatellites,
+                SensorValue::UnsignedInt(sim_state.gnss_satellites),
+            ),
+            (
+                SensorEnum::GnssHdop,
+                SensorValue::Float(sim_state.gnss_hdop),
+            ),
+            (
+                SensorEnum::GnssFixType,
+                SensorValue::State(sim_state.gnss_fix),
+            ),
             (
                 SensorEnum::VibrationX,
                 SensorValue::Float(sim_state.vibration_x_g + vibration_noise_val_x),
@@ -282,21 +950,197 @@ impl TelemetryGenerator {
                 SensorEnum::VibrationFreq,
                 SensorValue::Float(sim_state.vibration_freq_hz + vibration_freq_noise),
             ),
-            // (SensorEnum::HealthStatus, SensorValue::String(sim_state.health_status.clone())),
+            (
+                SensorEnum::GyroX,
+                SensorValue::Float(gyro[0] + gyro_noise[0]),
+            ),
+            (
+                SensorEnum::GyroY,
+                SensorValue::Float(gyro[1] + gyro_noise[1]),
+            ),
+            (
+                SensorEnum::GyroZ,
+                SensorValue::Float(gyro[2] + gyro_noise[2]),
+            ),
+            (
+                SensorEnum::MagnetometerX,
+                SensorValue::Float(magnetometer[0] + magnetometer_noise[0]),
+            ),
+            (
+                SensorEnum::MagnetometerY,
+                SensorValue::Float(magnetometer[1] + magnetometer_noise[1]),
+            ),
+            (
+                SensorEnum::MagnetometerZ,
+                SensorValue::Float(magnetometer[2] + magnetometer_noise[2]),
+            ),
+            (
+                SensorEnum::FramesTransmitted,
+                SensorValue::UnsignedInt(sim_state.frames_transmitted),
+            ),
+            (
+                SensorEnum::ValveActuations,
+                SensorValue::UnsignedInt(sim_state.valve_actuations),
+            ),
+            (
+                SensorEnum::MainValve,
+                SensorValue::State(sim_state.main_valve),
+            ),
+            (
+                SensorEnum::PyroStage,
+                SensorValue::State(sim_state.pyro_stage),
+            ),
+            (
+                SensorEnum::HeaterDuty,
+                SensorValue::State(sim_state.heater_duty),
+            ),
+            (
+                SensorEnum::HealthStatus,
+                SensorValue::State(sim_state.health),
+            ),
             // (SensorEnum::MissionPhase, SensorValue::String(sim_state.mission_phase.clone())),
         ];
 
         for (sensor_type, value) in sensor_values {
-            let jittered_timestamp = timestamp_jitter.apply(base_timestamp, &mut self.rng);
-            readings.push(TelemetryReading {
-                timestamp: jittered_timestamp,
-                time_since_launch_ms: sim_state.time_since_launch_ms,
-                sensor: sensor_type,
-                value,
-            });
+            // Sensors the schema evolution plan adds later don't report yet
+            if !self
+                .config
+                .is_reported(sensor_type, sim_state.time_since_launch_ms)
+            {
+                continue;
+            }
+            let Some(fanout) = &self.fanout else {
+                let jittered_timestamp = timestamp_jitter
+                    .apply_zscore(base_timestamp, self.noise.standard_normal(&mut self.rng));
+                readings.push(TelemetryReading::new(
+                    jittered_timestamp,
+                    sim_state.time_since_launch_ms,
+                    sim_state.phase,
+                    sensor_type,
+                    value,
+                ));
+                continue;
+            };
+
+            // Every instance is sampled on its own, so each gets its own jitter
+            for (instance, instance_value) in fanout.instances(&value) {
+                let jittered_timestamp = timestamp_jitter
+                    .apply_zscore(base_timestamp, self.noise.standard_normal(&mut self.rng));
+                readings.push(
+                    TelemetryReading::new(
+                        jittered_timestamp,
+                        sim_state.time_since_launch_ms,
+                        sim_state.phase,
+                        sensor_type,
+                        instance_value,
+                    )
+                    .with_instance(instance),
+                );
+            }
+        }
+    }
+
+    fn update_counters(&mut self, state: &mut SimulationState, time_step_s: f64) {
+        // One telemetry frame per timestep, plus the odd retransmit
+        state.frames_transmitted += 1;
+        if self.rng.gen_bool(FRAME_RETRANSMIT_CHANCE) {
+            state.frames_transmitted += 1;
+        }
+
+        // Valves only cycle while the engine is running
+        if state.chamber_pressure_pa > 0.0 {
+            let chance = (VALVE_ACTUATIONS_PER_S * time_step_s).min(1.0);
+            if self.rng.gen_bool(chance) {
+                state.valve_actuations += 1;
+                state.feed.valve_actuated();
+            }
+        }
+    }
+
+    fn update_channel_states(state: &mut SimulationState) {
+        let main_valve = if state.chamber_pressure_pa > 0.0 {
+            ChannelState::Open
+        } else {
+            ChannelState::Closed
+        };
+        if main_valve != state.main_valve {
+            state
+                .feed
+                .main_valve_moved(main_valve == ChannelState::Open);
+        }
+        state.main_valve = main_valve;
+
+        // Heater runs a fixed duty cycle
+        let in_period_ms = state.time_since_launch_ms % HEATER_PERIOD_MS;
+        state.heater_duty = if in_period_ms < HEATER_ON_MS {
+            ChannelState::On
+        } else {
+            ChannelState::Off
+        };
+    }
+
+    // Once clear of the pad the vehicle rolls onto its flight azimuth. Rolling kicks the yaw
+    // off a little, which the autopilot settles back out
+    fn update_roll_program(&self, state: &mut SimulationState, time_step_s: f64) {
+        if time_step_s <= 0.0 {
+            return;
+        }
+        let target_deg = if state.phase == MissionPhase::Prelaunch {
+            0.0
+        } else {
+            self.config.roll_program_deg
+        };
+        let max_rate_dps = self.config.roll_rate_dps.abs();
+        let rate_command =
+            (ROLL_GAIN_PER_S * (target_deg - state.roll_deg)).clamp(-max_rate_dps, max_rate_dps);
+        state.roll_rate_dps +=
+            (rate_command - state.roll_rate_dps) * (1.0 - (-time_step_s / ROLL_RATE_TAU_S).exp());
+        // Don't roll past the target when steps are long
+        let remaining_deg = target_deg - state.roll_deg;
+        let roll_step_deg = state.roll_rate_dps * time_step_s;
+        state.roll_deg += if roll_step_deg.signum() == remaining_deg.signum()
+            && roll_step_deg.abs() > remaining_deg.abs()
+        {
+            remaining_deg
+        } else {
+            roll_step_deg
+        };
+
+        let yaw_target_deg = ROLL_YAW_COUPLING_S * state.roll_rate_dps;
+        let previous_yaw_deg = state.yaw_deg;
+        state.yaw_deg +=
+            (yaw_target_deg - state.yaw_deg) * (1.0 - (-time_step_s / YAW_SETTLE_TAU_S).exp());
+        state.yaw_rate_dps = (state.yaw_deg - previous_yaw_deg) / time_step_s;
+    }
+
+    // Receiver loses satellites under high acceleration and vibration, worst at staging
+    fn update_gnss(&mut self, state: &mut SimulationState) {
+        if state.time_since_launch_ms < state.gnss_next_update_ms {
+            return;
         }
+        state.gnss_next_update_ms = state.time_since_launch_ms + GNSS_UPDATE_MS;
+
+        let dynamics = state.acceleration_mps2.abs() / 10.0 + state.vibration_z_g.abs() * 2.0;
+        let lost = (dynamics + self.rng.gen_range(-1.0..1.0)).round().max(0.0) as u64;
+        state.gnss_satellites = GNSS_MAX_SATELLITES.saturating_sub(lost);
+
+        state.gnss_fix = match state.gnss_satellites {
+            0..=2 => ChannelState::NoFix,
+            3 => ChannelState::Fix2d,
+            _ => ChannelState::Fix3d,
+        };
+        // Geometry gets worse fast as satellites drop out
+        state.gnss_hdop = match state.gnss_fix {
+            ChannelState::NoFix => GNSS_NO_FIX_HDOP,
+            _ => {
+                GNSS_BEST_HDOP * (GNSS_MAX_SATELLITES as f64 / state.gnss_satellites as f64).powi(2)
+            }
+        };
+    }
 
-        readings
+    // Seconds each stage's propellant is sized to last, as long as the first stage flies
+    fn stage_burn_s(&self) -> f64 {
+        self.config.duration as f64 * self.profile.staging_progress().unwrap_or(1.0)
     }
 
     fn update_simulation_state(
@@ -308,183 +1152,122 @@ impl TelemetryGenerator {
     ) {
         // Todo: Too many lines here. Break into methods
         let progress: f64 = idx as f64 / total_points as f64;
+        let progress = self
+            .timing
+            .map_or(progress, |timing| timing.nominal_progress(progress));
 
-        match progress {
-            p if p < 0.05 => {
-                // prelaunch and early lift off. (0-5%) of simulation
-                let throttle_up = (p / 0.05).min(1.0);
-                // debug!("Throttle up factor: {:.2} from p: {}", throttle_up, p);
-
-                // Engine start
-                state.chamber_pressure_pa = 5_000_000.0 * throttle_up; // 5 MPa max
-                state.chamber_temperature_k = 3500.0 * throttle_up; // 350
-                state.oxidizer_flow_rate_kgps = 250.0 * throttle_up; // 250 kg/s max
-                state.fuel_flow_rate_kgps = 50.0 * throttle_up; // 50 kg/s max
-                state.turbo_pump_rpm = 30_000.0 * throttle_up;
-                state.thrust_n = 1_000_000.0 * throttle_up; // 1 MN max
-                state.specific_impulse_s = 300.0 * throttle_up; // 300 s max
-                state.nozzle_temperature_k = 3500.0 * throttle_up;
-
-                state.acceleration_mps2 = if progress < 0.01 {
-                    0.0
-                } else {
-                    (progress - 0.01) / 0.04 * 15.0 // ramp to 15 m/s^2
-                };
-
-                // update pos based on acceleration
-                state.velocity_mps += state.acceleration_mps2 * time_step_s;
-                state.altitude_m += state.velocity_mps * time_step_s;
-
-                // Take off vibrations
-                state.vibration_x_g = 0.05 * self.rng.r#gen::<f64>();
-                state.vibration_y_g = 0.05 * self.rng.r#gen::<f64>();
-                state.vibration_z_g = 0.1 * self.rng.r#gen::<f64>();
-                state.vibration_freq_hz = 20.0 + 5.0 * self.rng.r#gen::<f64>();
-            }
-            p if p < 0.15 => {
-                // Max-Q (5-15%)
-                // Throttle down
-                let max_q = 1.0 - 0.2 * ((p - 0.05) / 0.10).clamp(0.0, 1.0); // .min(1.0).max(0.0);
-
-                state.chamber_pressure_pa = 5_000_000.0 * max_q;
-                state.thrust_n = 1_000_000.0 * max_q;
-                state.oxidizer_flow_rate_kgps = 250.0 * max_q;
-                state.fuel_flow_rate_kgps = 50.0 * max_q;
-
-                state.acceleration_mps2 = 15.0 * max_q;
-                state.velocity_mps += state.acceleration_mps2 * time_step_s;
-                state.altitude_m += state.velocity_mps * time_step_s;
-
-                // Start gravity turn and pitch over
-                state.pitch_deg = 90.0 - 15.0 * ((p - 0.05) / 0.10);
-                state.pitch_rate_dps = -0.3;
-
-                // Most vibrations here at max Q
-                state.vibration_x_g = 1.0 + (1.0 - max_q) * 2.0;
-                state.vibration_y_g = 1.0 + (1.0 - max_q) * 2.0;
-                state.vibration_z_g = 1.5 + (1.0 - max_q) * 3.0;
-                state.vibration_freq_hz = 80.0 + (1.0 - max_q) * 40.0;
-
-                state.nozzle_temperature_k = 1500.0 + ((p - 0.05) / 0.1) * 300.0;
-            }
-            p if p < 0.40 => {
-                // Main ascent (15-40%)
-                state.chamber_pressure_pa = 5_000_000.0;
-                state.thrust_n = 1_000_000.0;
-                state.oxidizer_flow_rate_kgps = 250.0;
-                state.fuel_flow_rate_kgps = 50.0;
-
-                // accelerate more as fuel is consumed. 1.5x
-                let acceleration_factor = 1.0 + ((p - 0.15) / 0.25) * 0.5;
-                state.acceleration_mps2 = 15.0 * acceleration_factor;
-
-                // update position
-                state.velocity_mps += state.acceleration_mps2 * time_step_s;
-                state.altitude_m += state.velocity_mps * time_step_s;
-
-                // Continue gravity turn
-                state.pitch_deg = 75.0 - 25.0 * ((p - 0.15) / 0.3);
-                state.pitch_rate_dps = -0.1;
-
-                // Decrease vibrations as atmosphere thins
-                let vib_factor = 1.0 - ((p - 0.15) / 0.3);
-                state.vibration_x_g = 0.5 * vib_factor;
-                state.vibration_y_g = 0.5 * vib_factor;
-                state.vibration_z_g = 0.75 * vib_factor;
-                state.vibration_freq_hz = 60.0;
-            }
-            p if p < 0.55 => {
-                // Stage separation and second stage ignition (40-55%)
-                let shutdown = 1.0 - ((p - 0.45) / 0.05).min(1.0);
-
-                state.chamber_pressure_pa = 5_000_000.0 * shutdown;
-                state.thrust_n = 1_000_000.0 * shutdown;
-                state.oxidizer_flow_rate_kgps = 250.0 * shutdown;
-                state.fuel_flow_rate_kgps = 50.0 * shutdown;
-                state.turbo_pump_rpm = 30_000.0 * shutdown;
-
-                if p > 0.5 && p < 0.52 {
-                    state.chamber_pressure_pa = 0.0;
-                    state.thrust_n = 0.0;
-                    state.oxidizer_flow_rate_kgps = 0.0;
-                    state.fuel_flow_rate_kgps = 0.0;
-                    state.turbo_pump_rpm = 0.0;
-                    state.acceleration_mps2 = -9.81; // falling now
-                }
+        let sample = self
+            .profile
+            .sample(progress, self.config.duration as f64, &mut self.rng);
+        state.phase = sample.phase;
+        if sample.stage != state.stage {
+            state.stage_propellant_used_kg = 0.0;
+        }
+        state.stage = sample.stage;
+        if let Some(engine) = sample.engine {
+            state.chamber_pressure_pa = engine.chamber_pressure_pa;
+            state.oxidizer_flow_rate_kgps = engine.oxidizer_flow_kgps;
+            state.fuel_flow_rate_kgps = engine.fuel_flow_kgps;
+            state.turbo_pump_target_rpm = engine.turbo_pump_rpm;
+            state.vacuum_thrust_n = engine.vacuum_thrust_n;
+            state.nozzle_exit_area_m2 = engine.exit_area_m2;
+        }
+        if let Some(chamber_temperature_k) = sample.chamber_temperature_k {
+            state.chamber_temperature_k = chamber_temperature_k;
+        }
+        if let Some(nozzle_temperature_k) = sample.nozzle_temperature_k {
+            state.nozzle_temperature_k = nozzle_temperature_k;
+        }
+        if let Some((pitch_deg, pitch_rate_dps)) = sample.pitch {
+            state.pitch_deg = pitch_deg;
+            state.pitch_rate_dps = pitch_rate_dps;
+        }
+        if let Some([x_g, y_g, z_g, freq_hz]) = sample.vibration {
+            state.vibration_x_g = x_g;
+            state.vibration_y_g = y_g;
+            state.vibration_z_g = z_g;
+            state.vibration_freq_hz = freq_hz;
+        }
 
-                if p > 0.5 && p < 0.51 {
-                    state.vibration_x_g = 3.0;
-                    state.vibration_y_g = 3.0;
-                    state.vibration_z_g = 5.0;
-                    state.vibration_freq_hz = 100.0;
-                } else {
-                    state.vibration_x_g = 0.5 * shutdown;
-                    state.vibration_y_g = 0.5 * shutdown;
-                    state.vibration_z_g = 0.75 * shutdown;
-                    state.vibration_freq_hz = 40.0 * shutdown;
-                }
-                // update positions
-                state.acceleration_mps2 = if p < 0.5 {
-                    20.0 * shutdown
-                } else if p < 0.52 {
-                    -9.81
-                } else {
-                    -9.81 + ((p - 0.52) / 0.03) * 15.0
-                };
-                state.velocity_mps += state.acceleration_mps2 * time_step_s;
-                state.altitude_m += state.velocity_mps * time_step_s;
+        // Pyros arm at lift off and fire at stage separation
+        state.pyro_stage = match state.phase {
+            MissionPhase::Prelaunch => ChannelState::Safe,
+            _ if self
+                .profile
+                .staging_progress()
+                .is_some_and(|staging| progress >= staging) =>
+            {
+                ChannelState::Fired
             }
-            p if p >= 0.55 => {
-                // Orbital insertion phase (55-100%)
-                let stage_time = (p - 0.55) / 0.45;
-                let startup = (stage_time / 20.0).min(1.0);
-
-                state.chamber_pressure_pa = 5_000_000.0 * startup;
-                state.chamber_temperature_k = 3500.0 * startup + 300.0;
-                state.oxidizer_flow_rate_kgps = 250.0 * startup;
-                state.fuel_flow_rate_kgps = 50.0 * startup;
-                state.turbo_pump_rpm = 30_000.0 * startup;
-                state.thrust_n = 2_000_000.0 * startup;
-                state.specific_impulse_s = 300.0 * startup;
-
-                state.acceleration_mps2 = 5.0 * startup;
-
-                if stage_time > 0.9 {
-                    let shutdown = 1.0 - ((stage_time - 0.9) / 0.1);
-                    state.chamber_pressure_pa *= shutdown;
-                    state.thrust_n *= shutdown;
-                    state.oxidizer_flow_rate_kgps *= shutdown;
-                    state.fuel_flow_rate_kgps *= shutdown;
-                    state.turbo_pump_rpm *= shutdown;
-                    state.acceleration_mps2 *= shutdown;
-                }
-
-                // update positions
-                state.velocity_mps += state.acceleration_mps2 * time_step_s;
-                state.altitude_m += state.velocity_mps * time_step_s;
+            _ => ChannelState::Armed,
+        };
+        self.update_roll_program(state, time_step_s);
 
-                // Low vibrations in space vacuum
-                state.vibration_x_g = 0.01 * startup;
-                state.vibration_y_g = 0.01 * startup;
-                state.vibration_z_g = 0.03 * startup;
-                state.vibration_freq_hz = 30.0 * startup;
+        // Burn propellant off the current stage
+        state.stage_propellant_used_kg += (state.oxidizer_flow_rate_kgps.max(0.0)
+            + state.fuel_flow_rate_kgps.max(0.0))
+            * time_step_s;
+        state.mass_kg = self.profile.mass_kg(
+            state.stage,
+            state.stage_propellant_used_kg,
+            self.stage_burn_s(),
+        );
 
-                state.pitch_deg = 50.0 - 40.0 * stage_time;
-            }
-            _ => {
-                // Landing and recovery (95-100%)
-                error!("Outside of p range");
-                todo!();
-            }
+        // Update altitude and velocity from the forces over this step
+        let timing = self.timing;
+        let profile = &self.profile;
+        let current = Propulsion {
+            vacuum_thrust_n: state.vacuum_thrust_n,
+            exit_area_m2: state.nozzle_exit_area_m2,
+            pitch_deg: state.pitch_deg,
+        };
+        let mass_kg = state.mass_kg;
+        let acceleration_at = |fraction: f64, altitude_m: f64, velocity_mps: f64| {
+            let progress = (idx as f64 + fraction) / total_points as f64;
+            let progress = timing.map_or(progress, |timing| timing.nominal_progress(progress));
+            profile.acceleration(progress, current, mass_kg, altitude_m, velocity_mps)
+        };
+        state.acceleration_mps2 = self.config.integrator.step(
+            &mut state.altitude_m,
+            &mut state.velocity_mps,
+            acceleration_at,
+            time_step_s,
+        );
+        // Back on the ground
+        if state.altitude_m < 0.0 {
+            state.altitude_m = 0.0;
+            state.velocity_mps = state.velocity_mps.max(0.0);
         }
 
         // Ensure physically realistic values
         state.chamber_pressure_pa = state.chamber_pressure_pa.max(0.0);
         state.chamber_temperature_k = state.chamber_temperature_k.max(273.0);
-        state.thrust_n = state.thrust_n.max(0.0);
         state.oxidizer_flow_rate_kgps = state.oxidizer_flow_rate_kgps.max(0.0);
         state.fuel_flow_rate_kgps = state.fuel_flow_rate_kgps.max(0.0);
-        state.turbo_pump_rpm = state.turbo_pump_rpm.max(1_000_000.0);
+        state.turbo_pump_target_rpm = state.turbo_pump_target_rpm.max(0.0);
+
+        state.thrust_n = thrust_n(
+            state.vacuum_thrust_n,
+            state.nozzle_exit_area_m2,
+            state.altitude_m,
+        );
+        state.specific_impulse_s = specific_impulse_s(
+            state.thrust_n,
+            state.oxidizer_flow_rate_kgps + state.fuel_flow_rate_kgps,
+        );
+
+        // The pump lags the commanded speed like a spinning mass, faster up than down
+        let spool_s = if state.turbo_pump_target_rpm > state.turbo_pump_rpm {
+            self.config.pump_spool_up_s
+        } else {
+            self.config.pump_spool_down_s
+        };
+        let spool = if spool_s > 0.0 {
+            1.0 - (-time_step_s / spool_s).exp()
+        } else {
+            1.0
+        };
+        state.turbo_pump_rpm += (state.turbo_pump_target_rpm - state.turbo_pump_rpm) * spool;
 
         // Update positions based on velocity and acceleration
         let distance_traveled_m = state.velocity_mps * time_step_s;
@@ -509,12 +1292,73 @@ impl TelemetryGenerator {
     }
 }
 
+// Isp in seconds, zero with nothing flowing
+fn specific_impulse_s(thrust_n: f64, mass_flow_kgps: f64) -> f64 {
+    if mass_flow_kgps > f64::EPSILON {
+        thrust_n / (STANDARD_GRAVITY_MPS2 * mass_flow_kgps)
+    } else {
+        0.0
+    }
+}
+
+// Steps the flight physics at a fixed timestep of its own, independent of the sample rate.
+// Keeps the states either side of the current sample so readings can be interpolated between
+// them, saving work at high rates and keeping the flight accurate at low ones
+struct PhysicsClock {
+    step_s: f64,
+    step_nanos: u64,
+    total_steps: usize,
+    // Index of the step `next` was integrated to
+    step: usize,
+    previous: SimulationState,
+    next: SimulationState,
+}
+
+impl PhysicsClock {
+    fn new(initial: &SimulationState, step_ms: u64, total_steps: usize) -> Self {
+        Self {
+            step_s: step_ms as f64 / 1000.0,
+            step_nanos: step_ms * 1_000_000,
+            total_steps,
+            step: 0,
+            previous: initial.clone(),
+            next: initial.clone(),
+        }
+    }
+
+    // Integrate until the next state is at or past `offset_nanos`
+    fn advance_to(
+        &mut self,
+        offset_nanos: u64,
+        mut update: impl FnMut(&mut SimulationState, f64, usize, usize),
+    ) {
+        while (self.step as u64) * self.step_nanos < offset_nanos {
+            self.previous.clone_from(&self.next);
+            update(&mut self.next, self.step_s, self.step, self.total_steps);
+            self.step += 1;
+        }
+    }
+
+    // Set the flight values of `state` to where the physics was at `offset_nanos`
+    fn interpolate(&self, state: &mut SimulationState, offset_nanos: u64) {
+        let next_nanos = self.step as u64 * self.step_nanos;
+        let frac = 1.0 - next_nanos.saturating_sub(offset_nanos) as f64 / self.step_nanos as f64;
+        state.interpolate_flight(&self.previous, &self.next, frac.clamp(0.0, 1.0));
+    }
+}
+
 #[derive(Debug, Clone)]
-struct SimulationState {
+pub(super) struct SimulationState {
     time_since_launch_ms: u64,
+    phase: MissionPhase,
+    // 1 until stage separation, then 2
+    stage: u64,
     altitude_m: f64,
     velocity_mps: f64,
     acceleration_mps2: f64,
+    mass_kg: f64,
+    // Propellant burnt from the current stage
+    stage_propellant_used_kg: f64,
     chamber_pressure_pa: f64,
     chamber_temperature_k: f64,
     oxidizer_flow_rate_kgps: f64,
@@ -524,7 +1368,13 @@ struct SimulationState {
     fuel_pressure_pa: f64,
     fuel_temperature_k: f64,
     turbo_pump_rpm: f64,
+    // Speed the phase commands, which `turbo_pump_rpm` spools towards
+    turbo_pump_target_rpm: f64,
     thrust_n: f64,
+    // Thrust before the ambient pressure on the nozzle exits, and their total area
+    vacuum_thrust_n: f64,
+    nozzle_exit_area_m2: f64,
+    // Follows from thrust and mass flow
     specific_impulse_s: f64,
     nozzle_temperature_k: f64,
     roll_deg: f64,
@@ -539,24 +1389,39 @@ struct SimulationState {
     vibration_y_g: f64,
     vibration_z_g: f64,
     vibration_freq_hz: f64,
+    thermal: ThermalState,
+    feed: FeedState,
+    frames_transmitted: u64,
+    valve_actuations: u64,
+    main_valve: ChannelState,
+    pyro_stage: ChannelState,
+    heater_duty: ChannelState,
+    health: ChannelState,
+    gnss_satellites: u64,
+    gnss_hdop: f64,
+    gnss_fix: ChannelState,
+    // When the receiver next reports a solution
+    gnss_next_update_ms: u64,
     // battery_voltage_v: f64,
     // battery_current_a: f64,
     // battery_temperature_c: f64,
     // power_consumption_w: f64,
     // cpu_usage_percent: f64,
     // memory_usage_mb: f64,
-    // health_status: String,
-    // mission_phase: String,
     // gyroscope_dps: f64,
 }
 
 impl SimulationState {
-    fn initialize() -> Self {
+    pub(super) fn initialize() -> Self {
         SimulationState {
             time_since_launch_ms: 0,
+            phase: MissionPhase::Prelaunch,
+            stage: 1,
             altitude_m: 0.0,
             velocity_mps: 0.0,
             acceleration_mps2: 0.0,
+            mass_kg: 0.0,
+            stage_propellant_used_kg: 0.0,
             chamber_pressure_pa: 0.0,
             chamber_temperature_k: 288.15,
             oxidizer_flow_rate_kgps: 0.0,
@@ -566,11 +1431,15 @@ impl SimulationState {
             fuel_pressure_pa: 101_325.0,
             fuel_temperature_k: 288.15,
             turbo_pump_rpm: 0.0,
+            turbo_pump_target_rpm: 0.0,
             thrust_n: 0.0,
+            vacuum_thrust_n: 0.0,
+            nozzle_exit_area_m2: 0.0,
             specific_impulse_s: 0.0,
             nozzle_temperature_k: 288.15,
             roll_deg: 0.0001,
-            pitch_deg: 0.0001,
+            // Standing on the pad
+            pitch_deg: 90.0,
             yaw_deg: 0.0001,
             roll_rate_dps: 0.0,
             pitch_rate_dps: 0.0,
@@ -581,15 +1450,142 @@ impl SimulationState {
             vibration_y_g: 0.0,
             vibration_z_g: 0.0,
             vibration_freq_hz: 0.0,
+            thermal: ThermalState::initialize(),
+            feed: FeedState::initialize(),
+            frames_transmitted: 0,
+            valve_actuations: 0,
+            main_valve: ChannelState::Closed,
+            pyro_stage: ChannelState::Safe,
+            heater_duty: ChannelState::Off,
+            health: ChannelState::Nominal,
+            gnss_satellites: GNSS_MAX_SATELLITES,
+            gnss_hdop: GNSS_BEST_HDOP,
+            gnss_fix: ChannelState::Fix3d,
+            gnss_next_update_ms: 0,
             // battery_voltage_v: 28.8, // Example nominal voltage
             // battery_current_a: 0.0,
             // battery_temperature_c: 25.0, // Room temperature (300k)
             // power_consumption_w: 0.0,
             // cpu_usage_percent: 5.0, // Idle CPU usage
             // memory_usage_mb: 100.0, // Example memory usage
-            // health_status: "OK".into(),
-            // mission_phase: "Pre-launch".into(),
             // gyroscope_dps: 0.0,
         }
     }
+
+    // Linearly interpolate what the flight physics integrates between two of its steps. Discrete
+    // values like the phase and stage hold the earlier step's until the later one is reached
+    fn interpolate_flight(&mut self, from: &Self, to: &Self, frac: f64) {
+        let lerp = |a: f64, b: f64| a + (b - a) * frac;
+        let discrete = if frac < 1.0 { from } else { to };
+        self.phase = discrete.phase;
+        self.stage = discrete.stage;
+        self.pyro_stage = discrete.pyro_stage;
+        self.altitude_m = lerp(from.altitude_m, to.altitude_m);
+        self.velocity_mps = lerp(from.velocity_mps, to.velocity_mps);
+        self.acceleration_mps2 = lerp(from.acceleration_mps2, to.acceleration_mps2);
+        self.chamber_pressure_pa = lerp(from.chamber_pressure_pa, to.chamber_pressure_pa);
+        self.chamber_temperature_k = lerp(from.chamber_temperature_k, to.chamber_temperature_k);
+        self.oxidizer_flow_rate_kgps =
+            lerp(from.oxidizer_flow_rate_kgps, to.oxidizer_flow_rate_kgps);
+        self.fuel_flow_rate_kgps = lerp(from.fuel_flow_rate_kgps, to.fuel_flow_rate_kgps);
+        self.turbo_pump_rpm = lerp(from.turbo_pump_rpm, to.turbo_pump_rpm);
+        self.thrust_n = lerp(from.thrust_n, to.thrust_n);
+        self.specific_impulse_s = specific_impulse_s(
+            self.thrust_n,
+            self.oxidizer_flow_rate_kgps + self.fuel_flow_rate_kgps,
+        );
+        self.nozzle_temperature_k = lerp(from.nozzle_temperature_k, to.nozzle_temperature_k);
+        self.pitch_deg = lerp(from.pitch_deg, to.pitch_deg);
+        self.pitch_rate_dps = lerp(from.pitch_rate_dps, to.pitch_rate_dps);
+        self.roll_deg = lerp(from.roll_deg, to.roll_deg);
+        self.roll_rate_dps = lerp(from.roll_rate_dps, to.roll_rate_dps);
+        self.yaw_deg = lerp(from.yaw_deg, to.yaw_deg);
+        self.yaw_rate_dps = lerp(from.yaw_rate_dps, to.yaw_rate_dps);
+        self.latitude_deg = lerp(from.latitude_deg, to.latitude_deg);
+        self.longitude_deg = lerp(from.longitude_deg, to.longitude_deg);
+        self.vibration_x_g = lerp(from.vibration_x_g, to.vibration_x_g);
+        self.vibration_y_g = lerp(from.vibration_y_g, to.vibration_y_g);
+        self.vibration_z_g = lerp(from.vibration_z_g, to.vibration_z_g);
+        self.vibration_freq_hz = lerp(from.vibration_freq_hz, to.vibration_freq_hz);
+    }
+
+    // Sustained burn in level flight used as the cruise mode setpoints
+    pub(super) fn cruise() -> Self {
+        SimulationState {
+            altitude_m: 10_000.0,
+            velocity_mps: 250.0,
+            chamber_pressure_pa: 5_000_000.0,
+            chamber_temperature_k: 3500.0,
+            oxidizer_flow_rate_kgps: 250.0,
+            fuel_flow_rate_kgps: 50.0,
+            turbo_pump_rpm: 30_000.0,
+            turbo_pump_target_rpm: 30_000.0,
+            thrust_n: 1_000_000.0,
+            specific_impulse_s: specific_impulse_s(1_000_000.0, 300.0),
+            nozzle_temperature_k: 1800.0,
+            pitch_deg: 45.0,
+            vibration_x_g: 0.5,
+            vibration_y_g: 0.5,
+            vibration_z_g: 0.75,
+            vibration_freq_hz: 60.0,
+            pyro_stage: ChannelState::Armed,
+            // Cruise has no flight phases, a sustained burn is closest to ascent
+            phase: MissionPhase::Ascent,
+            ..Self::initialize()
+        }
+    }
+
+    // Layer the wind's offsets onto the reported attitude, rates and accelerometers
+    fn apply_wind(&mut self, effect: &WindEffect) {
+        let [roll, pitch, yaw] = effect.attitude_deg;
+        let [roll_rate, pitch_rate, yaw_rate] = effect.rates_dps;
+        let [lateral_x, lateral_y] = effect.lateral_g;
+        self.roll_deg += roll;
+        self.pitch_deg += pitch;
+        self.yaw_deg += yaw;
+        self.roll_rate_dps += roll_rate;
+        self.pitch_rate_dps += pitch_rate;
+        self.yaw_rate_dps += yaw_rate;
+        self.vibration_x_g = self.vibration_x_g * effect.buffet + lateral_x;
+        self.vibration_y_g = self.vibration_y_g * effect.buffet + lateral_y;
+        self.vibration_z_g *= effect.buffet;
+    }
+
+    // Look up a field by name for scenario scripts and setpoints
+    pub(super) fn field_mut(&mut self, name: &str) -> Option<&mut f64> {
+        let field = match name {
+            "altitude_m" => &mut self.altitude_m,
+            "velocity_mps" => &mut self.velocity_mps,
+            "acceleration_mps2" => &mut self.acceleration_mps2,
+            "chamber_pressure_pa" => &mut self.chamber_pressure_pa,
+            "chamber_temperature_k" => &mut self.chamber_temperature_k,
+            "oxidizer_flow_rate_kgps" => &mut self.oxidizer_flow_rate_kgps,
+            "oxidizer_pressure_pa" => &mut self.oxidizer_pressure_pa,
+            "oxidizer_temperature_k" => &mut self.oxidizer_temperature_k,
+            "fuel_flow_rate_kgps" => &mut self.fuel_flow_rate_kgps,
+            "fuel_pressure_pa" => &mut self.fuel_pressure_pa,
+            "fuel_temperature_k" => &mut self.fuel_temperature_k,
+            "turbo_pump_rpm" => &mut self.turbo_pump_rpm,
+            "thrust_n" => &mut self.thrust_n,
+            "specific_impulse_s" => &mut self.specific_impulse_s,
+            "nozzle_temperature_k" => &mut self.nozzle_temperature_k,
+            "roll_deg" => &mut self.roll_deg,
+            "pitch_deg" => &mut self.pitch_deg,
+            "yaw_deg" => &mut self.yaw_deg,
+            "roll_rate_dps" => &mut self.roll_rate_dps,
+            "pitch_rate_dps" => &mut self.pitch_rate_dps,
+            "yaw_rate_dps" => &mut self.yaw_rate_dps,
+            "latitude_deg" => &mut self.latitude_deg,
+            "longitude_deg" => &mut self.longitude_deg,
+            "vibration_x_g" => &mut self.vibration_x_g,
+            "vibration_y_g" => &mut self.vibration_y_g,
+            "vibration_z_g" => &mut self.vibration_z_g,
+            "vibration_freq_hz" => &mut self.vibration_freq_hz,
+            "interstage_temperature_k" => &mut self.thermal.interstage_k,
+            "avionics_bay_temperature_k" => &mut self.thermal.avionics_bay_k,
+            "copv_temperature_k" => &mut self.thermal.copv_k,
+            _ => return None,
+        };
+        Some(field)
+    }
 }
@@ -0,0 +1,183 @@
+use crate::error::{Result, TelemetryError};
+use crate::models::ChannelState;
+use std::str::FromStr;
+use tracing::info;
+
+use super::generator::SimulationState;
+use super::script::{Condition, parse_time_ms};
+
+// How long the vehicle has to stay below its level before health steps down one level
+const RECOVERY_MS: u64 = 1_000;
+
+// Rule raising the health status while conditions on the reported state hold, e.g.
+//
+//   nozzle_temperature_k > 3000 for 1s => warning
+//   chamber_pressure_pa < 1000000 and altitude_m > 500 => caution
+//
+// Conditions use the same fields and operators as script hooks. `for` is how long they have to
+// hold before the rule trips, without it the rule trips straight away
+#[derive(Debug, Clone)]
+pub struct HealthRule {
+    conditions: Vec<Condition>,
+    hold_ms: u64,
+    level: ChannelState,
+    // The rule as written, for logs and the transitions file
+    source: String,
+    // When the conditions last became true. None while they don't hold
+    since_ms: Option<u64>,
+}
+
+// A change of health status during the run
+#[derive(Debug, Clone)]
+pub struct HealthTransition {
+    pub time_since_launch_ms: u64,
+    pub from: ChannelState,
+    pub to: ChannelState,
+    // Rule that tripped, or why health recovered
+    pub cause: String,
+}
+
+// Nominal, caution, warning and critical state machine driven by the rules. Health goes straight
+// up to the worst tripped rule's level and steps back down one level at a time once the vehicle
+// has stayed below its current level for a second, so flapping conditions don't flap the status
+#[derive(Debug, Clone)]
+pub struct HealthMonitor {
+    rules: Vec<HealthRule>,
+    level: ChannelState,
+    // When the tripped rules first dropped below the current level
+    below_since_ms: Option<u64>,
+    transitions: Vec<HealthTransition>,
+}
+
+impl HealthMonitor {
+    pub fn new(rules: Vec<HealthRule>) -> Self {
+        Self {
+            rules,
+            level: ChannelState::Nominal,
+            below_since_ms: None,
+            transitions: Vec::new(),
+        }
+    }
+
+    pub fn transitions(&self) -> &[HealthTransition] {
+        &self.transitions
+    }
+
+    // Move the state machine on to `now_ms` and return the health status
+    pub(super) fn step(&mut self, state: &SimulationState, now_ms: u64) -> ChannelState {
+        // Fields are only looked up by name through field_mut
+        let mut probe = state.clone();
+        // Index and level code of the worst tripped rule
+        let mut worst: Option<(usize, i64)> = None;
+        for (idx, rule) in self.rules.iter_mut().enumerate() {
+            let holds = rule
+                .conditions
+                .iter()
+                .all(|condition| condition.holds(&mut probe));
+            rule.since_ms = if holds {
+                rule.since_ms.or(Some(now_ms))
+            } else {
+                None
+            };
+            let tripped = rule
+                .since_ms
+                .is_some_and(|since| now_ms - since >= rule.hold_ms);
+            if tripped && worst.is_none_or(|(_, code)| rule.level.code() > code) {
+                worst = Some((idx, rule.level.code()));
+            }
+        }
+
+        let target = worst.map_or(ChannelState::Nominal, |(idx, _)| self.rules[idx].level);
+        if target.code() > self.level.code() {
+            let cause = worst.map(|(idx, _)| self.rules[idx].source.clone());
+            self.transition(now_ms, target, cause.unwrap_or_default());
+            self.below_since_ms = None;
+        } else if target.code() < self.level.code() {
+            let since = *self.below_since_ms.get_or_insert(now_ms);
+            if now_ms - since >= RECOVERY_MS {
+                let lower = HEALTH_LEVELS[self.level.code() as usize - 1];
+                self.transition(now_ms, lower, "recovered".to_string());
+                // Keep stepping down a level a second while it stays clear
+                self.below_since_ms = (target.code() < lower.code()).then_some(now_ms);
+            }
+        } else {
+            self.below_since_ms = None;
+        }
+        self.level
+    }
+
+    fn transition(&mut self, now_ms: u64, to: ChannelState, cause: String) {
+        info!(
+            time_since_launch_ms = now_ms,
+            "Health {} -> {}: {}",
+            self.level.name(),
+            to.name(),
+            cause
+        );
+        self.transitions.push(HealthTransition {
+            time_since_launch_ms: now_ms,
+            from: self.level,
+            to,
+            cause,
+        });
+        self.level = to;
+    }
+}
+
+// In order of severity, so a level's code is its index
+const HEALTH_LEVELS: [ChannelState; 4] = [
+    ChannelState::Nominal,
+    ChannelState::Caution,
+    ChannelState::Warning,
+    ChannelState::Critical,
+];
+
+impl FromStr for HealthRule {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (condition, level) = s.split_once("=>").ok_or_else(|| {
+            TelemetryError::config(format!(
+                "Health rule {s} should look like <conditions> [for <time>] => <level>"
+            ))
+        })?;
+        let level = HEALTH_LEVELS
+            .into_iter()
+            .skip(1)
+            .find(|state| state.name().eq_ignore_ascii_case(level.trim()))
+            .ok_or_else(|| {
+                TelemetryError::config(format!(
+                    "Unknown health level {}. Use caution, warning or critical",
+                    level.trim()
+                ))
+            })?;
+
+        let mut words = condition.split_whitespace();
+        let conditions = Condition::parse_all(&mut words)?;
+        let hold_ms = match words.next() {
+            Some("for") => words
+                .next()
+                .ok_or_else(|| TelemetryError::config("Missing time after `for`"))
+                .and_then(parse_time_ms)?,
+            Some(other) => {
+                return Err(TelemetryError::config(format!(
+                    "Unexpected {other} in health rule {s}"
+                )));
+            }
+            None => 0,
+        };
+        if let Some(extra) = words.next() {
+            return Err(TelemetryError::config(format!(
+                "Unexpected {extra} in health rule {s}"
+            )));
+        }
+
+        Ok(Self {
+            conditions,
+            hold_ms,
+            level,
+            source: s.trim().to_string(),
+            since_ms: None,
+        })
+    }
+}
@@ -0,0 +1,58 @@
+// Earth's field at Cape Canaveral in north, east, down (µT)
+const EARTH_FIELD_NED_UT: [f64; 3] = [24.2, -2.3, 40.5];
+
+// Sensor noise standard deviations
+pub(super) const GYRO_NOISE_DPS: f64 = 0.01;
+pub(super) const MAGNETOMETER_NOISE_UT: f64 = 0.05;
+
+// Raw IMU errors so sensor fusion can be tested against the attitude truth. Defaults to a
+// perfect sensor, leaving only the noise
+#[derive(Debug, Clone, Copy)]
+pub struct ImuErrors {
+    pub gyro_bias_dps: f64,
+    pub gyro_scale_factor: f64,
+    pub magnetometer_bias_ut: f64,
+    pub magnetometer_scale_factor: f64,
+}
+
+impl Default for ImuErrors {
+    fn default() -> Self {
+        Self {
+            gyro_bias_dps: 0.0,
+            gyro_scale_factor: 1.0,
+            magnetometer_bias_ut: 0.0,
+            magnetometer_scale_factor: 1.0,
+        }
+    }
+}
+
+impl ImuErrors {
+    // Body rates seen by the gyro from the euler angles and their rates, all in degrees
+    pub(super) fn gyro(&self, angles_deg: [f64; 3], rates_dps: [f64; 3]) -> [f64; 3] {
+        let [roll, pitch, _] = angles_deg.map(f64::to_radians);
+        let [roll_rate, pitch_rate, yaw_rate] = rates_dps;
+
+        let body = [
+            roll_rate - yaw_rate * pitch.sin(),
+            pitch_rate * roll.cos() + yaw_rate * pitch.cos() * roll.sin(),
+            -pitch_rate * roll.sin() + yaw_rate * pitch.cos() * roll.cos(),
+        ];
+        body.map(|rate| rate * self.gyro_scale_factor + self.gyro_bias_dps)
+    }
+
+    // Earth's field rotated into the body frame by roll, pitch and yaw in degrees
+    pub(super) fn magnetometer(&self, angles_deg: [f64; 3]) -> [f64; 3] {
+        let [roll, pitch, yaw] = angles_deg.map(f64::to_radians);
+        let (sr, cr) = roll.sin_cos();
+        let (sp, cp) = pitch.sin_cos();
+        let (sy, cy) = yaw.sin_cos();
+        let [n, e, d] = EARTH_FIELD_NED_UT;
+
+        let body = [
+            cp * cy * n + cp * sy * e - sp * d,
+            (sr * sp * cy - cr * sy) * n + (sr * sp * sy + cr * cy) * e + sr * cp * d,
+            (cr * sp * cy + sr * sy) * n + (cr * sp * sy - sr * cy) * e + cr * cp * d,
+        ];
+        body.map(|field| field * self.magnetometer_scale_factor + self.magnetometer_bias_ut)
+    }
+}
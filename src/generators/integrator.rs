@@ -0,0 +1,59 @@
+// How the flight integrates acceleration into velocity and altitude each step
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Integrator {
+    // Altitude moves at the velocity from the start of the step. Gains energy at low rates
+    #[default]
+    Euler,
+    // Velocity first, then altitude at the new velocity. How runs integrated before there was a
+    // choice, so older seeds reproduce with it
+    SemiImplicitEuler,
+    // Fourth order Runge-Kutta, sampling the forces mid and end of step. Keeps altitude and
+    // velocity close to a high rate run even at 1 Hz
+    Rk4,
+}
+
+impl Integrator {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Integrator::Euler => "euler",
+            Integrator::SemiImplicitEuler => "semi-implicit-euler",
+            Integrator::Rk4 => "rk4",
+        }
+    }
+
+    // Advance one step of `time_step_s`. `acceleration_at` takes the fraction through the step,
+    // the altitude and the velocity, as drag and gravity change with them. Returns the
+    // acceleration at the start, which is what the sensors read
+    pub(super) fn step(
+        &self,
+        altitude_m: &mut f64,
+        velocity_mps: &mut f64,
+        acceleration_at: impl Fn(f64, f64, f64) -> f64,
+        time_step_s: f64,
+    ) -> f64 {
+        let start = acceleration_at(0.0, *altitude_m, *velocity_mps);
+        match self {
+            Integrator::Euler => {
+                *altitude_m += *velocity_mps * time_step_s;
+                *velocity_mps += start * time_step_s;
+            }
+            Integrator::SemiImplicitEuler => {
+                *velocity_mps += start * time_step_s;
+                *altitude_m += *velocity_mps * time_step_s;
+            }
+            Integrator::Rk4 => {
+                let half_s = time_step_s / 2.0;
+                let (altitude, velocity) = (*altitude_m, *velocity_mps);
+                let v2 = velocity + half_s * start;
+                let a2 = acceleration_at(0.5, altitude + half_s * velocity, v2);
+                let v3 = velocity + half_s * a2;
+                let a3 = acceleration_at(0.5, altitude + half_s * v2, v3);
+                let v4 = velocity + time_step_s * a3;
+                let a4 = acceleration_at(1.0, altitude + time_step_s * v3, v4);
+                *altitude_m += time_step_s / 6.0 * (velocity + 2.0 * v2 + 2.0 * v3 + v4);
+                *velocity_mps += time_step_s / 6.0 * (start + 2.0 * a2 + 2.0 * a3 + a4);
+            }
+        }
+        start
+    }
+}
@@ -0,0 +1,199 @@
+use crate::error::{Result, TelemetryError};
+use crate::models::{PairSide, SensorEnum, SensorValue, TelemetryReading};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::info;
+
+// Yellow and red limits on a sensor, checked against every reading it reports, e.g.
+//
+//   cmb_pa > 8000000/9000000       yellow above 8 MPa, red above 9 MPa
+//   fuel_pressure < 200000/100000  yellow below 200 kPa, red below 100 kPa
+//
+// Sensors go by any of their exported names. Limits are in the generator's own units, before
+// `--units` and calibrations are applied
+#[derive(Debug, Clone)]
+pub struct SensorLimit {
+    sensor: SensorEnum,
+    // True when the limits are upper bounds
+    above: bool,
+    yellow: f64,
+    red: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LimitLevel {
+    Yellow,
+    Red,
+}
+
+impl LimitLevel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LimitLevel::Yellow => "yellow",
+            LimitLevel::Red => "red",
+        }
+    }
+}
+
+// A stretch of readings past a limit. Yellow and red are alerted on separately, so a red
+// exceedance sits inside the yellow one around it
+#[derive(Debug, Clone)]
+pub struct LimitAlert {
+    pub sensor: SensorEnum,
+    pub instance: Option<u16>,
+    pub side: Option<PairSide>,
+    pub level: LimitLevel,
+    pub limit: f64,
+    pub start: DateTime<Utc>,
+    pub start_ms: u64,
+    // First reading back inside the limit. None when it was still exceeded at the end of the run
+    pub end_ms: Option<u64>,
+    // Furthest past the limit the sensor got
+    pub peak: f64,
+}
+
+// Raises an alert whenever a reading crosses a limit and closes it once one comes back inside
+#[derive(Debug, Clone)]
+pub struct LimitMonitor {
+    limits: Vec<SensorLimit>,
+    alerts: Vec<LimitAlert>,
+    // Index into `alerts` of the open exceedances, per limit, instance, pair side and level
+    open: HashMap<(usize, Option<u16>, Option<PairSide>, LimitLevel), usize>,
+}
+
+impl LimitMonitor {
+    pub fn new(limits: Vec<SensorLimit>) -> Self {
+        Self {
+            limits,
+            alerts: Vec::new(),
+            open: HashMap::new(),
+        }
+    }
+
+    pub fn alerts(&self) -> &[LimitAlert] {
+        &self.alerts
+    }
+
+    // Check one step's readings
+    pub(super) fn observe(&mut self, readings: &[TelemetryReading]) {
+        for reading in readings {
+            let value = match reading.value {
+                SensorValue::Float(f) => f,
+                SensorValue::Int(i) => i as f64,
+                SensorValue::UnsignedInt(u) => u as f64,
+                SensorValue::String(_) | SensorValue::State(_) => continue,
+            };
+            for idx in 0..self.limits.len() {
+                if self.limits[idx].sensor != reading.sensor {
+                    continue;
+                }
+                for level in [LimitLevel::Yellow, LimitLevel::Red] {
+                    self.check(idx, level, reading, value);
+                }
+            }
+        }
+    }
+
+    fn check(&mut self, idx: usize, level: LimitLevel, reading: &TelemetryReading, value: f64) {
+        let limit = &self.limits[idx];
+        let threshold = match level {
+            LimitLevel::Yellow => limit.yellow,
+            LimitLevel::Red => limit.red,
+        };
+        let exceeded = if limit.above {
+            value > threshold
+        } else {
+            value < threshold
+        };
+        let key = (idx, reading.instance, reading.side, level);
+
+        match (self.open.get(&key), exceeded) {
+            (Some(&alert_idx), true) => {
+                let alert = &mut self.alerts[alert_idx];
+                alert.peak = if limit.above {
+                    alert.peak.max(value)
+                } else {
+                    alert.peak.min(value)
+                };
+            }
+            (Some(&alert_idx), false) => {
+                let alert = &mut self.alerts[alert_idx];
+                alert.end_ms = Some(reading.time_since_launch_ms);
+                info!(
+                    time_since_launch_ms = reading.time_since_launch_ms,
+                    "{} {} limit cleared, peak {}",
+                    reading.sensor.field_name(),
+                    level.name(),
+                    alert.peak
+                );
+                self.open.remove(&key);
+            }
+            (None, true) => {
+                info!(
+                    time_since_launch_ms = reading.time_since_launch_ms,
+                    "{} crossed {} limit {} at {}",
+                    reading.sensor.field_name(),
+                    level.name(),
+                    threshold,
+                    value
+                );
+                self.open.insert(key, self.alerts.len());
+                self.alerts.push(LimitAlert {
+                    sensor: reading.sensor,
+                    instance: reading.instance,
+                    side: reading.side,
+                    level,
+                    limit: threshold,
+                    start: reading.timestamp,
+                    start_ms: reading.time_since_launch_ms,
+                    end_ms: None,
+                    peak: value,
+                });
+            }
+            (None, false) => {}
+        }
+    }
+}
+
+impl FromStr for SensorLimit {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let usage = || {
+            TelemetryError::config(format!(
+                "Limit {s} should look like <sensor> > <yellow>/<red> or <sensor> < <yellow>/<red>"
+            ))
+        };
+        let (sensor, above, limits) = match (s.split_once('>'), s.split_once('<')) {
+            (Some((sensor, limits)), None) => (sensor, true, limits),
+            (None, Some((sensor, limits))) => (sensor, false, limits),
+            _ => return Err(usage()),
+        };
+        let sensor = SensorEnum::from_name(sensor.trim())
+            .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {}", sensor.trim())))?;
+        let (yellow, red) = limits.split_once('/').ok_or_else(usage)?;
+        let parse = |limit: &str| {
+            limit
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| TelemetryError::config(format!("Invalid limit {}", limit.trim())))
+        };
+        let (yellow, red) = (parse(yellow)?, parse(red)?);
+
+        // Red is the further limit, so it can only trip once yellow has
+        let ordered = if above { red >= yellow } else { red <= yellow };
+        if !ordered {
+            return Err(TelemetryError::config(format!(
+                "Red limit {red} should be past the yellow limit {yellow} in {s}"
+            )));
+        }
+
+        Ok(Self {
+            sensor,
+            above,
+            yellow,
+            red,
+        })
+    }
+}
@@ -1,2 +1,46 @@
+mod cancel;
+mod chaos;
+mod commanding;
+mod cruise;
+mod engine;
+mod fanout;
+mod feed;
+mod fuzz;
 mod generator;
+mod health;
+mod imu;
+mod integrator;
+mod limits;
+mod noise;
+mod observer;
+mod periodic;
+mod profile;
+mod redundancy;
+mod script;
+mod shape;
+mod summary;
+mod task;
+mod thermal;
+mod vehicle;
+mod wind;
+
+pub use cancel::CancelToken;
+pub use chaos::*;
+pub use commanding::*;
+pub use cruise::*;
+pub use engine::*;
+pub use fuzz::{Fuzz, FuzzBounds};
 pub use generator::*;
+pub use health::*;
+pub use imu::*;
+pub use integrator::*;
+pub use limits::*;
+pub use observer::ChunkObserver;
+pub use periodic::*;
+pub use profile::FlightProfile;
+pub use redundancy::*;
+pub use script::*;
+pub use shape::*;
+pub use summary::*;
+pub use task::GenerationTask;
+pub use wind::{WindLayer, WindPreset};
@@ -0,0 +1,117 @@
+use rand::Rng;
+use rand_distr::{Distribution, StandardNormal};
+use std::ops::Range;
+
+// Number of values pre-sampled per refill in batch mode
+const NOISE_BLOCK_SIZE: usize = 8192;
+
+// Source of raw noise for the generator.
+// Per sample mode draws straight from the rng so outputs match older versions for a seed.
+// Batch mode fills blocks of random bits at once and converts them in tight loops, which is a
+// lot cheaper than a ziggurat sample per value on high rate runs.
+pub struct NoiseSource {
+    batch: Option<NoiseBatch>,
+}
+
+impl NoiseSource {
+    pub fn new(batch_noise: bool) -> Self {
+        let batch = if batch_noise {
+            Some(NoiseBatch::new(NOISE_BLOCK_SIZE))
+        } else {
+            None
+        };
+        Self { batch }
+    }
+
+    // Z-score to be scaled by a distribution. See `Normal::from_zscore`
+    #[inline]
+    pub fn standard_normal<R: Rng>(&mut self, rng: &mut R) -> f64 {
+        match &mut self.batch {
+            Some(batch) => batch.next_normal(rng),
+            None => StandardNormal.sample(rng),
+        }
+    }
+
+    #[inline]
+    pub fn uniform<R: Rng>(&mut self, rng: &mut R, range: Range<f64>) -> f64 {
+        match &mut self.batch {
+            Some(batch) => range.start + (range.end - range.start) * batch.next_uniform(rng),
+            None => rng.gen_range(range),
+        }
+    }
+}
+
+struct NoiseBatch {
+    bits: Vec<u64>,
+    normals: Vec<f64>,
+    uniforms: Vec<f64>,
+    normal_idx: usize,
+    uniform_idx: usize,
+}
+
+impl NoiseBatch {
+    fn new(block_size: usize) -> Self {
+        // Box-Muller works on pairs so keep the block even
+        let block_size = block_size + block_size % 2;
+        Self {
+            bits: vec![0; block_size],
+            normals: vec![0.0; block_size],
+            uniforms: vec![0.0; block_size],
+            normal_idx: block_size,
+            uniform_idx: block_size,
+        }
+    }
+
+    #[inline]
+    fn next_normal<R: Rng>(&mut self, rng: &mut R) -> f64 {
+        if self.normal_idx == self.normals.len() {
+            self.refill_normals(rng);
+        }
+        let value = self.normals[self.normal_idx];
+        self.normal_idx += 1;
+        value
+    }
+
+    #[inline]
+    fn next_uniform<R: Rng>(&mut self, rng: &mut R) -> f64 {
+        if self.uniform_idx == self.uniforms.len() {
+            self.refill_uniforms(rng);
+        }
+        let value = self.uniforms[self.uniform_idx];
+        self.uniform_idx += 1;
+        value
+    }
+
+    fn refill_uniforms<R: Rng>(&mut self, rng: &mut R) {
+        rng.fill(&mut self.bits[..]);
+        for (uniform, bits) in self.uniforms.iter_mut().zip(&self.bits) {
+            *uniform = unit_interval(*bits);
+        }
+        self.uniform_idx = 0;
+    }
+
+    // Box-Muller transform over the whole block
+    fn refill_normals<R: Rng>(&mut self, rng: &mut R) {
+        rng.fill(&mut self.bits[..]);
+        for (pair, bits) in self
+            .normals
+            .chunks_exact_mut(2)
+            .zip(self.bits.chunks_exact(2))
+        {
+            // Shift into (0, 1] so ln never sees zero
+            let u1 = 1.0 - unit_interval(bits[0]);
+            let u2 = unit_interval(bits[1]);
+            let radius = (-2.0 * u1.ln()).sqrt();
+            let (sin, cos) = (std::f64::consts::TAU * u2).sin_cos();
+            pair[0] = radius * cos;
+            pair[1] = radius * sin;
+        }
+        self.normal_idx = 0;
+    }
+}
+
+// Top 53 bits into [0, 1)
+#[inline]
+fn unit_interval(bits: u64) -> f64 {
+    (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
@@ -0,0 +1,26 @@
+use crate::error::Result;
+use crate::models::TelemetryDataset;
+use std::ops::ControlFlow;
+
+// Follows a run chunk by chunk as it's generated, for live plots, on the fly validation or sinks
+// of your own without waiting for the whole dataset. Observers are called on the generating
+// thread before the chunk goes anywhere else, so keep them quick or hand the readings off.
+// Returning `ControlFlow::Break` or an error stops the run like the chunk callback does
+pub trait ChunkObserver: Send {
+    fn on_chunk(&mut self, chunk: &TelemetryDataset) -> Result<ControlFlow<()>>;
+
+    // Once the run is over, whether it finished or was stopped early, with the readings generated
+    fn on_finish(&mut self, _readings: usize) -> Result<()> {
+        Ok(())
+    }
+}
+
+// Closures work as observers that only care about the chunks
+impl<F> ChunkObserver for F
+where
+    F: FnMut(&TelemetryDataset) -> Result<ControlFlow<()>> + Send,
+{
+    fn on_chunk(&mut self, chunk: &TelemetryDataset) -> Result<ControlFlow<()>> {
+        self(chunk)
+    }
+}
@@ -0,0 +1,95 @@
+use crate::error::TelemetryError;
+use std::f64::consts::TAU;
+use std::str::FromStr;
+
+use super::generator::SimulationState;
+use super::script::{parse_number, parse_time_ms};
+
+// Wave layered onto one field, e.g. `fuel_temperature_k:5:24h` for a daily thermal cycle.
+// The phase is taken from wall clock time, so daily cycles line up with the time of day and
+// repeat the same way across runs and backfills.
+#[derive(Debug, Clone)]
+pub struct PeriodicPattern {
+    field: String,
+    amplitude: f64,
+    period_s: f64,
+    // Time of the first peak after midnight UTC, 1970-01-01
+    phase_s: f64,
+}
+
+// Ready made patterns for long runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum PeriodicPreset {
+    // Daily thermal cycle on the tank and engine temperatures, warmest at 15:00 UTC
+    Diurnal,
+    // Low earth orbit period swing in altitude, attitude and temperatures
+    Orbit,
+}
+
+impl PeriodicPreset {
+    pub fn patterns(&self) -> Vec<PeriodicPattern> {
+        let specs: &[&str] = match self {
+            PeriodicPreset::Diurnal => &[
+                "oxidizer_temperature_k:4:1d:15h",
+                "fuel_temperature_k:6:1d:15h",
+                "chamber_temperature_k:10:1d:15h",
+                "nozzle_temperature_k:15:1d:15h",
+            ],
+            PeriodicPreset::Orbit => &[
+                "altitude_m:8000:92m",
+                "roll_deg:2:92m",
+                "nozzle_temperature_k:40:92m:23m",
+                "oxidizer_temperature_k:3:92m:23m",
+            ],
+        };
+        specs
+            .iter()
+            .map(|spec| spec.parse().expect("Preset patterns are valid"))
+            .collect()
+    }
+}
+
+impl PeriodicPattern {
+    // Add the wave's value at `epoch_s` seconds since the unix epoch
+    pub(super) fn apply(&self, state: &mut SimulationState, epoch_s: f64) {
+        if let Some(value) = state.field_mut(&self.field) {
+            *value += self.amplitude * (TAU * (epoch_s - self.phase_s) / self.period_s).cos();
+        }
+    }
+}
+
+// Parse `<field>:<amplitude>:<period>[:<phase>]` with times like `92m` or `1d`
+impl FromStr for PeriodicPattern {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let (field, amplitude, period, phase) = match parts[..] {
+            [field, amplitude, period] => (field, amplitude, period, None),
+            [field, amplitude, period, phase] => (field, amplitude, period, Some(phase)),
+            _ => {
+                return Err(TelemetryError::config(format!(
+                    "Periodic pattern {s} should look like <field>:<amplitude>:<period>[:<phase>]"
+                )));
+            }
+        };
+
+        if SimulationState::initialize().field_mut(field).is_none() {
+            return Err(TelemetryError::config(format!("Unknown field {field}")));
+        }
+        let period_ms = parse_time_ms(period)?;
+        if period_ms == 0 {
+            return Err(TelemetryError::config(format!(
+                "Period of {s} must be greater than zero"
+            )));
+        }
+        let phase_ms = phase.map(parse_time_ms).transpose()?.unwrap_or(0);
+
+        Ok(Self {
+            field: field.to_string(),
+            amplitude: parse_number(amplitude)?,
+            period_s: period_ms as f64 / 1000.0,
+            phase_s: phase_ms as f64 / 1000.0,
+        })
+    }
+}
@@ -0,0 +1,471 @@
+use crate::error::{IoContext, Result, TelemetryError};
+use crate::models::MissionPhase;
+use rand::Rng;
+use serde::Deserialize;
+use std::path::Path;
+use tracing::info;
+
+use super::script::parse_time_ms;
+use super::thermal::SEA_LEVEL_PRESSURE_PA;
+use super::vehicle::{Propulsion, Vehicle};
+
+// Flight profile runs follow unless given one with `--scenario`
+const DEFAULT_PROFILE: &str = include_str!("../../profiles/default.toml");
+
+// Phases a run flies through and what the engine, trajectory and vibration do in each, loaded
+// from a TOML or YAML file. Curves are functions of the fraction through their phase, either a
+// constant or `[fraction, value]` points joined by straight lines. Two points at the same fraction
+// make a step. Values a phase leaves out carry over from the phase before
+#[derive(Debug, Clone)]
+pub struct FlightProfile {
+    name: String,
+    engine: EngineRating,
+    vehicle: Vehicle,
+    phases: Vec<ProfilePhase>,
+    // Where each phase ends as a fraction of the run. The last phase holds until the run ends
+    ends: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawProfile {
+    #[serde(default)]
+    name: Option<String>,
+    engine: EngineRating,
+    #[serde(default)]
+    vehicle: Vehicle,
+    phases: Vec<ProfilePhase>,
+}
+
+// One engine at full throttle. Thrust is at sea level, it grows with altitude as the ambient
+// pressure on the nozzle exit falls away
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EngineRating {
+    chamber_pressure_pa: f64,
+    oxidizer_flow_kgps: f64,
+    fuel_flow_kgps: f64,
+    turbo_pump_rpm: f64,
+    thrust_n: f64,
+    #[serde(default = "default_exit_area")]
+    nozzle_exit_area_m2: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfilePhase {
+    name: String,
+    // Fraction of the run like 0.4 or a time since launch like `90s`
+    #[serde(default)]
+    end: Option<PhaseEnd>,
+    #[serde(default = "first_stage")]
+    stage: u64,
+    // Scales the engine rating. Pressure, flows, pump speed and thrust follow it
+    #[serde(default)]
+    throttle: Option<Curve>,
+    // Engines burning together. Thrust and flows add up, pressure and pump speed are per engine
+    #[serde(default = "single_engine")]
+    engines: f64,
+    #[serde(default)]
+    chamber_temperature_k: Option<Curve>,
+    #[serde(default)]
+    nozzle_temperature_k: Option<Curve>,
+    // The pitch rate follows from the slope
+    #[serde(default)]
+    pitch_deg: Option<Curve>,
+    #[serde(default)]
+    vibration: Option<VibrationLevels>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VibrationLevels {
+    x_g: Curve,
+    y_g: Curve,
+    z_g: Curve,
+    freq_hz: Curve,
+    // Scale each axis by a fresh uniform draw every step, for the shaking of lift off
+    #[serde(default)]
+    random: bool,
+    // With `random`, add up to this much to the frequency
+    #[serde(default)]
+    freq_jitter_hz: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum PhaseEnd {
+    Fraction(f64),
+    Time(String),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Curve {
+    Constant(f64),
+    Points(Vec<(f64, f64)>),
+}
+
+fn first_stage() -> u64 {
+    1
+}
+
+fn single_engine() -> f64 {
+    1.0
+}
+
+// About a Merlin sized nozzle
+fn default_exit_area() -> f64 {
+    0.9
+}
+
+// What the profile sets at a point of the run. None leaves the value as it was
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ProfileSample {
+    pub phase: MissionPhase,
+    pub stage: u64,
+    pub engine: Option<EngineSample>,
+    pub chamber_temperature_k: Option<f64>,
+    pub nozzle_temperature_k: Option<f64>,
+    // Angle and rate
+    pub pitch: Option<(f64, f64)>,
+    // x, y and z levels and frequency
+    pub vibration: Option<[f64; 4]>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct EngineSample {
+    pub chamber_pressure_pa: f64,
+    pub oxidizer_flow_kgps: f64,
+    pub fuel_flow_kgps: f64,
+    pub turbo_pump_rpm: f64,
+    // Thrust in a vacuum, before the ambient pressure on the exit is taken off
+    pub vacuum_thrust_n: f64,
+    // Of every engine burning
+    pub exit_area_m2: f64,
+}
+
+impl Default for FlightProfile {
+    fn default() -> Self {
+        // Only uses fractions, so the duration doesn't matter
+        Self::from_toml(DEFAULT_PROFILE, 1).expect("The default flight profile is valid")
+    }
+}
+
+impl FlightProfile {
+    // `.toml` or `.yaml`/`.yml`. Phase ends given as times are placed in a run of `duration_s`
+    pub fn from_file(path: &Path, duration_s: usize) -> Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read flight profile {}", path.display()))?;
+        let profile = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&source, duration_s),
+            Some("yaml" | "yml") => Self::from_yaml(&source, duration_s),
+            _ => Err(TelemetryError::config(
+                "Flight profiles are .toml, .yaml or .yml files",
+            )),
+        }
+        .map_err(|e| {
+            TelemetryError::config(format!("Invalid flight profile {}: {e}", path.display()))
+        })?;
+
+        info!(
+            "Loaded flight profile {} with {} phases",
+            profile.name,
+            profile.phases.len()
+        );
+        Ok(profile)
+    }
+
+    pub fn from_toml(source: &str, duration_s: usize) -> Result<Self> {
+        let raw: RawProfile =
+            toml::from_str(source).map_err(|e| TelemetryError::config(e.to_string()))?;
+        Self::new(raw, duration_s)
+    }
+
+    pub fn from_yaml(source: &str, duration_s: usize) -> Result<Self> {
+        let raw: RawProfile =
+            serde_yaml::from_str(source).map_err(|e| TelemetryError::config(e.to_string()))?;
+        Self::new(raw, duration_s)
+    }
+
+    fn new(raw: RawProfile, duration_s: usize) -> Result<Self> {
+        if raw.phases.is_empty() {
+            return Err(TelemetryError::config("Profile has no phases"));
+        }
+        raw.vehicle.check()?;
+        if !(raw.engine.nozzle_exit_area_m2.is_finite() && raw.engine.nozzle_exit_area_m2 >= 0.0) {
+            return Err(TelemetryError::config(
+                "nozzle_exit_area_m2 can't be negative",
+            ));
+        }
+
+        let mut ends = Vec::with_capacity(raw.phases.len());
+        let mut previous_end = 0.0;
+        let mut previous_stage = raw.phases[0].stage;
+        for (idx, phase) in raw.phases.iter().enumerate() {
+            let located =
+                |e: TelemetryError| TelemetryError::config(format!("Phase {}: {e}", phase.name));
+            phase.check().map_err(located)?;
+            if phase.stage < previous_stage {
+                return Err(located(TelemetryError::config("Stages can't go back down")));
+            }
+            previous_stage = phase.stage;
+
+            let end = match &phase.end {
+                Some(PhaseEnd::Fraction(fraction)) => *fraction,
+                Some(PhaseEnd::Time(time)) => {
+                    parse_time_ms(time).map_err(located)? as f64
+                        / (duration_s.max(1) as f64 * 1000.0)
+                }
+                None if idx + 1 == raw.phases.len() => 1.0,
+                None => {
+                    return Err(located(TelemetryError::config(
+                        "Needs an end, only the last phase can leave it out",
+                    )));
+                }
+            };
+            if !end.is_finite() || end <= previous_end {
+                return Err(located(TelemetryError::config(
+                    "Ends have to be after the end of the phase before",
+                )));
+            }
+            ends.push(end);
+            previous_end = end;
+        }
+
+        Ok(Self {
+            name: raw.name.unwrap_or_else(|| "custom".to_string()),
+            engine: raw.engine,
+            vehicle: raw.vehicle,
+            phases: raw.phases,
+            ends,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Start of the first phase on a later stage, or None for single stage flights
+    pub fn staging_progress(&self) -> Option<f64> {
+        let first_stage = self.phases[0].stage;
+        let idx = self
+            .phases
+            .iter()
+            .position(|phase| phase.stage > first_stage)?;
+        Some(self.phase_start(idx))
+    }
+
+    fn phase_start(&self, idx: usize) -> f64 {
+        idx.checked_sub(1)
+            .map_or(0.0, |previous| self.ends[previous])
+    }
+
+    // Phase a point of the run falls in, the fraction through it and its length as a fraction
+    // of the run
+    fn locate(&self, progress: f64) -> (&ProfilePhase, f64, f64) {
+        let idx = self
+            .ends
+            .iter()
+            .position(|end| progress < *end)
+            .unwrap_or(self.phases.len() - 1);
+        let start = self.phase_start(idx);
+        let length = (self.ends[idx] - start).max(f64::EPSILON);
+        (
+            &self.phases[idx],
+            ((progress - start) / length).clamp(0.0, 1.0),
+            length,
+        )
+    }
+
+    // Mass of the vehicle on `stage` with `used_kg` of its propellant burnt. Sized by the sea
+    // level thrust of the first phase's engines, with each stage carrying enough propellant for
+    // `stage_burn_s` of one engine at full throttle
+    pub(super) fn mass_kg(&self, stage: u64, used_kg: f64, stage_burn_s: f64) -> f64 {
+        let liftoff_thrust_n = self.engine.thrust_n * self.phases[0].engines;
+        let propellant_kg =
+            (self.engine.oxidizer_flow_kgps + self.engine.fuel_flow_kgps) * stage_burn_s;
+        self.vehicle.mass_kg(
+            liftoff_thrust_n,
+            stage.saturating_sub(self.phases[0].stage),
+            used_kg / propellant_kg.max(f64::EPSILON),
+        )
+    }
+
+    // Acceleration along the flight path at a point of the run. `current` stands in for the
+    // engine and pitch where the phase leaves them out
+    pub(super) fn acceleration(
+        &self,
+        progress: f64,
+        current: Propulsion,
+        mass_kg: f64,
+        altitude_m: f64,
+        velocity_mps: f64,
+    ) -> f64 {
+        let propulsion = self.propulsion(progress, current);
+        self.vehicle
+            .acceleration(propulsion, mass_kg, altitude_m, velocity_mps)
+    }
+
+    fn propulsion(&self, progress: f64, current: Propulsion) -> Propulsion {
+        let (phase, fraction, _) = self.locate(progress);
+        let engine = self.engine_at(phase, fraction);
+        Propulsion {
+            vacuum_thrust_n: engine.map_or(current.vacuum_thrust_n, |e| e.vacuum_thrust_n),
+            exit_area_m2: engine.map_or(current.exit_area_m2, |e| e.exit_area_m2),
+            pitch_deg: phase
+                .pitch_deg
+                .as_ref()
+                .map_or(current.pitch_deg, |curve| curve.value(fraction)),
+        }
+    }
+
+    fn engine_at(&self, phase: &ProfilePhase, fraction: f64) -> Option<EngineSample> {
+        let rating = &self.engine;
+        let throttle = phase.throttle.as_ref()?.value(fraction);
+        // The exit pressure goes with the chamber, so the pressure thrust throttles too
+        let vacuum_thrust_n = rating.thrust_n + SEA_LEVEL_PRESSURE_PA * rating.nozzle_exit_area_m2;
+        Some(EngineSample {
+            chamber_pressure_pa: rating.chamber_pressure_pa * throttle,
+            oxidizer_flow_kgps: rating.oxidizer_flow_kgps * throttle * phase.engines,
+            fuel_flow_kgps: rating.fuel_flow_kgps * throttle * phase.engines,
+            turbo_pump_rpm: rating.turbo_pump_rpm * throttle,
+            vacuum_thrust_n: vacuum_thrust_n * throttle * phase.engines,
+            exit_area_m2: rating.nozzle_exit_area_m2 * phase.engines,
+        })
+    }
+
+    pub(super) fn sample(
+        &self,
+        progress: f64,
+        duration_s: f64,
+        rng: &mut impl Rng,
+    ) -> ProfileSample {
+        let (phase, fraction, length) = self.locate(progress);
+        let at = |curve: &Option<Curve>| curve.as_ref().map(|curve| curve.value(fraction));
+
+        let engine = self.engine_at(phase, fraction);
+        let pitch = phase.pitch_deg.as_ref().map(|curve| {
+            let phase_s = (length * duration_s).max(f64::EPSILON);
+            (curve.value(fraction), curve.slope(fraction) / phase_s)
+        });
+        let vibration = phase.vibration.as_ref().map(|levels| {
+            let mut vibration = [
+                levels.x_g.value(fraction),
+                levels.y_g.value(fraction),
+                levels.z_g.value(fraction),
+                levels.freq_hz.value(fraction),
+            ];
+            if levels.random {
+                for level in &mut vibration[..3] {
+                    *level *= rng.r#gen::<f64>();
+                }
+                vibration[3] += levels.freq_jitter_hz * rng.r#gen::<f64>();
+            }
+            vibration
+        });
+
+        ProfileSample {
+            phase: MissionPhase::from_name(&phase.name).unwrap_or(MissionPhase::Ascent),
+            stage: phase.stage,
+            engine,
+            chamber_temperature_k: at(&phase.chamber_temperature_k),
+            nozzle_temperature_k: at(&phase.nozzle_temperature_k),
+            pitch,
+            vibration,
+        }
+    }
+}
+
+impl ProfilePhase {
+    fn check(&self) -> Result<()> {
+        if MissionPhase::from_name(&self.name).is_none() {
+            let names: Vec<_> = MissionPhase::ALL.iter().map(MissionPhase::name).collect();
+            return Err(TelemetryError::config(format!(
+                "Unknown phase, expected one of {}",
+                names.join(", ")
+            )));
+        }
+        if self.stage == 0 {
+            return Err(TelemetryError::config("Stages count from 1"));
+        }
+        if !self.engines.is_finite() || self.engines < 0.0 {
+            return Err(TelemetryError::config("engines can't be negative"));
+        }
+
+        let mut curves = vec![
+            ("throttle", &self.throttle),
+            ("chamber_temperature_k", &self.chamber_temperature_k),
+            ("nozzle_temperature_k", &self.nozzle_temperature_k),
+            ("pitch_deg", &self.pitch_deg),
+        ]
+        .into_iter()
+        .filter_map(|(name, curve)| Some((name, curve.as_ref()?)))
+        .collect::<Vec<_>>();
+        if let Some(levels) = &self.vibration {
+            curves.extend([
+                ("vibration.x_g", &levels.x_g),
+                ("vibration.y_g", &levels.y_g),
+                ("vibration.z_g", &levels.z_g),
+                ("vibration.freq_hz", &levels.freq_hz),
+            ]);
+        }
+        for (name, curve) in curves {
+            curve
+                .check()
+                .map_err(|e| TelemetryError::config(format!("{name}: {e}")))?;
+        }
+        Ok(())
+    }
+}
+
+impl Curve {
+    fn check(&self) -> Result<()> {
+        let points = match self {
+            Curve::Constant(value) if value.is_finite() => return Ok(()),
+            Curve::Constant(_) => return Err(TelemetryError::config("Values must be finite")),
+            Curve::Points(points) => points,
+        };
+        if points.is_empty() {
+            return Err(TelemetryError::config("Needs at least one point"));
+        }
+        if points
+            .iter()
+            .any(|(fraction, value)| !(0.0..=1.0).contains(fraction) || !value.is_finite())
+        {
+            return Err(TelemetryError::config(
+                "Points are [fraction, value] with the fraction from 0 to 1",
+            ));
+        }
+        if points.windows(2).any(|pair| pair[1].0 < pair[0].0) {
+            return Err(TelemetryError::config("Points must be in fraction order"));
+        }
+        Ok(())
+    }
+
+    // The segment `fraction` falls in. At a step the later side wins
+    fn segment(points: &[(f64, f64)], fraction: f64) -> Option<((f64, f64), (f64, f64))> {
+        let next = points.iter().position(|(x, _)| *x > fraction)?;
+        Some((points[next.checked_sub(1)?], points[next]))
+    }
+
+    fn value(&self, fraction: f64) -> f64 {
+        match self {
+            Curve::Constant(value) => *value,
+            Curve::Points(points) => match Self::segment(points, fraction) {
+                Some(((x0, y0), (x1, y1))) => y0 + (y1 - y0) * (fraction - x0) / (x1 - x0),
+                None if fraction < points[0].0 => points[0].1,
+                None => points[points.len() - 1].1,
+            },
+        }
+    }
+
+    // Change per whole phase
+    fn slope(&self, fraction: f64) -> f64 {
+        match self {
+            Curve::Points(points) => Self::segment(points, fraction)
+                .map_or(0.0, |((x0, y0), (x1, y1))| (y1 - y0) / (x1 - x0)),
+            Curve::Constant(_) => 0.0,
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::models::{PairSide, SensorEnum, SensorValue, TelemetryReading};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::StandardNormal;
+use tracing::info;
+
+// Mixed into the run seed so the pairs get their own stream and leave the sensor noise untouched
+const REDUNDANCY_SEED_SALT: u64 = 0x0A0B_FA12;
+
+// Noise each copy adds on its own, as a fraction of the value
+const COPY_NOISE: f64 = 0.002;
+
+// How fast a drifting copy walks away from its partner, as a fraction of the value per second
+const MIN_DRIFT_PER_S: f64 = 0.001;
+const MAX_DRIFT_PER_S: f64 = 0.005;
+
+// One copy of a pair wandering off, like a transducer losing its calibration
+#[derive(Debug, Clone)]
+struct Drift {
+    side: PairSide,
+    start_ms: u64,
+    // Signed, so a copy can drift high or low
+    rate_per_s: f64,
+}
+
+#[derive(Debug, Clone)]
+struct RedundantPair {
+    sensor: SensorEnum,
+    drift: Option<Drift>,
+}
+
+// Critical sensors reported by an A and a B copy, e.g. `cmb_pa_a` and `cmb_pa_b`, for testing
+// voting and cross-check logic. Each copy adds its own noise on top of the reading, and with
+// `drift_chance` one copy of a pair starts drifting away from the other at a seeded time
+#[derive(Debug, Clone)]
+pub struct RedundantPairs {
+    pairs: Vec<RedundantPair>,
+    rng: StdRng,
+}
+
+impl RedundantPairs {
+    pub fn new(sensors: &[SensorEnum], drift_chance: f64, duration_s: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed ^ REDUNDANCY_SEED_SALT);
+        let duration_ms = duration_s as u64 * 1000;
+        let mut pairs: Vec<RedundantPair> = Vec::new();
+        for &sensor in sensors {
+            if pairs.iter().any(|pair| pair.sensor == sensor) {
+                continue;
+            }
+            // Drifts start somewhere in the middle of the run so there's agreement either side
+            let drift = rng.gen_bool(drift_chance.clamp(0.0, 1.0)).then(|| Drift {
+                side: if rng.gen_bool(0.5) {
+                    PairSide::A
+                } else {
+                    PairSide::B
+                },
+                start_ms: rng.gen_range(duration_ms / 10..=duration_ms * 9 / 10),
+                rate_per_s: rng.gen_range(MIN_DRIFT_PER_S..MAX_DRIFT_PER_S)
+                    * if rng.gen_bool(0.5) { 1.0 } else { -1.0 },
+            });
+            match &drift {
+                Some(drift) => info!(
+                    "Redundant {} copy {} drifts {:+.2}%/s from T+{:.1}s",
+                    sensor.field_name(),
+                    drift.side.suffix(),
+                    drift.rate_per_s * 100.0,
+                    drift.start_ms as f64 / 1000.0
+                ),
+                None => info!("Redundant {} copies agree", sensor.field_name()),
+            }
+            pairs.push(RedundantPair { sensor, drift });
+        }
+        Self { pairs, rng }
+    }
+
+    // Split the readings of redundant sensors in this step into their A and B copies
+    pub(super) fn apply(
+        &mut self,
+        now_ms: u64,
+        readings: &mut Vec<TelemetryReading>,
+        step_start: usize,
+    ) {
+        // Backwards so inserting a copy doesn't move the readings still to visit
+        for idx in (step_start..readings.len()).rev() {
+            let Some(pair) = self
+                .pairs
+                .iter()
+                .find(|pair| pair.sensor == readings[idx].sensor)
+            else {
+                continue;
+            };
+            let value = &readings[idx].value;
+            let value_a = Self::copy_value(&mut self.rng, pair, PairSide::A, value, now_ms);
+            let value_b = Self::copy_value(&mut self.rng, pair, PairSide::B, value, now_ms);
+            let mut copy_b = readings[idx].clone().with_side(PairSide::B);
+            copy_b.value = value_b;
+            readings[idx].side = Some(PairSide::A);
+            readings[idx].value = value_a;
+            readings.insert(idx + 1, copy_b);
+        }
+    }
+
+    // Only floats get noise and drift. Counts and discrete states read the same on both copies
+    fn copy_value(
+        rng: &mut StdRng,
+        pair: &RedundantPair,
+        side: PairSide,
+        value: &SensorValue,
+        now_ms: u64,
+    ) -> SensorValue {
+        let SensorValue::Float(value) = *value else {
+            return value.clone();
+        };
+        let noise: f64 = rng.sample(StandardNormal);
+        let drift = pair
+            .drift
+            .as_ref()
+            .filter(|drift| drift.side == side && now_ms > drift.start_ms)
+            .map_or(0.0, |drift| {
+                drift.rate_per_s * (now_ms - drift.start_ms) as f64 / 1000.0
+            });
+        SensorValue::Float(value * (1.0 + noise * COPY_NOISE + drift))
+    }
+}
@@ -0,0 +1,473 @@
+use crate::error::{IoContext, Result, TelemetryError};
+use crate::models::{CaptureWindow, SampleRate};
+use std::path::Path;
+use std::str::SplitWhitespace;
+use tracing::info;
+
+use super::generator::SimulationState;
+
+// Scenario scripts let test engineers tweak a run without Rust changes.
+// One statement per line, `#` starts a comment:
+//
+//   at 45s set fuel_pressure_pa bias +5%
+//   at 30s..40s set chamber_pressure_pa scale 0.5
+//   at 60s set turbo_pump_rpm offset -1000
+//   at 90s..95s set vibration_z_g value 4.0
+//   at 45s log Fuel pressure bias injected
+//
+// Times take `ms`, `s`, `m`, `h` or `d` suffixes. A single time applies from then until the end of the run.
+// Adjustments change the values the sensors report, not the underlying physics.
+//
+// Hooks fire when conditions on the simulated state become true, e.g. each time the vehicle
+// climbs through 10 km. Conditions are `<field> <op> <number>` joined with `and`:
+//
+//   when altitude_m > 10000 log Passed 10 km
+//   when chamber_pressure_pa < 1000000 and altitude_m > 500 set fuel_pressure_pa bias -10% for 5s
+//   when altitude_m > 50000 rate 10
+//   when velocity_mps > 1000 capture 5s 20s
+//
+// `set` injects a fault from then on, or `for` a while. `rate` drops the output to that many Hz
+// from then on, it can't go above the run's own rate. `capture` marks a window from the first
+// time before the hook to the second after it in `{name}.windows.csv`
+#[derive(Debug, Clone, Default)]
+pub struct ScenarioScript {
+    statements: Vec<Statement>,
+    hooks: Vec<Hook>,
+}
+
+#[derive(Debug, Clone)]
+struct Hook {
+    conditions: Vec<Condition>,
+    action: HookAction,
+    // The line as written, for logs and capture labels
+    source: String,
+    // Hooks fire when their conditions go from false to true
+    was_true: bool,
+}
+
+// `<field> <op> <number>` on the simulated state
+#[derive(Debug, Clone)]
+pub(super) struct Condition {
+    field: String,
+    above: bool,
+    inclusive: bool,
+    threshold: f64,
+}
+
+#[derive(Debug, Clone)]
+enum HookAction {
+    Act(Action, Option<u64>),
+    Rate(SampleRate),
+    Capture { before_ms: u64, after_ms: u64 },
+}
+
+// What a fired hook needs the generator to do
+#[derive(Debug, Clone)]
+pub(super) enum HookEffect {
+    Rate(SampleRate),
+    Capture(CaptureWindow),
+}
+
+#[derive(Debug, Clone)]
+struct Statement {
+    start_ms: u64,
+    end_ms: Option<u64>,
+    action: Action,
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    Adjust {
+        field: String,
+        adjustment: Adjustment,
+    },
+    Log(String),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Adjustment {
+    Bias(f64),
+    Scale(f64),
+    Offset(f64),
+    Value(f64),
+}
+
+impl Adjustment {
+    fn apply(&self, value: f64) -> f64 {
+        match self {
+            Adjustment::Bias(percent) => value * (1.0 + percent / 100.0),
+            Adjustment::Scale(factor) => value * factor,
+            Adjustment::Offset(amount) => value + amount,
+            Adjustment::Value(fixed) => *fixed,
+        }
+    }
+}
+
+impl ScenarioScript {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let script = std::fs::read_to_string(path)
+            .io_context(|| format!("Failed to read scenario script {}", path.display()))?;
+        Self::parse(&script).map_err(|e| {
+            TelemetryError::config(format!("Invalid scenario script {}: {e}", path.display()))
+        })
+    }
+
+    pub fn parse(script: &str) -> Result<Self> {
+        let mut statements = Vec::new();
+        let mut hooks = Vec::new();
+
+        for (idx, line) in script.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let located = |e: TelemetryError| {
+                TelemetryError::config(format!("Line {}: {line}: {e}", idx + 1))
+            };
+            if line.starts_with("when ") {
+                hooks.push(Self::parse_hook(line).map_err(located)?);
+            } else {
+                statements.push(Self::parse_statement(line).map_err(located)?);
+            }
+        }
+
+        info!(
+            "Parsed scenario script with {} statements and {} hooks",
+            statements.len(),
+            hooks.len()
+        );
+        Ok(Self { statements, hooks })
+    }
+
+    fn parse_hook(line: &str) -> Result<Hook> {
+        let mut words = line.split_whitespace();
+        words.next();
+
+        let conditions = Condition::parse_all(&mut words)?;
+
+        let action = match words.clone().next() {
+            Some("rate") => {
+                words.next();
+                let hz = words
+                    .next()
+                    .ok_or_else(|| TelemetryError::config("Missing Hz after `rate`"))?;
+                HookAction::Rate(hz.trim_end_matches("hz").parse()?)
+            }
+            Some("capture") => {
+                words.next();
+                let mut time = |what: &str| {
+                    words
+                        .next()
+                        .ok_or_else(|| TelemetryError::config(format!("Missing {what} time")))
+                        .and_then(parse_time_ms)
+                };
+                HookAction::Capture {
+                    before_ms: time("before")?,
+                    after_ms: time("after")?,
+                }
+            }
+            _ => {
+                let action = Self::parse_action(&mut words)?;
+                HookAction::Act(action, None)
+            }
+        };
+        // `set` takes a trailing `for <time>`, parse_action leaves it
+        let action = match (action, words.next()) {
+            (HookAction::Act(action @ Action::Adjust { .. }, _), Some("for")) => {
+                let duration = words
+                    .next()
+                    .ok_or_else(|| TelemetryError::config("Missing time after `for`"))?;
+                HookAction::Act(action, Some(parse_time_ms(duration)?))
+            }
+            (action, None) => action,
+            (_, Some(extra)) => {
+                return Err(TelemetryError::config(format!(
+                    "Unexpected `{extra}` after the action"
+                )));
+            }
+        };
+
+        Ok(Hook {
+            conditions,
+            action,
+            source: line.to_string(),
+            was_true: false,
+        })
+    }
+
+    fn parse_statement(line: &str) -> Result<Statement> {
+        let mut words = line.split_whitespace();
+        if words.next() != Some("at") {
+            return Err(TelemetryError::config(
+                "Statements start with `at <time>` or `when <condition>`",
+            ));
+        }
+
+        let window = words
+            .next()
+            .ok_or_else(|| TelemetryError::config("Missing time after `at`"))?;
+        let (start_ms, end_ms) = match window.split_once("..") {
+            Some((start, end)) => (parse_time_ms(start)?, Some(parse_time_ms(end)?)),
+            None => (parse_time_ms(window)?, None),
+        };
+        if end_ms.is_some_and(|end| end < start_ms) {
+            return Err(TelemetryError::config("Window ends before it starts"));
+        }
+
+        let action = Self::parse_action(&mut words)?;
+        if let Some(extra) = words.next() {
+            return Err(TelemetryError::config(format!(
+                "Unexpected `{extra}` after the action"
+            )));
+        }
+
+        Ok(Statement {
+            start_ms,
+            end_ms,
+            action,
+        })
+    }
+
+    // `set <field> <adjustment> <amount>` or `log <message>`
+    fn parse_action(words: &mut SplitWhitespace) -> Result<Action> {
+        let action = match words.next() {
+            Some("set") => {
+                let field = words
+                    .next()
+                    .ok_or_else(|| TelemetryError::config("Missing field after `set`"))?;
+                if SimulationState::initialize().field_mut(field).is_none() {
+                    return Err(TelemetryError::config(format!("Unknown field {field}")));
+                }
+                let kind = words.next().ok_or_else(|| {
+                    TelemetryError::config("Missing bias, scale, offset or value")
+                })?;
+                let amount = words
+                    .next()
+                    .ok_or_else(|| TelemetryError::config("Missing amount"))?;
+                let adjustment = match kind {
+                    "bias" => Adjustment::Bias(parse_number(amount.trim_end_matches('%'))?),
+                    "scale" => Adjustment::Scale(parse_number(amount)?),
+                    "offset" => Adjustment::Offset(parse_number(amount)?),
+                    "value" => Adjustment::Value(parse_number(amount)?),
+                    other => {
+                        return Err(TelemetryError::config(format!(
+                            "Unknown adjustment {other}. Use bias, scale, offset or value"
+                        )));
+                    }
+                };
+                Action::Adjust {
+                    field: field.to_string(),
+                    adjustment,
+                }
+            }
+            Some("log") => Action::Log(words.collect::<Vec<_>>().join(" ")),
+            Some(other) => {
+                return Err(TelemetryError::config(format!(
+                    "Unknown action {other}. Use set or log, or rate or capture in hooks"
+                )));
+            }
+            None => return Err(TelemetryError::config("Missing action")),
+        };
+        Ok(action)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty() && self.hooks.is_empty()
+    }
+
+    // Run the statements of `other` alongside these, e.g. a scenario template's plus the user's
+    pub fn extend(&mut self, other: ScenarioScript) {
+        self.statements.extend(other.statements);
+        self.hooks.extend(other.hooks);
+    }
+
+    // Fire the hooks whose conditions just became true on the simulated state. Faults and logs
+    // are scheduled as statements from `now_ms`, the rest is left to the generator
+    pub(super) fn trigger_hooks(
+        &mut self,
+        state: &SimulationState,
+        now_ms: u64,
+    ) -> Vec<HookEffect> {
+        let mut effects = Vec::new();
+        if self.hooks.is_empty() {
+            return effects;
+        }
+        // Fields are only looked up by name through field_mut
+        let mut probe = state.clone();
+        for hook in &mut self.hooks {
+            let holds = hook
+                .conditions
+                .iter()
+                .all(|condition| condition.holds(&mut probe));
+            let fired = holds && !hook.was_true;
+            hook.was_true = holds;
+            if !fired {
+                continue;
+            }
+
+            info!(time_since_launch_ms = now_ms, "Hook fired: {}", hook.source);
+            match &hook.action {
+                HookAction::Act(action, duration_ms) => self.statements.push(Statement {
+                    start_ms: now_ms,
+                    end_ms: duration_ms.map(|duration| now_ms + duration),
+                    action: action.clone(),
+                }),
+                HookAction::Rate(rate) => effects.push(HookEffect::Rate(*rate)),
+                HookAction::Capture {
+                    before_ms,
+                    after_ms,
+                } => effects.push(HookEffect::Capture(CaptureWindow::around(
+                    now_ms,
+                    *before_ms,
+                    *after_ms,
+                    hook.source.clone(),
+                ))),
+            }
+        }
+        effects
+    }
+
+    // Copy of the state with every adjustment active at `now_ms` applied
+    pub(super) fn apply(&self, state: &SimulationState, now_ms: u64) -> SimulationState {
+        let mut adjusted = state.clone();
+
+        for statement in self.statements.iter().filter(|s| s.is_active(now_ms)) {
+            if let Action::Adjust { field, adjustment } = &statement.action
+                && let Some(value) = adjusted.field_mut(field)
+            {
+                *value = adjustment.apply(*value);
+            }
+        }
+
+        adjusted
+    }
+
+    // Start time and field of every adjustment, in time order
+    pub(super) fn adjustment_starts(&self) -> Vec<(u64, String)> {
+        let mut starts: Vec<(u64, String)> = self
+            .statements
+            .iter()
+            .filter_map(|statement| match &statement.action {
+                Action::Adjust { field, .. } => Some((statement.start_ms, field.clone())),
+                Action::Log(_) => None,
+            })
+            .collect();
+        starts.sort();
+        starts
+    }
+
+    // Adjustments that kick in during (previous_ms, now_ms], counted as anomalies
+    pub(super) fn started_adjustments(&self, previous_ms: Option<u64>, now_ms: u64) -> usize {
+        self.statements
+            .iter()
+            .filter(|statement| {
+                matches!(statement.action, Action::Adjust { .. })
+                    && previous_ms.is_none_or(|previous| statement.start_ms > previous)
+                    && statement.start_ms <= now_ms
+            })
+            .count()
+    }
+
+    // Log messages scheduled in (previous_ms, now_ms]. The first step passes None to include t=0
+    pub(super) fn fire_logs(&self, previous_ms: Option<u64>, now_ms: u64) {
+        for statement in &self.statements {
+            if let Action::Log(message) = &statement.action
+                && previous_ms.is_none_or(|previous| statement.start_ms > previous)
+                && statement.start_ms <= now_ms
+            {
+                info!(time_since_launch_ms = now_ms, "Scenario event: {}", message);
+            }
+        }
+    }
+}
+
+impl Condition {
+    // One or more conditions joined with `and`
+    pub(super) fn parse_all(words: &mut SplitWhitespace) -> Result<Vec<Condition>> {
+        let mut conditions = Vec::new();
+        loop {
+            let field = words
+                .next()
+                .ok_or_else(|| TelemetryError::config("Missing condition"))?;
+            if SimulationState::initialize().field_mut(field).is_none() {
+                return Err(TelemetryError::config(format!("Unknown field {field}")));
+            }
+            let (above, inclusive) = match words.next() {
+                Some(">") => (true, false),
+                Some(">=") => (true, true),
+                Some("<") => (false, false),
+                Some("<=") => (false, true),
+                _ => {
+                    return Err(TelemetryError::config(format!(
+                        "Compare {field} with >, >=, < or <="
+                    )));
+                }
+            };
+            let threshold = words
+                .next()
+                .ok_or_else(|| TelemetryError::config("Missing number in condition"))?;
+            conditions.push(Condition {
+                field: field.to_string(),
+                above,
+                inclusive,
+                threshold: parse_number(threshold)?,
+            });
+            if words.clone().next() != Some("and") {
+                break;
+            }
+            words.next();
+        }
+        Ok(conditions)
+    }
+
+    pub(super) fn holds(&self, state: &mut SimulationState) -> bool {
+        let Some(value) = state.field_mut(&self.field).map(|value| *value) else {
+            return false;
+        };
+        match (self.above, self.inclusive) {
+            (true, false) => value > self.threshold,
+            (true, true) => value >= self.threshold,
+            (false, false) => value < self.threshold,
+            (false, true) => value <= self.threshold,
+        }
+    }
+}
+
+impl Statement {
+    fn is_active(&self, now_ms: u64) -> bool {
+        now_ms >= self.start_ms && self.end_ms.is_none_or(|end| now_ms <= end)
+    }
+}
+
+// Times like `500ms`, `45s`, `2m`, `6h` or `1d`
+pub fn parse_time_ms(time: &str) -> Result<u64> {
+    let (number, multiplier) = if let Some(ms) = time.strip_suffix("ms") {
+        (ms, 1.0)
+    } else if let Some(s) = time.strip_suffix('s') {
+        (s, 1_000.0)
+    } else if let Some(m) = time.strip_suffix('m') {
+        (m, 60_000.0)
+    } else if let Some(h) = time.strip_suffix('h') {
+        (h, 3_600_000.0)
+    } else if let Some(d) = time.strip_suffix('d') {
+        (d, 86_400_000.0)
+    } else {
+        return Err(TelemetryError::config(format!(
+            "Time {time} needs a unit of ms, s, m, h or d"
+        )));
+    };
+
+    let value = parse_number(number)?;
+    if value < 0.0 {
+        return Err(TelemetryError::config(format!(
+            "Time {time} can't be negative"
+        )));
+    }
+    Ok((value * multiplier).round() as u64)
+}
+
+pub(super) fn parse_number(number: &str) -> Result<f64> {
+    number
+        .parse()
+        .map_err(|_| TelemetryError::config(format!("Invalid number {number}")))
+}
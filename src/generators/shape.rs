@@ -0,0 +1,123 @@
+use crate::error::TelemetryError;
+use crate::models::{SensorEnum, SensorValue, TelemetryReading};
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::f64::consts::TAU;
+use std::str::FromStr;
+
+// Mixed into the run seed so shaped values get their own stream and leave the sensor noise untouched
+const SHAPE_SEED_SALT: u64 = 0x5A4E_0001;
+
+// Level the constant, slow and step shapes sit around
+const SHAPE_BASE: f64 = 1000.0;
+const SLOW_AMPLITUDE: f64 = 100.0;
+const SLOW_PERIOD_MS: f64 = 600_000.0;
+const STEP_EVERY_MS: u64 = 10_000;
+
+// Data shapes that stress different storage encodings, replacing the physics for a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ValueShape {
+    // Same value every reading. Best case for run length and dictionary encodings
+    Constant,
+    // Smooth wave with a 10 minute period. Small deltas suit gorilla and delta encodings
+    Slow,
+    // Uniformly random values with full mantissas. Worst case for every encoding
+    Random,
+    // Holds a level and jumps to a new one every 10 seconds
+    Step,
+}
+
+// Shape for one sensor by any of its exported names, e.g. `F_pa=random`. `*` matches every sensor
+#[derive(Debug, Clone)]
+pub struct SensorShape {
+    sensor: Option<SensorEnum>,
+    shape: ValueShape,
+}
+
+// Overrides the reported values of the shaped sensors after they are generated
+pub struct ValueShapes {
+    rng: StdRng,
+    shapes: Vec<SensorShape>,
+    // Current level and when it changes next for stepped sensors
+    steps: HashMap<SensorEnum, (f64, u64)>,
+}
+
+impl ValueShapes {
+    pub fn new(shapes: Vec<SensorShape>, seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed ^ SHAPE_SEED_SALT),
+            shapes,
+            steps: HashMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shapes.is_empty()
+    }
+
+    // Reshape the readings of the timestep at `now_ms`, which start at `step_start`
+    pub(super) fn apply(
+        &mut self,
+        now_ms: u64,
+        readings: &mut [TelemetryReading],
+        step_start: usize,
+    ) {
+        for reading in &mut readings[step_start..] {
+            // Later shapes win so `*=random F_pa=constant` works as expected
+            let Some(shape) = self
+                .shapes
+                .iter()
+                .rev()
+                .find(|s| s.sensor.is_none_or(|sensor| sensor == reading.sensor))
+                .map(|s| s.shape)
+            else {
+                continue;
+            };
+
+            let value = match shape {
+                ValueShape::Constant => SHAPE_BASE,
+                ValueShape::Slow => {
+                    SHAPE_BASE + SLOW_AMPLITUDE * (TAU * now_ms as f64 / SLOW_PERIOD_MS).sin()
+                }
+                ValueShape::Random => self.rng.gen_range(-SHAPE_BASE..SHAPE_BASE),
+                ValueShape::Step => {
+                    let (level, until_ms) = self.steps.entry(reading.sensor).or_insert((0.0, 0));
+                    if now_ms >= *until_ms {
+                        *level = SHAPE_BASE + self.rng.gen_range(-10..=10) as f64 * 10.0;
+                        *until_ms = now_ms + STEP_EVERY_MS;
+                    }
+                    *level
+                }
+            };
+            reading.value = SensorValue::Float(value);
+        }
+    }
+}
+
+impl FromStr for SensorShape {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (sensor, shape) = s.split_once('=').ok_or_else(|| {
+            TelemetryError::config(format!("Value shape {s} should look like <sensor>=<shape>"))
+        })?;
+        let (sensor, shape) = (sensor.trim(), shape.trim());
+
+        let sensor = match sensor {
+            "*" => None,
+            name => Some(
+                SensorEnum::from_name(name)
+                    .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {name}")))?,
+            ),
+        };
+        let shape = ValueShape::from_str(shape, true).map_err(|_| {
+            TelemetryError::config(format!(
+                "Unknown value shape {shape}. Use constant, slow, random or step"
+            ))
+        })?;
+
+        Ok(Self { sensor, shape })
+    }
+}
@@ -0,0 +1,55 @@
+use serde::Serialize;
+
+use super::thermal::air_density;
+
+// Key outcomes of a flight, worked out from the simulated state rather than what the sensors
+// report, so faults and noise don't skew them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FlightSummary {
+    // Peak dynamic pressure and when it happened
+    pub max_q_pa: f64,
+    pub max_q_time_s: f64,
+    // Velocity the last time the engine was burning, at shutdown or at the end of the run
+    pub burnout_velocity_mps: f64,
+    pub max_altitude_m: f64,
+    // Faults injected by scripts, fuzzing or chaos that went off during the run
+    pub anomalies: usize,
+}
+
+impl FlightSummary {
+    pub(super) fn record(
+        &mut self,
+        time_since_launch_ms: u64,
+        altitude_m: f64,
+        velocity_mps: f64,
+        thrust_n: f64,
+    ) {
+        let q_pa = 0.5 * air_density(altitude_m) * velocity_mps * velocity_mps;
+        if q_pa > self.max_q_pa {
+            self.max_q_pa = q_pa;
+            self.max_q_time_s = time_since_launch_ms as f64 / 1000.0;
+        }
+        if thrust_n > 0.0 {
+            self.burnout_velocity_mps = velocity_mps;
+        }
+        self.max_altitude_m = self.max_altitude_m.max(altitude_m);
+    }
+}
+
+// Something worth looking at in a run, e.g. to center a capture window on
+#[derive(Debug, Clone)]
+pub struct FlightEvent {
+    pub time_since_launch_ms: u64,
+    pub kind: FlightEventKind,
+    pub label: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum FlightEventKind {
+    // Stage separation, when the pyros fire
+    Staging,
+    // Faults from scenario scripts and fuzzing. Chaos faults are random as the run goes, so
+    // they aren't known up front
+    #[value(alias = "anomalies")]
+    Anomaly,
+}
@@ -0,0 +1,86 @@
+use super::TelemetryGenerator;
+use crate::error::{Result, TelemetryError};
+use crate::models::TelemetryDataset;
+use std::ops::ControlFlow;
+use tokio::sync::mpsc::{self, Receiver};
+use tokio::task::{JoinError, JoinHandle};
+
+// Generating is CPU bound, so on a tokio runtime it goes to the blocking pool and the executor
+// stays free for sinks, servers and other missions. Any number of runs can go at once, each on a
+// blocking thread of its own
+
+impl TelemetryGenerator {
+    // `generate` on the blocking pool. Hands the generator back with the dataset so its summary,
+    // alerts and events can still be read
+    pub async fn generate_async(
+        mut self,
+        disable_progress: bool,
+    ) -> Result<(Self, TelemetryDataset)> {
+        tokio::task::spawn_blocking(move || {
+            let dataset = self.generate(disable_progress)?;
+            Ok((self, dataset))
+        })
+        .await
+        .map_err(join_error)?
+    }
+
+    // `generate_chunked` on the blocking pool, with the chunks coming back through a queue of
+    // `queue_chunks` so an async sink can await each write. The generator waits while the queue
+    // is full, so it never gets further ahead of the sink than that. Dropping the task stops the
+    // run at its next chunk
+    pub fn spawn_chunked(
+        mut self,
+        disable_progress: bool,
+        chunk_readings: usize,
+        queue_chunks: usize,
+    ) -> GenerationTask {
+        let (sender, chunks) = mpsc::channel(queue_chunks.max(1));
+        let handle = tokio::task::spawn_blocking(move || {
+            self.generate_chunked(disable_progress, chunk_readings, |chunk| -> Result<_> {
+                let capacity = chunk.readings.capacity();
+                let readings = std::mem::replace(&mut chunk.readings, Vec::with_capacity(capacity));
+                let chunk = TelemetryDataset {
+                    readings,
+                    config: chunk.config.clone(),
+                    launch_time: chunk.launch_time,
+                };
+                // A closed queue means nobody is listening anymore
+                match sender.blocking_send(chunk) {
+                    Ok(()) => Ok(ControlFlow::Continue(())),
+                    Err(_) => Ok(ControlFlow::Break(())),
+                }
+            })?;
+            Ok(self)
+        });
+        GenerationTask { chunks, handle }
+    }
+}
+
+// A run generating on the blocking pool. Take chunks with `next_chunk` until it's out, then
+// `finish` for the generator or the error that ended the run
+pub struct GenerationTask {
+    chunks: Receiver<TelemetryDataset>,
+    handle: JoinHandle<Result<TelemetryGenerator>>,
+}
+
+impl GenerationTask {
+    // The next chunk, or None once the run is over
+    pub async fn next_chunk(&mut self) -> Option<TelemetryDataset> {
+        self.chunks.recv().await
+    }
+
+    // Wait for the run to end and hand the generator back. Chunks not taken yet are dropped and
+    // stop the run early
+    pub async fn finish(self) -> Result<TelemetryGenerator> {
+        drop(self.chunks);
+        self.handle.await.map_err(join_error)?
+    }
+}
+
+// A panic on the blocking thread carries on in the caller, like it would have in `generate`
+fn join_error(error: JoinError) -> TelemetryError {
+    match error.try_into_panic() {
+        Ok(panic) => std::panic::resume_unwind(panic),
+        Err(error) => TelemetryError::GenerationError(format!("Generation task failed: {error}")),
+    }
+}
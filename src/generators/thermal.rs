@@ -0,0 +1,87 @@
+// Lumped first order thermal model for structure and component temperatures. Each node relaxes
+// toward its surroundings with its own time constant, picks up aero heating in the atmosphere and
+// cools toward deep space once out of it
+
+// Sea level air density and pressure and scale height for the exponential atmosphere
+const SEA_LEVEL_DENSITY_KGPM3: f64 = 1.225;
+pub(super) const SEA_LEVEL_PRESSURE_PA: f64 = 101_325.0;
+const SCALE_HEIGHT_M: f64 = 8_500.0;
+// Above this the air is too thin to matter and nodes radiate to space instead
+const VACUUM_ALTITUDE_M: f64 = 100_000.0;
+const SPACE_SINK_K: f64 = 200.0;
+
+#[derive(Debug, Clone, Copy)]
+struct ThermalNode {
+    // Seconds to close ~63% of the gap to the sink
+    tau_s: f64,
+    // K/s per unit of rho * v^3
+    aero_gain: f64,
+    // Internal heating in K/s, e.g. electronics
+    internal_k_per_s: f64,
+}
+
+const INTERSTAGE: ThermalNode = ThermalNode {
+    tau_s: 60.0,
+    aero_gain: 1e-7,
+    internal_k_per_s: 0.0,
+};
+const AVIONICS_BAY: ThermalNode = ThermalNode {
+    tau_s: 600.0,
+    aero_gain: 1e-8,
+    internal_k_per_s: 0.02,
+};
+const COPV: ThermalNode = ThermalNode {
+    tau_s: 300.0,
+    aero_gain: 3e-8,
+    internal_k_per_s: 0.0,
+};
+
+#[derive(Debug, Clone)]
+pub(super) struct ThermalState {
+    pub(super) interstage_k: f64,
+    pub(super) avionics_bay_k: f64,
+    pub(super) copv_k: f64,
+}
+
+impl ThermalState {
+    pub(super) fn initialize() -> Self {
+        Self {
+            interstage_k: 288.15,
+            // Electronics run warm on the pad
+            avionics_bay_k: 300.0,
+            copv_k: 288.15,
+        }
+    }
+
+    pub(super) fn step(&mut self, altitude_m: f64, velocity_mps: f64, time_step_s: f64) {
+        let altitude_m = altitude_m.max(0.0);
+        let (sink_k, heating) = if altitude_m < VACUUM_ALTITUDE_M {
+            // Standard atmosphere lapse rate down to the tropopause
+            let air_k = (288.15 - 0.0065 * altitude_m).max(216.65);
+            (air_k, air_density(altitude_m) * velocity_mps.abs().powi(3))
+        } else {
+            (SPACE_SINK_K, 0.0)
+        };
+
+        for (temperature_k, node) in [
+            (&mut self.interstage_k, INTERSTAGE),
+            (&mut self.avionics_bay_k, AVIONICS_BAY),
+            (&mut self.copv_k, COPV),
+        ] {
+            let rate = (sink_k - *temperature_k) / node.tau_s
+                + node.aero_gain * heating
+                + node.internal_k_per_s;
+            *temperature_k += rate * time_step_s;
+        }
+    }
+}
+
+// Air density in kg/m³ from the exponential atmosphere
+pub(super) fn air_density(altitude_m: f64) -> f64 {
+    SEA_LEVEL_DENSITY_KGPM3 * (-altitude_m.max(0.0) / SCALE_HEIGHT_M).exp()
+}
+
+// Air pressure in Pa from the same atmosphere
+pub(super) fn ambient_pressure_pa(altitude_m: f64) -> f64 {
+    SEA_LEVEL_PRESSURE_PA * (-altitude_m.max(0.0) / SCALE_HEIGHT_M).exp()
+}
@@ -0,0 +1,129 @@
+use crate::error::{Result, TelemetryError};
+use serde::Deserialize;
+
+use super::thermal::{air_density, ambient_pressure_pa};
+
+pub(super) const STANDARD_GRAVITY_MPS2: f64 = 9.80665;
+const EARTH_RADIUS_M: f64 = 6_371_000.0;
+
+// Mass and shape of the vehicle, for the forces on it. Masses follow from the liftoff thrust so
+// the flight looks the same at any engine rating, and each stage's propellant lasts its share of
+// the run like the tanks in `FeedState`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub(super) struct Vehicle {
+    // Sea level thrust over weight on the pad
+    thrust_to_weight: f64,
+    // Share of each stage's mass at ignition that's propellant
+    propellant_fraction: f64,
+    // Mass left after each separation, as a share of the mass before the stack was lit
+    upper_stage_fraction: f64,
+    drag_coefficient: f64,
+    reference_area_m2: f64,
+}
+
+impl Default for Vehicle {
+    fn default() -> Self {
+        Self {
+            thrust_to_weight: 1.5,
+            propellant_fraction: 0.6,
+            upper_stage_fraction: 0.25,
+            drag_coefficient: 0.3,
+            // A 3.7 m body
+            reference_area_m2: 10.5,
+        }
+    }
+}
+
+// Engine and attitude over a step, to work the forces out from
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Propulsion {
+    pub vacuum_thrust_n: f64,
+    pub exit_area_m2: f64,
+    pub pitch_deg: f64,
+}
+
+impl Vehicle {
+    pub(super) fn check(&self) -> Result<()> {
+        let positive = [
+            ("thrust_to_weight", self.thrust_to_weight),
+            ("upper_stage_fraction", self.upper_stage_fraction),
+        ];
+        let non_negative = [
+            ("drag_coefficient", self.drag_coefficient),
+            ("reference_area_m2", self.reference_area_m2),
+        ];
+        for (name, value) in positive {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(TelemetryError::config(format!(
+                    "vehicle.{name} has to be above 0"
+                )));
+            }
+        }
+        for (name, value) in non_negative {
+            if !value.is_finite() || value < 0.0 {
+                return Err(TelemetryError::config(format!(
+                    "vehicle.{name} can't be negative"
+                )));
+            }
+        }
+        if !(0.0..1.0).contains(&self.propellant_fraction) {
+            return Err(TelemetryError::config(
+                "vehicle.propellant_fraction has to be at least 0 and below 1",
+            ));
+        }
+        if self.upper_stage_fraction >= 1.0 {
+            return Err(TelemetryError::config(
+                "vehicle.upper_stage_fraction has to be below 1",
+            ));
+        }
+        Ok(())
+    }
+
+    // Mass of the stack with `stages_dropped` stages gone and `used` of the current stage's
+    // propellant burnt, from 0 to 1
+    pub(super) fn mass_kg(&self, liftoff_thrust_n: f64, stages_dropped: u64, used: f64) -> f64 {
+        let liftoff_kg = liftoff_thrust_n / (self.thrust_to_weight * STANDARD_GRAVITY_MPS2);
+        let ignition_kg = liftoff_kg * self.upper_stage_fraction.powi(stages_dropped as i32);
+        ignition_kg * (1.0 - self.propellant_fraction * used.clamp(0.0, 1.0))
+    }
+
+    // Along the flight path: thrust less the ambient pressure on the nozzle exits, drag against
+    // the velocity and gravity's share along a path pitched up `pitch_deg`. Held down on the pad
+    // until the thrust beats the weight
+    pub(super) fn acceleration(
+        &self,
+        propulsion: Propulsion,
+        mass_kg: f64,
+        altitude_m: f64,
+        velocity_mps: f64,
+    ) -> f64 {
+        let thrust_n = thrust_n(
+            propulsion.vacuum_thrust_n,
+            propulsion.exit_area_m2,
+            altitude_m,
+        );
+        let drag_n = 0.5
+            * air_density(altitude_m)
+            * velocity_mps
+            * velocity_mps.abs()
+            * self.drag_coefficient
+            * self.reference_area_m2;
+        let gravity_mps2 = STANDARD_GRAVITY_MPS2
+            * (EARTH_RADIUS_M / (EARTH_RADIUS_M + altitude_m.max(0.0))).powi(2);
+
+        let acceleration = (thrust_n - drag_n) / mass_kg.max(f64::EPSILON)
+            - gravity_mps2 * propulsion.pitch_deg.to_radians().sin();
+        if altitude_m <= 0.0 && velocity_mps <= 0.0 {
+            acceleration.max(0.0)
+        } else {
+            acceleration
+        }
+    }
+}
+
+// The air pushing on the nozzle exits costs thrust low down. A throttled engine at sea level can
+// lose it all, as the nozzle flow separates
+pub(super) fn thrust_n(vacuum_thrust_n: f64, exit_area_m2: f64, altitude_m: f64) -> f64 {
+    (vacuum_thrust_n - ambient_pressure_pa(altitude_m) * exit_area_m2).max(0.0)
+}
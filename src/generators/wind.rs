@@ -0,0 +1,228 @@
+use crate::error::TelemetryError;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand_distr::{Distribution, StandardNormal};
+use std::str::FromStr;
+
+use super::script::parse_number;
+use super::thermal::air_density;
+
+// Mixed into the run seed so gusts leave the sensor noise stream untouched
+const WIND_SEED_SALT: u64 = 0x5717_D6B5;
+
+// Vehicle heads due east out of the Cape, offset by its yaw
+const LAUNCH_AZIMUTH_DEG: f64 = 90.0;
+
+// Dynamic pressure around max Q where the responses below are sized
+const REFERENCE_Q_PA: f64 = 35_000.0;
+
+// Responses to angle of attack at the reference dynamic pressure. The autopilot holds most
+// of the attitude error, what's left shows up in the angles and rates
+const ATTITUDE_DEG_PER_DEG: f64 = 0.3;
+const ROLL_COUPLING: f64 = 0.1;
+const LATERAL_G_PER_RAD: f64 = 6.0;
+// Extra vibration per unit of gust angle of attack, as a fraction of the engine's own
+const BUFFET_PER_RAD: f64 = 8.0;
+
+// Gust correlation length, turbulence is roughly this big a parcel of air
+const GUST_LENGTH_M: f64 = 300.0;
+
+// Below this the airspeed is taken as this, so the pad doesn't divide by zero
+const MIN_AIRSPEED_MPS: f64 = 10.0;
+
+// Wind at one altitude, e.g. `10000:45:270` for 45 m/s from the west at 10 km. Direction is
+// where the wind blows from, meteorological style
+#[derive(Debug, Clone, Copy)]
+pub struct WindLayer {
+    pub altitude_m: f64,
+    pub speed_mps: f64,
+    pub from_deg: f64,
+}
+
+// Ready made wind profiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum WindPreset {
+    // Light surface breeze, barely any turbulence
+    Calm,
+    // Fresh westerlies with moderate gusts
+    Breezy,
+    // Strong jet stream around 12 km and heavy turbulence in the shear
+    JetStream,
+}
+
+impl WindPreset {
+    pub fn layers(&self) -> Vec<WindLayer> {
+        let specs: &[&str] = match self {
+            WindPreset::Calm => &["0:2:180", "5000:5:200", "15000:3:220"],
+            WindPreset::Breezy => &["0:8:250", "3000:15:260", "10000:30:270", "20000:10:270"],
+            WindPreset::JetStream => &[
+                "0:10:240",
+                "5000:25:255",
+                "12000:70:270",
+                "16000:35:275",
+                "25000:10:280",
+            ],
+        };
+        specs
+            .iter()
+            .map(|spec| spec.parse().expect("Preset layers are valid"))
+            .collect()
+    }
+
+    // Gust standard deviation in m/s
+    pub fn gust_intensity(&self) -> f64 {
+        match self {
+            WindPreset::Calm => 0.5,
+            WindPreset::Breezy => 3.0,
+            WindPreset::JetStream => 6.0,
+        }
+    }
+}
+
+// Wind during atmospheric flight. The mean wind comes from the layers, interpolated by
+// altitude and held past either end, and seeded gusts are layered on top. Both tilt the
+// airflow over the vehicle, and the resulting angle of attack scaled by dynamic pressure
+// perturbs the reported attitude, rates, lateral loads and vibration. Thin air above the
+// layers fades it all out on its own
+#[derive(Debug, Clone)]
+pub struct Wind {
+    layers: Vec<WindLayer>,
+    gust_intensity_mps: f64,
+    rng: StdRng,
+    // Gust velocity in and across the flight plane
+    gust_mps: [f64; 2],
+    // Attitude offset from the last step, to turn into rates
+    previous_offset_deg: Option<[f64; 3]>,
+}
+
+impl Wind {
+    pub fn new(mut layers: Vec<WindLayer>, gust_intensity_mps: f64, seed: u64) -> Self {
+        layers.sort_by(|a, b| a.altitude_m.total_cmp(&b.altitude_m));
+        Self {
+            layers,
+            gust_intensity_mps: gust_intensity_mps.max(0.0),
+            rng: StdRng::seed_from_u64(seed ^ WIND_SEED_SALT),
+            gust_mps: [0.0; 2],
+            previous_offset_deg: None,
+        }
+    }
+
+    // Mean wind at `altitude_m` as speed and from direction
+    fn mean_at(&self, altitude_m: f64) -> (f64, f64) {
+        let (Some(first), Some(last)) = (self.layers.first(), self.layers.last()) else {
+            return (0.0, 0.0);
+        };
+        if altitude_m <= first.altitude_m {
+            return (first.speed_mps, first.from_deg);
+        }
+        if altitude_m >= last.altitude_m {
+            return (last.speed_mps, last.from_deg);
+        }
+        let upper = self
+            .layers
+            .iter()
+            .position(|layer| layer.altitude_m > altitude_m)
+            .unwrap_or(self.layers.len() - 1);
+        let (below, above) = (self.layers[upper - 1], self.layers[upper]);
+        let t = (altitude_m - below.altitude_m) / (above.altitude_m - below.altitude_m);
+        // Shortest way round the compass
+        let turn = (above.from_deg - below.from_deg + 540.0).rem_euclid(360.0) - 180.0;
+        (
+            below.speed_mps + t * (above.speed_mps - below.speed_mps),
+            below.from_deg + t * turn,
+        )
+    }
+
+    // Step the gusts and work out how the airflow perturbs what the sensors report
+    pub(super) fn step(
+        &mut self,
+        altitude_m: f64,
+        velocity_mps: f64,
+        attitude_deg: [f64; 3],
+        time_step_s: f64,
+    ) -> WindEffect {
+        let [roll_deg, pitch_deg, yaw_deg] = attitude_deg;
+        let airspeed = velocity_mps.abs().max(MIN_AIRSPEED_MPS);
+
+        // First order Gauss-Markov gusts, correlated over the time it takes to fly through one
+        let correlation = (-airspeed * time_step_s / GUST_LENGTH_M).exp();
+        let drive = self.gust_intensity_mps * (1.0 - correlation * correlation).sqrt();
+        for gust in &mut self.gust_mps {
+            let z: f64 = StandardNormal.sample(&mut self.rng);
+            *gust = *gust * correlation + drive * z;
+        }
+
+        // Split the mean wind into components in and across the flight plane
+        let (speed, from_deg) = self.mean_at(altitude_m);
+        let towards = (from_deg + 180.0 - LAUNCH_AZIMUTH_DEG - yaw_deg).to_radians();
+        let in_plane = speed * towards.cos() * pitch_deg.to_radians().cos() + self.gust_mps[0];
+        let cross = speed * towards.sin() + self.gust_mps[1];
+
+        // Into body axes, so once the vehicle has rolled a crosswind shows in pitch as well as yaw
+        let (flight_alpha, flight_beta) = ((in_plane / airspeed).atan(), (cross / airspeed).atan());
+        let (roll_sin, roll_cos) = roll_deg.to_radians().sin_cos();
+        let alpha = flight_alpha * roll_cos + flight_beta * roll_sin;
+        let beta = flight_beta * roll_cos - flight_alpha * roll_sin;
+        let gust_alpha = self.gust_mps[0].hypot(self.gust_mps[1]) / airspeed;
+
+        let q_ratio = 0.5 * air_density(altitude_m) * airspeed * airspeed / REFERENCE_Q_PA;
+
+        let attitude_deg = [
+            ROLL_COUPLING * beta.to_degrees(),
+            ATTITUDE_DEG_PER_DEG * alpha.to_degrees(),
+            ATTITUDE_DEG_PER_DEG * beta.to_degrees(),
+        ]
+        .map(|offset| offset * q_ratio);
+        let rates_dps = match self.previous_offset_deg {
+            Some(previous) if time_step_s > 0.0 => {
+                std::array::from_fn(|axis| (attitude_deg[axis] - previous[axis]) / time_step_s)
+            }
+            _ => [0.0; 3],
+        };
+        self.previous_offset_deg = Some(attitude_deg);
+
+        WindEffect {
+            attitude_deg,
+            rates_dps,
+            lateral_g: [alpha, beta].map(|angle| LATERAL_G_PER_RAD * angle * q_ratio),
+            buffet: 1.0 + BUFFET_PER_RAD * gust_alpha * q_ratio,
+        }
+    }
+}
+
+// Offsets the wind adds to one step's reported state
+#[derive(Debug, Clone, Copy)]
+pub(super) struct WindEffect {
+    // Roll, pitch and yaw
+    pub(super) attitude_deg: [f64; 3],
+    pub(super) rates_dps: [f64; 3],
+    // Side loads on the body x and y accelerometers
+    pub(super) lateral_g: [f64; 2],
+    // Multiplier on the vibration amplitude
+    pub(super) buffet: f64,
+}
+
+// Parse `<altitude m>:<speed m/s>:<from deg>`
+impl FromStr for WindLayer {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').map(str::trim).collect();
+        let [altitude, speed, from] = parts[..] else {
+            return Err(TelemetryError::config(format!(
+                "Wind layer {s} should look like <altitude m>:<speed m/s>:<from deg>"
+            )));
+        };
+        let layer = Self {
+            altitude_m: parse_number(altitude)?,
+            speed_mps: parse_number(speed)?,
+            from_deg: parse_number(from)?.rem_euclid(360.0),
+        };
+        if layer.speed_mps < 0.0 {
+            return Err(TelemetryError::config(format!(
+                "Wind speed of {s} can't be negative"
+            )));
+        }
+        Ok(layer)
+    }
+}
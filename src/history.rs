@@ -0,0 +1,466 @@
+use crate::{GenerateArgs, GenerateReport};
+use chrono::{DateTime, Utc};
+use num_format::{Locale, ToFormattedString};
+use rusqlite::{Connection, ErrorCode, params};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use telemetry_generator::error::{Result, TelemetryError};
+use telemetry_generator::exporters::run_output_files;
+use tracing::{info, warn};
+
+// Completed runs are kept in the `runs` and `run_outputs` tables of `{output_dir}/runs.sqlite`,
+// so we can trace which dataset a test consumed. `runs list` and `runs show <id>` read them back,
+// and the daemon records its jobs in `{run_dir}/runs.sqlite` and serves them on `/runs`
+pub const HISTORY_FILE: &str = "runs.sqlite";
+
+// How long a write waits on another run's before giving up
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Schema version in `PRAGMA user_version`. 1 added the status and error of a run
+const SCHEMA_VERSION: i64 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl RunStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        [Self::Done, Self::Failed, Self::Cancelled]
+            .into_iter()
+            .find(|status| status.name() == name)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunRecord {
+    // Random hex id. `runs show` takes any unique prefix of it
+    pub id: String,
+    // Only daemon jobs end any other way than done
+    pub status: RunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub finished_at: DateTime<Utc>,
+    pub elapsed_s: f64,
+    // Arguments that reproduce the run
+    pub command: Vec<String>,
+    // `{plan name}/{run name}` for runs from an experiment plan
+    pub experiment: Option<String>,
+    pub launch_id: String,
+    pub seed: u64,
+    pub sample_rate_hz: String,
+    pub duration_s: usize,
+    pub readings: usize,
+    pub outputs: Vec<RunOutput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunOutput {
+    pub path: PathBuf,
+    pub bytes: u64,
+    // Missing for the checksum sidecar itself
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl RunRecord {
+    pub fn new(
+        args: &GenerateArgs,
+        command: Vec<String>,
+        experiment: Option<String>,
+        report: &GenerateReport,
+        elapsed: Duration,
+    ) -> Self {
+        let outputs = run_output_files(&report.output_dir, &report.output_name)
+            .into_iter()
+            .map(|path| RunOutput {
+                bytes: std::fs::metadata(&path)
+                    .map(|m| m.len())
+                    .unwrap_or_default(),
+                sha256: report
+                    .checksums
+                    .iter()
+                    .find(|checksum| checksum.path == path)
+                    .map(|checksum| checksum.sha256.clone()),
+                path,
+            })
+            .collect();
+
+        Self {
+            id: new_run_id(),
+            status: RunStatus::Done,
+            error: None,
+            finished_at: Utc::now(),
+            elapsed_s: elapsed.as_secs_f64(),
+            command,
+            experiment,
+            launch_id: args.launch_id.clone(),
+            seed: args.seed,
+            sample_rate_hz: report.sample_rate_hz.to_string(),
+            duration_s: report.duration_s,
+            readings: report.readings,
+            outputs,
+        }
+    }
+}
+
+// 64 random bits, so runs recorded side by side in one history never share an id
+pub fn new_run_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+// Add the run to the history. Failing to record it only warns, the data is already written
+pub fn record_run(history_dir: &Path, record: &RunRecord) {
+    let history_file = history_dir.join(HISTORY_FILE);
+    match insert(&history_file, record) {
+        Ok(()) => info!("Recorded run {} in {}", record.id, history_file.display()),
+        Err(e) => warn!(
+            "Failed to record the run in the history: {:#}",
+            anyhow::Error::from(e)
+        ),
+    }
+}
+
+// Open the history, creating its tables the first time
+fn open(history_file: &Path) -> Result<Connection> {
+    let connection = Connection::open(history_file).map_err(db_error(history_file))?;
+    // Parallel experiment runs write from several threads, each on its own connection
+    connection
+        .busy_timeout(BUSY_TIMEOUT)
+        .and_then(|()| {
+            connection.execute_batch(
+                "CREATE TABLE IF NOT EXISTS runs (
+                    id TEXT PRIMARY KEY,
+                    finished_at TEXT NOT NULL,
+                    elapsed_s REAL NOT NULL,
+                    command TEXT NOT NULL,
+                    experiment TEXT,
+                    launch_id TEXT NOT NULL,
+                    seed INTEGER NOT NULL,
+                    sample_rate_hz TEXT NOT NULL,
+                    duration_s INTEGER NOT NULL,
+                    readings INTEGER NOT NULL
+                );
+                CREATE TABLE IF NOT EXISTS run_outputs (
+                    run_id TEXT NOT NULL REFERENCES runs(id),
+                    path TEXT NOT NULL,
+                    bytes INTEGER NOT NULL,
+                    sha256 TEXT
+                );
+                CREATE INDEX IF NOT EXISTS run_outputs_run_id ON run_outputs(run_id);",
+            )
+        })
+        .and_then(|()| migrate(&connection))
+        .map_err(db_error(history_file))?;
+    Ok(connection)
+}
+
+// Bring a history written by an older build up to `SCHEMA_VERSION`. Its runs all finished
+fn migrate(connection: &Connection) -> rusqlite::Result<()> {
+    let version = || -> rusqlite::Result<i64> {
+        connection.pragma_query_value(None, "user_version", |row| row.get(0))
+    };
+    if version()? >= SCHEMA_VERSION {
+        return Ok(());
+    }
+    connection.execute_batch("BEGIN IMMEDIATE")?;
+    // Another run may have got there while this one waited for the lock
+    let migrated = if version()? < 1 {
+        connection.execute_batch(
+            "ALTER TABLE runs ADD COLUMN status TEXT NOT NULL DEFAULT 'done';
+            ALTER TABLE runs ADD COLUMN error TEXT;",
+        )
+    } else {
+        Ok(())
+    }
+    .and_then(|()| connection.pragma_update(None, "user_version", SCHEMA_VERSION));
+    match migrated {
+        Ok(()) => connection.execute_batch("COMMIT"),
+        Err(e) => {
+            let _ = connection.execute_batch("ROLLBACK");
+            Err(e)
+        }
+    }
+}
+
+fn insert(history_file: &Path, record: &RunRecord) -> Result<()> {
+    let command = serde_json::to_string(&record.command)
+        .map_err(|e| TelemetryError::config(format!("Failed to serialize run command: {e}")))?;
+    let mut connection = open(history_file)?;
+    let transaction = connection.transaction().map_err(db_error(history_file))?;
+    transaction
+        .execute(
+            "INSERT INTO runs (id, finished_at, elapsed_s, command, experiment, launch_id, seed,
+                sample_rate_hz, duration_s, readings, status, error)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                record.id,
+                record.finished_at.to_rfc3339(),
+                record.elapsed_s,
+                command,
+                record.experiment,
+                record.launch_id,
+                // SQLite integers are signed, seeds past i64::MAX wrap and come back the same
+                record.seed as i64,
+                record.sample_rate_hz,
+                record.duration_s as i64,
+                record.readings as i64,
+                record.status.name(),
+                record.error,
+            ],
+        )
+        .map_err(db_error(history_file))?;
+    for output in &record.outputs {
+        transaction
+            .execute(
+                "INSERT INTO run_outputs (run_id, path, bytes, sha256) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    record.id,
+                    output.path.to_string_lossy(),
+                    output.bytes as i64,
+                    output.sha256,
+                ],
+            )
+            .map_err(db_error(history_file))?;
+    }
+    transaction.commit().map_err(db_error(history_file))
+}
+
+// Runs in the order they finished. Ids are matched by prefix when given
+pub fn load_runs(history_dir: &Path, id_prefix: Option<&str>) -> Result<Vec<RunRecord>> {
+    let history_file = history_dir.join(HISTORY_FILE);
+    if !history_file.exists() {
+        return Ok(Vec::new());
+    }
+    let connection = open(&history_file)?;
+    let mut runs_query = connection
+        .prepare(
+            "SELECT id, finished_at, elapsed_s, command, experiment, launch_id, seed,
+                sample_rate_hz, duration_s, readings, status, error
+            FROM runs WHERE substr(id, 1, length(?1)) = ?1 ORDER BY finished_at, rowid",
+        )
+        .map_err(db_error(&history_file))?;
+    let mut outputs_query = connection
+        .prepare("SELECT path, bytes, sha256 FROM run_outputs WHERE run_id = ?1 ORDER BY rowid")
+        .map_err(db_error(&history_file))?;
+
+    let rows = runs_query
+        .query_map(params![id_prefix.unwrap_or_default()], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, f64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, i64>(6)?,
+                row.get::<_, String>(7)?,
+                row.get::<_, i64>(8)?,
+                row.get::<_, i64>(9)?,
+                row.get::<_, String>(10)?,
+                row.get::<_, Option<String>>(11)?,
+            ))
+        })
+        .map_err(db_error(&history_file))?;
+
+    let mut runs = Vec::new();
+    for row in rows {
+        let (
+            id,
+            finished_at,
+            elapsed_s,
+            command,
+            experiment,
+            launch_id,
+            seed,
+            sample_rate_hz,
+            duration_s,
+            readings,
+            status,
+            error,
+        ) = row.map_err(db_error(&history_file))?;
+        let corrupt = |what: &str| TelemetryError::IoError {
+            message: format!("{}: run {id} has a bad {what}", history_file.display()),
+            source: std::io::Error::from(std::io::ErrorKind::InvalidData),
+        };
+        let finished_at = DateTime::parse_from_rfc3339(&finished_at)
+            .map_err(|_| corrupt("finish time"))?
+            .with_timezone(&Utc);
+        let command: Vec<String> =
+            serde_json::from_str(&command).map_err(|_| corrupt("command"))?;
+        let status = RunStatus::from_name(&status).ok_or_else(|| corrupt("status"))?;
+        let outputs = outputs_query
+            .query_map(params![id], |row| {
+                Ok(RunOutput {
+                    path: PathBuf::from(row.get::<_, String>(0)?),
+                    bytes: row.get::<_, i64>(1)? as u64,
+                    sha256: row.get(2)?,
+                })
+            })
+            .and_then(|outputs| outputs.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(db_error(&history_file))?;
+        runs.push(RunRecord {
+            id,
+            status,
+            error,
+            finished_at,
+            elapsed_s,
+            command,
+            experiment,
+            launch_id,
+            seed: seed as u64,
+            sample_rate_hz,
+            duration_s: duration_s as usize,
+            readings: readings as usize,
+            outputs,
+        });
+    }
+    Ok(runs)
+}
+
+// A history that's locked, corrupt or can't be read or written is the file's problem, anything
+// else SQLite refused is the write's
+fn db_error(history_file: &Path) -> impl Fn(rusqlite::Error) -> TelemetryError + '_ {
+    move |e| {
+        let message = format!("Run history {}", history_file.display());
+        match e.sqlite_error_code() {
+            Some(
+                ErrorCode::DatabaseBusy
+                | ErrorCode::DatabaseLocked
+                | ErrorCode::DatabaseCorrupt
+                | ErrorCode::NotADatabase
+                | ErrorCode::SystemIoFailure
+                | ErrorCode::DiskFull
+                | ErrorCode::CannotOpen
+                | ErrorCode::ReadOnly
+                | ErrorCode::PermissionDenied
+                | ErrorCode::FileLockingProtocolFailed,
+            ) => TelemetryError::IoError {
+                message,
+                source: std::io::Error::other(e),
+            },
+            _ => TelemetryError::ExportError {
+                sink: "history".to_string(),
+                message,
+                source: Some(e.into()),
+            },
+        }
+    }
+}
+
+// Most recent runs last, like a log
+pub fn list_runs(history_dir: &Path, limit: usize) -> Result<()> {
+    let runs = load_runs(history_dir, None)?;
+    if runs.is_empty() {
+        println!(
+            "No runs recorded in {}",
+            history_dir.join(HISTORY_FILE).display()
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{:<16}  {:<9}  {:<20}  {:>15}  {:>9}  {:<24}  output",
+        "id", "status", "finished", "readings", "elapsed", "experiment"
+    );
+    for run in &runs[runs.len().saturating_sub(limit)..] {
+        let parquet = run
+            .outputs
+            .iter()
+            .find(|output| output.path.extension().is_some_and(|e| e == "parquet"))
+            .map(|output| output.path.display().to_string())
+            .unwrap_or_default();
+        println!(
+            "{:<16}  {:<9}  {:<20}  {:>15}  {:>8.1}s  {:<24}  {}",
+            run.id,
+            run.status.name(),
+            run.finished_at.format("%Y-%m-%d %H:%M:%S"),
+            run.readings.to_formatted_string(&Locale::en),
+            run.elapsed_s,
+            run.experiment.as_deref().unwrap_or("-"),
+            parquet
+        );
+    }
+    Ok(())
+}
+
+// The run an id or unique prefix of one names, if any
+pub fn find_run(history_dir: &Path, id: &str) -> Result<Option<RunRecord>> {
+    let mut matches = load_runs(history_dir, Some(id))?;
+    if matches.len() > 1 {
+        return Err(TelemetryError::config(format!(
+            "Run id {id} is ambiguous, {} runs match",
+            matches.len()
+        )));
+    }
+    Ok(matches.pop())
+}
+
+pub fn show_run(history_dir: &Path, id: &str) -> Result<()> {
+    let run = find_run(history_dir, id)?
+        .ok_or_else(|| TelemetryError::config(format!("No run with id {id}")))?;
+
+    let json = serde_json::to_string_pretty(&run)
+        .map_err(|e| TelemetryError::config(format!("Failed to serialize run record: {e}")))?;
+    println!("{json}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histories_from_before_statuses_read_as_done() {
+        let dir = tempfile::tempdir().unwrap();
+        let connection = Connection::open(dir.path().join(HISTORY_FILE)).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE runs (
+                    id TEXT PRIMARY KEY,
+                    finished_at TEXT NOT NULL,
+                    elapsed_s REAL NOT NULL,
+                    command TEXT NOT NULL,
+                    experiment TEXT,
+                    launch_id TEXT NOT NULL,
+                    seed INTEGER NOT NULL,
+                    sample_rate_hz TEXT NOT NULL,
+                    duration_s INTEGER NOT NULL,
+                    readings INTEGER NOT NULL
+                );
+                INSERT INTO runs VALUES ('0cab1234', '2026-01-02T03:04:05+00:00', 1.5,
+                    '[\"generate\"]', NULL, 'SIM-001', 1337, '1000', 60, 3120000);",
+            )
+            .unwrap();
+        drop(connection);
+
+        let runs = load_runs(dir.path(), None).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Done);
+        assert_eq!(runs[0].error, None);
+        let run = find_run(dir.path(), "0cab").unwrap().unwrap();
+        assert_eq!(run.readings, 3_120_000);
+    }
+
+    #[test]
+    fn unreadable_histories_are_io_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(HISTORY_FILE), vec![b'x'; 4096]).unwrap();
+        assert!(matches!(
+            load_runs(dir.path(), None),
+            Err(TelemetryError::IoError { .. })
+        ));
+    }
+}
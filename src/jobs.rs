@@ -0,0 +1,553 @@
+use crate::daemon::LiveStats;
+use crate::history::{RunOutput, RunRecord, RunStatus, new_run_id, record_run};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use telemetry_generator::error::{IoContext, Result, TelemetryError};
+use telemetry_generator::exporters::{
+    ParquetConfig, ParquetExporter, output_file_path, run_output_files,
+};
+use telemetry_generator::generators::{CancelToken, TelemetryGenerator};
+use telemetry_generator::models::{MemoryBudget, TelemetryConfig};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+// Generate runs queued through `POST /jobs` and worked off by a fixed pool, so several people can
+// share one daemon without a big run starving the rest. Each job streams to
+// `{run_dir}/jobs/{id}/` in chunks sized from its memory limit, so none holds more than that, and
+// is capped at `JobLimits::max_rows` readings. Jobs are kept until the daemon stops, and every
+// job that ran is recorded in the run history however it ended
+
+// Limits every job runs under, from the `start` flags
+#[derive(Debug, Clone, Copy)]
+pub struct JobLimits {
+    pub workers: usize,
+    // Jobs waiting for a worker before submissions are turned away
+    pub queue_size: usize,
+    pub max_rows: usize,
+    // Also the default for jobs that don't ask for less
+    pub memory: MemoryBudget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    fn is_finished(self) -> bool {
+        matches!(self, Self::Done | Self::Failed | Self::Cancelled)
+    }
+}
+
+// What `GET /jobs/{id}` returns
+#[derive(Debug, Clone, Serialize)]
+pub struct JobReport {
+    pub id: u64,
+    pub status: JobStatus,
+    pub launch_id: String,
+    pub submitted_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub readings: usize,
+    pub total_readings: usize,
+    // From 0 to 1
+    pub progress: f64,
+    // Seconds left at the rate so far, once the job is running
+    pub eta_s: Option<f64>,
+    pub memory_limit_bytes: usize,
+    pub error: Option<String>,
+    // Where to download the parquet once it's done
+    pub result: Option<String>,
+}
+
+struct Job {
+    id: u64,
+    config: TelemetryConfig,
+    memory: MemoryBudget,
+    total_readings: usize,
+    submitted_at: DateTime<Utc>,
+    readings: AtomicUsize,
+    cancel: CancelToken,
+    state: Mutex<JobState>,
+}
+
+struct JobState {
+    status: JobStatus,
+    started: Option<(DateTime<Utc>, Instant)>,
+    finished_at: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+impl Job {
+    fn state(&self) -> std::sync::MutexGuard<'_, JobState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn finish(&self, status: JobStatus, error: Option<String>) {
+        let mut state = self.state();
+        state.status = status;
+        state.finished_at = Some(Utc::now());
+        state.error = error;
+    }
+
+    fn output_name(&self) -> String {
+        format!(
+            "{}_{}hz_{}s",
+            self.config.launch_id, self.config.sample_rate_hz, self.config.duration
+        )
+    }
+
+    // The job for the run history once it's over, unless it never got to run
+    fn run_record(&self, output_dir: &Path) -> Option<RunRecord> {
+        let state = self.state();
+        let (_, started) = state.started?;
+        let status = match state.status {
+            JobStatus::Done => RunStatus::Done,
+            JobStatus::Failed => RunStatus::Failed,
+            JobStatus::Cancelled => RunStatus::Cancelled,
+            JobStatus::Queued | JobStatus::Running => return None,
+        };
+        let outputs = run_output_files(output_dir, &self.output_name())
+            .into_iter()
+            .map(|path| RunOutput {
+                bytes: std::fs::metadata(&path)
+                    .map(|m| m.len())
+                    .unwrap_or_default(),
+                sha256: None,
+                path,
+            })
+            .collect();
+        Some(RunRecord {
+            id: new_run_id(),
+            status,
+            error: state.error.clone(),
+            finished_at: state.finished_at.unwrap_or_else(Utc::now),
+            elapsed_s: started.elapsed().as_secs_f64(),
+            command: self.command(),
+            experiment: None,
+            launch_id: self.config.launch_id.clone(),
+            seed: self.config.seed,
+            sample_rate_hz: self.config.sample_rate_hz.to_string(),
+            duration_s: self.config.duration,
+            readings: self.readings.load(Ordering::Relaxed),
+            outputs,
+        })
+    }
+
+    // Generate flags for the same run from the CLI
+    fn command(&self) -> Vec<String> {
+        let config = &self.config;
+        let mut command: Vec<String> = vec![
+            "generate".into(),
+            "--duration".into(),
+            config.duration.to_string(),
+            "--hz".into(),
+            config.sample_rate_hz.to_string(),
+            "--launch-id".into(),
+            config.launch_id.clone(),
+            "--seed".into(),
+            config.seed.to_string(),
+            "--timestamp-jitter".into(),
+            config.timestamp_jitter.to_string(),
+            "--sensor-instances".into(),
+            config.sensor_instances.to_string(),
+            "--units".into(),
+            config.units.name().into(),
+        ];
+        if let Some(naming) = config.naming.to_possible_value() {
+            command.extend(["--naming".into(), naming.get_name().into()]);
+        }
+        if let Some(max_rows) = config.max_rows {
+            command.extend(["--max-rows".into(), max_rows.to_string()]);
+        }
+        if let Some(step_ms) = config.physics_step_ms {
+            command.extend(["--physics-step".into(), format!("{step_ms}ms")]);
+        }
+        command
+    }
+
+    fn report(&self) -> JobReport {
+        let state = self.state();
+        let readings = self.readings.load(Ordering::Relaxed);
+        let progress = match (state.status, self.total_readings) {
+            (JobStatus::Done, _) | (_, 0) => 1.0,
+            _ => (readings as f64 / self.total_readings as f64).min(1.0),
+        };
+        let eta_s = match state.started {
+            Some((_, started)) if state.status == JobStatus::Running && readings > 0 => {
+                let elapsed_s = started.elapsed().as_secs_f64();
+                Some(
+                    elapsed_s * self.total_readings.saturating_sub(readings) as f64
+                        / readings as f64,
+                )
+            }
+            _ => None,
+        };
+        JobReport {
+            id: self.id,
+            status: state.status,
+            launch_id: self.config.launch_id.clone(),
+            submitted_at: self.submitted_at,
+            started_at: state.started.map(|(at, _)| at),
+            finished_at: state.finished_at,
+            readings,
+            total_readings: self.total_readings,
+            progress,
+            eta_s,
+            memory_limit_bytes: self.memory.limit_bytes,
+            error: state.error.clone(),
+            result: (state.status == JobStatus::Done).then(|| format!("/jobs/{}/result", self.id)),
+        }
+    }
+}
+
+// Why a job couldn't be queued
+pub enum SubmitError {
+    // Over the limits, the request needs changing
+    Rejected(TelemetryError),
+    // Every queue slot is taken, try again later
+    QueueFull,
+}
+
+pub struct JobQueue {
+    dir: PathBuf,
+    history_dir: PathBuf,
+    limits: JobLimits,
+    sender: mpsc::Sender<Arc<Job>>,
+    // Every job since the daemon started, oldest first
+    jobs: Mutex<Vec<Arc<Job>>>,
+    next_id: AtomicU64,
+}
+
+impl JobQueue {
+    // Start `limits.workers` workers writing under `dir` and recording the runs in the history
+    // in `history_dir`. Running jobs are cancelled once `shutdown` is
+    pub fn start(
+        dir: &Path,
+        history_dir: &Path,
+        limits: JobLimits,
+        stats: Arc<LiveStats>,
+        shutdown: CancelToken,
+    ) -> Result<Arc<Self>> {
+        if limits.workers == 0 || limits.queue_size == 0 {
+            return Err(TelemetryError::config(
+                "The job queue needs at least one worker and one slot",
+            ));
+        }
+        // Sizes the chunks, so a limit too small to generate in fails here rather than per job
+        limits.memory.chunk_readings()?;
+        std::fs::create_dir_all(dir)
+            .io_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let (sender, receiver) = mpsc::channel::<Arc<Job>>(limits.queue_size);
+        let receiver = Arc::new(tokio::sync::Mutex::new(receiver));
+        for worker in 1..=limits.workers {
+            let receiver = receiver.clone();
+            let stats = stats.clone();
+            let shutdown = shutdown.clone();
+            let dir = dir.to_path_buf();
+            let history_dir = history_dir.to_path_buf();
+            tokio::spawn(async move {
+                loop {
+                    let Some(job) = receiver.lock().await.recv().await else {
+                        break;
+                    };
+                    stats.job_started();
+                    let output_dir = dir.join(job.id.to_string());
+                    let shutdown = shutdown.clone();
+                    let ran = job.clone();
+                    let run = tokio::task::spawn_blocking(move || {
+                        run_job(&ran, &output_dir, &shutdown);
+                        output_dir
+                    });
+                    let output_dir = match run.await {
+                        Ok(output_dir) => output_dir,
+                        Err(e) => {
+                            error!("Worker {worker} panicked on job {}: {e}", job.id);
+                            job.finish(JobStatus::Failed, Some("The job panicked".to_string()));
+                            dir.join(job.id.to_string())
+                        }
+                    };
+                    if let Some(record) = job.run_record(&output_dir) {
+                        let history_dir = history_dir.clone();
+                        let _ =
+                            tokio::task::spawn_blocking(move || record_run(&history_dir, &record))
+                                .await;
+                    }
+                    stats.job_finished();
+                }
+            });
+        }
+        info!(
+            "Running jobs on {} workers with {} queue slots",
+            limits.workers, limits.queue_size
+        );
+        Ok(Arc::new(Self {
+            dir: dir.to_path_buf(),
+            history_dir: history_dir.to_path_buf(),
+            limits,
+            sender,
+            jobs: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }))
+    }
+
+    // Check the job against the limits and queue it
+    pub fn submit(
+        &self,
+        config: TelemetryConfig,
+        memory: Option<MemoryBudget>,
+        stats: &LiveStats,
+    ) -> std::result::Result<JobReport, SubmitError> {
+        // The generator stops at max_rows, so this is what the job writes and what progress and
+        // the limit go by
+        let total_readings = config.get_total_points();
+        if total_readings > self.limits.max_rows {
+            return Err(SubmitError::Rejected(TelemetryError::config(format!(
+                "The job would have {total_readings} readings, jobs are limited to {}. Lower the \
+                duration or rate or set max_rows",
+                self.limits.max_rows
+            ))));
+        }
+        let memory = match memory {
+            Some(memory) if memory.limit_bytes > self.limits.memory.limit_bytes => {
+                return Err(SubmitError::Rejected(TelemetryError::config(format!(
+                    "Jobs can use at most {} bytes of memory",
+                    self.limits.memory.limit_bytes
+                ))));
+            }
+            Some(memory) => {
+                memory.chunk_readings().map_err(SubmitError::Rejected)?;
+                memory
+            }
+            None => self.limits.memory,
+        };
+
+        let job = Arc::new(Job {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            config,
+            memory,
+            total_readings,
+            submitted_at: Utc::now(),
+            readings: AtomicUsize::new(0),
+            cancel: CancelToken::new(),
+            state: Mutex::new(JobState {
+                status: JobStatus::Queued,
+                started: None,
+                finished_at: None,
+                error: None,
+            }),
+        });
+        self.sender
+            .try_send(job.clone())
+            .map_err(|_| SubmitError::QueueFull)?;
+        stats.job_queued();
+        info!("Queued job {} for {}", job.id, job.config.launch_id);
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(job.clone());
+        Ok(job.report())
+    }
+
+    // Where finished jobs are recorded
+    pub fn history_dir(&self) -> &Path {
+        &self.history_dir
+    }
+
+    pub fn reports(&self) -> Vec<JobReport> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|job| job.report())
+            .collect()
+    }
+
+    pub fn report(&self, id: u64) -> Option<JobReport> {
+        self.job(id).map(|job| job.report())
+    }
+
+    // Stop a queued or running job. Finished jobs are left as they are
+    pub fn cancel(&self, id: u64) -> Option<JobReport> {
+        let job = self.job(id)?;
+        let status = job.state().status;
+        if !status.is_finished() {
+            info!("Cancelling job {id}");
+            job.cancel.cancel();
+        }
+        // Queued jobs are skipped when a worker gets to them
+        if status == JobStatus::Queued {
+            job.finish(JobStatus::Cancelled, None);
+        }
+        Some(job.report())
+    }
+
+    // Parquet file of a finished job
+    pub fn result_file(&self, id: u64) -> Option<(JobReport, PathBuf)> {
+        let job = self.job(id)?;
+        let file = output_file_path(
+            &self.dir.join(id.to_string()),
+            &job.output_name(),
+            "parquet",
+        );
+        Some((job.report(), file))
+    }
+
+    fn job(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|job| job.id == id)
+            .cloned()
+    }
+}
+
+// Generate a job into its directory on a worker's blocking thread
+fn run_job(job: &Job, output_dir: &Path, shutdown: &CancelToken) {
+    if job.state().status.is_finished() {
+        return;
+    }
+    if shutdown.is_cancelled() {
+        job.finish(JobStatus::Cancelled, None);
+        return;
+    }
+    {
+        let mut state = job.state();
+        state.status = JobStatus::Running;
+        state.started = Some((Utc::now(), Instant::now()));
+    }
+    info!("Running job {}", job.id);
+
+    let result = generate_job(job, output_dir, shutdown);
+    match result {
+        Ok(()) if job.cancel.is_cancelled() => {
+            info!("Job {} cancelled", job.id);
+            // Half a run isn't worth keeping
+            let _ = std::fs::remove_dir_all(output_dir);
+            job.finish(JobStatus::Cancelled, None);
+        }
+        Ok(()) => {
+            info!("Job {} done", job.id);
+            job.finish(JobStatus::Done, None);
+        }
+        Err(e) => {
+            error!("Job {} failed: {e:#}", job.id);
+            job.finish(JobStatus::Failed, Some(e.to_string()));
+        }
+    }
+}
+
+fn generate_job(job: &Job, output_dir: &Path, shutdown: &CancelToken) -> Result<()> {
+    let chunk_readings = job.memory.chunk_readings()?;
+    let exporter = ParquetExporter::new(ParquetConfig::default());
+    let mut writer = exporter.create_writer(&job.config, output_dir, &job.output_name())?;
+    let mut generator = TelemetryGenerator::new(job.config.clone()).with_cancel(job.cancel.clone());
+    generator.generate_chunked(true, chunk_readings, |chunk| -> Result<_> {
+        writer.write_chunk(chunk)?;
+        job.readings
+            .fetch_add(chunk.readings.len(), Ordering::Relaxed);
+        // The daemon going down ends its jobs with it
+        if shutdown.is_cancelled() {
+            job.cancel.cancel();
+        }
+        Ok(ControlFlow::Continue(()))
+    })?;
+    writer.close()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::load_runs;
+    use std::time::Duration;
+
+    fn queue(dir: &Path, max_rows: usize) -> Arc<JobQueue> {
+        let limits = JobLimits {
+            workers: 1,
+            queue_size: 4,
+            max_rows,
+            memory: "64MB".parse().unwrap(),
+        };
+        JobQueue::start(
+            &dir.join("jobs"),
+            dir,
+            limits,
+            Arc::new(LiveStats::default()),
+            CancelToken::new(),
+        )
+        .unwrap()
+    }
+
+    fn config(duration: usize, max_rows: Option<usize>) -> TelemetryConfig {
+        TelemetryConfig {
+            duration,
+            max_rows,
+            ..TelemetryConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn jobs_over_the_limit_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = queue(dir.path(), 1000);
+        let submitted = jobs.submit(config(10, None), None, &LiveStats::default());
+        assert!(matches!(submitted, Err(SubmitError::Rejected(_))));
+    }
+
+    #[tokio::test]
+    async fn max_rows_caps_what_a_job_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let jobs = queue(dir.path(), 1000);
+        let Ok(report) = jobs.submit(config(600, Some(100)), None, &LiveStats::default()) else {
+            panic!("a job capped under the limit should be queued");
+        };
+        assert_eq!(report.total_readings, 100);
+
+        let report = loop {
+            let report = jobs.report(report.id).unwrap();
+            if report.status.is_finished() {
+                break report;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(report.status, JobStatus::Done);
+        assert_eq!(report.readings, 100);
+        assert_eq!(report.progress, 1.0);
+
+        // Recorded just after it finishes
+        let runs = loop {
+            let runs = load_runs(dir.path(), None).unwrap();
+            if !runs.is_empty() {
+                break runs;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0].status, RunStatus::Done);
+        assert_eq!(runs[0].readings, 100);
+        assert!(runs[0].command.contains(&"--max-rows".to_string()));
+    }
+}
@@ -0,0 +1,85 @@
+//! Synthetic rocket launch telemetry, as a library.
+//!
+//! The `telemetry_generator` binary is a command line wrapper over this crate, adding only run
+//! history and experiments on top. Use it directly to generate readings from your own code and
+//! hand them to any of the exporters:
+//!
+//! ```no_run
+//! use telemetry_generator::{
+//!     ParquetConfig, ParquetExporter, SampleRate, TelemetryConfig, TelemetryGenerator,
+//! };
+//!
+//! let config = TelemetryConfig::default()
+//!     .with_duration(60)
+//!     .with_sample_rate(SampleRate::from_hz(100))
+//!     .with_launch_id("SIM-042")
+//!     .with_seed(7);
+//! let dataset = TelemetryGenerator::new(config).generate(true)?;
+//!
+//! ParquetExporter::new(ParquetConfig::default()).export(
+//!     &dataset,
+//!     "output".as_ref(),
+//!     "SIM-042",
+//! )?;
+//! # Ok::<(), telemetry_generator::TelemetryError>(())
+//! ```
+//!
+//! The generator is configured the same way, e.g. `with_health`, `with_limits` or `with_chaos`
+//! before generating. `generate_chunked` hands readings over a chunk at a time for runs too big
+//! to hold in memory. Every fallible call returns a [`TelemetryError`] to match on.
+//!
+//! To follow a run as it's generated, for live plots or checks, add a [`ChunkObserver`]. Closures
+//! over the chunk work, and `exporters::RecordBatchObserver` hands chunks over as Arrow:
+//!
+//! ```no_run
+//! use std::ops::ControlFlow;
+//! use telemetry_generator::{TelemetryConfig, TelemetryDataset, TelemetryGenerator};
+//!
+//! let mut generator = TelemetryGenerator::new(TelemetryConfig::default()).with_observer(
+//!     |chunk: &TelemetryDataset| {
+//!         println!("{} more readings", chunk.readings.len());
+//!         Ok(ControlFlow::Continue(()))
+//!     },
+//! );
+//! generator.generate_chunked(true, 10_000, |_chunk| {
+//!     Ok::<_, telemetry_generator::TelemetryError>(ControlFlow::Continue(()))
+//! })?;
+//! # Ok::<(), telemetry_generator::TelemetryError>(())
+//! ```
+//!
+//! On a tokio runtime, `generate_async` and `spawn_chunked` generate on the blocking pool so the
+//! executor stays free, and several missions can fly at once. Chunks from `spawn_chunked` come
+//! back through a bounded queue, ready for an async sink to await:
+//!
+//! ```no_run
+//! use telemetry_generator::{InfluxDBConfig, InfluxDBExporter, TelemetryConfig, TelemetryGenerator};
+//!
+//! # async fn run() -> telemetry_generator::Result<()> {
+//! let mut exporter = InfluxDBExporter::new(InfluxDBConfig::default());
+//! let mut task = TelemetryGenerator::new(TelemetryConfig::default()).spawn_chunked(true, 5000, 8);
+//! while let Some(chunk) = task.next_chunk().await {
+//!     exporter.write_readings(&chunk.readings).await?;
+//! }
+//! let generator = task.finish().await?;
+//! println!("Apogee {:.0} m", generator.flight_summary().max_altitude_m);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod bench;
+pub mod error;
+pub mod exporters;
+pub mod generators;
+pub mod models;
+pub mod query;
+pub mod scenario;
+
+pub use error::{Result, TelemetryError};
+pub use exporters::{
+    CsvMetadataExporter, InfluxDBConfig, InfluxDBExporter, ParquetConfig, ParquetExporter,
+    SensorCatalogExporter, StreamExporter,
+};
+pub use generators::{ChunkObserver, GenerationTask, TelemetryGenerator};
+pub use models::{
+    SampleRate, SensorEnum, SensorValue, TelemetryConfig, TelemetryDataset, TelemetryReading,
+};
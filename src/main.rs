@@ -1,24 +1,79 @@
-use anyhow::Result;
-use chrono::Utc;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use clap::{Args, Parser, Subcommand};
 use num_format::{Locale, ToFormattedString};
-use std::path::PathBuf;
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Instant;
-use tracing::{Level, debug, info, warn};
+use tracing::{Level, debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-mod exporters;
-mod generators;
-mod models;
+// Built on the generate command, so they live with the CLI rather than in the library
+mod daemon;
+mod experiment;
+mod history;
+mod jobs;
+mod selftest;
+mod server;
 
-use crate::exporters::{CsvMetadataExporter, InfluxDBConfig, InfluxDBExporter, ParquetExporter};
-use crate::generators::TelemetryGenerator;
-use crate::models::{SensorEnum, TelemetryConfig, TelemetryDataset};
+use crate::daemon::{DaemonState, LiveStats, PidFile, STATE_INTERVAL};
+use crate::experiment::{Campaign, ExperimentPlan, run_campaign, run_experiment};
+use crate::history::{RunRecord, list_runs, record_run, show_run};
+use crate::jobs::{JobLimits, JobQueue};
+use crate::selftest::run_selftest;
+#[cfg(feature = "query")]
+use telemetry_generator::bench::bench_read_datafusion;
+use telemetry_generator::bench::{
+    Codec, CodecBenchConfig, LayoutBenchConfig, LoadedDataset, ReadBenchConfig, bench_codecs,
+    bench_layouts, bench_read, print_codec_table, print_layout_table, print_read_table,
+    write_codec_report, write_layout_report, write_read_report,
+};
+use telemetry_generator::error::TelemetryError;
+#[cfg(feature = "opcua")]
+use telemetry_generator::exporters::OpcUaServer;
+use telemetry_generator::exporters::{
+    AlertExporter, AnonymizeConfig, ArrowExporter, ArrowFormat, ArrowStreamWriter, AvroCodec,
+    AvroConfig, AvroExporter, AvroStreamWriter, BatchTuning, CalibrationExporter, CaptureExporter,
+    CcsdsExporter, ChecksumExporter, CsvDataExporter, CsvMetadataExporter, DEFAULT_MQTT_TOPIC,
+    DataFormat, DatasetAnonymizer, DiskWatchdog, EvolutionManifestExporter, ExportJournal,
+    ExportRun, ExportStatus, FileChecksum, FrameFormat, GapPolicy, HealthExporter, InfluxDBConfig,
+    InfluxDBExporter, Interpolation, JsonExporter, JsonLayout, MergeAlignment, MergeConfig,
+    ModbusRegister, ModbusServer, MqttBroker, MqttConfig, MqttExporter, OverwritePolicy,
+    PLUGIN_PROTOCOL_VERSION, ParquetConfig, ParquetExporter, ParquetStreamWriter, ReadingDigest,
+    ResampleConfig, Resampler, RowLayout, RunMerger, RunOverview, STREAM_QUEUE_CHUNKS,
+    SensorCatalogExporter, SensorStatistics, SinkTarget, SqliteExporter, SqliteStreamWriter,
+    StreamExporter, StreamProgress, StreamSink, TextCompression, ValueColumns,
+    WATCHED_CHUNK_READINGS, WatchdogVerdict, XlsxSummaryExporter, output_file_path,
+    parse_time_shift, plugin_schema, prepare_output_file, resolve_output_name, run_output_files,
+    validate_plugin_stream,
+};
+#[cfg(feature = "hdf5")]
+use telemetry_generator::exporters::{Hdf5Exporter, Hdf5StreamWriter};
+#[cfg(feature = "orc")]
+use telemetry_generator::exporters::{OrcExporter, OrcStreamWriter};
+use telemetry_generator::generators::{
+    CancelToken, ChaosLevel, Cruise, EngineSpread, EngineUnit, FlightEvent, FlightEventKind,
+    FlightProfile, FlightSummary, Fuzz, FuzzBounds, HealthMonitor, HealthRule, ImuErrors,
+    Integrator, LimitMonitor, PeriodicPattern, PeriodicPreset, ScenarioScript, SensorLimit,
+    SensorShape, Setpoint, TelemetryGenerator, WindLayer, WindPreset, parse_time_ms,
+};
+use telemetry_generator::models::{
+    Calibration, CaptureWindow, CaptureWindows, MIN_CHUNK_READINGS, MemoryBudget, ReadingFilter,
+    SampleRate, SchemaChange, SensorEnum, SensorNaming, TelemetryConfig, TelemetryDataset,
+    UnitSystem, parse_byte_size,
+};
+use telemetry_generator::query::{QueryOutput, run_query};
+use telemetry_generator::scenario::{ScenarioSource, expand_scenario_args, list_scenarios};
 
 #[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
+async fn main() -> ExitCode {
+    let cli = Cli::parse_from(expand_scenario_args(std::env::args().collect()));
 
     // Setup logger
     // let _guard = init_logger(cli.log_level, cli.log_dir);
@@ -27,7 +82,9 @@ async fn main() {
             EnvFilter::try_from_default_env().unwrap_or_else(|_| "telemetry_generator=info".into()),
         )
         .with(
+            // Logs go to stderr so `runs` output can be piped
             tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
                 .with_thread_ids(true)
                 .with_thread_names(true),
         )
@@ -39,224 +96,2870 @@ async fn main() {
     debug!("All cli: {:?}", cli);
     info!("Command: {:?}", cli.command);
 
-    match &cli.command {
-        Commands::Generate {
-            duration,
-            khz,
-            launch_id,
-            seed,
-            disable_progress,
-            max_rows,
-            timestamp_jitter,
-        } => {
+    let result: Result<()> = match &cli.command {
+        Commands::Generate(args) if args.verify_determinism => {
+            info!("Checking the run generates the same readings however it's chunked");
+            let cancel = cancel_on_signal();
+            tokio::task::block_in_place(|| verify_determinism(args, &cancel)).map(
+                |(readings, sha256)| {
+                    println!("Deterministic: {readings} readings, sha256 {sha256}")
+                },
+            )
+        }
+        Commands::Generate(args) => {
             info!("Generating telemetry data...");
-            let _ = generate_to_parquet(
-                *duration,
-                (*khz * 1000.0).round() as usize,
-                launch_id, // other run details. vehicle type, engine type, etc.
-                *seed,
-                *disable_progress,
-                *max_rows, // pass as Option<usize>
-                *timestamp_jitter,
-            );
-            // Call the generate function from the generate module
-            // if let Err(e) = telemetry_generator::generate::generate_telemetry(
-            //     *duration,
-            //     *sample_rate_khz,
-            //     launch_id,
-            //     *seed,
-            //     *no_progress,
-            //     *max_rows,
-            //     *timestamp_jitter,
-            // ) {
-            //     error!("Error generating telemetry data: {:?}", e);
-            // }
-        }
-        Commands::InfluxDB {
-            url,
-            token,
-            org,
-            bucket,
-            batch_size,
+            let start_time = Instant::now();
+            let cancel = cancel_on_signal();
+            // Generating blocks, so hand the runtime's other work to another thread meanwhile
+            tokio::task::block_in_place(|| generate_to_parquet(args, Some(&cancel))).map(|report| {
+                let command = std::env::args().skip(1).collect();
+                let record = RunRecord::new(args, command, None, &report, start_time.elapsed());
+                record_run(&args.output_dir, &record);
+            })
+        }
+        Commands::Experiment(args) => {
+            info!("Running experiment plan {}", args.plan.display());
+            ExperimentPlan::from_file(&args.plan, &args.output_dir)
+                .map_err(anyhow::Error::from)
+                .and_then(|mut plan| {
+                    if let Some(parallel) = args.parallel {
+                        plan.parallel = parallel as usize;
+                    }
+                    run_experiment(plan, &args.output_dir)
+                })
+        }
+        Commands::Montecarlo(args) => {
+            info!("Flying a campaign of {} dispersed missions", args.runs);
+            let campaign = Campaign {
+                name: args.name.clone(),
+                runs: args.runs as usize,
+                base_seed: args.base_seed,
+                parallel: args.parallel as usize,
+                fault_probability: args.fault_probability,
+                max_faults: args.max_faults,
+                base_flags: args.generate.clone(),
+            };
+            run_campaign(campaign, &args.output_dir)
+        }
+        Commands::Anonymize(args) => {
+            info!("Anonymizing {}", args.parquet_file.display());
+            anonymize(args)
+        }
+        Commands::Merge(args) => {
+            info!("Merging {} runs", args.runs.len());
+            merge(args)
+        }
+        Commands::Resample(args) => {
+            info!("Resampling {}", args.parquet_file.display());
+            resample(args)
+        }
+        Commands::BenchCodecs(args) => {
+            info!("Benchmarking codecs on {}", args.parquet_file.display());
+            run_codec_bench(args)
+        }
+        Commands::BenchLayouts(args) => {
+            info!("Benchmarking layouts on {}", args.parquet_file.display());
+            run_layout_bench(args)
+        }
+        Commands::BenchRead(args) => {
+            info!("Benchmarking reads of {} datasets", args.datasets.len());
+            run_read_bench(args).await
+        }
+        Commands::Query(args) => {
+            info!("Querying {} files", args.files.len());
+            query(args).await
+        }
+        Commands::Schema(args) => {
+            info!("Plugin protocol version {}", PLUGIN_PROTOCOL_VERSION);
+            schema(args)
+        }
+        Commands::Selftest(args) => {
+            info!("Running self tests with seed {}", args.seed);
+            let cancel = cancel_on_signal();
+            tokio::task::block_in_place(|| run_selftest(args, &cancel))
+        }
+        Commands::Scenario {
+            command: ScenarioCommand::List,
         } => {
-            info!("Sending data to InfluxDB at {}", url);
-            info!("Sending data to InfluxDB bucket {}", bucket);
-            info!("InfluxDB batch size {}", batch_size);
-            debug!("Token: {}", token);
-
-            let influx_exporter = InfluxDBExporter::new(InfluxDBConfig {
-                url: url.clone(), //: url.take(),
-                token: token.clone(),
-                org: org.clone(),
-                bucket: bucket.clone(),
-                batch_size: *batch_size,
-            });
-
-            info!("Calling into influx generator");
-            let dataset = TelemetryDataset {
-                readings: Vec::new(),
-                config: TelemetryConfig::default(),
-                launch_time: Utc::now(),
+            list_scenarios();
+            Ok(())
+        }
+        Commands::Runs { command } => match command {
+            RunsCommand::List { output_dir, limit } => {
+                list_runs(output_dir, *limit).map_err(anyhow::Error::from)
+            }
+            RunsCommand::Show { id, output_dir } => {
+                show_run(output_dir, id).map_err(anyhow::Error::from)
+            }
+        },
+        Commands::InfluxDB(args) => {
+            info!("Sending data to InfluxDB at {}", args.url);
+            info!("Sending data to InfluxDB bucket {}", args.bucket);
+            info!("InfluxDB batch size {}", args.batch_size);
+            debug!("Token: {}", args.token);
+            stream_to_influx(args).await
+        }
+        Commands::Stream(args) => {
+            info!("Streaming live telemetry to {}", args.to);
+            stream_live(args, None).await
+        }
+        Commands::Start(args) => {
+            info!("Starting the daemon in {}", args.run_dir.display());
+            start_daemon(args).await
+        }
+        Commands::Stop(args) => {
+            info!("Stopping the daemon in {}", args.run_dir.display());
+            daemon::stop(&args.run_dir)
+                .map(|pid| println!("Stopped pid {pid}"))
+                .map_err(anyhow::Error::from)
+        }
+        Commands::Status(args) => daemon::status(&args.run_dir).map_err(anyhow::Error::from),
+    };
+
+    match result {
+        Ok(()) => {
+            info!("Process ending...");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            let failure = Failure::of(&e);
+            error!(exit_code = failure as u8, "{:#}", e);
+            ExitCode::from(failure as u8)
+        }
+    }
+}
+
+fn anonymize(args: &AnonymizeArgs) -> Result<()> {
+    let anonymizer = DatasetAnonymizer::new(AnonymizeConfig {
+        time_shift_ms: parse_time_shift(&args.time_shift)?,
+        latitude_offset_deg: args.lat_offset,
+        longitude_offset_deg: args.lon_offset,
+        launch_id_map: args.launch_id_map.clone(),
+        launch_id: args.launch_id.clone(),
+    });
+    let policy = if args.force {
+        OverwritePolicy::Overwrite
+    } else {
+        OverwritePolicy::Error
+    };
+    let output = anonymizer.anonymize(&args.parquet_file, &args.output_dir, policy)?;
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn merge(args: &MergeArgs) -> Result<()> {
+    let merger = RunMerger::new(MergeConfig {
+        alignment: args.align,
+        tags: args.tag.clone(),
+    });
+    let policy = if args.force {
+        OverwritePolicy::Overwrite
+    } else if args.append_suffix {
+        OverwritePolicy::AppendSuffix
+    } else {
+        OverwritePolicy::Error
+    };
+    let (output, rows) = merger.merge(&args.runs, &args.output_dir, &args.output_name, policy)?;
+    info!("Wrote {} rows", rows.to_formatted_string(&Locale::en));
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn resample(args: &ResampleArgs) -> Result<()> {
+    let resampler = Resampler::new(ResampleConfig {
+        rate: args.hz,
+        interpolation: args.method,
+        max_gap_ms: args.max_gap.as_deref().map(parse_time_ms).transpose()?,
+        gap_policy: args.gap_policy,
+    });
+    let output_name = args.output_name.clone().unwrap_or_else(|| {
+        let input = args.parquet_file.file_stem().unwrap_or_default();
+        format!("{}_{}hz_grid", input.to_string_lossy(), args.hz)
+    });
+    let policy = if args.force {
+        OverwritePolicy::Overwrite
+    } else if args.append_suffix {
+        OverwritePolicy::AppendSuffix
+    } else {
+        OverwritePolicy::Error
+    };
+    let (output, rows) =
+        resampler.resample(&args.parquet_file, &args.output_dir, &output_name, policy)?;
+    info!("Wrote {} grid rows", rows.to_formatted_string(&Locale::en));
+    println!("{}", output.display());
+    Ok(())
+}
+
+fn run_codec_bench(args: &BenchCodecsArgs) -> Result<()> {
+    let dataset = LoadedDataset::read(&args.parquet_file)?;
+    let results = bench_codecs(
+        &dataset,
+        &CodecBenchConfig {
+            codecs: args.codecs.clone(),
+            row_group_rows: args.row_group_rows.clone(),
+            repeat: args.repeat as usize,
+            work_dir: args.work_dir.clone(),
+            keep_files: args.keep_files,
+        },
+    )?;
+    print_codec_table(&dataset, &results);
+    if let Some(report) = &args.report {
+        write_codec_report(&args.parquet_file, &results, report)?;
+    }
+    Ok(())
+}
+
+fn run_layout_bench(args: &BenchLayoutsArgs) -> Result<()> {
+    let dataset = LoadedDataset::read(&args.parquet_file)?;
+    let (results, pivot) = bench_layouts(
+        &dataset,
+        &LayoutBenchConfig {
+            codec: args.codec,
+            partition_window_ms: parse_time_ms(&args.partition_window)?,
+            work_dir: args.work_dir.clone(),
+            keep_files: args.keep_files,
+        },
+    )?;
+    print_layout_table(&results, pivot);
+    if let Some(report) = &args.report {
+        write_layout_report(&args.parquet_file, &results, report)?;
+    }
+    Ok(())
+}
+
+async fn run_read_bench(args: &BenchReadArgs) -> Result<()> {
+    let time_slice_ms = match &args.time_slice {
+        Some(slice) => {
+            let (start, end) = slice
+                .split_once("..")
+                .ok_or_else(|| anyhow!("Time slice {slice} should look like 60s..90s"))?;
+            Some((parse_time_ms(start)?, parse_time_ms(end)?))
+        }
+        None => None,
+    };
+    let config = ReadBenchConfig {
+        sensor: args.sensor.clone(),
+        time_slice_ms,
+        repeat: args.repeat as usize,
+    };
+    #[allow(unused_mut)] // Only added to with the query feature
+    let mut results = bench_read(&args.datasets, &config)?;
+    // The same queries through DataFusion, in builds that have it
+    #[cfg(feature = "query")]
+    results.extend(bench_read_datafusion(&args.datasets, &config).await?);
+    print_read_table(&results);
+    if let Some(report) = &args.report {
+        write_read_report(&results, report)?;
+    }
+    Ok(())
+}
+
+async fn query(args: &QueryArgs) -> Result<()> {
+    run_query(&args.sql, &args.files, args.format).await?;
+    Ok(())
+}
+
+fn schema(args: &SchemaArgs) -> Result<()> {
+    let Some(stream) = &args.validate else {
+        let json = serde_json::to_string_pretty(&plugin_schema())?;
+        match &args.output {
+            Some(path) => {
+                std::fs::write(path, json + "\n").context(Failure::Io)?;
+                info!("Schema written to {}", path.display());
+            }
+            None => println!("{json}"),
+        }
+        return Ok(());
+    };
+
+    let (lines, violations) = validate_plugin_stream(stream)?;
+    if violations.is_empty() {
+        info!(
+            "All {} frames in {} match the schema",
+            lines,
+            stream.display()
+        );
+        return Ok(());
+    }
+    for violation in &violations {
+        error!("{violation}");
+    }
+    Err(anyhow!(
+        "{} does not match the plugin schema, see the problems above",
+        stream.display()
+    ))
+}
+
+// Generate on a blocking thread and stream to InfluxDB as chunks come off it. The queue between
+// them is bounded so a slow server holds the generator back instead of filling memory
+async fn stream_to_influx(args: &InfluxArgs) -> Result<()> {
+    let run = ExportRun {
+        sink: "influxdb".to_string(),
+        target: format!("{} {}/{}", args.url, args.org, args.bucket),
+        launch_id: args.launch_id.clone(),
+        seed: args.seed,
+        sample_rate_hz: args.hz.to_string(),
+        duration_s: args.duration,
+    };
+    std::fs::create_dir_all(&args.output_dir).context(Failure::Io)?;
+    let journal_path = ExportJournal::path(&args.output_dir, &run);
+    let previous = ExportJournal::load(&journal_path)?;
+    let mut journal = match previous {
+        Some(journal) if args.resume_export => {
+            journal.check_resumable(&run)?;
+            info!(
+                "Resuming after {} confirmed readings, T+{:.1}s",
+                journal.confirmed_readings.to_formatted_string(&Locale::en),
+                journal.last_time_since_launch_ms.unwrap_or_default() as f64 / 1000.0
+            );
+            journal
+        }
+        None if args.resume_export => {
+            return Err(
+                anyhow!("No export journal at {} to resume", journal_path.display())
+                    .context(Failure::Config),
+            );
+        }
+        Some(journal) if journal.status != ExportStatus::Complete && !args.force => {
+            return Err(anyhow!(
+                "An unfinished export of {} stopped after {} readings, see {}. Pass \
+                --resume-export to continue it or --force to start over",
+                journal.run.launch_id,
+                journal.confirmed_readings.to_formatted_string(&Locale::en),
+                journal_path.display()
+            )
+            .context(Failure::OutputExists));
+        }
+        // The launch time is pinned so a resume regenerates the same readings. Idempotent runs
+        // take it from the seed so every rerun lands on the same timestamps
+        _ if args.idempotent => {
+            ExportJournal::new(run, TelemetryConfig::launch_time_from_seed(args.seed))
+        }
+        _ => ExportJournal::new(run, Utc::now()),
+    };
+    info!("Recording export progress in {}", journal_path.display());
+    journal.begin(&journal_path)?;
+
+    let config = TelemetryConfig {
+        duration: args.duration,
+        sample_rate_hz: args.hz,
+        launch_id: args.launch_id.clone(),
+        seed: args.seed,
+        launch_time: Some(journal.launch_time),
+        naming: args.naming,
+        ..TelemetryConfig::default()
+    };
+    let progress = StreamProgress::new(
+        config.get_total_points(),
+        args.duration as u64 * 1000,
+        args.disable_progress,
+    )?;
+    // Cancelling ends the generator early, and the readings it got to are still sent
+    let cancel = cancel_on_signal();
+    let mut generator = TelemetryGenerator::new(config).with_cancel(cancel.clone());
+    if !args.limits.is_empty() {
+        generator = generator.with_limits(LimitMonitor::new(args.limits.clone()));
+    }
+    let mut influx_exporter = InfluxDBExporter::new(InfluxDBConfig {
+        url: args.url.clone(),
+        token: args.token.clone(),
+        org: args.org.clone(),
+        bucket: args.bucket.clone(),
+        batch_size: args.batch_size,
+        launch_id: args.launch_id.clone(),
+        naming: args.naming,
+        auto_batch: args
+            .auto_batch
+            .then(|| -> Result<_> {
+                Ok(BatchTuning {
+                    min_batch_size: args.min_batch_size,
+                    max_batch_size: args.max_batch_size,
+                    target_latency: std::time::Duration::from_millis(parse_time_ms(
+                        &args.target_latency,
+                    )?),
+                    write_timeout: std::time::Duration::from_millis(parse_time_ms(
+                        &args.write_timeout,
+                    )?),
+                    ..BatchTuning::default()
+                })
+            })
+            .transpose()?,
+    })
+    .with_annotations(generator.events());
+
+    // Readings confirmed by an earlier attempt are regenerated but not sent again
+    let mut skip_readings = journal.confirmed_readings;
+    let resumed_ms = journal.last_time_since_launch_ms.unwrap_or_default();
+    progress.generated(skip_readings, resumed_ms);
+    progress.sent(skip_readings, resumed_ms);
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(STREAM_QUEUE_CHUNKS);
+    let chunk_readings = args.batch_size;
+    let generator_progress = progress.clone();
+    let generation = tokio::task::spawn_blocking(move || {
+        // The generator's own bar is off, the stream progress covers it
+        generator.generate_chunked(true, chunk_readings, |chunk| -> Result<_, TelemetryError> {
+            let skipped = skip_readings.min(chunk.readings.len());
+            chunk.readings.drain(..skipped);
+            skip_readings -= skipped;
+            if chunk.readings.is_empty() {
+                return Ok(ControlFlow::Continue(()));
+            }
+            let generated_ms = chunk.readings.iter().map(|r| r.time_since_launch_ms).max();
+            generator_progress.generated(chunk.readings.len(), generated_ms.unwrap_or_default());
+            // A closed queue means the sender gave up, its error is the one reported
+            match sender.blocking_send(chunk.readings.drain(..).collect()) {
+                Ok(()) => Ok(ControlFlow::Continue(())),
+                Err(_) => Ok(ControlFlow::Break(())),
+            }
+        })?;
+        Ok::<_, TelemetryError>(generator.limit_alerts().to_vec())
+    });
+
+    info!("Calling into influx generator");
+    let sent = influx_exporter
+        .stream(receiver, &progress, &mut journal, &journal_path)
+        .await;
+    progress.finish();
+    let sent = match sent {
+        Ok(sent) => sent,
+        Err(e) => {
+            let e = anyhow::Error::from(e);
+            journal.fail(&journal_path, format!("{e:#}"))?;
+            error!(
+                "Export stopped with {} readings confirmed up to T+{:.1}s. Rerun with \
+                --resume-export to continue from there",
+                journal.confirmed_readings.to_formatted_string(&Locale::en),
+                journal.last_time_since_launch_ms.unwrap_or_default() as f64 / 1000.0
+            );
+            return Err(e.context(Failure::Export));
+        }
+    };
+    let alerts = generation.await??;
+    if cancel.is_cancelled() {
+        journal.fail(&journal_path, "Cancelled".to_string())?;
+        return Err(anyhow!(
+            "Cancelled with {} readings confirmed up to T+{:.1}s. Rerun with --resume-export to \
+            continue from there",
+            journal.confirmed_readings.to_formatted_string(&Locale::en),
+            journal.last_time_since_launch_ms.unwrap_or_default() as f64 / 1000.0
+        )
+        .context(Failure::Cancelled));
+    }
+    influx_exporter
+        .write_alerts(&alerts)
+        .await
+        .context(Failure::Export)?;
+    journal.complete(&journal_path)?;
+    info!(
+        "Streamed {} readings to InfluxDB. {}",
+        sent.to_formatted_string(&Locale::en),
+        progress.summary()
+    );
+    Ok(())
+}
+
+// Longest the stream command sleeps between checks for Ctrl-C
+const STOP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+// Wait before the daemon launches again after its sink failed
+const SINK_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(5);
+
+// Fly at wall clock pace, sending each timestep once its time comes, until Ctrl-C. Each flight
+// is followed by the next on the next seed unless --once. Stopping part way still closes the
+// sink properly, so the end frame goes out. The daemon passes `stats` to follow along, and rides
+// out sink failures by launching again after a wait
+async fn stream_live(args: &StreamArgs, stats: Option<Arc<LiveStats>>) -> Result<()> {
+    let stop = cancel_on_signal();
+    // Pollers stay connected from one flight to the next, so the server outlives them
+    let server = match &args.to {
+        LiveTarget::Modbus(addr) => Some(LiveServer::Modbus(
+            ModbusServer::bind(*addr, &args.registers, stop.clone()).await?,
+        )),
+        #[cfg(feature = "opcua")]
+        LiveTarget::OpcUa(addr) => Some(LiveServer::OpcUa(OpcUaServer::bind(
+            *addr,
+            &args.pki_dir,
+            stop.clone(),
+        )?)),
+        #[cfg(not(feature = "opcua"))]
+        LiveTarget::OpcUa(_) => {
+            return Err(TelemetryError::config(
+                "--to opc.tcp:// needs the opcua feature. Rebuild with --features opcua",
+            )
+            .into());
+        }
+        _ => None,
+    };
+
+    let flight_args = args.clone();
+    let runtime = tokio::runtime::Handle::current();
+    let launch_offset = args.launch_offset();
+    let (flights, sent) = tokio::task::spawn_blocking(move || -> Result<_> {
+        let mut flights = 0;
+        let mut sent = 0;
+        while !stop.is_cancelled() {
+            flights += 1;
+            let launch_id = if flights == 1 {
+                flight_args.launch_id.clone()
+            } else {
+                format!("{}-{flights}", flight_args.launch_id)
+            };
+            let config = TelemetryConfig {
+                duration: flight_args.duration,
+                sample_rate_hz: flight_args.hz,
+                launch_id,
+                seed: flight_args.seed.wrapping_add(flights - 1),
+                launch_time: Some(Utc::now() - launch_offset),
+                ..TelemetryConfig::default()
             };
-            let ret = influx_exporter.export(&dataset).await;
+            match (
+                fly_live(
+                    config,
+                    &flight_args,
+                    &runtime,
+                    &stop,
+                    stats.as_deref(),
+                    server.as_ref(),
+                ),
+                &stats,
+            ) {
+                (Ok(readings), _) => sent += readings,
+                (Err(e), Some(stats)) => {
+                    warn!("Sink failed, launching again in {SINK_RETRY_DELAY:?}: {e:#}");
+                    stats.failed(format!("{e:#}"));
+                    sleep_until(Instant::now() + SINK_RETRY_DELAY, &stop);
+                }
+                (Err(e), None) => return Err(e),
+            }
+            if flight_args.once {
+                break;
+            }
+        }
+        Ok((flights, sent))
+    })
+    .await??;
+    info!(
+        "Streamed {} readings over {flights} flights to {}",
+        sent.to_formatted_string(&Locale::en),
+        args.to
+    );
+    Ok(())
+}
+
+// Cancelled on the first Ctrl-C or SIGTERM so the run can close its outputs. A second one quits
+// straight away
+fn cancel_on_signal() -> CancelToken {
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        warn!("Stopping, Ctrl-C again to quit now");
+        token.cancel();
+        shutdown_signal().await;
+        std::process::exit(Failure::Cancelled as i32);
+    });
+    cancel
+}
+
+// Ctrl-C, or SIGTERM from `stop`
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                Ok(()) = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    if tokio::signal::ctrl_c().await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+// Sleep in short naps until `due` so a stop is picked up promptly at low rates. False when
+// stopped first
+fn sleep_until(due: Instant, stop: &CancelToken) -> bool {
+    loop {
+        if stop.is_cancelled() {
+            return false;
+        }
+        let now = Instant::now();
+        if now >= due {
+            return true;
+        }
+        std::thread::sleep((due - now).min(STOP_CHECK_INTERVAL));
+    }
+}
+
+// Start the stream command and the API in the background, or run them here with --foreground. The
+// daemon holds the pid file while it runs and keeps the state file up to date for `status`
+async fn start_daemon(args: &StartArgs) -> Result<()> {
+    if !args.foreground {
+        if !args.api_only && matches!(args.stream.to, LiveTarget::Sink(SinkTarget::Stdout)) {
+            return Err(anyhow!(
+                "The daemon's stdout is its log, pick a sink with --to or use --foreground"
+            )
+            .context(Failure::Config));
+        }
+        let daemon_args: Vec<String> = std::env::args().skip(1).collect();
+        let pid = daemon::spawn(&args.run_dir, &daemon_args)?;
+        info!(
+            "Daemon running as pid {pid}, logging to {}",
+            args.run_dir.join(daemon::LOG_FILE).display()
+        );
+        println!("{pid}");
+        return Ok(());
+    }
+
+    let _pid_file = PidFile::claim(&args.run_dir)?;
+    let stats = Arc::new(LiveStats::default());
+    let mut state = DaemonState::new(
+        (!args.api_only).then(|| args.stream.to.to_string()),
+        args.listen.map(|addr| addr.to_string()),
+    );
+    state.write(&args.run_dir)?;
+
+    let api = async {
+        let Some(addr) = args.listen else {
+            return Ok(());
+        };
+        let shutdown = cancel_on_signal();
+        let limits = JobLimits {
+            workers: args.workers,
+            queue_size: args.queue_size,
+            max_rows: args.job_max_rows,
+            memory: args.job_memory_limit,
+        };
+        let jobs = JobQueue::start(
+            &args.run_dir.join("jobs"),
+            &args.run_dir,
+            limits,
+            stats.clone(),
+            shutdown.clone(),
+        )?;
+        server::serve(addr, stats.clone(), jobs, shutdown)
+            .await
+            .map_err(anyhow::Error::from)
+    };
+    let stream = async {
+        if args.api_only {
+            Ok(())
+        } else {
+            stream_live(&args.stream, Some(stats.clone())).await
+        }
+    };
+    // Both stop on the same signal, and a failing API takes the stream down with it
+    let stream = async { tokio::try_join!(api, stream).map(|_| ()) };
+    tokio::pin!(stream);
+    let mut updates = tokio::time::interval(STATE_INTERVAL);
+    let result = loop {
+        tokio::select! {
+            result = &mut stream => break result,
+            _ = updates.tick() => {
+                state.update(&stats);
+                if let Err(e) = state.write(&args.run_dir) {
+                    warn!("Failed to update the daemon state: {e:#}");
+                }
+            }
+        }
+    };
+    state.update(&stats);
+    state.stopped_at = Some(Utc::now());
+    state.write(&args.run_dir)?;
+    result
+}
+
+// One flight of the stream command. Returns the readings sent
+fn fly_live(
+    config: TelemetryConfig,
+    args: &StreamArgs,
+    runtime: &tokio::runtime::Handle,
+    stop: &CancelToken,
+    stats: Option<&LiveStats>,
+    server: Option<&LiveServer>,
+) -> Result<usize> {
+    info!("Launching {}", config.launch_id);
+    if let Some(stats) = stats {
+        stats.launched(&config.launch_id);
+    }
+    let chunk_readings = config.readings_per_step();
+    let mut generator = TelemetryGenerator::new(config);
+    let events = generator.events();
+    // Readings are due this long after launch
+    let launched = Instant::now();
+    let mut sink: Option<LiveSink> = None;
+    let mut sent = 0;
+    generator.generate_chunked(true, chunk_readings, |chunk| -> Result<_> {
+        let live = match &mut sink {
+            Some(live) => live,
+            None => sink.insert(LiveSink::start(args, chunk, &events, server, runtime)?),
+        };
+        for step in chunk
+            .readings
+            .chunk_by(|a, b| a.time_since_launch_ms == b.time_since_launch_ms)
+        {
+            let due = launched + std::time::Duration::from_millis(step[0].time_since_launch_ms);
+            if !sleep_until(due, stop) {
+                return Ok(ControlFlow::Break(()));
+            }
+            live.write(step, &chunk.config, runtime)?;
+            sent += step.len();
+            if let Some(stats) = stats {
+                stats.sent(step.len());
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    })?;
+    if let Some(live) = sink {
+        live.close(runtime)?;
+    }
+    Ok(sent)
+}
+
+// A stream command sink, started on a flight's first readings
+enum LiveSink {
+    Frames(StreamSink),
+    InfluxDB(Box<InfluxDBExporter>),
+    Mqtt(Box<MqttExporter>),
+    Server(LiveServer),
+}
+
+// A server the stream command binds before its first flight, which readings are written into
+#[derive(Clone)]
+enum LiveServer {
+    Modbus(ModbusServer),
+    #[cfg(feature = "opcua")]
+    OpcUa(OpcUaServer),
+}
+
+impl LiveSink {
+    fn start(
+        args: &StreamArgs,
+        chunk: &TelemetryDataset,
+        events: &[FlightEvent],
+        server: Option<&LiveServer>,
+        runtime: &tokio::runtime::Handle,
+    ) -> Result<Self> {
+        let launch_id = &chunk.config.launch_id;
+        Ok(match &args.to {
+            LiveTarget::Sink(target) => {
+                let exporter = StreamExporter {
+                    target: target.clone(),
+                    format: args.format,
+                };
+                LiveSink::Frames(exporter.start(chunk, launch_id)?)
+            }
+            LiveTarget::InfluxDB => LiveSink::InfluxDB(Box::new(
+                InfluxDBExporter::new(InfluxDBConfig {
+                    url: args.url.clone(),
+                    token: args.token.clone().unwrap_or_default(),
+                    org: args.org.clone().unwrap_or_default(),
+                    bucket: args.bucket.clone().unwrap_or_default(),
+                    batch_size: chunk.config.readings_per_step(),
+                    launch_id: launch_id.clone(),
+                    naming: chunk.config.naming,
+                    auto_batch: None,
+                })
+                .with_annotations(events.to_vec()),
+            )),
+            LiveTarget::Mqtt(broker) => {
+                // The exporter drives its connection on the runtime
+                let _runtime = runtime.enter();
+                LiveSink::Mqtt(Box::new(MqttExporter::connect(MqttConfig {
+                    broker: broker.clone(),
+                    client_id: args
+                        .client_id
+                        .clone()
+                        .unwrap_or_else(|| format!("telemetry-generator-{launch_id}")),
+                    topic: args.topic.clone(),
+                    qos: args.qos,
+                    retain: args.retain,
+                    ca_file: args.ca_file.clone(),
+                    username: args.username.clone(),
+                    password: args.password.clone(),
+                })?))
+            }
+            LiveTarget::Modbus(_) | LiveTarget::OpcUa(_) => LiveSink::Server(
+                server
+                    .cloned()
+                    .expect("The server is bound before the first flight"),
+            ),
+        })
+    }
+
+    // Send a timestep's readings and push them out rather than leave them in a buffer
+    fn write(
+        &mut self,
+        readings: &[telemetry_generator::models::TelemetryReading],
+        config: &TelemetryConfig,
+        runtime: &tokio::runtime::Handle,
+    ) -> Result<()> {
+        match self {
+            LiveSink::Frames(sink) => {
+                sink.write_readings(readings, config)?;
+                sink.flush()?;
+            }
+            LiveSink::InfluxDB(exporter) => runtime.block_on(exporter.write_readings(readings))?,
+            LiveSink::Mqtt(exporter) => {
+                runtime.block_on(exporter.write_readings(readings, config))?
+            }
+            LiveSink::Server(LiveServer::Modbus(server)) => server.write_readings(readings, config),
+            #[cfg(feature = "opcua")]
+            LiveSink::Server(LiveServer::OpcUa(server)) => server.write_readings(readings, config),
+        }
+        Ok(())
+    }
+
+    fn close(self, runtime: &tokio::runtime::Handle) -> Result<()> {
+        match self {
+            LiveSink::Frames(sink) => sink.close()?,
+            LiveSink::InfluxDB(_) | LiveSink::Server(_) => {}
+            LiveSink::Mqtt(exporter) => runtime.block_on(exporter.close())?,
+        }
+        Ok(())
+    }
+}
+
+// Process exit code per kind of failure so wrapping scripts can react. Clap already exits
+// with 2 on bad arguments. Mostly follows the `TelemetryError` variant. Attach one with
+// `.context(Failure::...)` to override it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    General = 1,
+    // Bad script, memory limit and other run settings
+    Config = 3,
+    // Output from an earlier run is in the way. See --force and --append-suffix
+    OutputExists = 4,
+    // Reading or writing files, including a full disk
+    Io = 5,
+    // Sending data to InfluxDB or a plugin
+    Export = 6,
+    // Stopped with Ctrl-C or SIGTERM, like a shell reports an interrupted command
+    Cancelled = 130,
+}
 
-            // // Call the function to send data to InfluxDB
-            // if let Err(e) =
-            //     telemetry_generator::influxdb::send_to_influxdb(url, token, bucket, batch_size)
-            // {
-            //     error!("Error sending data to InfluxDB: {:?}", e);
-            // }
+impl Failure {
+    fn of(error: &anyhow::Error) -> Self {
+        if let Some(failure) = error.downcast_ref::<Failure>() {
+            return *failure;
         }
-        Commands::Start => {
-            info!("Starting server...");
-            // Call the start server function
+        match error.downcast_ref::<TelemetryError>() {
+            Some(TelemetryError::ConfigError(_)) => Failure::Config,
+            Some(TelemetryError::GenerationError(_)) => Failure::General,
+            Some(TelemetryError::ExportError { .. }) => Failure::Export,
+            Some(TelemetryError::IoError { .. }) => Failure::Io,
+            None if error.chain().any(|cause| cause.is::<std::io::Error>()) => Failure::Io,
+            None => Failure::General,
         }
-        Commands::Stop => {
-            info!("Stopping server...");
-            // Call the stop server function
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Failure::General => "Run failed",
+            Failure::Config => "Invalid run configuration",
+            Failure::OutputExists => "Refusing to overwrite output",
+            Failure::Io => "Failed to write output",
+            Failure::Export => "Export failed",
+            Failure::Cancelled => "Run cancelled",
+        };
+        write!(f, "{message}")
+    }
+}
+
+// Readings in the smaller of the two --target-bytes sizing runs. The other writes twice as many
+const SIZING_READINGS: usize = 200_000;
+
+// --target-rows and --target-bytes. Works out the duration that gives a run of that size at the
+// chosen rate, rounded up to a whole second. For bytes, two short runs with the same settings
+// measure what a reading adds to the output once the footer and side files are paid for, so
+// byte targets land near the target rather than on it
+fn sized_to_target(args: &GenerateArgs) -> Result<GenerateArgs> {
+    let mut sized = args.clone();
+    sized.target_rows = None;
+    sized.target_bytes = None;
+    let readings_per_step = TelemetryConfig {
+        sensor_instances: args.sensor_instances,
+        redundant_sensors: args.redundant.clone(),
+        ..TelemetryConfig::default()
+    }
+    .readings_per_step();
+    let sample_rate = args.sample_rate()?;
+    // Shortest flight with at least `readings`
+    let duration_for = |readings: usize| -> usize {
+        let steps = readings.div_ceil(readings_per_step);
+        let mut duration = (steps as f64 * sample_rate.period_s()).ceil().max(1.0) as usize;
+        while sample_rate.samples_in(duration) < steps {
+            duration += 1;
         }
-        Commands::Status => {
-            info!("Checking server status...");
-            // Call the status function
+        duration
+    };
+
+    let readings = match (args.target_rows, args.target_bytes) {
+        // A wide row holds a whole timestep
+        (Some(rows), _) if args.layout == RowLayout::Wide => rows * readings_per_step,
+        (Some(rows), _) => rows,
+        (None, Some(target_bytes)) => {
+            // Removed when dropped, even when a sizing run fails
+            std::fs::create_dir_all(&args.output_dir).context(Failure::Io)?;
+            let sizing_dir = tempfile::tempdir_in(&args.output_dir).context(Failure::Io)?;
+            // Readings and bytes written by a run of about `readings`
+            let sizing_run = |readings: usize| -> Result<(f64, f64)> {
+                let mut sample = sized.clone();
+                sample.output_dir = sizing_dir.path().to_path_buf();
+                sample.force = true;
+                sample.append_suffix = false;
+                sample.disable_progress = true;
+                sample.checksum_file = false;
+                sample.max_output_bytes = None;
+                // Nothing leaves the machine for a sizing run
+                sample.plugins.clear();
+                sample.sinks.clear();
+                sample.duration = duration_for(readings);
+                let report = generate_to_parquet(&sample, None)?;
+                let bytes: u64 = run_output_files(&report.output_dir, &report.output_name)
+                    .iter()
+                    .filter_map(|path| std::fs::metadata(path).ok())
+                    .map(|metadata| metadata.len())
+                    .sum();
+                Ok((report.readings as f64, bytes as f64))
+            };
+            info!("Writing two short runs to size the output");
+            let sizing = sizing_run(SIZING_READINGS)
+                .and_then(|small| Ok((small, sizing_run(2 * SIZING_READINGS)?)));
+            if let Err(e) = sizing_dir.close() {
+                warn!("Failed to remove the sizing runs: {e}");
+            }
+            let ((small_readings, small_bytes), (readings, bytes)) = sizing?;
+            if readings <= small_readings || bytes == 0.0 {
+                return Err(TelemetryError::config(
+                    "The sizing runs wrote nothing, so --target-bytes can't be worked out",
+                )
+                .into());
+            }
+            let mut bytes_per_reading = (bytes - small_bytes) / (readings - small_readings);
+            let mut fixed_bytes = small_bytes - small_readings * bytes_per_reading;
+            if bytes_per_reading <= 0.0 || fixed_bytes < 0.0 {
+                bytes_per_reading = bytes / readings;
+                fixed_bytes = 0.0;
+            }
+            info!(
+                "Each reading adds {bytes_per_reading:.1} bytes to {:.0} KB of fixed output",
+                fixed_bytes / 1024.0
+            );
+            ((target_bytes as f64 - fixed_bytes) / bytes_per_reading).max(0.0) as usize
         }
+        (None, None) => unreachable!("Only called with a target"),
+    };
+    if readings == 0 {
+        return Err(TelemetryError::config("The target is too small for a single reading").into());
     }
-    info!("Process ending...");
+    sized.duration = duration_for(readings);
+    info!(
+        "Flying {}s at {} Hz for {} readings",
+        sized.duration,
+        sample_rate,
+        (sample_rate.samples_in(sized.duration) * readings_per_step)
+            .to_formatted_string(&Locale::en)
+    );
+    Ok(sized)
+}
+
+// What a generate run produced
+#[derive(Debug)]
+struct GenerateReport {
+    output_dir: PathBuf,
+    output_name: String,
+    sample_rate_hz: SampleRate,
+    // Seconds flown, worked out by --target-rows and --target-bytes when given
+    duration_s: usize,
+    readings: usize,
+    flight: FlightSummary,
+    checksums: Vec<FileChecksum>,
 }
 
+impl GenerateReport {
+    fn parquet_file(&self) -> PathBuf {
+        output_file_path(&self.output_dir, &self.output_name, "parquet")
+    }
+}
+
+// `cancel` ends the run early, still closing out everything written so far
 fn generate_to_parquet(
-    duration: usize,
-    sample_rate_hz: usize,
-    launch_id: &str,
-    seed: u64,
-    disable_progress: bool,
-    max_rows: Option<usize>,
-    timestamp_jitter: f64,
-) -> Result<()> {
+    args: &GenerateArgs,
+    cancel: Option<&CancelToken>,
+) -> Result<GenerateReport> {
+    if args.target_rows.is_some() || args.target_bytes.is_some() {
+        let sized = sized_to_target(args)?;
+        return generate_to_parquet(&sized, cancel);
+    }
     info!("Inside generate_to_parquet fn");
     let start_time = Instant::now();
+    let duration = args.duration;
+    let sample_rate_hz: SampleRate = args.sample_rate()?;
+    let launch_id = &args.launch_id; // other run details. vehicle type, engine type, etc.
 
     info!("Number of sensors: {}", SensorEnum::number_of_sensors());
-    info!(
-        "Hz to run sim at: {}",
-        sample_rate_hz.to_formatted_string(&Locale::en)
-    );
+    info!("Hz to run sim at: {}", sample_rate_hz);
     info!("Duration of the test run: {}", duration);
 
+    // Setup telemetry generation
+    let config = generate_config(args)?;
+
     // Warn if sample rate is too high and would create too many rows for max_rows
-    let estimated_points: usize = duration * sample_rate_hz * SensorEnum::number_of_sensors();
+    let estimated_points: usize = sample_rate_hz.samples_in(duration) * config.readings_per_step();
     info!(
         "Estimated number of data-points: {}",
         estimated_points.to_formatted_string(&Locale::en)
     );
-    if max_rows.is_some() && estimated_points > max_rows.unwrap() {
+    if config.get_total_points() < estimated_points {
         warn!(
-            "Estimated points ({}) exceed max rows ({}). Consider increasing max rows or decreasing sample rate/duration.",
-            estimated_points,
-            max_rows.unwrap()
+            "Estimated points ({}) exceed max rows ({:?}). Consider increasing max rows or decreasing sample rate/duration.",
+            estimated_points, config.max_rows
         );
     }
 
-    // Setup telemetry generation
-    let config: TelemetryConfig = TelemetryConfig {
-        duration,
-        sample_rate_hz,
-        launch_id: launch_id.to_string(),
-        seed,
-        // disable_progress,
-        max_rows,
-        timestamp_jitter,
+    // Write to Parquet
+    // Todo geneate output file name from params. OR concatenate onto provided name. Make it optional if not already
+    let output_dir = args.output_dir.as_path();
+    let overwrite_policy = if args.force {
+        OverwritePolicy::Overwrite
+    } else if args.append_suffix {
+        OverwritePolicy::AppendSuffix
+    } else {
+        OverwritePolicy::Error
+    };
+    let output_file = resolve_output_name(
+        output_dir,
+        &format!("{launch_id}_{sample_rate_hz}hz_{duration}s"), //craft_file_name_parquet(config);
+        overwrite_policy,
+    )
+    .context(Failure::OutputExists)?;
+    if args.layout == RowLayout::Wide {
+        if args.format.is_text()
+            || matches!(
+                args.format,
+                DataFormat::Sqlite | DataFormat::Avro | DataFormat::Ccsds | DataFormat::Hdf5
+            )
+        {
+            return Err(TelemetryError::config(
+                "--layout wide only applies to --format parquet, arrow and orc",
+            )
+            .into());
+        }
+        if args.bloom_filter {
+            return Err(TelemetryError::config(
+                "--bloom-filter indexes the sensor_type column, which --layout wide doesn't have",
+            )
+            .into());
+        }
+    }
+    let parquet_config = ParquetConfig {
+        sort_by_timestamp: args.sort_by_timestamp,
+        bloom_filter: args.bloom_filter,
+        bloom_filter_fpp: args.bloom_filter_fpp,
+        bloom_filter_ndv: config.readings_per_step() as u64,
+        column_index: !args.disable_column_index,
+        data_page_row_count_limit: args.data_page_row_limit,
+        value_columns: args.value_columns,
+        raw_values: args.raw_values,
+        layout: args.layout,
+    };
+    // Text formats write the readings through a file sink instead
+    let data_file = match args.format {
+        DataFormat::Parquet => Some(DataFileExporter::Parquet(ParquetExporter::new(
+            parquet_config,
+        ))),
+        DataFormat::Arrow => Some(DataFileExporter::Arrow(ArrowExporter::new(
+            parquet_config,
+            ArrowFormat::File,
+        ))),
+        DataFormat::ArrowStream => Some(DataFileExporter::Arrow(ArrowExporter::new(
+            parquet_config,
+            ArrowFormat::Stream,
+        ))),
+        #[cfg(feature = "orc")]
+        DataFormat::Orc => Some(DataFileExporter::Orc(OrcExporter::new(parquet_config))),
+        #[cfg(not(feature = "orc"))]
+        DataFormat::Orc => {
+            return Err(TelemetryError::config(
+                "--format orc needs the orc feature. Rebuild with --features orc",
+            )
+            .into());
+        }
+        #[cfg(feature = "hdf5")]
+        DataFormat::Hdf5 => Some(DataFileExporter::Hdf5),
+        #[cfg(not(feature = "hdf5"))]
+        DataFormat::Hdf5 => {
+            return Err(TelemetryError::config(
+                "--format hdf5 needs the hdf5 feature. Rebuild with --features hdf5",
+            )
+            .into());
+        }
+        DataFormat::Sqlite => Some(DataFileExporter::Sqlite),
+        DataFormat::Avro => Some(DataFileExporter::Avro(AvroExporter::new(AvroConfig {
+            codec: args.avro_codec,
+            block_bytes: args.avro_block_size as usize,
+        }))),
+        DataFormat::Csv | DataFormat::Json | DataFormat::Ndjson | DataFormat::Ccsds => None,
+    };
+
+    let mut stream_exporters: Vec<StreamExporter> = args
+        .plugins
+        .iter()
+        .map(|path| StreamExporter {
+            target: SinkTarget::Plugin(path.clone()),
+            format: args.plugin_format,
+        })
+        .chain(args.sinks.iter().map(|target| StreamExporter {
+            target: target.clone(),
+            format: args.sink_format,
+        }))
+        .collect();
+    if let Some(format) = args.text_output
+        && format.extension() == args.format.extension()
+    {
+        return Err(TelemetryError::config(format!(
+            "--format {0} and --text-output would both write {output_file}.{0}, drop --text-output",
+            args.format.extension()
+        ))
+        .into());
+    }
+    let data_exporter = match args.format {
+        DataFormat::Parquet
+        | DataFormat::Arrow
+        | DataFormat::ArrowStream
+        | DataFormat::Orc
+        | DataFormat::Hdf5
+        | DataFormat::Sqlite
+        | DataFormat::Avro => None,
+        DataFormat::Csv => Some(CsvDataExporter::stream_exporter(
+            output_dir,
+            &output_file,
+            args.text_compression,
+        )?),
+        DataFormat::Json => Some(JsonExporter::stream_exporter(
+            output_dir,
+            &output_file,
+            JsonLayout::Document,
+            args.text_compression,
+        )?),
+        DataFormat::Ndjson => Some(JsonExporter::stream_exporter(
+            output_dir,
+            &output_file,
+            JsonLayout::Lines,
+            args.text_compression,
+        )?),
+        DataFormat::Ccsds => Some(CcsdsExporter::stream_exporter(output_dir, &output_file)?),
     };
+    stream_exporters.extend(data_exporter);
+    if let Some(format) = args.text_output {
+        let extension = format!("{}{}", format.extension(), args.text_compression.suffix());
+        stream_exporters.push(StreamExporter {
+            target: SinkTarget::File(
+                prepare_output_file(output_dir, &output_file, &extension)?,
+                args.text_compression,
+            ),
+            format,
+        });
+    }
+
+    // Make sure the output fits before spending hours generating it
+    let watchdog = DiskWatchdog::new(output_dir, args.max_output_bytes);
+    watchdog.preflight(DiskWatchdog::estimate_output_bytes(
+        config.get_total_points(),
+    ))?;
 
+    // Gathered as the readings go by, for the workbook written at the end
+    let summary = args
+        .xlsx_summary
+        .then(|| (SensorStatistics::default(), config.clone()));
     let mut generator = TelemetryGenerator::new(config);
-    let dataset: TelemetryDataset = generator.generate(disable_progress);
+    if let Some(cancel) = cancel {
+        generator = generator.with_cancel(cancel.clone());
+    }
+    if let Some((statistics, _)) = &summary {
+        generator = generator.with_observer(statistics.clone());
+    }
+    generator = configure_generator(generator, args)?;
+
+    let chunk_readings: Option<usize> = match (args.memory_limit, args.chunk_rows) {
+        (Some(budget), _) => {
+            // Size the chunks flushed to disk from the memory budget
+            let chunk_readings = budget.chunk_readings()?;
+            info!(
+                "Memory limit of {} bytes allows chunks of {} readings",
+                budget.limit_bytes.to_formatted_string(&Locale::en),
+                chunk_readings.to_formatted_string(&Locale::en)
+            );
+            Some(chunk_readings)
+        }
+        (None, Some(chunk_rows)) if chunk_rows < MIN_CHUNK_READINGS => {
+            return Err(TelemetryError::config(format!(
+                "Chunks of {chunk_rows} readings are too small. Use at least {MIN_CHUNK_READINGS}"
+            ))
+            .into());
+        }
+        (None, Some(chunk_rows)) => Some(chunk_rows),
+        (None, None) => args.max_output_bytes.map(|_| WATCHED_CHUNK_READINGS),
+    };
+    let total_readings: usize = match chunk_readings.map(|c| watchdog.chunk_readings(c)) {
+        Some(chunk_readings) => export_chunked(
+            &mut generator,
+            data_file.as_ref(),
+            &stream_exporters,
+            &watchdog,
+            args,
+            chunk_readings,
+            &output_file,
+        )?,
+        None => {
+            let selection = ReadingSelection::new(args, &generator);
+            let mut dataset: TelemetryDataset = generator.generate(args.disable_progress)?;
 
-    // Debug output here...
+            // Debug output here...
 
-    // Write to Parquet
-    // Todo geneate output file name from params. OR concatenate onto provided name. Make it optional if not already
-    let output_file = format!("{launch_id}_{sample_rate_hz}hz_{duration}s"); //craft_file_name_parquet(config);
-    ParquetExporter::export(&dataset, &output_file)?;
+            // Save metadata to CSV. It describes the whole run, so it goes before the filter
+            info!("Write out metadata around the run");
+            CsvMetadataExporter::export(&dataset, output_dir, &output_file)?;
+            SensorCatalogExporter::export(&dataset.config, output_dir, &output_file)?;
+            EvolutionManifestExporter::export(&dataset.config, output_dir, &output_file)?;
+            CalibrationExporter::export(&dataset.config, output_dir, &output_file)?;
+            selection.export_windows(&generator, output_dir, &output_file)?;
+
+            selection.apply(&mut dataset);
+            if let Some(data_file) = &data_file {
+                data_file.export(&dataset, output_dir, &output_file)?;
+            }
 
-    // Save metadata to CSV
-    info!("Write out metadata around the run");
-    CsvMetadataExporter::export(&dataset, &output_file)?;
+            for stream_exporter in &stream_exporters {
+                stream_exporter.export(&dataset, &output_file)?;
+            }
+            dataset.readings.len()
+        }
+    };
 
     let elapsed = start_time.elapsed();
     info!("Generation completed in {:.2?}s", elapsed.as_secs_f64());
     info!(
         "Generated {} readings",
-        dataset.readings.len().to_formatted_string(&Locale::en)
+        total_readings.to_formatted_string(&Locale::en)
     );
 
-    Ok(())
-}
+    let flight = generator.flight_summary().clone();
+    info!(
+        "Max Q of {:.0} Pa at T+{:.1}s, burnout at {:.1} m/s, {} anomalies",
+        flight.max_q_pa, flight.max_q_time_s, flight.burnout_velocity_mps, flight.anomalies
+    );
 
-#[derive(Parser, Debug)]
-#[command(name = "Telemetry Generator")]
-#[command(about = "A tool to generate mock telemetry data", long_about = None)]
-#[command(author = "Jason Gedamke")]
-struct Cli {
-    #[arg(long, value_name = "LEVEL")]
-    log_level: Option<Level>,
+    if !args.health_rules.is_empty() {
+        HealthExporter::export(generator.health_transitions(), output_dir, &output_file)?;
+    }
+    if !args.limits.is_empty() {
+        AlertExporter::export(generator.limit_alerts(), output_dir, &output_file)?;
+    }
+    if let Some((statistics, config)) = &summary {
+        let overview = RunOverview {
+            config,
+            flight: &flight,
+            events: &generator.events(),
+            health: generator.health_transitions(),
+            alerts: generator.limit_alerts(),
+        };
+        XlsxSummaryExporter::export(&overview, statistics, output_dir, &output_file)?;
+    }
 
-    // Location to save the log files
-    #[arg(long, value_name = "DIRECTORY")]
-    log_dir: Option<PathBuf>,
+    let checksums = ChecksumExporter::export(output_dir, &output_file, args.checksum_file)?;
+    if generator.cancelled() {
+        return Err(anyhow!(
+            "Cancelled after {} readings. The {output_file} outputs in {} are closed and cover \
+            the run up to there",
+            total_readings.to_formatted_string(&Locale::en),
+            output_dir.display()
+        )
+        .context(Failure::Cancelled));
+    }
 
-    #[command(subcommand)]
-    command: Commands,
+    Ok(GenerateReport {
+        output_dir: output_dir.to_path_buf(),
+        output_name: output_file,
+        sample_rate_hz,
+        duration_s: duration,
+        readings: total_readings,
+        flight,
+        checksums,
+    })
 }
 
-#[derive(Subcommand, Debug)]
-enum Commands {
-    /// Start the server
-    Generate {
-        // Duration of simulated flight in seconds
-        #[arg(short, long, value_name = "DURATION", default_value = "120")]
-        duration: usize,
+// Chunks are only ever cut between timesteps and nothing the generator draws depends on where,
+// so the readings of a seed are the same whatever the chunk size. Generation runs on one thread,
+// parallel runs in experiments and campaigns each have their own generator. Gives the reading
+// count and SHA-256 both runs agree on
+fn verify_determinism(args: &GenerateArgs, cancel: &CancelToken) -> Result<(usize, String)> {
+    let mut config = generate_config(args)?;
+    // Both runs need the same launch time
+    config.launch_time.get_or_insert_with(Utc::now);
+    let ways = [
+        ("as one chunk", usize::MAX),
+        ("a timestep per chunk", config.readings_per_step()),
+    ];
+    let mut digests = Vec::with_capacity(ways.len());
+    for (way, chunk_readings) in ways {
+        let start_time = Instant::now();
+        let generator = TelemetryGenerator::new(config.clone()).with_cancel(cancel.clone());
+        let mut generator = configure_generator(generator, args)?;
+        let mut digest = ReadingDigest::new();
+        generator.generate_chunked(args.disable_progress, chunk_readings, |chunk| {
+            digest.update(&chunk.readings);
+            Ok::<_, TelemetryError>(ControlFlow::Continue(()))
+        })?;
+        if generator.cancelled() {
+            return Err(anyhow!("Cancelled before the check finished").context(Failure::Cancelled));
+        }
+        let readings = digest.readings();
+        let sha256 = digest.finish_hex();
+        info!(
+            "Generated {} readings {way} in {:.2?}s, sha256 {sha256}",
+            readings.to_formatted_string(&Locale::en),
+            start_time.elapsed().as_secs_f64()
+        );
+        digests.push((way, readings, sha256));
+    }
 
-        // Frequency rate. Default is 1 kHz = 1,000 Hz
-        #[arg(long, value_name = "FREQUENCY", default_value = "1")]
-        khz: f64,
+    let (first_way, first_readings, first_sha256) = &digests[0];
+    for (way, readings, sha256) in &digests[1..] {
+        if (readings, sha256) != (first_readings, first_sha256) {
+            return Err(TelemetryError::GenerationError(format!(
+                "Not deterministic: {first_readings} readings {first_way} hash to {first_sha256}, \
+                but {readings} readings {way} hash to {sha256}"
+            ))
+            .into());
+        }
+    }
+    let (_, readings, sha256) = digests.swap_remove(0);
+    Ok((readings, sha256))
+}
 
-        // TODO: Could also add other meta data. vehicle_type, engine_type, etc.
-        #[arg(long, default_value = "SIM-001")]
-        launch_id: String,
+// Generator settings from the command line, checked
+fn generate_config(args: &GenerateArgs) -> Result<TelemetryConfig> {
+    if !(args.command_rate >= 0.0 && args.command_rate.is_finite()) {
+        return Err(TelemetryError::config("--command-rate can't be negative").into());
+    }
+    if args.command_ack == 0 {
+        return Err(TelemetryError::config("--command-ack has to be at least 1ms").into());
+    }
+    if let Some(sensor) = args
+        .redundant
+        .iter()
+        .find(|sensor| sensor.is_event_driven())
+    {
+        return Err(TelemetryError::config(format!(
+            "{} is a command channel and can't be made redundant",
+            sensor.field_name()
+        ))
+        .into());
+    }
 
-        #[arg(long, default_value = "1337")]
-        seed: u64,
+    Ok(TelemetryConfig {
+        duration: args.duration,
+        sample_rate_hz: args.sample_rate()?,
+        launch_id: args.launch_id.clone(),
+        seed: args.seed,
+        // disable_progress,
+        max_rows: args.max_rows,
+        timestamp_jitter: args.timestamp_jitter,
+        batch_noise: args.batch_noise,
+        launch_time: resolve_launch_time(args),
+        sensor_instances: args.sensor_instances,
+        redundant_sensors: args.redundant.clone(),
+        redundant_drift_chance: args.redundant_drift_chance,
+        physics_step_ms: args.physics_step,
+        integrator: args.integrator,
+        pump_spool_up_s: args.pump_spool_up as f64 / 1000.0,
+        pump_spool_down_s: args.pump_spool_down as f64 / 1000.0,
+        roll_program_deg: args.roll_program,
+        roll_rate_dps: args.roll_rate,
+        command_rate_per_min: args.command_rate,
+        command_ack_ms: args.command_ack as f64,
+        units: args.units,
+        naming: args.naming,
+        schema_evolution: args.evolve.clone(),
+        point_ids: args.idempotent,
+        calibrations: args.calibrations.clone(),
+    })
+}
 
-        // Disable progress bar
-        #[arg(long, default_value = "false")]
-        disable_progress: bool,
+// Everything the generate flags layer onto the generator besides its config
+fn configure_generator(
+    mut generator: TelemetryGenerator,
+    args: &GenerateArgs,
+) -> Result<TelemetryGenerator> {
+    let mut script = match &args.script {
+        Some(script_path) => ScenarioScript::from_file(script_path)?,
+        None => ScenarioScript::default(),
+    };
+    if let Some(path) = args.scenario.as_ref().and_then(ScenarioSource::profile) {
+        generator = generator.with_profile(FlightProfile::from_file(path, args.duration)?);
+    }
+    if let Some(template) = args
+        .scenario
+        .as_ref()
+        .and_then(ScenarioSource::template)
+        .and_then(|scenario| scenario.script())
+    {
+        script.extend(ScenarioScript::parse(template)?);
+    }
+    if !script.is_empty() {
+        generator = generator.with_script(script);
+    }
+    if !args.health_rules.is_empty() {
+        generator = generator.with_health(HealthMonitor::new(args.health_rules.clone()));
+    }
+    if !args.limits.is_empty() {
+        generator = generator.with_limits(LimitMonitor::new(args.limits.clone()));
+    }
+    if args.fuzz {
+        let bounds = FuzzBounds {
+            phase_jitter: args.fuzz_phase_jitter,
+            max_faults: args.fuzz_max_faults,
+            noise_scale_min: args.fuzz_noise_min,
+            noise_scale_max: args.fuzz_noise_max,
+        };
+        generator = generator.with_fuzz(Fuzz::new(bounds, args.seed, args.duration)?);
+    }
+    if args.engine_variation {
+        let spread = EngineSpread {
+            thrust_pct: args.thrust_spread,
+            isp_pct: args.isp_spread,
+            pump_efficiency_pct: args.pump_efficiency_spread,
+        };
+        generator = generator.with_engine(EngineUnit::from_seed(args.seed, spread)?);
+    }
+    if args.cruise {
+        generator = generator.with_cruise(Cruise::new(args.setpoints.clone()));
+    }
+    let mut periodic_patterns = args.periodic.clone();
+    for preset in &args.periodic_presets {
+        periodic_patterns.extend(preset.patterns());
+    }
+    generator = generator.with_periodic(periodic_patterns);
+    generator = generator.with_imu_errors(ImuErrors {
+        gyro_bias_dps: args.gyro_bias,
+        gyro_scale_factor: args.gyro_scale_factor,
+        magnetometer_bias_ut: args.magnetometer_bias,
+        magnetometer_scale_factor: args.magnetometer_scale_factor,
+    });
+    if let Some(preset) = args.wind_preset {
+        let gusts = args
+            .gust_intensity
+            .unwrap_or_else(|| preset.gust_intensity());
+        generator = generator.with_wind(preset.layers(), gusts);
+    } else if !args.wind.is_empty() || args.gust_intensity.is_some() {
+        generator = generator.with_wind(args.wind.clone(), args.gust_intensity.unwrap_or(0.0));
+    }
+    generator = generator.with_value_shapes(args.value_shapes.clone());
+    if let Some(level) = args.chaos {
+        generator = generator.with_chaos(level);
+    }
+    Ok(generator)
+}
 
-        #[arg(long)]
-        max_rows: Option<usize>,
+// Explicit launch time wins, then one derived from the seed, then backfill from N days ago.
+// Otherwise leave it to the generator
+fn resolve_launch_time(args: &GenerateArgs) -> Option<DateTime<Utc>> {
+    if args.seeded_launch_time || args.idempotent {
+        return Some(TelemetryConfig::launch_time_from_seed(args.seed));
+    }
 
-        #[arg(long, default_value = "50.0")]
-        timestamp_jitter: f64,
-    },
+    match (args.launch_time, args.backfill_days) {
+        (Some(launch_time), _) => Some(launch_time),
+        (None, Some(days)) => Some(Utc::now() - Duration::days(days as i64)),
+        (None, None) => None,
+    }
+}
+
+// Which readings of a run get exported: the ones matching --filter and inside the capture
+// windows, if either is set
+struct ReadingSelection<'a> {
+    filter: Option<&'a ReadingFilter>,
+    capture: Option<CaptureWindows>,
+}
+
+impl<'a> ReadingSelection<'a> {
+    // Capture windows are worked out before generating, from the run's planned events
+    fn new(args: &'a GenerateArgs, generator: &TelemetryGenerator) -> Self {
+        let mut windows = args.windows.clone();
+        if !args.capture.is_empty() {
+            let events: Vec<FlightEvent> = generator
+                .events()
+                .into_iter()
+                .filter(|event| args.capture.contains(&event.kind))
+                .collect();
+            if events.is_empty() {
+                warn!("None of the events to capture happen in this run");
+            }
+            windows.extend(events.into_iter().map(|event| {
+                info!(
+                    "Capturing {} at T+{:.1}s",
+                    event.label,
+                    event.time_since_launch_ms as f64 / 1000.0
+                );
+                CaptureWindow::around(
+                    event.time_since_launch_ms,
+                    args.capture_before,
+                    args.capture_after,
+                    event.label,
+                )
+            }));
+        }
+        let capture = (!args.windows.is_empty() || !args.capture.is_empty())
+            .then(|| CaptureWindows::new(windows));
+        Self {
+            filter: args.filter.as_ref(),
+            capture,
+        }
+    }
+
+    // Write the windows sidecar once the run is generated, so it has the hook marked ones too
+    fn export_windows(
+        &self,
+        generator: &TelemetryGenerator,
+        output_dir: &Path,
+        output_file: &str,
+    ) -> Result<()> {
+        let marked = generator.marked_windows();
+        if self.capture.is_some() || !marked.is_empty() {
+            CaptureExporter::export(self.capture.as_ref(), marked, output_dir, output_file)?;
+        }
+        Ok(())
+    }
+
+    // Drop the readings outside the selection before they reach any exporter
+    fn apply(&self, dataset: &mut TelemetryDataset) {
+        if self.filter.is_none() && self.capture.is_none() {
+            return;
+        }
+        let config = &dataset.config;
+        dataset.readings.retain(|reading| {
+            self.capture
+                .as_ref()
+                .is_none_or(|capture| capture.contains(reading.time_since_launch_ms))
+                && self
+                    .filter
+                    .is_none_or(|filter| filter.matches(reading, config))
+        });
+    }
+}
+
+// Writes the readings file of the binary formats, a chunk at a time or a whole dataset
+enum DataFileExporter {
+    Parquet(ParquetExporter),
+    Arrow(ArrowExporter),
+    #[cfg(feature = "orc")]
+    Orc(OrcExporter),
+    #[cfg(feature = "hdf5")]
+    Hdf5,
+    Sqlite,
+    Avro(AvroExporter),
+}
+
+impl DataFileExporter {
+    fn export(
+        &self,
+        dataset: &TelemetryDataset,
+        output_dir: &Path,
+        output_file: &str,
+    ) -> Result<()> {
+        match self {
+            DataFileExporter::Parquet(exporter) => {
+                exporter.export(dataset, output_dir, output_file)?
+            }
+            DataFileExporter::Arrow(exporter) => {
+                exporter.export(dataset, output_dir, output_file)?
+            }
+            #[cfg(feature = "orc")]
+            DataFileExporter::Orc(exporter) => exporter.export(dataset, output_dir, output_file)?,
+            #[cfg(feature = "hdf5")]
+            DataFileExporter::Hdf5 => Hdf5Exporter::export(dataset, output_dir, output_file)?,
+            DataFileExporter::Sqlite => SqliteExporter::export(dataset, output_dir, output_file)?,
+            DataFileExporter::Avro(exporter) => {
+                exporter.export(dataset, output_dir, output_file)?
+            }
+        }
+        Ok(())
+    }
+
+    // Opened on the first chunk
+    fn create_writer(
+        &self,
+        first: &TelemetryDataset,
+        output_dir: &Path,
+        output_file: &str,
+    ) -> Result<DataFileWriter<'_>> {
+        Ok(match self {
+            DataFileExporter::Parquet(exporter) => DataFileWriter::Parquet(
+                exporter.create_writer(&first.config, output_dir, output_file)?,
+            ),
+            DataFileExporter::Arrow(exporter) => DataFileWriter::Arrow(exporter.create_writer(
+                &first.config,
+                output_dir,
+                output_file,
+            )?),
+            #[cfg(feature = "orc")]
+            DataFileExporter::Orc(exporter) => DataFileWriter::Orc(exporter.create_writer(
+                &first.config,
+                output_dir,
+                output_file,
+            )?),
+            #[cfg(feature = "hdf5")]
+            DataFileExporter::Hdf5 => {
+                DataFileWriter::Hdf5(Hdf5Exporter::create_writer(first, output_dir, output_file)?)
+            }
+            DataFileExporter::Sqlite => DataFileWriter::Sqlite(SqliteExporter::create_writer(
+                first,
+                output_dir,
+                output_file,
+            )?),
+            DataFileExporter::Avro(exporter) => DataFileWriter::Avro(exporter.create_writer(
+                &first.config,
+                output_dir,
+                output_file,
+            )?),
+        })
+    }
+}
+
+enum DataFileWriter<'a> {
+    Parquet(ParquetStreamWriter<'a>),
+    Arrow(ArrowStreamWriter<'a>),
+    #[cfg(feature = "orc")]
+    Orc(OrcStreamWriter<'a>),
+    #[cfg(feature = "hdf5")]
+    Hdf5(Hdf5StreamWriter),
+    Sqlite(SqliteStreamWriter),
+    Avro(AvroStreamWriter),
+}
+
+impl DataFileWriter<'_> {
+    fn write_chunk(&mut self, chunk: &TelemetryDataset) -> Result<()> {
+        match self {
+            DataFileWriter::Parquet(writer) => writer.write_chunk(chunk)?,
+            DataFileWriter::Arrow(writer) => writer.write_chunk(chunk)?,
+            #[cfg(feature = "orc")]
+            DataFileWriter::Orc(writer) => writer.write_chunk(chunk)?,
+            #[cfg(feature = "hdf5")]
+            DataFileWriter::Hdf5(writer) => writer.write_chunk(chunk)?,
+            DataFileWriter::Sqlite(writer) => writer.write_chunk(chunk)?,
+            DataFileWriter::Avro(writer) => writer.write_chunk(chunk)?,
+        }
+        Ok(())
+    }
+
+    fn bytes_written(&self) -> u64 {
+        match self {
+            DataFileWriter::Parquet(writer) => writer.bytes_written(),
+            DataFileWriter::Arrow(writer) => writer.bytes_written(),
+            #[cfg(feature = "orc")]
+            DataFileWriter::Orc(writer) => writer.bytes_written(),
+            #[cfg(feature = "hdf5")]
+            DataFileWriter::Hdf5(writer) => writer.bytes_written(),
+            DataFileWriter::Sqlite(writer) => writer.bytes_written(),
+            DataFileWriter::Avro(writer) => writer.bytes_written(),
+        }
+    }
+
+    fn close(self) -> Result<()> {
+        match self {
+            DataFileWriter::Parquet(writer) => writer.close()?,
+            DataFileWriter::Arrow(writer) => writer.close()?,
+            #[cfg(feature = "orc")]
+            DataFileWriter::Orc(writer) => writer.close()?,
+            #[cfg(feature = "hdf5")]
+            DataFileWriter::Hdf5(writer) => writer.close()?,
+            DataFileWriter::Sqlite(writer) => writer.close()?,
+            DataFileWriter::Avro(writer) => writer.close()?,
+        }
+        Ok(())
+    }
+}
+
+// Stream chunks from the generator into parquet so only one chunk is held in memory at a time.
+// The watchdog can stop the run between chunks, in which case the file is still closed out cleanly.
+// For the text formats the data file sink is the one watched
+fn export_chunked(
+    generator: &mut TelemetryGenerator,
+    data_file: Option<&DataFileExporter>,
+    stream_exporters: &[StreamExporter],
+    watchdog: &DiskWatchdog,
+    args: &GenerateArgs,
+    chunk_readings: usize,
+    output_file: &str,
+) -> Result<usize> {
+    let output_dir = args.output_dir.as_path();
+    let mut started = false;
+    let mut data_writer = None;
+    let mut stream_sinks = Vec::with_capacity(stream_exporters.len());
+    let mut total_readings: usize = 0;
+    let mut disk_low_bytes: Option<u64> = None;
+    let selection = ReadingSelection::new(args, generator);
+
+    generator.generate_chunked(
+        args.disable_progress,
+        chunk_readings,
+        |chunk| -> Result<_> {
+            if !started {
+                started = true;
+                // Save metadata to CSV
+                info!("Write out metadata around the run");
+                CsvMetadataExporter::export(chunk, output_dir, output_file)?;
+                SensorCatalogExporter::export(&chunk.config, output_dir, output_file)?;
+                EvolutionManifestExporter::export(&chunk.config, output_dir, output_file)?;
+                CalibrationExporter::export(&chunk.config, output_dir, output_file)?;
+                for stream_exporter in stream_exporters {
+                    stream_sinks.push(stream_exporter.start(chunk, output_file)?);
+                }
+                if let Some(data_file) = data_file {
+                    data_writer = Some(data_file.create_writer(chunk, output_dir, output_file)?);
+                }
+            }
+
+            selection.apply(chunk);
+            total_readings += chunk.readings.len();
+            for sink in &mut stream_sinks {
+                sink.write_chunk(chunk)?;
+            }
+            let bytes_written = match &mut data_writer {
+                Some(writer) => {
+                    writer.write_chunk(chunk)?;
+                    writer.bytes_written()
+                }
+                None => stream_sinks.iter().map(StreamSink::file_bytes).sum(),
+            };
+
+            match watchdog.check(bytes_written, total_readings, chunk_readings)? {
+                WatchdogVerdict::Continue => Ok(ControlFlow::Continue(())),
+                WatchdogVerdict::OutputCapReached => {
+                    warn!(
+                        "Stopping at {} bytes as the next chunk would pass the max output size",
+                        bytes_written.to_formatted_string(&Locale::en)
+                    );
+                    Ok(ControlFlow::Break(()))
+                }
+                WatchdogVerdict::DiskLow { available_bytes } => {
+                    disk_low_bytes = Some(available_bytes);
+                    Ok(ControlFlow::Break(()))
+                }
+            }
+        },
+    )?;
+
+    match data_writer {
+        Some(writer) => writer.close()?,
+        None if !started => warn!("No readings to export. Exiting export."),
+        None => {}
+    }
+    for sink in stream_sinks {
+        sink.close()?;
+    }
+    selection.export_windows(generator, output_dir, output_file)?;
+
+    if let Some(available_bytes) = disk_low_bytes {
+        let data_extension = match args.format {
+            format if format.is_text() => {
+                format!("{}{}", format.extension(), args.text_compression.suffix())
+            }
+            format => format.extension().to_string(),
+        };
+        return Err(anyhow!(
+            "Disk is nearly full with {} bytes free. Stopped early and closed {} \
+            with the first {} readings",
+            available_bytes.to_formatted_string(&Locale::en),
+            output_file_path(output_dir, output_file, &data_extension).display(),
+            total_readings.to_formatted_string(&Locale::en)
+        )
+        .context(Failure::Io));
+    }
+
+    Ok(total_readings)
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "Telemetry Generator")]
+#[command(about = "A tool to generate mock telemetry data", long_about = None)]
+#[command(author = "Jason Gedamke")]
+struct Cli {
+    #[arg(long, value_name = "LEVEL")]
+    log_level: Option<Level>,
+
+    // Location to save the log files
+    #[arg(long, value_name = "DIRECTORY")]
+    log_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Start the server
+    // Later flags override earlier ones so the command line wins over --scenario templates
+    #[command(args_override_self = true)]
+    Generate(Box<GenerateArgs>),
     // Generate data to send to InfluxDB
     // todo reuse some params from above in generate
-    InfluxDB {
-        #[arg(long, default_value = "http://localhost:8086")]
-        url: String,
-        #[arg(short, long)]
-        token: String,
-        #[arg(short, long)]
-        org: String,
-        #[arg(short, long)]
-        bucket: String,
-        #[arg(long, default_value = "5000")]
-        batch_size: usize,
+    InfluxDB(InfluxArgs),
+    /// Generate readings at wall clock pace and send each timestep as it comes due, until Ctrl-C
+    Stream(StreamArgs),
+    /// Run a plan of named generate runs, one after another or in parallel
+    Experiment(ExperimentArgs),
+    /// Fly N dispersed missions from one set of generate flags and summarize their outcomes
+    Montecarlo(MontecarloArgs),
+    /// Copy a run with shifted times, offset positions and a shared launch id for sharing
+    Anonymize(AnonymizeArgs),
+    /// Combine several runs into one dataset with a run column
+    Merge(MergeArgs),
+    /// Interpolate every channel of a run onto a uniform time grid, one column per channel
+    Resample(ResampleArgs),
+    /// Compare Parquet codecs and row group sizes on the same data
+    BenchCodecs(BenchCodecsArgs),
+    /// Write the same run tall and wide, as one file and partitioned, and compare them
+    BenchLayouts(BenchLayoutsArgs),
+    /// Time a full scan, a sensor filter and a time slice against generated files
+    BenchRead(BenchReadArgs),
+    /// Run SQL over parquet and CSV outputs with DataFusion
+    Query(QueryArgs),
+    /// Print the JSON Schema of the plugin NDJSON frames, or check a captured stream against it
+    Schema(SchemaArgs),
+    /// Check this build before trusting it with a big campaign: a parquet round trip, line
+    /// protocol, physics invariants and determinism, each on a short run
+    Selftest(SelftestArgs),
+    /// Ready made scenario templates for --scenario
+    Scenario {
+        #[command(subcommand)]
+        command: ScenarioCommand,
+    },
+    /// Look through the history of completed runs
+    Runs {
+        #[command(subcommand)]
+        command: RunsCommand,
+    },
+    /// Stream in the background until stopped, like `stream` with a pid and state file. With
+    /// --listen it also serves the HTTP API for generating datasets on demand
+    Start(StartArgs),
+    /// Stop the background stream and wait for it to close its sink
+    Stop(DaemonArgs),
+    /// Show whether the background stream is up, its readings per second and sink health
+    Status(DaemonArgs),
+}
+
+#[derive(Args, Debug)]
+struct StartArgs {
+    #[command(flatten)]
+    stream: StreamArgs,
+
+    // Where the pid, state and log files are kept
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    run_dir: PathBuf,
+
+    // Run in this process instead of in the background, e.g. under systemd
+    #[arg(long)]
+    foreground: bool,
+
+    // Serve the HTTP API on this address, e.g. 127.0.0.1:8080. See `server.rs` for the routes
+    #[arg(long, value_name = "ADDR")]
+    listen: Option<SocketAddr>,
+
+    // Only serve the API, without streaming to --to
+    #[arg(long, requires = "listen")]
+    api_only: bool,
+
+    // Jobs from `POST /jobs` generated at once, each on a core of its own
+    #[arg(long, default_value_t = 2, requires = "listen")]
+    workers: usize,
+
+    // Jobs waiting for a worker before new ones are turned away
+    #[arg(long, default_value_t = 16, requires = "listen")]
+    queue_size: usize,
+
+    // Most readings a job can ask for
+    #[arg(
+        long,
+        value_name = "READINGS",
+        default_value_t = 50_000_000,
+        requires = "listen"
+    )]
+    job_max_rows: usize,
+
+    // Memory a job generates in, e.g. 256MB. Jobs can ask for less
+    #[arg(
+        long,
+        value_name = "BYTES",
+        default_value = "256MB",
+        requires = "listen"
+    )]
+    job_memory_limit: MemoryBudget,
+}
+
+#[derive(Args, Debug)]
+struct SelftestArgs {
+    // Seed of the runs checked
+    #[arg(long, default_value = "1337")]
+    seed: u64,
+
+    // Scratch files go in `.selftest` under here and are removed after
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct DaemonArgs {
+    // Directory the daemon was started with
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    run_dir: PathBuf,
+}
+
+#[derive(Subcommand, Debug)]
+enum ScenarioCommand {
+    /// List the templates with what they set
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum RunsCommand {
+    /// List the most recent runs
+    List {
+        // Directory holding the runs.sqlite history, the output dir the runs wrote to
+        #[arg(long, value_name = "DIR", default_value = "output")]
+        output_dir: PathBuf,
+
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Show everything recorded about a run. Any unique prefix of the id works
+    Show {
+        id: String,
+
+        #[arg(long, value_name = "DIR", default_value = "output")]
+        output_dir: PathBuf,
     },
-    // Todo idea: Generate data nonstop and feed into a local InfluxDB instance
-    // Use it to test out theories for data storage
-    Start,
-    // Stop the server
-    Stop,
-    // Check the server status
-    Status,
+}
+
+#[derive(Args, Debug)]
+struct ExperimentArgs {
+    // YAML plan listing the runs. See src/experiment/plan.rs for the format
+    plan: PathBuf,
+
+    // Runs to execute at once. Overrides `parallel` from the plan
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    parallel: Option<u16>,
+
+    // Runs write to {output_dir}/{plan name}/{run name}, next to the experiment.csv report
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct MontecarloArgs {
+    // Missions to fly
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    runs: u32,
+
+    // Seed of the first mission, the rest count up from it
+    #[arg(long, default_value_t = 1)]
+    base_seed: u64,
+
+    // Runs write to {output_dir}/{name}/run-0001 and on, next to the montecarlo.csv summary
+    #[arg(long, default_value = "montecarlo")]
+    name: String,
+
+    // Missions to fly at once
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    parallel: u16,
+
+    // Chance a mission gets faults injected
+    #[arg(long, value_name = "P", default_value_t = 0.3)]
+    fault_probability: f64,
+
+    // Most faults a faulty mission gets
+    #[arg(long, default_value_t = 3)]
+    max_faults: usize,
+
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+
+    // Generate flags every mission starts from, after `--`, e.g. -- --khz 1 -d 300
+    #[arg(last = true, value_name = "GENERATE_FLAGS")]
+    generate: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct AnonymizeArgs {
+    // Parquet file of the run. Its metadata, sensor catalog and evolution files come along
+    parquet_file: PathBuf,
+
+    // CSV of launch_id,shared_id. Keep it out of what you share, it undoes the anonymization.
+    // Launches missing from it get a random shared id added. Created readable by you only
+    #[arg(long, value_name = "CSV")]
+    launch_id_map: PathBuf,
+
+    // Added to every absolute timestamp, like 30d or -6h. Time since launch is unchanged
+    #[arg(
+        long,
+        value_name = "TIME",
+        default_value = "0",
+        allow_hyphen_values = true
+    )]
+    time_shift: String,
+
+    // Degrees added to every latitude reading
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    lat_offset: f64,
+
+    // Degrees added to every longitude reading
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    lon_offset: f64,
+
+    // Launch id of the run, for when its metadata CSV is missing
+    #[arg(long, value_name = "ID")]
+    launch_id: Option<String>,
+
+    #[arg(long, value_name = "DIR", default_value = "shared")]
+    output_dir: PathBuf,
+
+    // Overwrite an earlier anonymized copy
+    #[arg(long, default_value = "false")]
+    force: bool,
+}
+
+#[derive(Args, Debug)]
+struct MergeArgs {
+    // Parquet files of the runs. They need the same value columns and units
+    #[arg(required = true, num_args = 2..)]
+    runs: Vec<PathBuf>,
+
+    // Interleave by absolute time, or line the runs up on time since launch
+    #[arg(long, value_enum, default_value = "absolute")]
+    align: MergeAlignment,
+
+    // Value of the run column, one per run in order. Defaults to the file names
+    #[arg(long, value_name = "TAG")]
+    tag: Vec<String>,
+
+    #[arg(long, value_name = "NAME", default_value = "merged")]
+    output_name: String,
+
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+
+    // Overwrite an earlier merge with the same name
+    #[arg(long, default_value = "false", conflicts_with = "append_suffix")]
+    force: bool,
+
+    // Add a `_1`, `_2`, ... suffix instead of failing when the output already exists
+    #[arg(long, default_value = "false")]
+    append_suffix: bool,
+}
+
+#[derive(Args, Debug)]
+struct ResampleArgs {
+    // Parquet file of the run, in either value column layout
+    parquet_file: PathBuf,
+
+    // Grid rate in Hz. Decimals like 0.5 work
+    #[arg(long, value_name = "FREQUENCY")]
+    hz: SampleRate,
+
+    #[arg(long, value_enum, default_value = "linear")]
+    method: Interpolation,
+
+    // Readings further apart than this, like 500ms or 2s, are a gap. Off by default
+    #[arg(long, value_name = "TIME")]
+    max_gap: Option<String>,
+
+    // Fill for grid points inside a gap
+    #[arg(long, value_enum, default_value = "null", requires = "max_gap")]
+    gap_policy: GapPolicy,
+
+    // Defaults to `{input name}_{hz}hz_grid`
+    #[arg(long, value_name = "NAME")]
+    output_name: Option<String>,
+
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+
+    // Overwrite an earlier grid with the same name
+    #[arg(long, default_value = "false", conflicts_with = "append_suffix")]
+    force: bool,
+
+    // Add a `_1`, `_2`, ... suffix instead of failing when the output already exists
+    #[arg(long, default_value = "false")]
+    append_suffix: bool,
+}
+
+#[derive(Args, Debug)]
+struct BenchCodecsArgs {
+    // Parquet file of the run to rewrite
+    parquet_file: PathBuf,
+
+    // Comma separated, all of them by default
+    #[arg(
+        long = "codec",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "uncompressed,snappy,gzip,lz4,zstd,brotli"
+    )]
+    codecs: Vec<Codec>,
+
+    // Comma separated max rows per row group, like 131072,1048576
+    #[arg(long, value_delimiter = ',', default_value = "1048576")]
+    row_group_rows: Vec<usize>,
+
+    // Write and read each variant this many times and keep the fastest
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    repeat: u16,
+
+    // Scratch directory for the variants
+    #[arg(long, value_name = "DIR", default_value = "output/bench-codecs")]
+    work_dir: PathBuf,
+
+    // Leave the variants in the work dir to poke at afterwards
+    #[arg(long, default_value = "false")]
+    keep_files: bool,
+
+    // Also write the comparison as CSV
+    #[arg(long, value_name = "CSV")]
+    report: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct BenchLayoutsArgs {
+    // Parquet file of the run to rewrite
+    parquet_file: PathBuf,
+
+    #[arg(long, value_enum, default_value = "snappy")]
+    codec: Codec,
+
+    // Time since launch per file when partitioning by time, like 60s or 10m
+    #[arg(long, value_name = "TIME", default_value = "60s")]
+    partition_window: String,
+
+    // Scratch directory for the variants
+    #[arg(long, value_name = "DIR", default_value = "output/bench-layouts")]
+    work_dir: PathBuf,
+
+    // Leave the variants in the work dir to poke at afterwards
+    #[arg(long, default_value = "false")]
+    keep_files: bool,
+
+    // Also write the comparison as CSV
+    #[arg(long, value_name = "CSV")]
+    report: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct BenchReadArgs {
+    // Parquet files, or directories of them like partitioned bench-layouts output
+    #[arg(required = true)]
+    datasets: Vec<PathBuf>,
+
+    // Sensor for the filter query. Defaults to the first one in each dataset
+    #[arg(long, value_name = "SENSOR_TYPE")]
+    sensor: Option<String>,
+
+    // Time since launch range for the slice query, like 60s..90s. Defaults to the middle tenth
+    #[arg(long, value_name = "START..END")]
+    time_slice: Option<String>,
+
+    // Runs per query, min, median and max are reported
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u16).range(1..))]
+    repeat: u16,
+
+    // Also write the latencies as CSV
+    #[arg(long, value_name = "CSV")]
+    report: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct InfluxArgs {
+    #[arg(long, default_value = "http://localhost:8086")]
+    url: String,
+    #[arg(short, long)]
+    token: String,
+    #[arg(short, long)]
+    org: String,
+    #[arg(short, long)]
+    bucket: String,
+    // Readings per write, or the starting point with --auto-batch
+    #[arg(long, default_value = "5000")]
+    batch_size: usize,
+
+    // Tune the batch size for throughput from write latency, 429s and timeouts
+    #[arg(long)]
+    auto_batch: bool,
+
+    #[arg(long, default_value = "500", requires = "auto_batch")]
+    min_batch_size: usize,
+
+    #[arg(long, default_value = "100000", requires = "auto_batch")]
+    max_batch_size: usize,
+
+    // Writes slower than this shrink the batch, like 500ms or 2s
+    #[arg(
+        long,
+        value_name = "TIME",
+        default_value = "1s",
+        requires = "auto_batch"
+    )]
+    target_latency: String,
+
+    // Writes slower than this are retried smaller
+    #[arg(
+        long,
+        value_name = "TIME",
+        default_value = "10s",
+        requires = "auto_batch"
+    )]
+    write_timeout: String,
+
+    // Duration of simulated flight in seconds
+    #[arg(short, long, value_name = "DURATION", default_value = "120")]
+    duration: usize,
+
+    // Frequency rate in Hz, e.g. 0.5 or 333.3
+    #[arg(long, value_name = "FREQUENCY", default_value = "100")]
+    hz: SampleRate,
+
+    #[arg(long, default_value = "SIM-001")]
+    launch_id: String,
+
+    #[arg(long, default_value = "1337")]
+    seed: u64,
+
+    // Naming convention for the `sensor` tag, like generate's --naming
+    #[arg(long, value_enum, default_value_t = SensorNaming::Short)]
+    naming: SensorNaming,
+
+    // Disable progress bar
+    #[arg(long, default_value = "false")]
+    disable_progress: bool,
+
+    // Where the export journal `{launch id}.influxdb.journal.json` is kept
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+
+    // Continue an export that failed part way, after the last batch the journal confirmed
+    #[arg(long)]
+    resume_export: bool,
+
+    // Start over even though the journal has an unfinished export
+    #[arg(long, conflicts_with = "resume_export")]
+    force: bool,
+
+    // Launch time from the seed, so a rerun writes every point at the same timestamp and
+    // overwrites it rather than adding a duplicate
+    #[arg(long)]
+    idempotent: bool,
+
+    // Yellow and red limits on a sensor, e.g. `cmb_pa > 8000000/9000000`. Repeat for several.
+    // Exceedances are written to the `rocket_alerts` measurement once the readings are sent
+    #[arg(long = "limit", value_name = "LIMIT")]
+    limits: Vec<SensorLimit>,
+}
+
+#[derive(Args, Debug, Clone)]
+struct StreamArgs {
+    // stdout, tcp://host:port, udp://host:port, ws://host:port/path, kafka://host:port/topic,
+    // mqtt://host:port, mqtts://host:port, modbus://ip:port to serve --register channels, opc.tcp://ip:port to serve
+    // every channel over OPC UA, or influxdb for the server at --url
+    #[arg(long, value_name = "SINK", default_value = "stdout")]
+    to: LiveTarget,
+
+    // Encoding sent to stdout, tcp, udp, ws and kafka
+    #[arg(long, value_enum, default_value = "json")]
+    format: FrameFormat,
+
+    #[arg(long, default_value = "http://localhost:8086")]
+    url: String,
+    #[arg(long, required_if_eq("to", "influxdb"))]
+    token: Option<String>,
+    #[arg(long, required_if_eq("to", "influxdb"))]
+    org: Option<String>,
+    #[arg(long, required_if_eq("to", "influxdb"))]
+    bucket: Option<String>,
+
+    // MQTT topic per reading. {launch_id} and {sensor} are filled in, leave {sensor} out for one
+    // topic
+    #[arg(long, default_value = DEFAULT_MQTT_TOPIC)]
+    topic: String,
+    #[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=2))]
+    qos: u8,
+    // Have the broker keep each topic's last reading for late subscribers
+    #[arg(long)]
+    retain: bool,
+    // PEM CA certificate for mqtts:// brokers, instead of the system's
+    #[arg(long, value_name = "FILE")]
+    ca_file: Option<PathBuf>,
+    // MQTT client id, telemetry-generator-{launch id} by default
+    #[arg(long)]
+    client_id: Option<String>,
+    #[arg(long)]
+    username: Option<String>,
+    #[arg(long, requires = "username")]
+    password: Option<String>,
+
+    // Channel served over Modbus as a float in two holding registers from an address counting
+    // from 0, e.g. `alt=0`. Repeat for several
+    #[arg(long = "register", value_name = "SENSOR=ADDRESS")]
+    registers: Vec<ModbusRegister>,
+
+    // Where the OPC UA server keeps its certificate, made the first time
+    #[arg(long, value_name = "DIR", default_value = "pki")]
+    pki_dir: PathBuf,
+
+    // Duration of each simulated flight in seconds
+    #[arg(short, long, value_name = "DURATION", default_value = "120")]
+    duration: usize,
+
+    // Frequency rate in Hz, e.g. 0.5 or 333.3
+    #[arg(long, value_name = "FREQUENCY", default_value = "10")]
+    hz: SampleRate,
+
+    // Later flights get `-2`, `-3` and so on appended
+    #[arg(long, default_value = "LIVE-001")]
+    launch_id: String,
+
+    // Seed of the first flight, each one after it takes the next
+    #[arg(long, default_value = "1337")]
+    seed: u64,
+
+    // Launch time of the first flight as RFC3339, e.g. 2024-01-01T12:00:00Z. Defaults to now.
+    // Later flights keep the same offset from the wall clock
+    #[arg(long, value_name = "RFC3339")]
+    launch_time: Option<DateTime<Utc>>,
+
+    // Launch this many days in the past to backfill historical data while streaming
+    #[arg(long, value_name = "DAYS", conflicts_with = "launch_time")]
+    backfill_days: Option<u32>,
+
+    // Stop after one flight instead of launching the next
+    #[arg(long)]
+    once: bool,
+}
+
+impl StreamArgs {
+    // How far behind the wall clock flights launch, negative for a launch time in the future
+    fn launch_offset(&self) -> Duration {
+        match (self.launch_time, self.backfill_days) {
+            (Some(launch_time), _) => Utc::now() - launch_time,
+            (None, Some(days)) => Duration::days(days as i64),
+            (None, None) => Duration::zero(),
+        }
+    }
+}
+
+// Where the stream command sends readings
+#[derive(Debug, Clone)]
+enum LiveTarget {
+    Sink(SinkTarget),
+    // The server at --url, written with --token, --org and --bucket
+    InfluxDB,
+    // Published a reading per message to --topic
+    Mqtt(MqttBroker),
+    // Served to pollers from the registers mapped with --register
+    Modbus(SocketAddr),
+    // Every channel served to OPC UA clients as a variable
+    OpcUa(SocketAddr),
+}
+
+impl FromStr for LiveTarget {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "influxdb" => Ok(LiveTarget::InfluxDB),
+            _ if s.starts_with("mqtt://") || s.starts_with("mqtts://") => {
+                s.parse().map(LiveTarget::Mqtt)
+            }
+            _ if let Some(addr) = s.strip_prefix("modbus://") => {
+                addr.parse().map(LiveTarget::Modbus).map_err(|_| {
+                    TelemetryError::config(format!(
+                        "Modbus server {s} should look like modbus://ip:port"
+                    ))
+                })
+            }
+            _ if let Some(addr) = s.strip_prefix("opc.tcp://") => {
+                addr.parse().map(LiveTarget::OpcUa).map_err(|_| {
+                    TelemetryError::config(format!(
+                        "OPC UA server {s} should look like opc.tcp://ip:port"
+                    ))
+                })
+            }
+            _ => s.parse().map(LiveTarget::Sink),
+        }
+    }
+}
+
+impl fmt::Display for LiveTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LiveTarget::Sink(target) => write!(f, "{target}"),
+            LiveTarget::InfluxDB => write!(f, "influxdb"),
+            LiveTarget::Mqtt(broker) => write!(f, "{broker}"),
+            LiveTarget::Modbus(addr) => write!(f, "modbus://{addr}"),
+            LiveTarget::OpcUa(addr) => write!(f, "opc.tcp://{addr}"),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct QueryArgs {
+    // Any query DataFusion runs. See src/query/engine.rs for the table names
+    sql: String,
+
+    // Parquet or CSV files, each a table of its own and together as `t`
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+
+    #[arg(long, value_enum, default_value = "table")]
+    format: QueryOutput,
+}
+
+#[derive(Args, Debug)]
+struct SchemaArgs {
+    // Write the schema here instead of stdout
+    #[arg(long, value_name = "FILE", conflicts_with = "validate")]
+    output: Option<PathBuf>,
+
+    // NDJSON captured from a plugin to check frame by frame instead
+    #[arg(long, value_name = "FILE")]
+    validate: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+struct GenerateArgs {
+    // Duration of simulated flight in seconds
+    #[arg(short, long, value_name = "DURATION", default_value = "120")]
+    duration: usize,
+
+    // Frequency rate. Default is 1 kHz = 1,000 Hz
+    #[arg(long, value_name = "FREQUENCY", default_value = "1")]
+    khz: SampleRate,
+
+    // Frequency rate in Hz instead of kHz, e.g. 0.5 or 333.3
+    #[arg(long, value_name = "FREQUENCY", overrides_with = "khz")]
+    hz: Option<SampleRate>,
+
+    // TODO: Could also add other meta data. vehicle_type, engine_type, etc.
+    #[arg(long, default_value = "SIM-001")]
+    launch_id: String,
+
+    #[arg(long, default_value = "1337")]
+    seed: u64,
+
+    // Disable progress bar
+    #[arg(long, default_value = "false")]
+    disable_progress: bool,
+
+    #[arg(long)]
+    max_rows: Option<usize>,
+
+    // Work out the duration that gives this many rows at --hz, instead of flying --duration
+    #[arg(long, value_name = "ROWS", conflicts_with_all = ["duration", "max_rows", "target_bytes"])]
+    target_rows: Option<usize>,
+
+    // Work out the duration that gives about this much output at --hz, e.g. 100GB. Measured on a
+    // short run with the same settings first
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size, conflicts_with_all = ["duration", "max_rows"])]
+    target_bytes: Option<u64>,
+
+    #[arg(long, default_value = "50.0")]
+    timestamp_jitter: f64,
+
+    // Sort rows by timestamp and record the sort order in the parquet metadata
+    #[arg(long, default_value = "false")]
+    sort_by_timestamp: bool,
+
+    // Write a bloom filter on the sensor_type column
+    #[arg(long, default_value = "false")]
+    bloom_filter: bool,
+
+    // False positive probability of the bloom filter
+    #[arg(long, default_value = "0.05")]
+    bloom_filter_fpp: f64,
+
+    // Only write row group statistics, no page level column index
+    #[arg(long, default_value = "false")]
+    disable_column_index: bool,
+
+    // Max rows per data page. Smaller pages make the column index more selective
+    #[arg(long)]
+    data_page_row_limit: Option<usize>,
+
+    // Single float `value` column or split value_float/value_int/value_str columns
+    #[arg(long, value_enum, default_value = "single")]
+    value_columns: ValueColumns,
+
+    // Long writes a row per reading. Wide writes a row per timestep with a column per sensor,
+    // named like ChamberPressure, for ML training
+    #[arg(long, value_enum, default_value = "long")]
+    layout: RowLayout,
+
+    // Memory to use for buffered readings, e.g. 2GB. Chunk sizes and flushes are derived from it
+    #[arg(long, value_name = "SIZE")]
+    memory_limit: Option<MemoryBudget>,
+
+    // Stream to parquet in chunks of this many readings, e.g. 1000000, each written as a row group
+    // as soon as it's generated. Only one chunk is held in memory
+    #[arg(long, value_name = "READINGS", conflicts_with = "memory_limit")]
+    chunk_rows: Option<usize>,
+
+    // Sample noise in blocks. Faster on high rate runs but values differ from the default for a seed
+    #[arg(long, default_value = "false")]
+    batch_noise: bool,
+
+    // Launch time as RFC3339, e.g. 2024-01-01T12:00:00Z. Defaults to now
+    #[arg(long, value_name = "RFC3339")]
+    launch_time: Option<DateTime<Utc>>,
+
+    // Launch this many days in the past to backfill historical data
+    #[arg(long, value_name = "DAYS", conflicts_with = "launch_time")]
+    backfill_days: Option<u32>,
+
+    // Derive the launch time from the seed so identical configs produce byte identical files
+    #[arg(long, default_value = "false", conflicts_with_all = ["launch_time", "backfill_days"])]
+    seeded_launch_time: bool,
+
+    // Seeded launch time plus a stable `{launch_id}-{seed}-{index}` id per reading in the plugin
+    // frames and the outputs, so rerunning after a failure overwrites points in stores that
+    // dedupe instead of duplicating them
+    #[arg(long, conflicts_with_all = ["launch_time", "backfill_days"])]
+    idempotent: bool,
+
+    // Start from a ready made template, see `scenario list`. Other flags override its settings.
+    // Or a .toml or .yaml flight profile of the phases to fly, see profiles/default.toml
+    #[arg(long, value_name = "NAME|PATH")]
+    scenario: Option<ScenarioSource>,
+
+    // Scenario script adjusting sensor values at given times, e.g. `at 45s set fuel_pressure_pa bias +5%`
+    #[arg(long, value_name = "PATH")]
+    script: Option<PathBuf>,
+
+    // Rule driving the health status channel, e.g. `nozzle_temperature_k > 3000 for 1s => warning`.
+    // Repeat for several. Transitions are written to `{name}.health.csv`
+    #[arg(long = "health-rule", value_name = "RULE")]
+    health_rules: Vec<HealthRule>,
+
+    // Yellow and red limits on a sensor, e.g. `cmb_pa > 8000000/9000000`. Repeat for several.
+    // Exceedances are written to `{name}.alerts.csv`
+    #[arg(long = "limit", value_name = "LIMIT")]
+    limits: Vec<SensorLimit>,
+
+    // Exporter plugin to also stream readings to as NDJSON on stdin. Repeat for several plugins
+    #[arg(long = "plugin", value_name = "PATH")]
+    plugins: Vec<PathBuf>,
+
+    // Encoding sent to plugins
+    #[arg(long, value_enum, default_value = "json")]
+    plugin_format: FrameFormat,
+
+    // Also stream readings to tcp://host:port or udp://host:port. Repeat for several sinks
+    #[arg(long = "sink", value_name = "URL")]
+    sinks: Vec<SinkTarget>,
+
+    // Encoding sent to --sink targets
+    #[arg(long, value_enum, default_value = "json")]
+    sink_format: FrameFormat,
+
+    // Write the readings to `{name}.parquet`, or to `{name}.csv`, `{name}.json`, `{name}.ndjson`,
+    // `{name}.sqlite`, `{name}.arrow`, `{name}.arrows`, `{name}.avro`, `{name}.orc` or `{name}.h5`
+    // instead
+    #[arg(long, value_enum, default_value = "parquet")]
+    format: DataFormat,
+
+    // Block compression of --format avro
+    #[arg(long, value_enum, default_value = "deflate")]
+    avro_codec: AvroCodec,
+
+    // Uncompressed size of the record blocks in --format avro, e.g. 64KB
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size, default_value = "64KiB")]
+    avro_block_size: u64,
+
+    // Also write the readings as text, `{name}.ndjson` for json and ndjson, `{name}.csv` for csv
+    // or `{name}.json` for json-document
+    #[arg(long, value_enum, value_name = "FORMAT")]
+    text_output: Option<FrameFormat>,
+
+    // Compress the text output, or the data file of a text --format, as it's written, adding
+    // .zst or .gz
+    #[arg(long, value_enum, default_value = "none")]
+    text_compression: TextCompression,
+
+    // Also write the SHA-256 of each output file to `{name}.sha256` for sha256sum -c. They're
+    // always in the run history
+    #[arg(long)]
+    checksum_file: bool,
+
+    // Instead of writing anything, generate the run twice, as one chunk and as a chunk per
+    // timestep, and check both give the same digest of the readings
+    #[arg(long)]
+    verify_determinism: bool,
+
+    // Also write `{name}.summary.xlsx` with the run settings, statistics per sensor and the event
+    // timeline, for reading in Excel. No raw readings
+    #[arg(long)]
+    xlsx_summary: bool,
+
+    // Stop cleanly once the parquet output reaches this size, e.g. 10GB
+    #[arg(long, value_name = "SIZE", value_parser = parse_byte_size)]
+    max_output_bytes: Option<u64>,
+
+    // Hold every sensor steady around a setpoint instead of flying the mission profile
+    #[arg(long, default_value = "false")]
+    cruise: bool,
+
+    // Override a cruise setpoint, e.g. `chamber_pressure_pa=4500000`. Repeat for more fields
+    #[arg(long = "setpoint", value_name = "FIELD=VALUE", requires = "cruise")]
+    setpoints: Vec<Setpoint>,
+
+    // Wave layered onto a field, e.g. `fuel_temperature_k:5:1d` or `altitude_m:8000:92m:23m` with a phase.
+    // Repeat for more
+    #[arg(long, value_name = "FIELD:AMPLITUDE:PERIOD[:PHASE]")]
+    periodic: Vec<PeriodicPattern>,
+
+    // Ready made periodic patterns for long runs. Repeat for more
+    #[arg(long = "periodic-preset", value_enum, value_name = "PRESET")]
+    periodic_presets: Vec<PeriodicPreset>,
+
+    // Units values are exported in. raw keeps what the simulation produces
+    #[arg(long, value_enum, default_value_t = UnitSystem::Raw)]
+    units: UnitSystem,
+
+    // Naming convention for exported sensor names: F_pa, FuelPressure_pa, FuelPressure or
+    // fuel_pressure
+    #[arg(long, value_enum, default_value_t = SensorNaming::Short)]
+    naming: SensorNaming,
+
+    // Change the schema part way through the run to test downstream readers, e.g. add:GyX@5m
+    // or rename:F_pa=fuel_psi@10m. Repeatable. The plan is saved as {name}.evolution.json
+    #[arg(long, value_name = "CHANGE")]
+    evolve: Vec<SchemaChange>,
+
+    // Constant bias added to each gyro axis in deg/s
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    gyro_bias: f64,
+
+    // Gyro scale factor, e.g. 1.001 for a 1000 ppm error
+    #[arg(long, default_value_t = 1.0)]
+    gyro_scale_factor: f64,
+
+    // Constant bias added to each magnetometer axis in µT
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    magnetometer_bias: f64,
+
+    #[arg(long, default_value_t = 1.0)]
+    magnetometer_scale_factor: f64,
+
+    // Wind at an altitude, e.g. `10000:45:270` for 45 m/s from the west at 10 km. Repeat for a
+    // profile, it's interpolated by altitude
+    #[arg(long, value_name = "ALT:SPEED:FROM", conflicts_with = "wind_preset")]
+    wind: Vec<WindLayer>,
+
+    // Ready made wind profile: calm, breezy or jet-stream
+    #[arg(long, value_enum, value_name = "PRESET")]
+    wind_preset: Option<WindPreset>,
+
+    // Gust standard deviation in m/s. Defaults to the preset's, or none with --wind
+    #[arg(long, value_name = "M/S")]
+    gust_intensity: Option<f64>,
+
+    // Force a data shape onto a sensor, e.g. F_pa=random or *=constant. Shapes are constant, slow,
+    // random or step. Repeatable, later ones win
+    #[arg(long = "value-shape", value_name = "SENSOR=SHAPE")]
+    value_shapes: Vec<SensorShape>,
+
+    // Only export the readings matching this, e.g. `sensor in (alt, vel) and
+    // time_since_launch_ms > 30s`. See src/models/filter.rs for the syntax
+    #[arg(long, value_name = "EXPRESSION")]
+    filter: Option<ReadingFilter>,
+
+    // Only export this stretch of the run, e.g. 40s..70s. Repeatable
+    #[arg(long = "window", value_name = "START..END")]
+    windows: Vec<CaptureWindow>,
+
+    // Only export windows centered on these events: staging or anomalies. Repeatable
+    #[arg(long, value_enum, value_name = "EVENT")]
+    capture: Vec<FlightEventKind>,
+
+    // How much of the run to keep before and after each captured event
+    #[arg(long, value_name = "TIME", default_value = "10s", value_parser = parse_time_ms, requires = "capture")]
+    capture_before: u64,
+
+    #[arg(long, value_name = "TIME", default_value = "20s", value_parser = parse_time_ms, requires = "capture")]
+    capture_after: u64,
+
+    // Calibration curve applied to a sensor's raw value, e.g. F_pa=offset:-120, cmb_pa=scale:1.02
+    // or Trst=poly:0.5,1.01,-2e-9. Repeatable. Saved as {name}.calibration.json
+    #[arg(long = "calibrate", value_name = "SENSOR=CURVE")]
+    calibrations: Vec<Calibration>,
+
+    // Also export calibrated sensors' raw values in a raw_value column
+    #[arg(long, requires = "calibrations")]
+    raw_values: bool,
+
+    // Derive phase timings, fault placement and noise level from the seed, so every seed flies a
+    // different mission that can be generated again
+    #[arg(long, default_value = "false")]
+    fuzz: bool,
+
+    // Fly an engine off nominal, drawn from the seed, so a fleet of seeds shows unit to unit
+    // variation in thrust, Isp and pump efficiency
+    #[arg(long)]
+    engine_variation: bool,
+
+    // Engine spreads across the fleet, in percent of nominal
+    #[arg(
+        long,
+        value_name = "PCT",
+        default_value_t = 3.0,
+        requires = "engine_variation"
+    )]
+    thrust_spread: f64,
+
+    #[arg(
+        long,
+        value_name = "PCT",
+        default_value_t = 2.0,
+        requires = "engine_variation"
+    )]
+    isp_spread: f64,
+
+    #[arg(
+        long,
+        value_name = "PCT",
+        default_value_t = 3.0,
+        requires = "engine_variation"
+    )]
+    pump_efficiency_spread: f64,
+
+    // Most faults a fuzzed run injects
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "fuzz")]
+    fuzz_max_faults: usize,
+
+    // How far fuzzed phase boundaries move, as a fraction of the neighbouring phase. Below 0.5
+    #[arg(
+        long,
+        value_name = "FRACTION",
+        default_value_t = 0.25,
+        requires = "fuzz"
+    )]
+    fuzz_phase_jitter: f64,
+
+    // Range of fuzzed noise levels, as multiples of the standard sensor noise
+    #[arg(long, default_value_t = 0.5, requires = "fuzz")]
+    fuzz_noise_min: f64,
+
+    #[arg(long, default_value_t = 3.0, requires = "fuzz")]
+    fuzz_noise_max: f64,
+
+    // Sprinkle seeded faults, gaps, duplicates and out of order readings across the run
+    #[arg(long, value_enum, value_name = "LEVEL")]
+    chaos: Option<ChaosLevel>,
+
+    // Fan each sensor out into N synthetic instances, F_pa_001 to F_pa_N, for cardinality stress.
+    // Also scales the volume N times at the same timing, like N engines or telemetry nodes
+    #[arg(long, visible_alias = "sensor-multiplier", value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=999))]
+    sensor_instances: u16,
+
+    // Integrate the flight physics at this fixed step, like 1ms, rather than once per sample.
+    // Readings are interpolated between steps. Faster at high rates, more accurate at low ones
+    #[arg(long, value_name = "TIME", value_parser = parse_time_ms)]
+    physics_step: Option<u64>,
+
+    // How the flight integrates acceleration. rk4 keeps low sample rate runs from drifting in
+    // altitude and velocity. Anything but the default changes the values for a seed
+    #[arg(long, value_enum, value_name = "METHOD", default_value_t = Integrator::Euler)]
+    integrator: Integrator,
+
+    // Time constants the turbopump spools up and down to its commanded speed with, shaping the
+    // RPM transients at ignition, staging and shutdown. 0s follows the command instantly
+    #[arg(long, value_name = "TIME", default_value = "1s", value_parser = parse_time_ms)]
+    pump_spool_up: u64,
+    #[arg(long, value_name = "TIME", default_value = "500ms", value_parser = parse_time_ms)]
+    pump_spool_down: u64,
+
+    // Roll angle the vehicle rolls onto after lift off, negative to roll the other way. 0 skips
+    // the roll program
+    #[arg(
+        long,
+        value_name = "DEG",
+        default_value_t = 90.0,
+        allow_negative_numbers = true
+    )]
+    roll_program: f64,
+
+    // Fastest the roll program rolls
+    #[arg(long, value_name = "DEG_PER_S", default_value_t = 5.0)]
+    roll_rate: f64,
+
+    // Report a critical sensor as an A and a B copy with their own noise, e.g. `cmb_pa_a` and
+    // `cmb_pa_b`, for testing voting and cross-check logic. Repeat for several
+    #[arg(long, value_name = "SENSOR")]
+    redundant: Vec<SensorEnum>,
+
+    // Chance each redundant pair has one copy start drifting away from the other mid run
+    #[arg(
+        long,
+        value_name = "CHANCE",
+        default_value_t = 0.5,
+        requires = "redundant"
+    )]
+    redundant_drift_chance: f64,
+
+    // Interleave a synthetic uplink command channel, this many commands a minute on average. A
+    // command goes out as a `Cmd` reading with its sequence number and comes back as an `Ack`
+    // reading with the same number and an `Ack_s` reading with the latency
+    #[arg(long, value_name = "PER_MIN", default_value_t = 0.0)]
+    command_rate: f64,
+
+    // Mean time a command takes to be acked. About 1% are never acked
+    #[arg(long, value_name = "TIME", default_value = "250ms", value_parser = parse_time_ms)]
+    command_ack: u64,
+
+    // Directory to write output files to. Created if missing
+    #[arg(long, value_name = "DIR", default_value = "output")]
+    output_dir: PathBuf,
+
+    // Overwrite output files left by an earlier run with the same name
+    #[arg(long, default_value = "false", conflicts_with = "append_suffix")]
+    force: bool,
+
+    // Add a `_1`, `_2`, ... suffix instead of failing when the output already exists
+    #[arg(long, default_value = "false")]
+    append_suffix: bool,
+}
+
+impl GenerateArgs {
+    fn sample_rate(&self) -> Result<SampleRate> {
+        match self.hz {
+            Some(hz) => Ok(hz),
+            None => Ok(self.khz.scaled(1000)?),
+        }
+    }
 }
 
 // // fn init_logger(log_level: Option<Level>, log_dir: Option<&Path>) -> Option<WorkerGuard> {
@@ -0,0 +1,82 @@
+use crate::error::TelemetryError;
+use serde::Serialize;
+use std::str::FromStr;
+
+use super::sensor::SensorEnum;
+
+// Calibration curve turning a sensor's raw value into the reported one, for testing
+// calibration pipelines end to end:
+//
+//   F_pa=offset:-120.5          raw - 120.5
+//   cmb_pa=scale:1.02           raw * 1.02
+//   Trst=poly:0.5,1.01,-2e-9    0.5 + 1.01 raw - 2e-9 raw²
+//
+// The simulated value is taken as the raw one. Only float readings are calibrated
+#[derive(Debug, Clone, Serialize)]
+pub struct Calibration {
+    pub sensor: SensorEnum,
+    pub model: CalibrationModel,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CalibrationModel {
+    Offset { offset: f64 },
+    Scale { scale: f64 },
+    // Lowest order first
+    Polynomial { coefficients: Vec<f64> },
+}
+
+impl CalibrationModel {
+    pub fn apply(&self, raw: f64) -> f64 {
+        match self {
+            CalibrationModel::Offset { offset } => raw + offset,
+            CalibrationModel::Scale { scale } => raw * scale,
+            // Horner's method, highest order first
+            CalibrationModel::Polynomial { coefficients } => coefficients
+                .iter()
+                .rev()
+                .fold(0.0, |acc, coefficient| acc * raw + coefficient),
+        }
+    }
+
+    // Same curve as polynomial coefficients, lowest order first
+    pub fn coefficients(&self) -> Vec<f64> {
+        match self {
+            CalibrationModel::Offset { offset } => vec![*offset, 1.0],
+            CalibrationModel::Scale { scale } => vec![0.0, *scale],
+            CalibrationModel::Polynomial { coefficients } => coefficients.clone(),
+        }
+    }
+}
+
+impl FromStr for Calibration {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || {
+            TelemetryError::config(format!(
+                "Calibration {s} should look like <sensor>=offset:<n>, <sensor>=scale:<n> or \
+                <sensor>=poly:<c0>,<c1>,..."
+            ))
+        };
+        let (sensor, curve) = s.split_once('=').ok_or_else(usage)?;
+        let (kind, numbers) = curve.split_once(':').ok_or_else(usage)?;
+        let sensor = SensorEnum::from_name(sensor.trim())
+            .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {}", sensor.trim())))?;
+        let numbers = numbers
+            .split(',')
+            .map(|n| n.trim().parse::<f64>().map_err(|_| usage()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let model = match (kind.trim(), numbers.as_slice()) {
+            ("offset", [offset]) => CalibrationModel::Offset { offset: *offset },
+            ("scale", [scale]) => CalibrationModel::Scale { scale: *scale },
+            ("poly", coefficients) if !coefficients.is_empty() => CalibrationModel::Polynomial {
+                coefficients: coefficients.to_vec(),
+            },
+            _ => return Err(usage()),
+        };
+        Ok(Self { sensor, model })
+    }
+}
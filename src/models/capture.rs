@@ -0,0 +1,84 @@
+use crate::error::TelemetryError;
+use crate::generators::parse_time_ms;
+use std::str::FromStr;
+
+// Stretch of a run to export, e.g. `40s..70s`. Ends are inclusive
+#[derive(Debug, Clone)]
+pub struct CaptureWindow {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    // What the window is for, e.g. the events it's centered on
+    pub label: String,
+}
+
+impl CaptureWindow {
+    // Window from `before_ms` ahead of an event to `after_ms` past it
+    pub fn around(time_ms: u64, before_ms: u64, after_ms: u64, label: String) -> Self {
+        Self {
+            start_ms: time_ms.saturating_sub(before_ms),
+            end_ms: time_ms.saturating_add(after_ms),
+            label,
+        }
+    }
+}
+
+// Parse `<start>..<end>` with times like `40s` or `2m`
+impl FromStr for CaptureWindow {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once("..").ok_or_else(|| {
+            TelemetryError::config(format!(
+                "Window {s} should look like <start>..<end>, e.g. 40s..70s"
+            ))
+        })?;
+        let start_ms = parse_time_ms(start.trim())?;
+        let end_ms = parse_time_ms(end.trim())?;
+        if end_ms < start_ms {
+            return Err(TelemetryError::config(format!(
+                "Window {s} ends before it starts"
+            )));
+        }
+        Ok(Self {
+            start_ms,
+            end_ms,
+            label: s.trim().to_string(),
+        })
+    }
+}
+
+// The windows of a run to export, overlapping ones merged
+#[derive(Debug, Clone)]
+pub struct CaptureWindows {
+    windows: Vec<CaptureWindow>,
+}
+
+impl CaptureWindows {
+    pub fn new(mut windows: Vec<CaptureWindow>) -> Self {
+        windows.sort_by_key(|window| (window.start_ms, window.end_ms));
+        let mut merged: Vec<CaptureWindow> = Vec::with_capacity(windows.len());
+        for window in windows {
+            match merged.last_mut() {
+                Some(last) if window.start_ms <= last.end_ms => {
+                    last.end_ms = last.end_ms.max(window.end_ms);
+                    last.label = format!("{}; {}", last.label, window.label);
+                }
+                _ => merged.push(window),
+            }
+        }
+        Self { windows: merged }
+    }
+
+    pub fn windows(&self) -> &[CaptureWindow] {
+        &self.windows
+    }
+
+    pub fn contains(&self, time_since_launch_ms: u64) -> bool {
+        // Windows are sorted and don't overlap, so only the last one starting by then can hold it
+        let idx = self
+            .windows
+            .partition_point(|window| window.start_ms <= time_since_launch_ms);
+        idx.checked_sub(1)
+            .is_some_and(|idx| time_since_launch_ms <= self.windows[idx].end_ms)
+    }
+}
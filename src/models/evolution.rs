@@ -0,0 +1,66 @@
+use crate::error::TelemetryError;
+use crate::generators::parse_time_ms;
+use serde::Serialize;
+use std::str::FromStr;
+
+use super::sensor::SensorEnum;
+
+// A planned change to the output schema part way through a run, for testing how downstream
+// readers cope with schema evolution:
+//
+//   add:GyX@5m                 GyX only starts reporting 5 minutes in
+//   rename:F_pa=fuel_psi@10m   F_pa is exported as fuel_psi from 10 minutes on
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SchemaChange {
+    Add {
+        sensor: SensorEnum,
+        at_ms: u64,
+    },
+    Rename {
+        sensor: SensorEnum,
+        name: String,
+        at_ms: u64,
+    },
+}
+
+impl FromStr for SchemaChange {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let usage = || {
+            TelemetryError::config(format!(
+                "Schema change {s} should look like add:<sensor>@<time> or rename:<sensor>=<name>@<time>"
+            ))
+        };
+        let (kind, rest) = s.split_once(':').ok_or_else(usage)?;
+        let (target, at) = rest.rsplit_once('@').ok_or_else(usage)?;
+        let at_ms = parse_time_ms(at.trim())?;
+        let sensor = |name: &str| {
+            SensorEnum::from_name(name.trim())
+                .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {name}")))
+        };
+
+        match kind.trim() {
+            "add" => Ok(SchemaChange::Add {
+                sensor: sensor(target)?,
+                at_ms,
+            }),
+            "rename" => {
+                let (from, to) = target.split_once('=').ok_or_else(usage)?;
+                let name = to.trim();
+                if name.is_empty() {
+                    return Err(usage());
+                }
+                Ok(SchemaChange::Rename {
+                    sensor: sensor(from)?,
+                    name: name.to_string(),
+                    at_ms,
+                })
+            }
+            other => Err(TelemetryError::config(format!(
+                "Unknown schema change {other}. Use add or rename"
+            ))),
+        }
+    }
+}
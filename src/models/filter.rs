@@ -0,0 +1,370 @@
+use crate::error::TelemetryError;
+use crate::generators::parse_time_ms;
+use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use super::sensor::{MissionPhase, SensorEnum, SensorValue};
+use super::telemetry::{TelemetryConfig, TelemetryReading};
+
+// Expression picking which readings get exported, e.g.
+//
+//   sensor in (alt, vel) and time_since_launch_ms > 30000
+//   not sensor = Rpm or (value >= 1e6 and time_since_launch_ms <= 2m)
+//   sensor = Pyro and value = fired
+//   phase in (max_q, staging)
+//
+// Fields are `sensor`, `time_since_launch_ms`, `value`, `timestamp` and `phase`. Sensors take any naming
+// convention. Times take a plain number of ms or a unit like `30s`. Values are compared as they're
+// exported, after calibration and unit conversion, and discrete channels match their code or
+// state name. Conditions join with `and`, `or` and `not`, `and` binding tighter
+#[derive(Debug, Clone)]
+pub struct ReadingFilter {
+    expression: Expression,
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Sensor(SensorEnum),
+    Phase(MissionPhase),
+    Time(CompareOp, u64),
+    Timestamp(CompareOp, DateTime<Utc>),
+    Value(CompareOp, f64),
+    // Text can only be checked for equality
+    ValueName(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+}
+
+impl CompareOp {
+    fn holds(&self, ordering: Option<Ordering>) -> bool {
+        match (self, ordering) {
+            (_, None) => false,
+            (CompareOp::Eq, Some(o)) => o == Ordering::Equal,
+            (CompareOp::NotEq, Some(o)) => o != Ordering::Equal,
+            (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+            (CompareOp::LtEq, Some(o)) => o != Ordering::Greater,
+            (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+            (CompareOp::GtEq, Some(o)) => o != Ordering::Less,
+        }
+    }
+}
+
+impl ReadingFilter {
+    pub fn matches(&self, reading: &TelemetryReading, config: &TelemetryConfig) -> bool {
+        self.expression.matches(reading, config)
+    }
+}
+
+impl Expression {
+    fn matches(&self, reading: &TelemetryReading, config: &TelemetryConfig) -> bool {
+        match self {
+            Expression::And(left, right) => {
+                left.matches(reading, config) && right.matches(reading, config)
+            }
+            Expression::Or(left, right) => {
+                left.matches(reading, config) || right.matches(reading, config)
+            }
+            Expression::Not(inner) => !inner.matches(reading, config),
+            Expression::Sensor(sensor) => reading.sensor == *sensor,
+            Expression::Phase(phase) => reading.phase == *phase,
+            Expression::Time(op, ms) => op.holds(Some(reading.time_since_launch_ms.cmp(ms))),
+            Expression::Timestamp(op, timestamp) => {
+                op.holds(Some(reading.timestamp.cmp(timestamp)))
+            }
+            Expression::Value(op, number) => {
+                let value = match &reading.value {
+                    SensorValue::Float(raw) => config.export_float(reading.sensor, *raw),
+                    SensorValue::Int(value) => *value as f64,
+                    SensorValue::UnsignedInt(value) => *value as f64,
+                    SensorValue::State(state) => state.code() as f64,
+                    SensorValue::String(_) => return false,
+                };
+                op.holds(value.partial_cmp(number))
+            }
+            Expression::ValueName(name) => match &reading.value {
+                SensorValue::String(text) => text == name,
+                SensorValue::State(state) => state.name() == name,
+                _ => false,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Op(CompareOp),
+    Comma,
+    LParen,
+    RParen,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, TelemetryError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            ',' => tokens.push(Token::Comma),
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            '=' | '!' | '<' | '>' => {
+                let equals = chars.next_if_eq(&'=').is_some();
+                let op = match (c, equals) {
+                    ('=', _) => CompareOp::Eq,
+                    ('!', true) => CompareOp::NotEq,
+                    ('<', true) => CompareOp::LtEq,
+                    ('<', false) if chars.next_if_eq(&'>').is_some() => CompareOp::NotEq,
+                    ('<', false) => CompareOp::Lt,
+                    ('>', true) => CompareOp::GtEq,
+                    ('>', false) => CompareOp::Gt,
+                    _ => return Err(TelemetryError::config("Expected != in filter")),
+                };
+                tokens.push(Token::Op(op));
+            }
+            '\'' | '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(other) => text.push(other),
+                        None => {
+                            return Err(TelemetryError::config(format!(
+                                "Unterminated {c} quote in filter"
+                            )));
+                        }
+                    }
+                }
+                tokens.push(Token::Word(text));
+            }
+            _ => {
+                let mut word = c.to_string();
+                while let Some(w) =
+                    chars.next_if(|w| !w.is_whitespace() && !"=!<>(),'\"".contains(*w))
+                {
+                    word.push(w);
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, token: Token, what: &str) -> Result<(), TelemetryError> {
+        if self.peek() == Some(&token) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected(what))
+        }
+    }
+
+    fn unexpected(&self, expected: &str) -> TelemetryError {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                TelemetryError::config(format!("Expected {expected} in filter, found `{word}`"))
+            }
+            Some(token) => {
+                TelemetryError::config(format!("Expected {expected} in filter, found {token:?}"))
+            }
+            None => TelemetryError::config(format!("Expected {expected} in filter, found the end")),
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, TelemetryError> {
+        match self.peek() {
+            Some(Token::Word(word)) => {
+                let word = word.clone();
+                self.pos += 1;
+                Ok(word)
+            }
+            _ => Err(self.unexpected(what)),
+        }
+    }
+
+    fn or(&mut self) -> Result<Expression, TelemetryError> {
+        let mut expression = self.and()?;
+        while self.keyword("or") {
+            expression = Expression::Or(Box::new(expression), Box::new(self.and()?));
+        }
+        Ok(expression)
+    }
+
+    fn and(&mut self) -> Result<Expression, TelemetryError> {
+        let mut expression = self.not()?;
+        while self.keyword("and") {
+            expression = Expression::And(Box::new(expression), Box::new(self.not()?));
+        }
+        Ok(expression)
+    }
+
+    fn not(&mut self) -> Result<Expression, TelemetryError> {
+        if self.keyword("not") {
+            return Ok(Expression::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::LParen) {
+            self.pos += 1;
+            let expression = self.or()?;
+            self.expect(Token::RParen, ")")?;
+            return Ok(expression);
+        }
+        self.condition()
+    }
+
+    // `<field> <op> <literal>` or `<field> [not] in (<literal>, ...)`
+    fn condition(&mut self) -> Result<Expression, TelemetryError> {
+        let field = self.word("a field")?;
+        let negated = self.keyword("not");
+        if self.keyword("in") {
+            self.expect(Token::LParen, "(")?;
+            let mut expression =
+                Field::parse(&field)?.compare(CompareOp::Eq, &self.word("a value")?)?;
+            while self.peek() == Some(&Token::Comma) {
+                self.pos += 1;
+                let next = Field::parse(&field)?.compare(CompareOp::Eq, &self.word("a value")?)?;
+                expression = Expression::Or(Box::new(expression), Box::new(next));
+            }
+            self.expect(Token::RParen, ")")?;
+            return Ok(if negated {
+                Expression::Not(Box::new(expression))
+            } else {
+                expression
+            });
+        }
+        if negated {
+            return Err(self.unexpected("in"));
+        }
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => return Err(self.unexpected("a comparison")),
+        };
+        self.pos += 1;
+        Field::parse(&field)?.compare(op, &self.word("a value")?)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Field {
+    Sensor,
+    Phase,
+    Time,
+    Timestamp,
+    Value,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Self, TelemetryError> {
+        match name.to_ascii_lowercase().as_str() {
+            "sensor" | "sensor_type" => Ok(Field::Sensor),
+            "phase" | "mission_phase" => Ok(Field::Phase),
+            "time_since_launch_ms" => Ok(Field::Time),
+            "timestamp" => Ok(Field::Timestamp),
+            "value" => Ok(Field::Value),
+            _ => Err(TelemetryError::config(format!(
+                "Unknown filter field {name}. Use sensor, time_since_launch_ms, timestamp, value or phase"
+            ))),
+        }
+    }
+
+    fn compare(self, op: CompareOp, literal: &str) -> Result<Expression, TelemetryError> {
+        match self {
+            Field::Sensor => {
+                let sensor = SensorEnum::from_name(literal)
+                    .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {literal}")))?;
+                match op {
+                    CompareOp::Eq => Ok(Expression::Sensor(sensor)),
+                    CompareOp::NotEq => Ok(Expression::Not(Box::new(Expression::Sensor(sensor)))),
+                    _ => Err(TelemetryError::config(
+                        "Sensors can only be compared with =, != or in",
+                    )),
+                }
+            }
+            Field::Phase => {
+                let phase = MissionPhase::from_name(literal).ok_or_else(|| {
+                    TelemetryError::config(format!("Unknown mission phase {literal}"))
+                })?;
+                match op {
+                    CompareOp::Eq => Ok(Expression::Phase(phase)),
+                    CompareOp::NotEq => Ok(Expression::Not(Box::new(Expression::Phase(phase)))),
+                    _ => Err(TelemetryError::config(
+                        "Phases can only be compared with =, != or in",
+                    )),
+                }
+            }
+            Field::Time => {
+                let ms = match literal.parse::<u64>() {
+                    Ok(ms) => ms,
+                    Err(_) => parse_time_ms(literal)?,
+                };
+                Ok(Expression::Time(op, ms))
+            }
+            Field::Timestamp => {
+                let timestamp = DateTime::parse_from_rfc3339(literal).map_err(|e| {
+                    TelemetryError::config(format!(
+                        "Timestamp {literal} should be RFC 3339 like 2025-01-01T12:00:00Z: {e}"
+                    ))
+                })?;
+                Ok(Expression::Timestamp(op, timestamp.with_timezone(&Utc)))
+            }
+            Field::Value => match (literal.parse::<f64>(), op) {
+                (Ok(number), _) => Ok(Expression::Value(op, number)),
+                (Err(_), CompareOp::Eq) => Ok(Expression::ValueName(literal.to_string())),
+                (Err(_), CompareOp::NotEq) => Ok(Expression::Not(Box::new(Expression::ValueName(
+                    literal.to_string(),
+                )))),
+                (Err(_), _) => Err(TelemetryError::config(format!(
+                    "Value {literal} isn't a number, so it can only be compared with = or !="
+                ))),
+            },
+        }
+    }
+}
+
+impl FromStr for ReadingFilter {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let expression = parser.or()?;
+        if parser.peek().is_some() {
+            return Err(parser.unexpected("the end"));
+        }
+        Ok(Self { expression })
+    }
+}
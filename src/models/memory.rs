@@ -0,0 +1,79 @@
+use super::telemetry::TelemetryReading;
+use crate::error::{Result, TelemetryError};
+use std::str::FromStr;
+
+// Rough bytes held per reading while a chunk is in flight. The reading itself, its arrow
+// columns (timestamp, time since launch, sensor name, value) and the encoded row group buffer
+const CONVERSION_BYTES_PER_READING: usize = 160;
+
+// Smaller row groups than this make parquet files slow to scan and bloat the footer
+pub const MIN_CHUNK_READINGS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryBudget {
+    pub limit_bytes: usize,
+}
+
+impl MemoryBudget {
+    pub fn bytes_per_reading() -> usize {
+        std::mem::size_of::<TelemetryReading>() + CONVERSION_BYTES_PER_READING
+    }
+
+    // Number of readings to buffer before flushing a chunk out as a row group
+    pub fn chunk_readings(&self) -> Result<usize> {
+        let chunk_readings = self.limit_bytes / Self::bytes_per_reading();
+
+        if chunk_readings < MIN_CHUNK_READINGS {
+            return Err(TelemetryError::config(format!(
+                "Memory limit of {} bytes is too small. A single row group of {} readings needs about {} bytes",
+                self.limit_bytes,
+                MIN_CHUNK_READINGS,
+                MIN_CHUNK_READINGS * Self::bytes_per_reading()
+            )));
+        }
+
+        Ok(chunk_readings)
+    }
+}
+
+// Parse sizes like `512MB`, `2GB` or `2GiB`. Plain numbers are bytes
+impl FromStr for MemoryBudget {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(MemoryBudget {
+            limit_bytes: parse_byte_size(s)? as usize,
+        })
+    }
+}
+
+// Byte sizes with decimal (KB, MB, ...) or binary (KiB, MiB, ...) units
+pub fn parse_byte_size(s: &str) -> Result<u64> {
+    let trimmed = s.trim();
+    let split_at = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split_at);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| TelemetryError::config(format!("Invalid size: {s}")))?;
+    let multiplier: f64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "KB" | "K" => 1e3,
+        "MB" | "M" => 1e6,
+        "GB" | "G" => 1e9,
+        "TB" | "T" => 1e12,
+        "KIB" => 1024.0,
+        "MIB" => 1024.0 * 1024.0,
+        "GIB" => 1024.0 * 1024.0 * 1024.0,
+        "TIB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        other => {
+            return Err(TelemetryError::config(format!(
+                "Unknown size unit {other} in {s}. Use B, KB, MB, GB or TB"
+            )));
+        }
+    };
+
+    Ok((number * multiplier) as u64)
+}
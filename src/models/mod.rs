@@ -1,5 +1,19 @@
+mod calibration;
+mod capture;
+mod evolution;
+mod filter;
+mod memory;
+mod sample_rate;
 mod sensor;
 mod telemetry;
+mod units;
 
+pub use calibration::*;
+pub use capture::*;
+pub use evolution::*;
+pub use filter::*;
+pub use memory::*;
+pub use sample_rate::*;
 pub use sensor::*;
 pub use telemetry::*;
+pub use units::*;
@@ -0,0 +1,109 @@
+use crate::error::{Result, TelemetryError};
+use std::fmt;
+use std::str::FromStr;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+// Exact sample rate in Hz stored as a fraction, so rates like 0.5 Hz or 333.3 Hz
+// produce timestamps without floating point drift over long runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleRate {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl SampleRate {
+    pub fn from_hz(hz: u64) -> Self {
+        Self {
+            numerator: hz,
+            denominator: 1,
+        }
+    }
+
+    pub fn period_s(&self) -> f64 {
+        self.denominator as f64 / self.numerator as f64
+    }
+
+    // Multiply the rate, e.g. by 1000 for a rate given in kHz
+    pub fn scaled(&self, factor: u64) -> Result<Self> {
+        let numerator = self.numerator.checked_mul(factor).ok_or_else(|| {
+            TelemetryError::config(format!("Sample rate {self} times {factor} is too large"))
+        })?;
+        Ok(Self {
+            numerator,
+            denominator: self.denominator,
+        }
+        .reduced())
+    }
+
+    // Number of samples taken over the duration
+    // Saturates rather than wrapping, so an absurd duration can't pass for a short one
+    pub fn samples_in(&self, duration_s: usize) -> usize {
+        let samples = duration_s as u128 * self.numerator as u128 / self.denominator as u128;
+        samples.min(usize::MAX as u128) as usize
+    }
+
+    // Exact offset from launch of the sample at `index`, floored to the nanosecond
+    pub fn offset_nanos(&self, index: usize) -> u64 {
+        (index as u128 * NANOS_PER_SECOND * self.denominator as u128 / self.numerator as u128)
+            as u64
+    }
+
+    // Strip shared powers of ten so the decimal form stays short
+    fn reduced(mut self) -> Self {
+        while self.denominator > 1
+            && self.numerator.is_multiple_of(10)
+            && self.denominator.is_multiple_of(10)
+        {
+            self.numerator /= 10;
+            self.denominator /= 10;
+        }
+        self
+    }
+}
+
+// Parse decimal rates like `1000`, `0.5` or `333.3`
+impl FromStr for SampleRate {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let trimmed = s.trim();
+        let (whole, fraction) = trimmed.split_once('.').unwrap_or((trimmed, ""));
+        if fraction.len() > 9 {
+            return Err(TelemetryError::config(format!(
+                "Sample rate {s} has more than 9 decimal places"
+            )));
+        }
+
+        let digits = format!("{whole}{fraction}");
+        let numerator: u64 = digits
+            .parse()
+            .map_err(|_| TelemetryError::config(format!("Invalid sample rate: {s}")))?;
+        if numerator == 0 {
+            return Err(TelemetryError::config(
+                "Sample rate must be greater than zero",
+            ));
+        }
+
+        Ok(Self {
+            numerator,
+            denominator: 10u64.pow(fraction.len() as u32),
+        }
+        .reduced())
+    }
+}
+
+// Exact decimal form, e.g. `333.3`
+impl fmt::Display for SampleRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let whole = self.numerator / self.denominator;
+        let remainder = self.numerator % self.denominator;
+        if remainder == 0 {
+            return write!(f, "{whole}");
+        }
+
+        let width = self.denominator.ilog10() as usize;
+        let fraction = format!("{remainder:0width$}");
+        write!(f, "{whole}.{}", fraction.trim_end_matches('0'))
+    }
+}
@@ -1,13 +1,26 @@
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::fmt::Formatter;
+use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum SensorType {
-    Temperature,
-    Pressure,
-    Flow,
-    Vibration,
+use super::units::Quantity;
+use crate::error::TelemetryError;
+use clap::ValueEnum;
+
+// Naming convention for exported sensor names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum SensorNaming {
+    // Compact names like `F_pa`. The default
+    #[default]
+    Short,
+    // Long names with the raw unit, like `FuelPressure_pa`
+    Full,
+    // `FuelPressure`
+    #[value(name = "PascalCase")]
+    PascalCase,
+    // `fuel_pressure`
+    #[value(name = "snake_case")]
+    SnakeCase,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -31,6 +44,21 @@ pub enum SensorEnum {
     SpecificImpulse,
     NozzleTemperature,
 
+    // Stage in flight and each stage's own engine. The engine channels above follow whichever
+    // stage is burning
+    ActiveStage,
+    Stage1ChamberPressure,
+    Stage2ChamberPressure,
+    Stage1Thrust,
+    Stage2Thrust,
+    Stage1TurboPumpRpm,
+    Stage2TurboPumpRpm,
+
+    // Structure and component temperatures
+    InterstageTemperature,
+    AvionicsBayTemperature,
+    CopvTemperature,
+
     // GNC Sensors
     RollAngle,
     PitchAngle,
@@ -40,12 +68,24 @@ pub enum SensorEnum {
     YawRate,
     Latitude,
     Longitude,
+    GnssSatellites,
+    GnssHdop,
+    GnssFixType,
 
     // Vibration Sensors
     VibrationX,
     VibrationY,
     VibrationZ,
     VibrationFreq,
+
+    // Counters. Only ever count up
+    FramesTransmitted,
+    ValveActuations,
+
+    // Discrete states
+    MainValve,
+    PyroStage,
+    HeaterDuty,
     // Electrical System
     // BatteryVoltage,
     // BatteryCurrent,
@@ -55,11 +95,20 @@ pub enum SensorEnum {
     // System Health
     // CpuUsage,
     // MemoryUsage,
-    // HealthStatus,
+    HealthStatus,
     // MissionPhase,
-    // Others
-    // Gyroscope,
-    // Magnetometer,
+    // Uplink commanding, only with --command-rate. Reported as commands go up and acks come
+    // down rather than every step
+    CommandSent,
+    CommandAck,
+    CommandAckLatency,
+    // IMU raw channels
+    GyroX,
+    GyroY,
+    GyroZ,
+    MagnetometerX,
+    MagnetometerY,
+    MagnetometerZ,
 }
 
 impl fmt::Display for SensorEnum {
@@ -68,49 +117,86 @@ impl fmt::Display for SensorEnum {
     }
 }
 
+// Any of the exported names, see `from_name`
+impl FromStr for SensorEnum {
+    type Err = TelemetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_name(s.trim())
+            .ok_or_else(|| TelemetryError::config(format!("Unknown sensor {s}")))
+    }
+}
+
 impl SensorEnum {
-    // Get the unit of measurement for each sensor type
-    // pub fn unit(&self) -> &'static str {
-    pub fn unit(sensor_type: SensorEnum) -> &'static str {
-        match sensor_type {
-            SensorEnum::Acceleration => "m/s²",
-            SensorEnum::Altitude => "meters",
+    // What each sensor measures. The unit it is exported in comes from the `UnitSystem`
+    pub fn quantity(&self) -> Quantity {
+        match self {
+            SensorEnum::Acceleration => Quantity::Acceleration,
+            SensorEnum::Altitude => Quantity::Length,
             SensorEnum::ChamberPressure
+            | SensorEnum::Stage1ChamberPressure
+            | SensorEnum::Stage2ChamberPressure
             | SensorEnum::OxidizerPressure
-            | SensorEnum::FuelPressure => "psi",
-            SensorEnum::OxidizerFlowRate | SensorEnum::FuelFlowRate => "kg/s",
+            | SensorEnum::FuelPressure => Quantity::Pressure,
+            SensorEnum::OxidizerFlowRate | SensorEnum::FuelFlowRate => Quantity::MassFlow,
             SensorEnum::OxidizerTemperature
             | SensorEnum::FuelTemperature
             | SensorEnum::ChamberTemperature
-            | SensorEnum::NozzleTemperature => "°C",
-            SensorEnum::Velocity => "m/s",
-            SensorEnum::TurboPumpRpm => "RPM",
-            SensorEnum::Thrust => "N",
-            SensorEnum::SpecificImpulse => "s",
+            | SensorEnum::NozzleTemperature
+            | SensorEnum::InterstageTemperature
+            | SensorEnum::AvionicsBayTemperature
+            | SensorEnum::CopvTemperature => Quantity::Temperature,
+            SensorEnum::Velocity => Quantity::Speed,
+            SensorEnum::TurboPumpRpm
+            | SensorEnum::Stage1TurboPumpRpm
+            | SensorEnum::Stage2TurboPumpRpm => Quantity::RotationRate,
+            SensorEnum::Thrust | SensorEnum::Stage1Thrust | SensorEnum::Stage2Thrust => {
+                Quantity::Force
+            }
+            SensorEnum::SpecificImpulse => Quantity::Time,
             SensorEnum::RollAngle
             | SensorEnum::PitchAngle
             | SensorEnum::YawAngle
             | SensorEnum::Latitude
-            | SensorEnum::Longitude => "degrees",
-            SensorEnum::RollRate | SensorEnum::PitchRate | SensorEnum::YawRate => "degrees/s",
-            SensorEnum::VibrationX | SensorEnum::VibrationY | SensorEnum::VibrationZ => "g",
-            SensorEnum::VibrationFreq => "Hz",
+            | SensorEnum::Longitude => Quantity::Angle,
+            SensorEnum::RollRate
+            | SensorEnum::PitchRate
+            | SensorEnum::YawRate
+            | SensorEnum::GyroX
+            | SensorEnum::GyroY
+            | SensorEnum::GyroZ => Quantity::AngularRate,
+            SensorEnum::VibrationX | SensorEnum::VibrationY | SensorEnum::VibrationZ => {
+                Quantity::GForce
+            }
+            SensorEnum::VibrationFreq => Quantity::Frequency,
+            SensorEnum::MagnetometerX | SensorEnum::MagnetometerY | SensorEnum::MagnetometerZ => {
+                Quantity::MagneticField
+            }
+            SensorEnum::GnssSatellites
+            | SensorEnum::FramesTransmitted
+            | SensorEnum::ValveActuations => Quantity::Count,
+            SensorEnum::GnssHdop => Quantity::Ratio,
+            SensorEnum::GnssFixType
+            | SensorEnum::MainValve
+            | SensorEnum::PyroStage
+            | SensorEnum::HeaterDuty
+            | SensorEnum::ActiveStage
+            | SensorEnum::HealthStatus => Quantity::State,
+            SensorEnum::CommandSent | SensorEnum::CommandAck => Quantity::Identifier,
+            SensorEnum::CommandAckLatency => Quantity::Time,
             // SensorType::BatteryVoltage => "V",
             // SensorType::BatteryCurrent => "A",
             // SensorType::BatteryTemperature => "°C",
             // SensorType::PowerConsumption => "W",
             // SensorType::CpuUsage => "%",
             // SensorType::MemoryUsage => "MB",
-            // SensorEnum::HealthStatus => "status",
             // SensorEnum::MissionPhase => "phase",
-            // SensorType::Gyroscope => "degrees/s",
-            // SensorType::Magnetometer => "µT",
         }
     }
 
     // Todo method to get all field_names
     // Todo could have concatenated with above method somehow?
-    pub fn field_name(&self) -> &str {
+    pub fn field_name(&self) -> &'static str {
         match self {
             SensorEnum::Acceleration => "acc",
             SensorEnum::Altitude => "alt",
@@ -123,14 +209,24 @@ impl SensorEnum {
             SensorEnum::FuelFlowRate => "F_f",
             SensorEnum::FuelPressure => "F_pa",
             SensorEnum::FuelTemperature => "F_k",
-            // SensorType::Gyroscope => "Gyroscope_x",
-            // SensorEnum::HealthStatus => "HealthStatus",
+            SensorEnum::GyroX => "GyX",
+            SensorEnum::GyroY => "GyY",
+            SensorEnum::GyroZ => "GyZ",
+            SensorEnum::HealthStatus => "Hlth",
             SensorEnum::Latitude => "Lat",
             SensorEnum::Longitude => "Lng",
-            // SensorType::Magnetometer => "magnometer_t",
+            SensorEnum::GnssSatellites => "Sats",
+            SensorEnum::GnssHdop => "HDOP",
+            SensorEnum::GnssFixType => "Fix",
+            SensorEnum::MagnetometerX => "MgX",
+            SensorEnum::MagnetometerY => "MgY",
+            SensorEnum::MagnetometerZ => "MgZ",
             // SensorType::MemoryUsage => "MemoryUsage_pct",
             // SensorEnum::MissionPhase => "MissionPhase",
             SensorEnum::NozzleTemperature => "Nz",
+            SensorEnum::InterstageTemperature => "IS_k",
+            SensorEnum::AvionicsBayTemperature => "Av_k",
+            SensorEnum::CopvTemperature => "Copv_k",
             SensorEnum::OxidizerFlowRate => "Ox_f",
             SensorEnum::OxidizerPressure => "ox_pa",
             SensorEnum::OxidizerTemperature => "Ox_k",
@@ -144,6 +240,21 @@ impl SensorEnum {
             SensorEnum::TurboPumpRpm => "Rpm",
             SensorEnum::Velocity => "vel",
             SensorEnum::VibrationFreq => "Vb_hz",
+            SensorEnum::FramesTransmitted => "Frm_n",
+            SensorEnum::ValveActuations => "Vlv_n",
+            SensorEnum::MainValve => "MV",
+            SensorEnum::PyroStage => "Pyro",
+            SensorEnum::HeaterDuty => "Htr",
+            SensorEnum::ActiveStage => "Stg",
+            SensorEnum::CommandSent => "Cmd",
+            SensorEnum::CommandAck => "Ack",
+            SensorEnum::CommandAckLatency => "Ack_s",
+            SensorEnum::Stage1ChamberPressure => "S1_cmb_pa",
+            SensorEnum::Stage2ChamberPressure => "S2_cmb_pa",
+            SensorEnum::Stage1Thrust => "S1_Trst",
+            SensorEnum::Stage2Thrust => "S2_Trst",
+            SensorEnum::Stage1TurboPumpRpm => "S1_Rpm",
+            SensorEnum::Stage2TurboPumpRpm => "S2_Rpm",
             SensorEnum::VibrationX => "VbX",
             SensorEnum::VibrationY => "VbY",
             SensorEnum::VibrationZ => "VbZ",
@@ -152,7 +263,18 @@ impl SensorEnum {
         }
     }
 
-    pub fn field_name_full(&self) -> &str {
+    // The one place exported sensor names come from, so every exporter agrees
+    pub fn name(&self, naming: SensorNaming) -> &'static str {
+        match naming {
+            SensorNaming::Short => self.field_name(),
+            SensorNaming::Full => self.field_name_full(),
+            SensorNaming::PascalCase => self.pascal_case_name(),
+            SensorNaming::SnakeCase => self.snake_case_name(),
+        }
+    }
+
+    // Long names with the unit of the raw value, e.g. `chamber_pressure_pa`
+    pub fn field_name_full(&self) -> &'static str {
         match self {
             SensorEnum::Acceleration => "acceleration_mps2",
             SensorEnum::Altitude => "altitude_m",
@@ -165,14 +287,24 @@ impl SensorEnum {
             SensorEnum::FuelFlowRate => "FuelFlowRate_kgps",
             SensorEnum::FuelPressure => "FuelPressure_pa",
             SensorEnum::FuelTemperature => "FuelTemperature_k",
-            // SensorType::Gyroscope => "Gyroscope_x",
-            // SensorEnum::HealthStatus => "HealthStatus",
+            SensorEnum::GyroX => "GyroX_dps",
+            SensorEnum::GyroY => "GyroY_dps",
+            SensorEnum::GyroZ => "GyroZ_dps",
+            SensorEnum::HealthStatus => "HealthStatus",
             SensorEnum::Latitude => "Latitude_deg",
             SensorEnum::Longitude => "Longitude_deg",
-            // SensorType::Magnetometer => "magnometer_t",
+            SensorEnum::GnssSatellites => "GnssSatellites",
+            SensorEnum::GnssHdop => "GnssHdop",
+            SensorEnum::GnssFixType => "GnssFixType",
+            SensorEnum::MagnetometerX => "MagnetometerX_ut",
+            SensorEnum::MagnetometerY => "MagnetometerY_ut",
+            SensorEnum::MagnetometerZ => "MagnetometerZ_ut",
             // SensorType::MemoryUsage => "MemoryUsage_pct",
             // SensorEnum::MissionPhase => "MissionPhase",
             SensorEnum::NozzleTemperature => "NozzleTemperature_k",
+            SensorEnum::InterstageTemperature => "InterstageTemperature_k",
+            SensorEnum::AvionicsBayTemperature => "AvionicsBayTemperature_k",
+            SensorEnum::CopvTemperature => "CopvTemperature_k",
             SensorEnum::OxidizerFlowRate => "OxidizerFlowRate_kgps",
             SensorEnum::OxidizerPressure => "oxidizer_pressure_pa",
             SensorEnum::OxidizerTemperature => "OxidizerTemperature_k",
@@ -191,14 +323,228 @@ impl SensorEnum {
             SensorEnum::VibrationZ => "VibrationZ_g",
             SensorEnum::YawAngle => "YawAngle_deg",
             SensorEnum::YawRate => "YawRate_dps",
+            SensorEnum::FramesTransmitted => "FramesTransmitted",
+            SensorEnum::ValveActuations => "ValveActuations",
+            SensorEnum::MainValve => "MainValve",
+            SensorEnum::PyroStage => "PyroStage",
+            SensorEnum::HeaterDuty => "HeaterDuty",
+            SensorEnum::ActiveStage => "ActiveStage",
+            SensorEnum::CommandSent => "CommandSent",
+            SensorEnum::CommandAck => "CommandAck",
+            SensorEnum::CommandAckLatency => "CommandAckLatency_s",
+            SensorEnum::Stage1ChamberPressure => "stage1_chamber_pressure_pa",
+            SensorEnum::Stage2ChamberPressure => "stage2_chamber_pressure_pa",
+            SensorEnum::Stage1Thrust => "Stage1Thrust_n",
+            SensorEnum::Stage2Thrust => "Stage2Thrust_n",
+            SensorEnum::Stage1TurboPumpRpm => "Stage1TurboPumpRpm",
+            SensorEnum::Stage2TurboPumpRpm => "Stage2TurboPumpRpm",
+        }
+    }
+
+    // Variant name, e.g. `ChamberPressure`. No unit suffix since the unit depends on `--units`
+    fn pascal_case_name(&self) -> &'static str {
+        match self {
+            SensorEnum::Acceleration => "Acceleration",
+            SensorEnum::Altitude => "Altitude",
+            SensorEnum::ChamberPressure => "ChamberPressure",
+            SensorEnum::ChamberTemperature => "ChamberTemperature",
+            SensorEnum::FuelFlowRate => "FuelFlowRate",
+            SensorEnum::FuelPressure => "FuelPressure",
+            SensorEnum::FuelTemperature => "FuelTemperature",
+            SensorEnum::GyroX => "GyroX",
+            SensorEnum::GyroY => "GyroY",
+            SensorEnum::GyroZ => "GyroZ",
+            SensorEnum::Latitude => "Latitude",
+            SensorEnum::Longitude => "Longitude",
+            SensorEnum::GnssSatellites => "GnssSatellites",
+            SensorEnum::GnssHdop => "GnssHdop",
+            SensorEnum::GnssFixType => "GnssFixType",
+            SensorEnum::MagnetometerX => "MagnetometerX",
+            SensorEnum::MagnetometerY => "MagnetometerY",
+            SensorEnum::MagnetometerZ => "MagnetometerZ",
+            SensorEnum::NozzleTemperature => "NozzleTemperature",
+            SensorEnum::InterstageTemperature => "InterstageTemperature",
+            SensorEnum::AvionicsBayTemperature => "AvionicsBayTemperature",
+            SensorEnum::CopvTemperature => "CopvTemperature",
+            SensorEnum::OxidizerFlowRate => "OxidizerFlowRate",
+            SensorEnum::OxidizerPressure => "OxidizerPressure",
+            SensorEnum::OxidizerTemperature => "OxidizerTemperature",
+            SensorEnum::PitchAngle => "PitchAngle",
+            SensorEnum::PitchRate => "PitchRate",
+            SensorEnum::RollAngle => "RollAngle",
+            SensorEnum::RollRate => "RollRate",
+            SensorEnum::SpecificImpulse => "SpecificImpulse",
+            SensorEnum::Thrust => "Thrust",
+            SensorEnum::TurboPumpRpm => "TurboPumpRpm",
+            SensorEnum::Velocity => "Velocity",
+            SensorEnum::VibrationFreq => "VibrationFreq",
+            SensorEnum::VibrationX => "VibrationX",
+            SensorEnum::VibrationY => "VibrationY",
+            SensorEnum::VibrationZ => "VibrationZ",
+            SensorEnum::YawAngle => "YawAngle",
+            SensorEnum::YawRate => "YawRate",
+            SensorEnum::FramesTransmitted => "FramesTransmitted",
+            SensorEnum::ValveActuations => "ValveActuations",
+            SensorEnum::MainValve => "MainValve",
+            SensorEnum::PyroStage => "PyroStage",
+            SensorEnum::HeaterDuty => "HeaterDuty",
+            SensorEnum::ActiveStage => "ActiveStage",
+            SensorEnum::HealthStatus => "HealthStatus",
+            SensorEnum::CommandSent => "CommandSent",
+            SensorEnum::CommandAck => "CommandAck",
+            SensorEnum::CommandAckLatency => "CommandAckLatency",
+            SensorEnum::Stage1ChamberPressure => "Stage1ChamberPressure",
+            SensorEnum::Stage2ChamberPressure => "Stage2ChamberPressure",
+            SensorEnum::Stage1Thrust => "Stage1Thrust",
+            SensorEnum::Stage2Thrust => "Stage2Thrust",
+            SensorEnum::Stage1TurboPumpRpm => "Stage1TurboPumpRpm",
+            SensorEnum::Stage2TurboPumpRpm => "Stage2TurboPumpRpm",
+        }
+    }
+
+    fn snake_case_name(&self) -> &'static str {
+        match self {
+            SensorEnum::Acceleration => "acceleration",
+            SensorEnum::Altitude => "altitude",
+            SensorEnum::ChamberPressure => "chamber_pressure",
+            SensorEnum::ChamberTemperature => "chamber_temperature",
+            SensorEnum::FuelFlowRate => "fuel_flow_rate",
+            SensorEnum::FuelPressure => "fuel_pressure",
+            SensorEnum::FuelTemperature => "fuel_temperature",
+            SensorEnum::GyroX => "gyro_x",
+            SensorEnum::GyroY => "gyro_y",
+            SensorEnum::GyroZ => "gyro_z",
+            SensorEnum::Latitude => "latitude",
+            SensorEnum::Longitude => "longitude",
+            SensorEnum::GnssSatellites => "gnss_satellites",
+            SensorEnum::GnssHdop => "gnss_hdop",
+            SensorEnum::GnssFixType => "gnss_fix_type",
+            SensorEnum::MagnetometerX => "magnetometer_x",
+            SensorEnum::MagnetometerY => "magnetometer_y",
+            SensorEnum::MagnetometerZ => "magnetometer_z",
+            SensorEnum::NozzleTemperature => "nozzle_temperature",
+            SensorEnum::InterstageTemperature => "interstage_temperature",
+            SensorEnum::AvionicsBayTemperature => "avionics_bay_temperature",
+            SensorEnum::CopvTemperature => "copv_temperature",
+            SensorEnum::OxidizerFlowRate => "oxidizer_flow_rate",
+            SensorEnum::OxidizerPressure => "oxidizer_pressure",
+            SensorEnum::OxidizerTemperature => "oxidizer_temperature",
+            SensorEnum::PitchAngle => "pitch_angle",
+            SensorEnum::PitchRate => "pitch_rate",
+            SensorEnum::RollAngle => "roll_angle",
+            SensorEnum::RollRate => "roll_rate",
+            SensorEnum::SpecificImpulse => "specific_impulse",
+            SensorEnum::Thrust => "thrust",
+            SensorEnum::TurboPumpRpm => "turbo_pump_rpm",
+            SensorEnum::Velocity => "velocity",
+            SensorEnum::VibrationFreq => "vibration_freq",
+            SensorEnum::VibrationX => "vibration_x",
+            SensorEnum::VibrationY => "vibration_y",
+            SensorEnum::VibrationZ => "vibration_z",
+            SensorEnum::YawAngle => "yaw_angle",
+            SensorEnum::YawRate => "yaw_rate",
+            SensorEnum::FramesTransmitted => "frames_transmitted",
+            SensorEnum::ValveActuations => "valve_actuations",
+            SensorEnum::MainValve => "main_valve",
+            SensorEnum::PyroStage => "pyro_stage",
+            SensorEnum::HeaterDuty => "heater_duty",
+            SensorEnum::ActiveStage => "active_stage",
+            SensorEnum::HealthStatus => "health_status",
+            SensorEnum::CommandSent => "command_sent",
+            SensorEnum::CommandAck => "command_ack",
+            SensorEnum::CommandAckLatency => "command_ack_latency",
+            SensorEnum::Stage1ChamberPressure => "stage1_chamber_pressure",
+            SensorEnum::Stage2ChamberPressure => "stage2_chamber_pressure",
+            SensorEnum::Stage1Thrust => "stage1_thrust",
+            SensorEnum::Stage2Thrust => "stage2_thrust",
+            SensorEnum::Stage1TurboPumpRpm => "stage1_turbo_pump_rpm",
+            SensorEnum::Stage2TurboPumpRpm => "stage2_turbo_pump_rpm",
         }
     }
 
+    // One line explanation for catalogs, so nobody has to guess what `cmb_pa` means
+    pub fn description(&self) -> &'static str {
+        match self {
+            SensorEnum::Acceleration => "Vehicle acceleration along the flight path",
+            SensorEnum::Altitude => "Altitude above the launch site",
+            SensorEnum::Velocity => "Vehicle speed along the flight path",
+            SensorEnum::ChamberPressure => "Combustion chamber pressure",
+            SensorEnum::ChamberTemperature => "Combustion chamber temperature",
+            SensorEnum::OxidizerPressure => "Oxidizer feed pressure",
+            SensorEnum::OxidizerFlowRate => "Oxidizer mass flow rate",
+            SensorEnum::OxidizerTemperature => "Oxidizer tank temperature",
+            SensorEnum::FuelPressure => "Fuel feed pressure",
+            SensorEnum::FuelFlowRate => "Fuel mass flow rate",
+            SensorEnum::FuelTemperature => "Fuel tank temperature",
+            SensorEnum::TurboPumpRpm => "Turbopump shaft speed",
+            SensorEnum::Thrust => "Engine thrust",
+            SensorEnum::SpecificImpulse => "Engine specific impulse",
+            SensorEnum::NozzleTemperature => "Nozzle wall temperature",
+            SensorEnum::ActiveStage => "Stage in flight: 1 until separation, then 2",
+            SensorEnum::Stage1ChamberPressure => "First stage engine chamber pressure",
+            SensorEnum::Stage2ChamberPressure => "Second stage engine chamber pressure",
+            SensorEnum::Stage1Thrust => "First stage engine thrust",
+            SensorEnum::Stage2Thrust => "Second stage engine thrust",
+            SensorEnum::Stage1TurboPumpRpm => "First stage turbopump shaft speed",
+            SensorEnum::Stage2TurboPumpRpm => "Second stage turbopump shaft speed",
+            SensorEnum::InterstageTemperature => "Interstage structure temperature",
+            SensorEnum::AvionicsBayTemperature => "Avionics bay temperature",
+            SensorEnum::CopvTemperature => "Composite overwrapped pressure vessel temperature",
+            SensorEnum::RollAngle => "Roll attitude",
+            SensorEnum::PitchAngle => "Pitch attitude",
+            SensorEnum::YawAngle => "Yaw attitude",
+            SensorEnum::RollRate => "Roll rate from the attitude solution",
+            SensorEnum::PitchRate => "Pitch rate from the attitude solution",
+            SensorEnum::YawRate => "Yaw rate from the attitude solution",
+            SensorEnum::Latitude => "Geodetic latitude",
+            SensorEnum::Longitude => "Geodetic longitude",
+            SensorEnum::GnssSatellites => "GNSS satellites tracked",
+            SensorEnum::GnssHdop => "GNSS horizontal dilution of precision",
+            SensorEnum::GnssFixType => "GNSS fix type: 0 no fix, 1 2D, 2 3D",
+            SensorEnum::VibrationX => "Vibration along the body X axis",
+            SensorEnum::VibrationY => "Vibration along the body Y axis",
+            SensorEnum::VibrationZ => "Vibration along the body Z axis",
+            SensorEnum::VibrationFreq => "Dominant vibration frequency",
+            SensorEnum::FramesTransmitted => "Telemetry frames transmitted since power on",
+            SensorEnum::ValveActuations => "Valve actuations since power on",
+            SensorEnum::MainValve => "Main engine valve: 0 closed, 1 open",
+            SensorEnum::PyroStage => "Stage separation pyros: 0 safe, 1 armed, 2 fired",
+            SensorEnum::HeaterDuty => "Tank heater: 0 off, 1 on",
+            SensorEnum::HealthStatus => {
+                "Vehicle health from --health-rule: 0 nominal, 1 caution, 2 warning, 3 critical"
+            }
+            SensorEnum::CommandSent => "Sequence number of an uplink command as it's sent",
+            SensorEnum::CommandAck => "Sequence number of an uplink command as its ack arrives",
+            SensorEnum::CommandAckLatency => "Time from sending a command to its ack",
+            SensorEnum::GyroX => "Raw gyro rate about the body X axis",
+            SensorEnum::GyroY => "Raw gyro rate about the body Y axis",
+            SensorEnum::GyroZ => "Raw gyro rate about the body Z axis",
+            SensorEnum::MagnetometerX => "Raw magnetic field along the body X axis",
+            SensorEnum::MagnetometerY => "Raw magnetic field along the body Y axis",
+            SensorEnum::MagnetometerZ => "Raw magnetic field along the body Z axis",
+        }
+    }
+
+    // Look a sensor up by any of the names it is exported under, e.g. `F_pa` or `fuel_pressure`
+    pub fn from_name(name: &str) -> Option<SensorEnum> {
+        Self::get_all_sensor_enums().into_iter().find(|sensor| {
+            SensorNaming::value_variants()
+                .iter()
+                .any(|naming| sensor.name(*naming) == name)
+        })
+    }
+
+    // Reported as things happen rather than every step, so not counted in a step's readings
+    pub fn is_event_driven(&self) -> bool {
+        matches!(
+            self,
+            SensorEnum::CommandSent | SensorEnum::CommandAck | SensorEnum::CommandAckLatency
+        )
+    }
+
     pub fn number_of_sensors() -> usize {
         //29 // 37
-        // todo get programatically
-        29
-        // Self::get_all_sensors_enums().len()
+        Self::get_all_sensor_enums().len()
     }
 
     pub fn get_all_sensor_enums() -> Vec<SensorEnum> {
@@ -214,14 +560,24 @@ impl SensorEnum {
             SensorEnum::FuelFlowRate,
             SensorEnum::FuelPressure,
             SensorEnum::FuelTemperature,
-            // SensorType::Gyroscope,
-            // SensorEnum::HealthStatus,
+            SensorEnum::GyroX,
+            SensorEnum::GyroY,
+            SensorEnum::GyroZ,
+            SensorEnum::HealthStatus,
             SensorEnum::Latitude,
             SensorEnum::Longitude,
-            // SensorType::Magnetometer,
+            SensorEnum::GnssSatellites,
+            SensorEnum::GnssHdop,
+            SensorEnum::GnssFixType,
+            SensorEnum::MagnetometerX,
+            SensorEnum::MagnetometerY,
+            SensorEnum::MagnetometerZ,
             // SensorType::MemoryUsage,
             // SensorEnum::MissionPhase,
             SensorEnum::NozzleTemperature,
+            SensorEnum::InterstageTemperature,
+            SensorEnum::AvionicsBayTemperature,
+            SensorEnum::CopvTemperature,
             SensorEnum::OxidizerFlowRate,
             SensorEnum::OxidizerPressure,
             SensorEnum::OxidizerTemperature,
@@ -240,6 +596,21 @@ impl SensorEnum {
             SensorEnum::VibrationZ,
             SensorEnum::YawAngle,
             SensorEnum::YawRate,
+            SensorEnum::FramesTransmitted,
+            SensorEnum::ValveActuations,
+            SensorEnum::MainValve,
+            SensorEnum::PyroStage,
+            SensorEnum::HeaterDuty,
+            SensorEnum::ActiveStage,
+            SensorEnum::Stage1ChamberPressure,
+            SensorEnum::Stage2ChamberPressure,
+            SensorEnum::Stage1Thrust,
+            SensorEnum::Stage2Thrust,
+            SensorEnum::Stage1TurboPumpRpm,
+            SensorEnum::Stage2TurboPumpRpm,
+            SensorEnum::CommandSent,
+            SensorEnum::CommandAck,
+            SensorEnum::CommandAckLatency,
         ]
     }
 }
@@ -247,9 +618,148 @@ impl SensorEnum {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum SensorValue {
     Float(f64),
-    // Int(i64),
-    // UnsignedInt(u64),
+    Int(i64),
+    UnsignedInt(u64),
     String(String),
-    // State(u8),
+    State(ChannelState),
     // Status(u32),
 }
+
+// Positions a discrete channel flips between. Exported as the code with the name alongside where
+// the layout has room for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChannelState {
+    // Valves
+    Closed,
+    Open,
+    // Pyros
+    Safe,
+    Armed,
+    Fired,
+    // Heaters
+    Off,
+    On,
+    // GNSS fix
+    NoFix,
+    Fix2d,
+    Fix3d,
+    // Health status, in order of severity
+    Nominal,
+    Caution,
+    Warning,
+    Critical,
+}
+
+impl ChannelState {
+    // Codes count up from 0 within each kind of channel
+    pub fn code(&self) -> i64 {
+        match self {
+            ChannelState::Closed
+            | ChannelState::Safe
+            | ChannelState::Off
+            | ChannelState::NoFix
+            | ChannelState::Nominal => 0,
+            ChannelState::Open
+            | ChannelState::Armed
+            | ChannelState::On
+            | ChannelState::Fix2d
+            | ChannelState::Caution => 1,
+            ChannelState::Fired | ChannelState::Fix3d | ChannelState::Warning => 2,
+            ChannelState::Critical => 3,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChannelState::Closed => "closed",
+            ChannelState::Open => "open",
+            ChannelState::Safe => "safe",
+            ChannelState::Armed => "armed",
+            ChannelState::Fired => "fired",
+            ChannelState::Off => "off",
+            ChannelState::On => "on",
+            ChannelState::NoFix => "no_fix",
+            ChannelState::Fix2d => "2d",
+            ChannelState::Fix3d => "3d",
+            ChannelState::Nominal => "nominal",
+            ChannelState::Caution => "caution",
+            ChannelState::Warning => "warning",
+            ChannelState::Critical => "critical",
+        }
+    }
+}
+
+// Stretch of the flight a reading was taken in, worked out from the simulation state. Exported
+// next to every reading so queries can group by phase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MissionPhase {
+    // On the pad until lift off
+    Prelaunch,
+    // Throttled down through peak dynamic pressure
+    MaxQ,
+    // First stage burn either side of max Q
+    Ascent,
+    // First stage shutdown, separation and second stage ignition
+    Staging,
+    // Second stage burn to orbit
+    Insertion,
+    // Never reached yet, runs end at second stage cutoff. Todo: simulate landing and recovery
+    Recovery,
+}
+
+impl MissionPhase {
+    pub const ALL: [MissionPhase; 6] = [
+        MissionPhase::Prelaunch,
+        MissionPhase::MaxQ,
+        MissionPhase::Ascent,
+        MissionPhase::Staging,
+        MissionPhase::Insertion,
+        MissionPhase::Recovery,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MissionPhase::Prelaunch => "prelaunch",
+            MissionPhase::MaxQ => "max_q",
+            MissionPhase::Ascent => "ascent",
+            MissionPhase::Staging => "staging",
+            MissionPhase::Insertion => "insertion",
+            MissionPhase::Recovery => "recovery",
+        }
+    }
+
+    // Also takes `max-q` and any case
+    pub fn from_name(name: &str) -> Option<MissionPhase> {
+        let name = name.to_ascii_lowercase().replace('-', "_");
+        Self::ALL.into_iter().find(|phase| phase.name() == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn every_naming_gives_distinct_names() {
+        let sensors = SensorEnum::get_all_sensor_enums();
+        for naming in SensorNaming::value_variants() {
+            let names: HashSet<&str> = sensors.iter().map(|sensor| sensor.name(*naming)).collect();
+            assert_eq!(names.len(), sensors.len(), "{naming:?} names clash");
+        }
+    }
+
+    #[test]
+    fn names_round_trip_through_from_name() {
+        for sensor in SensorEnum::get_all_sensor_enums() {
+            for naming in SensorNaming::value_variants() {
+                let name = sensor.name(*naming);
+                assert_eq!(
+                    SensorEnum::from_name(name),
+                    Some(sensor),
+                    "{naming:?} name {name}"
+                );
+            }
+        }
+    }
+}
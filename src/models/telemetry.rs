@@ -1,22 +1,104 @@
-use super::sensor::{SensorEnum, SensorValue};
+use super::calibration::{Calibration, CalibrationModel};
+use super::evolution::SchemaChange;
+use super::sample_rate::SampleRate;
+use super::sensor::{MissionPhase, SensorEnum, SensorNaming, SensorValue};
+use super::units::UnitSystem;
+use crate::generators::Integrator;
 use chrono::{DateTime, Utc};
-use rand::Rng;
-use rand_distr::{Distribution, Normal};
-use tracing::info;
+use rand_distr::Normal;
+use std::borrow::Cow;
+use std::fmt::Write;
+use tracing::trace;
 
 #[derive(Debug, Clone)]
 pub struct TelemetryConfig {
     pub duration: usize,
-    pub sample_rate_hz: usize,
+    pub sample_rate_hz: SampleRate,
     pub launch_id: String,
     pub seed: u64,
     pub max_rows: Option<usize>,
     pub timestamp_jitter: f64,
+    // Sample noise in blocks for speed. Changes the values produced for a seed
+    pub batch_noise: bool,
+    // Pin the launch time, e.g. to stamp datasets in the past. Defaults to now
+    pub launch_time: Option<DateTime<Utc>>,
+    // Synthetic instances reporting each sensor, for high cardinality workloads. 1 is off
+    pub sensor_instances: u16,
+    // Units the exporters convert values into
+    pub units: UnitSystem,
+    // Naming convention for exported sensor names
+    pub naming: SensorNaming,
+    // Sensors added and renamed part way through the run
+    pub schema_evolution: Vec<SchemaChange>,
+    // Give sinks a stable id per reading so reruns overwrite instead of duplicating
+    pub point_ids: bool,
+    // Curves exporters apply to raw float values. Later ones win
+    pub calibrations: Vec<Calibration>,
+    // Sensors reported by an A and a B copy with their own noise, for cross-check testing
+    pub redundant_sensors: Vec<SensorEnum>,
+    // Chance each redundant pair has one copy drift away from the other part way through
+    pub redundant_drift_chance: f64,
+    // Fixed step the flight physics integrates at, with readings interpolated between steps.
+    // None integrates once per sample. Changes the values produced for a seed
+    pub physics_step_ms: Option<u64>,
+    // How the flight integrates acceleration into velocity and altitude. Anything but the
+    // default changes the values produced for a seed
+    pub integrator: Integrator,
+    // Time constants the turbopump spools up and down towards its commanded speed with. 0
+    // follows the command instantly
+    pub pump_spool_up_s: f64,
+    pub pump_spool_down_s: f64,
+    // Roll angle the vehicle rolls to after lift off, at up to `roll_rate_dps`. 0 skips the
+    // roll program
+    pub roll_program_deg: f64,
+    pub roll_rate_dps: f64,
+    // Synthetic uplink commands sent a minute on average, each acked after a seeded latency
+    // around `command_ack_ms`. 0 leaves the command channels out
+    pub command_rate_per_min: f64,
+    pub command_ack_ms: f64,
 }
 
 impl TelemetryConfig {
+    // Chained setters for the settings most runs change, starting from `default()`
+    pub fn with_duration(mut self, duration_s: usize) -> Self {
+        self.duration = duration_s;
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate_hz: SampleRate) -> Self {
+        self.sample_rate_hz = sample_rate_hz;
+        self
+    }
+
+    pub fn with_launch_id(mut self, launch_id: impl Into<String>) -> Self {
+        self.launch_id = launch_id.into();
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_launch_time(mut self, launch_time: DateTime<Utc>) -> Self {
+        self.launch_time = Some(launch_time);
+        self
+    }
+
+    pub fn with_integrator(mut self, integrator: Integrator) -> Self {
+        self.integrator = integrator;
+        self
+    }
+
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = Some(max_rows);
+        self
+    }
+
     pub fn get_total_points(&self) -> usize {
-        let total_points = self.duration * self.sample_rate_hz * SensorEnum::number_of_sensors();
+        let total_points = self
+            .get_total_readings()
+            .saturating_mul(self.readings_per_step());
 
         if let Some(max) = self.max_rows {
             std::cmp::min(total_points, max)
@@ -26,20 +108,164 @@ impl TelemetryConfig {
     }
 
     pub fn get_total_readings(&self) -> usize {
-        self.duration * self.sample_rate_hz
+        self.sample_rate_hz.samples_in(self.duration)
+    }
+
+    // Readings produced per timestep, every sensor times every instance of it. The command
+    // channels only report now and then so aren't counted
+    pub fn readings_per_step(&self) -> usize {
+        let sensors = SensorEnum::get_all_sensor_enums();
+        let every_step = sensors
+            .iter()
+            .filter(|sensor| !sensor.is_event_driven())
+            .count();
+        // Redundant sensors report twice
+        let redundant = sensors
+            .iter()
+            .filter(|sensor| self.is_redundant(**sensor))
+            .count();
+        (every_step + redundant) * self.sensor_instances.max(1) as usize
+    }
+
+    pub fn reports_commands(&self) -> bool {
+        self.command_rate_per_min > 0.0
+    }
+
+    pub fn is_redundant(&self, sensor: SensorEnum) -> bool {
+        self.redundant_sensors.contains(&sensor)
+    }
+
+    // False for sensors the schema evolution plan hasn't added yet
+    pub fn is_reported(&self, sensor: SensorEnum, now_ms: u64) -> bool {
+        !self.schema_evolution.iter().any(|change| {
+            matches!(change, SchemaChange::Add { sensor: added, at_ms } if *added == sensor && now_ms < *at_ms)
+        })
+    }
+
+    // Name the sensor is exported under at `now_ms`. The latest rename in effect wins
+    pub fn sensor_name(&self, sensor: SensorEnum, now_ms: u64) -> &str {
+        self.schema_evolution
+            .iter()
+            .filter_map(|change| match change {
+                SchemaChange::Rename {
+                    sensor: renamed,
+                    name,
+                    at_ms,
+                } if *renamed == sensor && now_ms >= *at_ms => Some((*at_ms, name.as_str())),
+                _ => None,
+            })
+            .max_by_key(|(at_ms, _)| *at_ms)
+            .map_or_else(|| sensor.name(self.naming), |(_, name)| name)
+    }
+
+    // Every name the sensor is exported under over the run, renames included
+    pub fn exported_names(&self, sensor: SensorEnum) -> Vec<&str> {
+        let renames = self
+            .schema_evolution
+            .iter()
+            .filter_map(|change| match change {
+                SchemaChange::Rename {
+                    sensor: renamed,
+                    name,
+                    ..
+                } if *renamed == sensor => Some(name.as_str()),
+                _ => None,
+            });
+        std::iter::once(sensor.name(self.naming))
+            .chain(renames)
+            .collect()
+    }
+
+    pub fn calibration(&self, sensor: SensorEnum) -> Option<&CalibrationModel> {
+        self.calibrations
+            .iter()
+            .rev()
+            .find(|calibration| calibration.sensor == sensor)
+            .map(|calibration| &calibration.model)
+    }
+
+    // Float value as exported, calibrated and then converted into the unit system
+    pub fn export_float(&self, sensor: SensorEnum, raw: f64) -> f64 {
+        let calibrated = self
+            .calibration(sensor)
+            .map_or(raw, |model| model.apply(raw));
+        self.units.convert(sensor, calibrated)
+    }
+
+    // Stable id of the `index`th reading of the run, `{launch_id}-{seed}-{index}`. Generation is
+    // deterministic for a seed, so a rerun gives the same reading the same id
+    pub fn point_id(&self, index: usize) -> String {
+        format!("{}-{}-{index}", self.launch_id, self.seed)
+    }
+
+    // Launch time picked from the seed so reruns of the same config produce identical files.
+    // Lands somewhere within the ten years after 2020-01-01
+    pub fn launch_time_from_seed(seed: u64) -> DateTime<Utc> {
+        const SEED_EPOCH_S: i64 = 1_577_836_800; // 2020-01-01T00:00:00Z
+        const SEED_RANGE_S: u64 = 10 * 365 * 24 * 60 * 60;
+
+        DateTime::from_timestamp(SEED_EPOCH_S + (seed % SEED_RANGE_S) as i64, 0)
+            .expect("Seeded launch time is always in range")
     }
 }
 
 impl Default for TelemetryConfig {
     fn default() -> Self {
         TelemetryConfig {
-            duration: 120,          // 2 minutes
-            sample_rate_hz: 10_000, // 10 kHz
+            duration: 120,                               // 2 minutes
+            sample_rate_hz: SampleRate::from_hz(10_000), // 10 kHz
             launch_id: "eg_launch".into(),
             seed: 1337,
             max_rows: None,
             timestamp_jitter: 25.0, // 25 microseconds
+            batch_noise: false,
+            launch_time: None,
+            sensor_instances: 1,
+            units: UnitSystem::Raw,
+            naming: SensorNaming::Short,
+            schema_evolution: Vec::new(),
+            point_ids: false,
+            calibrations: Vec::new(),
+            redundant_sensors: Vec::new(),
+            redundant_drift_chance: 0.5,
+            physics_step_ms: None,
+            integrator: Integrator::Euler,
+            pump_spool_up_s: 1.0,
+            pump_spool_down_s: 0.5,
+            roll_program_deg: 90.0,
+            roll_rate_dps: 5.0,
+            command_rate_per_min: 0.0,
+            command_ack_ms: 250.0,
+        }
+    }
+}
+
+// Which copy of a redundant pair a reading came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PairSide {
+    A,
+    B,
+}
+
+impl PairSide {
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            PairSide::A => "a",
+            PairSide::B => "b",
+        }
+    }
+}
+
+// Name a sensor is exported under, with the instance suffix when fanned out and the side of a
+// redundant pair after that, e.g. `F_pa_007_b`
+pub fn sensor_label(name: &str, instance: Option<u16>, side: Option<PairSide>) -> Cow<'_, str> {
+    match (instance, side) {
+        (Some(instance), Some(side)) => {
+            Cow::Owned(format!("{name}_{instance:03}_{}", side.suffix()))
         }
+        (Some(instance), None) => Cow::Owned(format!("{name}_{instance:03}")),
+        (None, Some(side)) => Cow::Owned(format!("{name}_{}", side.suffix())),
+        (None, None) => Cow::Borrowed(name),
     }
 }
 
@@ -53,8 +279,9 @@ impl TimestampJitter {
             distribution: Normal::new(0.0, std_dev_us).unwrap(),
         }
     }
-    pub fn apply<R: Rng>(&self, timestamp: DateTime<Utc>, rng: &mut R) -> DateTime<Utc> {
-        let jitter_micros = self.distribution.sample(rng).round() as i64;
+    // Takes a pre-sampled standard normal value so the generator controls how noise is drawn
+    pub fn apply_zscore(&self, timestamp: DateTime<Utc>, zscore: f64) -> DateTime<Utc> {
+        let jitter_micros = self.distribution.from_zscore(zscore).round() as i64;
 
         // Add jitter to provided timestamp
         timestamp + chrono::Duration::microseconds(jitter_micros)
@@ -69,32 +296,190 @@ pub struct TelemetryDataset {
     // pub base_timestamps: Vec<DateTime<Utc>>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TelemetryReading {
     pub timestamp: DateTime<Utc>,
     pub time_since_launch_ms: u64,
+    pub phase: MissionPhase,
     pub sensor: SensorEnum,
     pub value: SensorValue,
-    // Todo InfluxDb tags
+    // Set when the sensor is fanned out into synthetic instances
+    pub instance: Option<u16>,
+    // Set when the sensor is one of a redundant pair
+    pub side: Option<PairSide>,
 }
 
 impl TelemetryReading {
     pub fn new(
         timestamp: DateTime<Utc>,
         time_since_launch_ms: u64,
+        phase: MissionPhase,
         sensor: SensorEnum,
         value: SensorValue,
     ) -> Self {
         Self {
             timestamp,
             time_since_launch_ms,
+            phase,
             sensor,
             value,
+            instance: None,
+            side: None,
+        }
+    }
+
+    pub fn with_instance(mut self, instance: u16) -> Self {
+        self.instance = Some(instance);
+        self
+    }
+
+    pub fn with_side(mut self, side: PairSide) -> Self {
+        self.side = Some(side);
+        self
+    }
+
+    // Sensor name with the instance and pair suffixes, e.g. `F_pa_007`. Only allocates for those
+    pub fn sensor_label<'a>(&self, config: &'a TelemetryConfig) -> Cow<'a, str> {
+        sensor_label(
+            config.sensor_name(self.sensor, self.time_since_launch_ms),
+            self.instance,
+            self.side,
+        )
+    }
+    // Append the line to an existing buffer to avoid allocating a String per reading, e.g.
+    // `rocket_telemetry,sensor=F_pa,launch_id=eg_launch FuelPressure=1.5 1700000000000000000`.
+    // The sensor tag is named by `naming`. NaN and infinite floats have no line protocol form, so
+    // those readings are skipped, leaving `out` as it was, and false returned
+    pub fn write_line_protocol(
+        &self,
+        measurement: &str,
+        naming: SensorNaming,
+        launch_id: &str,
+        out: &mut String,
+    ) -> bool {
+        if let SensorValue::Float(f) = self.value
+            && !f.is_finite()
+        {
+            return false;
+        }
+        trace!("Measurement is: {}. at ts: {}", measurement, self.timestamp);
+        push_escaped(out, measurement, &[',', ' ']);
+        out.push_str(",sensor=");
+        let sensor = sensor_label(self.sensor.name(naming), self.instance, self.side);
+        push_escaped(out, &sensor, &[',', '=', ' ']);
+        out.push_str(",launch_id=");
+        push_escaped(out, launch_id, &[',', '=', ' ']);
+        out.push(' ');
+        push_escaped(out, self.sensor.field_name_full(), &[',', '=', ' ']);
+        out.push('=');
+        match &self.value {
+            SensorValue::Float(f) => write!(out, "{f}"),
+            SensorValue::Int(i) => write!(out, "{i}i"),
+            SensorValue::UnsignedInt(u) => write!(out, "{u}u"),
+            SensorValue::String(s) => push_string_field(out, s),
+            SensorValue::State(state) => push_string_field(out, state.name()),
         }
+        .expect("Writing to a String can't fail");
+        write!(out, " {}", line_protocol_nanos(self.timestamp))
+            .expect("Writing to a String can't fail");
+        true
     }
-    pub fn to_line_protocol(&self, measurement: &str) -> String {
-        info!("Measurement is: {}. at ts: {}", measurement, self.timestamp);
-        // let tags = format!("sensor_type={}", self.sensor_type.field_name());
-        "todo".to_string()
+}
+
+// Line protocol timestamp. Widened so timestamps past 2262 still print rather than overflowing
+// i64 nanoseconds
+pub fn line_protocol_nanos(timestamp: DateTime<Utc>) -> i128 {
+    timestamp.timestamp() as i128 * 1_000_000_000 + timestamp.timestamp_subsec_nanos() as i128
+}
+
+// Backslash escape `special` characters. Measurements, tags and field keys each have their own set
+pub fn push_escaped(out: &mut String, text: &str, special: &[char]) {
+    for c in text.chars() {
+        if special.contains(&c) || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+// String field values are quoted, so only quotes and backslashes need escaping
+fn push_string_field(out: &mut String, value: &str) -> std::fmt::Result {
+    out.push('"');
+    push_escaped(out, value, &['"']);
+    out.push('"');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::PairSide;
+
+    fn reading(value: SensorValue) -> TelemetryReading {
+        TelemetryReading::new(
+            DateTime::from_timestamp(1_700_000_000, 5).unwrap(),
+            0,
+            MissionPhase::Prelaunch,
+            SensorEnum::FuelPressure,
+            value,
+        )
+    }
+
+    fn line(reading: &TelemetryReading, naming: SensorNaming) -> String {
+        let mut out = String::new();
+        assert!(reading.write_line_protocol("rocket_telemetry", naming, "SIM-001", &mut out));
+        out
+    }
+
+    #[test]
+    fn line_protocol_sensor_tag_follows_naming() {
+        let reading = reading(SensorValue::Float(1.5));
+        assert_eq!(
+            line(&reading, SensorNaming::Short),
+            "rocket_telemetry,sensor=F_pa,launch_id=SIM-001 FuelPressure_pa=1.5 1700000000000000005"
+        );
+        for naming in [
+            SensorNaming::Full,
+            SensorNaming::PascalCase,
+            SensorNaming::SnakeCase,
+        ] {
+            let expected = format!(",sensor={},", SensorEnum::FuelPressure.name(naming));
+            assert!(line(&reading, naming).contains(&expected), "{naming:?}");
+        }
+    }
+
+    #[test]
+    fn line_protocol_skips_non_finite_floats() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            let mut out = "kept\n".to_string();
+            assert!(!reading(SensorValue::Float(value)).write_line_protocol(
+                "rocket_telemetry",
+                SensorNaming::Short,
+                "SIM-001",
+                &mut out
+            ));
+            assert_eq!(out, "kept\n");
+        }
+    }
+
+    #[test]
+    fn line_protocol_escapes_names_and_types_values() {
+        let mut out = String::new();
+        reading(SensorValue::String("say \"hi\"".to_string()))
+            .with_instance(7)
+            .with_side(PairSide::B)
+            .write_line_protocol(
+                "rocket telemetry",
+                SensorNaming::Short,
+                "SIM 1,a=b",
+                &mut out,
+            );
+        assert_eq!(
+            out,
+            "rocket\\ telemetry,sensor=F_pa_007_b,launch_id=SIM\\ 1\\,a\\=b \
+            FuelPressure_pa=\"say \\\"hi\\\"\" 1700000000000000005"
+        );
+        assert!(line(&reading(SensorValue::Int(-3)), SensorNaming::Short).contains("=-3i "));
+        assert!(line(&reading(SensorValue::UnsignedInt(3)), SensorNaming::Short).contains("=3u "));
     }
 }
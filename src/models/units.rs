@@ -0,0 +1,112 @@
+use super::sensor::SensorEnum;
+
+// Physical quantity a sensor measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quantity {
+    Pressure,
+    Temperature,
+    Length,
+    Speed,
+    Acceleration,
+    MassFlow,
+    Force,
+    Time,
+    Angle,
+    AngularRate,
+    RotationRate,
+    GForce,
+    Frequency,
+    MagneticField,
+    Count,
+    Ratio,
+    State,
+    // Sequence numbers and the like, not a measurement
+    Identifier,
+}
+
+// Units values are exported in. The simulation works in `Raw` units and exporters convert
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum UnitSystem {
+    // As simulated. Mostly SI, but angles in degrees, vibration in g and the pump in rpm
+    #[default]
+    Raw,
+    // Strict SI: radians, rad/s, m/s² and tesla
+    Si,
+    // psi, °F, feet, pounds and pound-force
+    Imperial,
+}
+
+const PA_PER_PSI: f64 = 6_894.757_293_168;
+const M_PER_FT: f64 = 0.3048;
+const LB_PER_KG: f64 = 2.204_622_621_8;
+const N_PER_LBF: f64 = 4.448_221_615_3;
+const MPS2_PER_G: f64 = 9.806_65;
+
+impl UnitSystem {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UnitSystem::Raw => "raw",
+            UnitSystem::Si => "si",
+            UnitSystem::Imperial => "imperial",
+        }
+    }
+
+    pub fn unit(&self, sensor: SensorEnum) -> &'static str {
+        let quantity = sensor.quantity();
+        match (self, quantity) {
+            (UnitSystem::Si, Quantity::Angle) => "rad",
+            (UnitSystem::Si, Quantity::AngularRate | Quantity::RotationRate) => "rad/s",
+            (UnitSystem::Si, Quantity::GForce) => "m/s²",
+            (UnitSystem::Si, Quantity::MagneticField) => "T",
+            (UnitSystem::Imperial, Quantity::Pressure) => "psi",
+            (UnitSystem::Imperial, Quantity::Temperature) => "°F",
+            (UnitSystem::Imperial, Quantity::Length) => "ft",
+            (UnitSystem::Imperial, Quantity::Speed) => "ft/s",
+            (UnitSystem::Imperial, Quantity::Acceleration) => "ft/s²",
+            (UnitSystem::Imperial, Quantity::MassFlow) => "lb/s",
+            (UnitSystem::Imperial, Quantity::Force) => "lbf",
+            _ => Self::raw_unit(quantity),
+        }
+    }
+
+    // Convert a value the simulation produced for `sensor` into this system
+    pub fn convert(&self, sensor: SensorEnum, value: f64) -> f64 {
+        match (self, sensor.quantity()) {
+            (UnitSystem::Si, Quantity::Angle | Quantity::AngularRate) => value.to_radians(),
+            (UnitSystem::Si, Quantity::RotationRate) => value * std::f64::consts::TAU / 60.0,
+            (UnitSystem::Si, Quantity::GForce) => value * MPS2_PER_G,
+            (UnitSystem::Si, Quantity::MagneticField) => value * 1e-6,
+            (UnitSystem::Imperial, Quantity::Pressure) => value / PA_PER_PSI,
+            (UnitSystem::Imperial, Quantity::Temperature) => (value - 273.15) * 9.0 / 5.0 + 32.0,
+            (UnitSystem::Imperial, Quantity::Length | Quantity::Speed | Quantity::Acceleration) => {
+                value / M_PER_FT
+            }
+            (UnitSystem::Imperial, Quantity::MassFlow) => value * LB_PER_KG,
+            (UnitSystem::Imperial, Quantity::Force) => value / N_PER_LBF,
+            _ => value,
+        }
+    }
+
+    fn raw_unit(quantity: Quantity) -> &'static str {
+        match quantity {
+            Quantity::Pressure => "Pa",
+            Quantity::Temperature => "K",
+            Quantity::Length => "m",
+            Quantity::Speed => "m/s",
+            Quantity::Acceleration => "m/s²",
+            Quantity::MassFlow => "kg/s",
+            Quantity::Force => "N",
+            Quantity::Time => "s",
+            Quantity::Angle => "deg",
+            Quantity::AngularRate => "deg/s",
+            Quantity::RotationRate => "rpm",
+            Quantity::GForce => "g",
+            Quantity::Frequency => "Hz",
+            Quantity::MagneticField => "µT",
+            Quantity::Count => "count",
+            Quantity::Ratio => "",
+            Quantity::State => "state",
+            Quantity::Identifier => "id",
+        }
+    }
+}
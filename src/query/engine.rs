@@ -0,0 +1,253 @@
+use super::QueryOutput;
+use crate::error::{ExportContext, Result, TelemetryError};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::prelude::{CsvReadOptions, ParquetReadOptions, SessionContext};
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::info;
+
+// Sink name reported in export errors
+const SINK: &str = "query";
+
+// Every file as one table, when they're all parquet or all CSV
+pub const ALL_FILES_TABLE: &str = "t";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileFormat {
+    Parquet,
+    Csv,
+}
+
+// Runs SQL over parquet and CSV outputs with DataFusion. Each file is registered as a table named
+// after it, `SIM-001_1000hz_600s.parquet` as `sim_001_1000hz_600s` and its metadata CSV as
+// `sim_001_1000hz_600s_metadata`, so outputs can be joined. `t` is all of them as one table when
+// they're the same format
+pub async fn run_query(sql: &str, files: &[impl AsRef<Path>], output: QueryOutput) -> Result<()> {
+    let batches = query_batches(sql, files).await?;
+    match output {
+        QueryOutput::Table => {
+            let table = datafusion::arrow::util::pretty::pretty_format_batches(&batches)
+                .export_context(SINK, || "Failed to format the result")?;
+            println!("{table}");
+        }
+        QueryOutput::Csv => {
+            let mut writer = datafusion::arrow::csv::WriterBuilder::new()
+                .with_header(true)
+                .build(std::io::stdout());
+            for batch in &batches {
+                writer
+                    .write(batch)
+                    .export_context(SINK, || "Failed to write the result")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Result of `sql` over `files`, named as for `run_query`
+pub async fn query_batches(sql: &str, files: &[impl AsRef<Path>]) -> Result<Vec<RecordBatch>> {
+    let context = SessionContext::new();
+    register_files(&context, files).await?;
+
+    // DataFusion's planning errors are mistakes in the query
+    let frame = context
+        .sql(sql)
+        .await
+        .map_err(|e| TelemetryError::config(e.to_string()))?;
+    let batches: Vec<RecordBatch> = frame
+        .collect()
+        .await
+        .export_context(SINK, || "Failed to run the query")?;
+    info!(
+        "Query returned {} rows",
+        batches.iter().map(RecordBatch::num_rows).sum::<usize>()
+    );
+    Ok(batches)
+}
+
+async fn register_files(context: &SessionContext, files: &[impl AsRef<Path>]) -> Result<()> {
+    let mut names = HashSet::new();
+    let mut formats = HashSet::new();
+    let mut paths = Vec::with_capacity(files.len());
+    for file in files {
+        let path = file.as_ref();
+        if !path.is_file() {
+            return Err(TelemetryError::config(format!(
+                "{} isn't a file",
+                path.display()
+            )));
+        }
+        let format = file_format(path)?;
+        let name = table_name(path);
+        if name == ALL_FILES_TABLE || !names.insert(name.clone()) {
+            return Err(TelemetryError::config(format!(
+                "{} would be table {name}, which is already taken",
+                path.display()
+            )));
+        }
+        let location = path.to_string_lossy().into_owned();
+        match format {
+            FileFormat::Parquet => {
+                context
+                    .register_parquet(name.as_str(), &location, ParquetReadOptions::default())
+                    .await
+            }
+            FileFormat::Csv => {
+                context
+                    .register_csv(name.as_str(), &location, CsvReadOptions::new())
+                    .await
+            }
+        }
+        .export_context(SINK, || format!("Failed to read {}", path.display()))?;
+        info!("{} is table {name}", path.display());
+        formats.insert(format);
+        paths.push(location);
+    }
+
+    let all_files = match formats.iter().collect::<Vec<_>>()[..] {
+        [FileFormat::Parquet] => {
+            context
+                .read_parquet(paths, ParquetReadOptions::default())
+                .await
+        }
+        [FileFormat::Csv] => context.read_csv(paths, CsvReadOptions::new()).await,
+        // Mixed formats are only there by name
+        _ => return Ok(()),
+    };
+    // Nor are CSVs with different headers, like a run and its metadata
+    let all_files = match all_files {
+        Ok(all_files) => all_files,
+        Err(e) => {
+            info!("The files aren't one table {ALL_FILES_TABLE}: {e}");
+            return Ok(());
+        }
+    };
+    context
+        .register_table(ALL_FILES_TABLE, all_files.into_view())
+        .export_context(SINK, || "Failed to register the files as one table")?;
+    Ok(())
+}
+
+fn file_format(path: &Path) -> Result<FileFormat> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("parquet") => Ok(FileFormat::Parquet),
+        Some("csv") => Ok(FileFormat::Csv),
+        _ => Err(TelemetryError::config(format!(
+            "{} isn't a .parquet or .csv file",
+            path.display()
+        ))),
+    }
+}
+
+// File name less its extension, lowercased with anything but letters and digits as `_` so it
+// can be written unquoted
+fn table_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::arrow::array::{Array, Int64Array, StringArray};
+    use datafusion::arrow::compute::cast;
+    use datafusion::arrow::datatypes::DataType;
+
+    // Writes `files` as CSVs into a tempdir and runs `sql` over them
+    async fn query_csvs(sql: &str, files: &[(&str, &str)]) -> Result<Vec<RecordBatch>> {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<_> = files
+            .iter()
+            .map(|(name, contents)| {
+                let path = dir.path().join(name);
+                std::fs::write(&path, contents).unwrap();
+                path
+            })
+            .collect();
+        query_batches(sql, &paths).await
+    }
+
+    const READINGS: &str = "launch_id,sensor_type,value\nSIM-001,alt,10\nSIM-001,alt,20\n";
+    const METADATA: &str = "launch_id,seed\nSIM-001,1337\n";
+
+    #[test]
+    fn table_names_are_the_file_names_made_sql_safe() {
+        assert_eq!(
+            table_name(Path::new("out/SIM-001_1000hz_600s.metadata.csv")),
+            "sim_001_1000hz_600s_metadata"
+        );
+    }
+
+    #[tokio::test]
+    async fn files_join_by_table_name() {
+        let batches = query_csvs(
+            "select m.seed, sum(r.value) as total from sim_001_1000hz r \
+             join sim_001_1000hz_metadata m on r.launch_id = m.launch_id group by m.seed",
+            &[
+                ("SIM-001_1000hz.csv", READINGS),
+                ("SIM-001_1000hz.metadata.csv", METADATA),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(batches.iter().map(RecordBatch::num_rows).sum::<usize>(), 1);
+        let column = |name: &str| batches[0].column_by_name(name).unwrap().clone();
+        let seed = column("seed");
+        let total = column("total");
+        assert_eq!(
+            seed.as_any().downcast_ref::<Int64Array>().unwrap().value(0),
+            1337
+        );
+        assert_eq!(
+            total
+                .as_any()
+                .downcast_ref::<Int64Array>()
+                .unwrap()
+                .value(0),
+            30
+        );
+    }
+
+    #[tokio::test]
+    async fn files_of_one_format_are_also_one_table() {
+        let batches = query_csvs(
+            "select distinct launch_id from t order by launch_id",
+            &[
+                ("a.csv", READINGS),
+                ("b.csv", &*READINGS.replace("SIM-001", "SIM-002")),
+            ],
+        )
+        .await
+        .unwrap();
+        let ids: Vec<_> = batches
+            .iter()
+            .flat_map(|batch| {
+                // Strings may come back as views
+                let ids = cast(batch.column(0), &DataType::Utf8).unwrap();
+                let ids = ids.as_any().downcast_ref::<StringArray>().unwrap();
+                ids.iter()
+                    .map(|id| id.unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(ids, ["SIM-001", "SIM-002"]);
+    }
+
+    #[tokio::test]
+    async fn bad_sql_is_a_config_error() {
+        let error = query_csvs("select nope from a", &[("a.csv", READINGS)])
+            .await
+            .unwrap_err();
+        assert!(matches!(error, TelemetryError::ConfigError(_)), "{error}");
+    }
+}
@@ -0,0 +1,23 @@
+#[cfg(feature = "query")]
+mod engine;
+
+#[cfg(feature = "query")]
+pub use engine::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum QueryOutput {
+    Table,
+    Csv,
+}
+
+// DataFusion is left out of default builds, so without it there's nothing to run the SQL
+#[cfg(not(feature = "query"))]
+pub async fn run_query(
+    _sql: &str,
+    _files: &[impl AsRef<std::path::Path>],
+    _output: QueryOutput,
+) -> crate::error::Result<()> {
+    Err(crate::error::TelemetryError::config(
+        "query runs on DataFusion, which this build left out. Rebuild with --features query",
+    ))
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+// Ready made runs for new users. A template is a set of generate flags, plus a scenario script
+// for the ones that inject events. Flags given on the command line win over the template's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scenario {
+    NominalFlight,
+    #[value(name = "engine-failure-t30")]
+    EngineFailureT30,
+    GappyDownlink,
+    HighCardinalityStress,
+    MlTrainingSet,
+}
+
+// What `--scenario` names: a ready made template, or a TOML or YAML flight profile file
+// describing the phases to fly, see `FlightProfile`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScenarioSource {
+    Template(Scenario),
+    Profile(PathBuf),
+}
+
+impl ScenarioSource {
+    pub fn template(&self) -> Option<Scenario> {
+        match self {
+            ScenarioSource::Template(scenario) => Some(*scenario),
+            ScenarioSource::Profile(_) => None,
+        }
+    }
+
+    pub fn profile(&self) -> Option<&Path> {
+        match self {
+            ScenarioSource::Template(_) => None,
+            ScenarioSource::Profile(path) => Some(path),
+        }
+    }
+}
+
+impl FromStr for ScenarioSource {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        if let Some(scenario) = Scenario::all()
+            .iter()
+            .find(|scenario| scenario.name() == name)
+        {
+            return Ok(ScenarioSource::Template(*scenario));
+        }
+        let path = PathBuf::from(name);
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml" | "yaml" | "yml") => Ok(ScenarioSource::Profile(path)),
+            _ => {
+                let names: Vec<_> = Scenario::all().iter().map(Scenario::name).collect();
+                Err(format!(
+                    "Unknown scenario {name}, expected one of {} or a .toml or .yaml flight profile",
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+// Engine cuts out at T+30s. Only the engine channels react, the trajectory carries on
+const ENGINE_FAILURE_SCRIPT: &str = "
+at 30s log Engine failure, turbopump spinning down
+at 30s..31s set turbo_pump_rpm scale 0.5
+at 31s set turbo_pump_rpm scale 0.05
+at 30s set chamber_pressure_pa scale 0.02
+at 30s set thrust_n value 0
+at 30s set fuel_flow_rate_kgps scale 0.05
+at 30s set oxidizer_flow_rate_kgps scale 0.05
+at 30s..33s set vibration_z_g value 6.0
+";
+
+// A few labelled anomalies to train against. The log lines mark when each one starts
+const ML_TRAINING_SCRIPT: &str = "
+at 300s log Anomaly: fuel pressure bias
+at 300s..360s set fuel_pressure_pa bias +8%
+at 900s log Anomaly: vibration spike
+at 900s..905s set vibration_z_g scale 4
+at 1500s log Anomaly: turbopump droop
+at 1500s..1560s set turbo_pump_rpm scale 0.9
+";
+
+impl Scenario {
+    pub fn all() -> &'static [Scenario] {
+        <Scenario as clap::ValueEnum>::value_variants()
+    }
+
+    pub fn name(&self) -> String {
+        clap::ValueEnum::to_possible_value(self)
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Scenario::NominalFlight => "The standard mission profile at 1 kHz for 10 minutes",
+            Scenario::EngineFailureT30 => {
+                "Engine cuts out at T+30s. Pressures, flows and thrust collapse and the turbopump spins down"
+            }
+            Scenario::GappyDownlink => {
+                "Lossy downlink with frequent gaps, duplicates and late readings"
+            }
+            Scenario::HighCardinalityStress => {
+                "100 instances of every sensor for time series database cardinality tests"
+            }
+            Scenario::MlTrainingSet => {
+                "Half an hour with light chaos and three labelled anomalies, split value columns"
+            }
+        }
+    }
+
+    pub fn flags(&self) -> &'static [&'static str] {
+        match self {
+            Scenario::NominalFlight => &["--hz", "1000", "-d", "600"],
+            Scenario::EngineFailureT30 => &["--hz", "1000", "-d", "120"],
+            Scenario::GappyDownlink => &["--hz", "1000", "-d", "600", "--chaos", "high"],
+            Scenario::HighCardinalityStress => &[
+                "--hz",
+                "100",
+                "-d",
+                "600",
+                "--sensor-instances",
+                "100",
+                "--memory-limit",
+                "2GB",
+            ],
+            Scenario::MlTrainingSet => &[
+                "--hz",
+                "100",
+                "-d",
+                "1800",
+                "--chaos",
+                "low",
+                "--value-columns",
+                "split",
+            ],
+        }
+    }
+
+    pub fn script(&self) -> Option<&'static str> {
+        match self {
+            Scenario::EngineFailureT30 => Some(ENGINE_FAILURE_SCRIPT),
+            Scenario::MlTrainingSet => Some(ML_TRAINING_SCRIPT),
+            _ => None,
+        }
+    }
+}
+
+// Put the flags of the template picked with `--scenario` in front of the generate flags, so
+// anything given explicitly overrides them. Unknown names are left for clap to report
+pub fn expand_scenario(flags: Vec<String>) -> Vec<String> {
+    let name =
+        flags
+            .iter()
+            .enumerate()
+            .find_map(|(idx, flag)| match flag.strip_prefix("--scenario") {
+                Some("") => flags.get(idx + 1).map(String::as_str),
+                Some(rest) => rest.strip_prefix('='),
+                None => None,
+            });
+    let Some(scenario) = name.and_then(|name| {
+        Scenario::all()
+            .iter()
+            .find(|scenario| scenario.name() == name)
+    }) else {
+        return flags;
+    };
+
+    scenario
+        .flags()
+        .iter()
+        .map(|flag| flag.to_string())
+        .chain(flags)
+        .collect()
+}
+
+// Arguments of the whole command line with the template expanded after `generate`
+pub fn expand_scenario_args(args: Vec<String>) -> Vec<String> {
+    let Some(generate) = args.iter().position(|arg| arg == "generate") else {
+        return args;
+    };
+    let mut args = args;
+    let flags = args.split_off(generate + 1);
+    args.extend(expand_scenario(flags));
+    args
+}
+
+pub fn list_scenarios() {
+    for scenario in Scenario::all() {
+        println!("{}", scenario.name());
+        println!("    {}", scenario.description());
+        println!("    flags: {}", scenario.flags().join(" "));
+        if scenario.script().is_some() {
+            println!("    plus a built in scenario script");
+        }
+    }
+}
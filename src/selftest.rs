@@ -0,0 +1,676 @@
+use crate::{
+    Failure, GenerateArgs, SelftestArgs, configure_generator, generate_config, generate_to_parquet,
+    verify_determinism,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{Float64Type, Int64Type, TimestampMicrosecondType, UInt64Type};
+use clap::Parser;
+use num_format::{Locale, ToFormattedString};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use telemetry_generator::TelemetryGenerator;
+use telemetry_generator::exporters::SENSOR_UNITS_KEY;
+use telemetry_generator::generators::{CancelToken, FlightSummary};
+use telemetry_generator::models::{
+    MissionPhase, PairSide, SensorEnum, SensorValue, TelemetryDataset, TelemetryReading,
+    line_protocol_nanos,
+};
+use tracing::{info, warn};
+
+// Generate flags every check flies with. Fanned out instances, a redundant pair and the command
+// link cover every kind of reading, and the split value columns keep all of them in the parquet
+const SELFTEST_FLAGS: &[&str] = &[
+    "--hz",
+    "100",
+    "--duration",
+    "30",
+    "--sensor-instances",
+    "2",
+    "--redundant",
+    "cmb_pa",
+    "--command-rate",
+    "30",
+    "--value-columns",
+    "split",
+    "--seeded-launch-time",
+    "--disable-progress",
+    "--force",
+];
+
+// Measurement and launch id for the line protocol check, with every character that needs escaping
+const LINE_MEASUREMENT: &str = "rocket telemetry,selftest";
+const LINE_LAUNCH_ID: &str = "SIM 1,a=b\\";
+
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true, args_override_self = true)]
+struct SelftestRun {
+    #[command(flatten)]
+    args: GenerateArgs,
+}
+
+// Runs the built in checks against this build, a short run each, and prints how each went.
+// Files go in `{output_dir}/.selftest` and are removed after. Fails if any check does
+pub fn run_selftest(args: &SelftestArgs, cancel: &CancelToken) -> Result<()> {
+    let work_dir = args.output_dir.join(".selftest");
+    let run = selftest_run(args.seed, &work_dir, &[])?;
+    let chaotic = selftest_run(
+        args.seed,
+        &work_dir,
+        &["--chaos", "medium", "--batch-noise"],
+    )?;
+
+    let results = generate(&run, cancel).and_then(|(dataset, flight)| {
+        let checks: [(&str, &dyn Fn() -> Result<String>); 4] = [
+            ("parquet round trip", &|| {
+                parquet_round_trip(&run, &dataset, cancel)
+            }),
+            ("line protocol", &|| line_protocol(&dataset)),
+            ("physics invariants", &|| {
+                physics_invariants(&dataset, &flight)
+            }),
+            ("determinism", &|| determinism(&chaotic, cancel)),
+        ];
+        let mut results = Vec::with_capacity(checks.len());
+        for (name, check) in checks {
+            info!("Checking {name}");
+            let start_time = Instant::now();
+            let outcome = check();
+            if cancel.is_cancelled() {
+                return Err(
+                    anyhow!("Cancelled during the {name} check").context(Failure::Cancelled)
+                );
+            }
+            results.push((name, outcome, start_time.elapsed()));
+        }
+        Ok(results)
+    });
+    if work_dir.exists()
+        && let Err(e) = std::fs::remove_dir_all(&work_dir)
+    {
+        warn!("Failed to remove {}: {e}", work_dir.display());
+    }
+
+    let results = results?;
+    let failed = results
+        .iter()
+        .filter(|(_, outcome, _)| outcome.is_err())
+        .count();
+    for (name, outcome, elapsed) in &results {
+        print_result(name, outcome, *elapsed);
+    }
+    if failed > 0 {
+        bail!("{failed} of {} self tests failed", results.len());
+    }
+    println!("All {} self tests passed", results.len());
+    Ok(())
+}
+
+fn print_result(name: &str, outcome: &Result<String>, elapsed: Duration) {
+    let (status, detail) = match outcome {
+        Ok(detail) => ("ok", detail.clone()),
+        Err(e) => ("FAIL", format!("{e:#}")),
+    };
+    println!(
+        "{status:<4}  {name:<18}  {:>6.2}s  {detail}",
+        elapsed.as_secs_f64()
+    );
+}
+
+fn selftest_run(seed: u64, work_dir: &Path, extra: &[&str]) -> Result<GenerateArgs> {
+    let seed = seed.to_string();
+    let output_dir = work_dir.display().to_string();
+    let flags = SELFTEST_FLAGS
+        .iter()
+        .copied()
+        .chain(["--seed", &seed, "--output-dir", &output_dir])
+        .chain(extra.iter().copied());
+    let run = SelftestRun::try_parse_from(flags).context("Self test flags don't parse")?;
+    Ok(run.args)
+}
+
+// The run held in memory, for the checks to compare against
+fn generate(
+    args: &GenerateArgs,
+    cancel: &CancelToken,
+) -> Result<(TelemetryDataset, FlightSummary)> {
+    let generator = TelemetryGenerator::new(generate_config(args)?).with_cancel(cancel.clone());
+    let mut generator = configure_generator(generator, args)?;
+    let dataset = generator.generate(args.disable_progress)?;
+    if generator.cancelled() {
+        return Err(anyhow!("Cancelled while generating").context(Failure::Cancelled));
+    }
+    Ok((dataset, generator.flight_summary().clone()))
+}
+
+// Write the run through `generate` and read every row back, which has to match what was generated
+fn parquet_round_trip(
+    args: &GenerateArgs,
+    dataset: &TelemetryDataset,
+    cancel: &CancelToken,
+) -> Result<String> {
+    let report = generate_to_parquet(args, Some(cancel))?;
+    let parquet_file = report.parquet_file();
+    let file = File::open(&parquet_file)
+        .with_context(|| format!("Failed to open {}", parquet_file.display()))?;
+    let builder = ParquetRecordBatchReaderBuilder::try_new(file)?;
+    let has_units = builder
+        .metadata()
+        .file_metadata()
+        .key_value_metadata()
+        .is_some_and(|metadata| metadata.iter().any(|kv| kv.key == SENSOR_UNITS_KEY));
+    if !has_units {
+        bail!("The footer has no {SENSOR_UNITS_KEY}");
+    }
+
+    let config = &dataset.config;
+    let mut readings = dataset.readings.iter();
+    let mut row = 0;
+    for batch in builder.build()? {
+        let batch = batch?;
+        let column = |name: &str| {
+            batch
+                .column_by_name(name)
+                .ok_or_else(|| anyhow!("No {name} column"))
+        };
+        let wrong_type = |name: &str| anyhow!("The {name} column has the wrong type");
+        let timestamps = column("timestamp")?
+            .as_primitive_opt::<TimestampMicrosecondType>()
+            .ok_or_else(|| wrong_type("timestamp"))?;
+        let times = column("time_since_launch_ms")?
+            .as_primitive_opt::<UInt64Type>()
+            .ok_or_else(|| wrong_type("time_since_launch_ms"))?;
+        let sensors = column("sensor_type")?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| wrong_type("sensor_type"))?;
+        let floats = column("value_float")?
+            .as_primitive_opt::<Float64Type>()
+            .ok_or_else(|| wrong_type("value_float"))?;
+        let ints = column("value_int")?
+            .as_primitive_opt::<Int64Type>()
+            .ok_or_else(|| wrong_type("value_int"))?;
+        let strings = column("value_str")?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| wrong_type("value_str"))?;
+        let phases = column("mission_phase")?
+            .as_string_opt::<i32>()
+            .ok_or_else(|| wrong_type("mission_phase"))?;
+
+        for idx in 0..batch.num_rows() {
+            let reading = readings.next().ok_or_else(|| {
+                anyhow!("More rows than the {} generated", dataset.readings.len())
+            })?;
+            let (float, int, string) = match &reading.value {
+                SensorValue::Float(f) => {
+                    (Some(config.export_float(reading.sensor, *f)), None, None)
+                }
+                SensorValue::Int(i) => (None, Some(*i), None),
+                SensorValue::UnsignedInt(u) => (None, Some(*u as i64), None),
+                SensorValue::String(s) => (None, None, Some(s.as_str())),
+                SensorValue::State(state) => (None, Some(state.code()), Some(state.name())),
+            };
+            let read_float = floats.is_valid(idx).then(|| floats.value(idx));
+            let read_int = ints.is_valid(idx).then(|| ints.value(idx));
+            let read_string = strings.is_valid(idx).then(|| strings.value(idx));
+            let differs = [
+                (
+                    "timestamp",
+                    timestamps.value(idx) != reading.timestamp.timestamp_micros(),
+                ),
+                (
+                    "time_since_launch_ms",
+                    times.value(idx) != reading.time_since_launch_ms,
+                ),
+                (
+                    "sensor_type",
+                    sensors.value(idx) != reading.sensor_label(config),
+                ),
+                // Bit for bit, so NaN compares equal to itself
+                (
+                    "value_float",
+                    read_float.map(f64::to_bits) != float.map(f64::to_bits),
+                ),
+                ("value_int", read_int != int),
+                ("value_str", read_string != string),
+                ("mission_phase", phases.value(idx) != reading.phase.name()),
+            ];
+            if let Some((name, _)) = differs.iter().find(|(_, differs)| *differs) {
+                bail!("Row {row} has a different {name} to the reading generated");
+            }
+            row += 1;
+        }
+    }
+    if row != dataset.readings.len() {
+        bail!(
+            "Read {row} rows of the {} generated",
+            dataset.readings.len()
+        );
+    }
+    Ok(format!(
+        "{} rows read back unchanged",
+        row.to_formatted_string(&Locale::en)
+    ))
+}
+
+// Every reading's line, plus awkward values and names, parsed back by the rules of the InfluxDB
+// line protocol and compared to the reading
+fn line_protocol(dataset: &TelemetryDataset) -> Result<String> {
+    let awkward = |value: SensorValue| {
+        TelemetryReading::new(
+            dataset.launch_time,
+            0,
+            MissionPhase::Prelaunch,
+            SensorEnum::FuelPressure,
+            value,
+        )
+        .with_instance(7)
+        .with_side(PairSide::B)
+    };
+    let awkward = [
+        awkward(SensorValue::Float(-0.0)),
+        awkward(SensorValue::Float(f64::MAX)),
+        awkward(SensorValue::Float(f64::MIN_POSITIVE)),
+        awkward(SensorValue::Int(i64::MIN)),
+        awkward(SensorValue::UnsignedInt(u64::MAX)),
+        awkward(SensorValue::String("say \"hi\", a=b \\ bye".to_string())),
+    ];
+
+    let mut line = String::new();
+    let mut lines = 0usize;
+    for reading in dataset.readings.iter().chain(&awkward) {
+        line.clear();
+        if !reading.write_line_protocol(
+            LINE_MEASUREMENT,
+            dataset.config.naming,
+            LINE_LAUNCH_ID,
+            &mut line,
+        ) {
+            bail!(
+                "{} wasn't written as line protocol",
+                reading.sensor.field_name_full()
+            );
+        }
+        let parsed = ParsedLine::parse(&line).map_err(|e| anyhow!("{e} in `{line}`"))?;
+        let label = reading.sensor_label(&dataset.config);
+        let expected = match &reading.value {
+            SensorValue::Float(f) => LineValue::Float(*f),
+            SensorValue::Int(i) => LineValue::Int(*i),
+            SensorValue::UnsignedInt(u) => LineValue::UnsignedInt(*u),
+            SensorValue::String(s) => LineValue::String(s.clone()),
+            SensorValue::State(state) => LineValue::String(state.name().to_string()),
+        };
+        let differs = [
+            ("measurement", parsed.measurement != LINE_MEASUREMENT),
+            (
+                "tags",
+                parsed.tags
+                    != [
+                        ("sensor".to_string(), label.into_owned()),
+                        ("launch_id".to_string(), LINE_LAUNCH_ID.to_string()),
+                    ],
+            ),
+            (
+                "field",
+                parsed.fields != [(reading.sensor.field_name_full().to_string(), expected)],
+            ),
+            (
+                "timestamp",
+                parsed.timestamp.map(i128::from) != Some(line_protocol_nanos(reading.timestamp)),
+            ),
+        ];
+        if let Some((name, _)) = differs.iter().find(|(_, differs)| *differs) {
+            bail!("`{line}` parses to a different {name} to the reading");
+        }
+        lines += 1;
+    }
+    Ok(format!(
+        "{} lines parse back to their readings",
+        lines.to_formatted_string(&Locale::en)
+    ))
+}
+
+// Field value of a parsed line
+#[derive(Debug, PartialEq)]
+enum LineValue {
+    // Compared bit for bit
+    Float(f64),
+    Int(i64),
+    UnsignedInt(u64),
+    String(String),
+    Bool(bool),
+}
+
+// A line split up the way InfluxDB reads it, with the escapes undone
+#[derive(Debug)]
+struct ParsedLine {
+    measurement: String,
+    tags: Vec<(String, String)>,
+    fields: Vec<(String, LineValue)>,
+    timestamp: Option<i64>,
+}
+
+impl ParsedLine {
+    // `measurement(,tag=value)* field=value(,field=value)* [timestamp]`. See
+    // https://docs.influxdata.com/influxdb/v2/reference/syntax/line-protocol/
+    fn parse(line: &str) -> Result<Self, String> {
+        if line.starts_with('#') {
+            return Err("A line starting with # is a comment".to_string());
+        }
+        if line.contains('\n') {
+            return Err("Unescaped newline".to_string());
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let mut pos = 0;
+        let measurement = read_name(&chars, &mut pos, &[',', ' '], &[',', ' '], "measurement")?;
+
+        let mut tags = Vec::new();
+        while chars.get(pos) == Some(&',') {
+            pos += 1;
+            let key = read_name(
+                &chars,
+                &mut pos,
+                &[',', '=', ' '],
+                &[',', '=', ' '],
+                "tag key",
+            )?;
+            expect(&chars, &mut pos, '=', "after a tag key")?;
+            let value = read_name(&chars, &mut pos, &[',', '=', ' '], &[',', ' '], "tag value")?;
+            tags.push((key, value));
+        }
+        expect(&chars, &mut pos, ' ', "before the fields")?;
+
+        let mut fields = Vec::new();
+        loop {
+            let key = read_name(
+                &chars,
+                &mut pos,
+                &[',', '=', ' '],
+                &[',', '=', ' '],
+                "field key",
+            )?;
+            expect(&chars, &mut pos, '=', "after a field key")?;
+            fields.push((key, read_field_value(&chars, &mut pos)?));
+            if chars.get(pos) != Some(&',') {
+                break;
+            }
+            pos += 1;
+        }
+
+        let timestamp = match chars.get(pos) {
+            None => None,
+            Some(' ') => {
+                let digits: String = chars[pos + 1..].iter().collect();
+                let valid = digits
+                    .strip_prefix('-')
+                    .unwrap_or(&digits)
+                    .chars()
+                    .all(|c| c.is_ascii_digit());
+                Some(
+                    digits
+                        .parse::<i64>()
+                        .ok()
+                        .filter(|_| valid)
+                        .ok_or_else(|| format!("Timestamp {digits} isn't 64 bit nanoseconds"))?,
+                )
+            }
+            Some(c) => return Err(format!("Unexpected {c:?} after the fields")),
+        };
+        Ok(Self {
+            measurement,
+            tags,
+            fields,
+            timestamp,
+        })
+    }
+}
+
+// Read up to the first unescaped `stops`, undoing backslash escapes of `escaped` and of
+// backslash itself. Any other backslash is taken as it is
+fn read_name(
+    chars: &[char],
+    pos: &mut usize,
+    escaped: &[char],
+    stops: &[char],
+    what: &str,
+) -> Result<String, String> {
+    let mut name = String::new();
+    while let Some(&c) = chars.get(*pos) {
+        if stops.contains(&c) {
+            break;
+        }
+        match chars.get(*pos + 1) {
+            Some(&next) if c == '\\' && (escaped.contains(&next) || next == '\\') => {
+                name.push(next);
+                *pos += 2;
+            }
+            _ => {
+                name.push(c);
+                *pos += 1;
+            }
+        }
+    }
+    if name.is_empty() {
+        return Err(format!("Empty {what}"));
+    }
+    Ok(name)
+}
+
+fn expect(chars: &[char], pos: &mut usize, expected: char, what: &str) -> Result<(), String> {
+    match chars.get(*pos) {
+        Some(&c) if c == expected => {
+            *pos += 1;
+            Ok(())
+        }
+        found => Err(format!("Expected {expected:?} {what}, found {found:?}")),
+    }
+}
+
+fn read_field_value(chars: &[char], pos: &mut usize) -> Result<LineValue, String> {
+    if chars.get(*pos) == Some(&'"') {
+        *pos += 1;
+        let mut value = String::new();
+        loop {
+            match (chars.get(*pos), chars.get(*pos + 1)) {
+                (Some('\\'), Some(&next)) if next == '"' || next == '\\' => {
+                    value.push(next);
+                    *pos += 2;
+                }
+                (Some('"'), _) => {
+                    *pos += 1;
+                    return Ok(LineValue::String(value));
+                }
+                (Some(&c), _) => {
+                    value.push(c);
+                    *pos += 1;
+                }
+                (None, _) => return Err("Unterminated string field".to_string()),
+            }
+        }
+    }
+
+    let start = *pos;
+    while chars.get(*pos).is_some_and(|c| *c != ',' && *c != ' ') {
+        *pos += 1;
+    }
+    let raw: String = chars[start..*pos].iter().collect();
+    let invalid = || format!("Invalid field value {raw}");
+    match raw.as_str() {
+        "t" | "T" | "true" | "True" | "TRUE" => return Ok(LineValue::Bool(true)),
+        "f" | "F" | "false" | "False" | "FALSE" => return Ok(LineValue::Bool(false)),
+        _ => {}
+    }
+    if let Some(int) = raw.strip_suffix('i') {
+        return int.parse().map(LineValue::Int).map_err(|_| invalid());
+    }
+    if let Some(uint) = raw.strip_suffix('u') {
+        return uint
+            .parse()
+            .map(LineValue::UnsignedInt)
+            .map_err(|_| invalid());
+    }
+    // Plain decimal or scientific notation. Rust would also take `NaN` and `inf`, InfluxDB won't
+    let mantissa = raw
+        .split(['e', 'E'])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches(['-', '+']);
+    let well_formed = !mantissa.is_empty()
+        && mantissa.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && mantissa.chars().filter(|c| *c == '.').count() <= 1
+        && mantissa != ".";
+    match raw.parse::<f64>() {
+        Ok(value) if well_formed && value.is_finite() => Ok(LineValue::Float(value)),
+        _ => Err(invalid()),
+    }
+}
+
+// Things any flight has to get right, checked on the readings and the flight summary
+fn physics_invariants(dataset: &TelemetryDataset, flight: &FlightSummary) -> Result<String> {
+    let config = &dataset.config;
+    let (events, sampled): (Vec<&TelemetryReading>, Vec<&TelemetryReading>) = dataset
+        .readings
+        .iter()
+        .partition(|reading| reading.sensor.is_event_driven());
+
+    // A reading of every channel every step
+    if sampled.len() != config.get_total_points() {
+        bail!(
+            "{} readings for {} steps of {} channels",
+            sampled.len(),
+            config.get_total_readings(),
+            config.readings_per_step()
+        );
+    }
+    let mut per_channel: HashMap<_, usize> = HashMap::new();
+    for reading in &sampled {
+        *per_channel.entry(reading.sensor_label(config)).or_default() += 1;
+    }
+    if per_channel.len() != config.readings_per_step() {
+        bail!(
+            "{} channels reported, {} expected",
+            per_channel.len(),
+            config.readings_per_step()
+        );
+    }
+    if let Some((channel, count)) = per_channel
+        .iter()
+        .find(|(_, count)| **count != config.get_total_readings())
+    {
+        bail!(
+            "{channel} reported {count} times in {} steps",
+            config.get_total_readings()
+        );
+    }
+
+    // Time only moves forward and timestamps stay within jitter of the step they belong to
+    let jitter_limit = chrono::Duration::milliseconds(1);
+    for pair in sampled.windows(2) {
+        if pair[1].time_since_launch_ms < pair[0].time_since_launch_ms {
+            bail!(
+                "{} at {}ms comes after {}ms",
+                pair[1].sensor_label(config),
+                pair[1].time_since_launch_ms,
+                pair[0].time_since_launch_ms
+            );
+        }
+    }
+    for reading in &sampled {
+        let step_time = dataset.launch_time
+            + chrono::Duration::milliseconds(reading.time_since_launch_ms as i64);
+        if (reading.timestamp - step_time).abs() > jitter_limit {
+            bail!(
+                "{} at {}ms is stamped {}",
+                reading.sensor_label(config),
+                reading.time_since_launch_ms,
+                reading.timestamp
+            );
+        }
+    }
+
+    // Every number is a number, and the pad is never returned to once left
+    if let Some(reading) = dataset
+        .readings
+        .iter()
+        .find(|reading| matches!(reading.value, SensorValue::Float(f) if !f.is_finite()))
+    {
+        bail!(
+            "{} at {}ms isn't finite",
+            reading.sensor_label(config),
+            reading.time_since_launch_ms
+        );
+    }
+    let liftoff = dataset
+        .readings
+        .iter()
+        .position(|reading| reading.phase != MissionPhase::Prelaunch)
+        .unwrap_or(dataset.readings.len());
+    if let Some(reading) = dataset.readings[liftoff..]
+        .iter()
+        .find(|reading| reading.phase == MissionPhase::Prelaunch)
+    {
+        bail!(
+            "Back to prelaunch at {}ms after lifting off",
+            reading.time_since_launch_ms
+        );
+    }
+
+    // The vehicle flies
+    let run_s = config.duration as f64;
+    if !(flight.max_altitude_m > 0.0 && flight.max_q_pa > 0.0 && flight.burnout_velocity_mps > 0.0)
+    {
+        bail!(
+            "Never flew: apogee {:.1} m, max q {:.1} Pa, burnout {:.1} m/s",
+            flight.max_altitude_m,
+            flight.max_q_pa,
+            flight.burnout_velocity_mps
+        );
+    }
+    if !(0.0..=run_s).contains(&flight.max_q_time_s) {
+        bail!(
+            "Max q at {:.1}s is outside the {run_s}s run",
+            flight.max_q_time_s
+        );
+    }
+
+    // Every ack answers a command sent before it, after a positive latency
+    let mut sent_ms = HashMap::new();
+    for reading in &events {
+        match (reading.sensor, &reading.value) {
+            (SensorEnum::CommandSent, SensorValue::UnsignedInt(id)) => {
+                sent_ms.insert(*id, reading.time_since_launch_ms);
+            }
+            (SensorEnum::CommandAck, SensorValue::UnsignedInt(id))
+                if sent_ms
+                    .get(id)
+                    .is_none_or(|sent| *sent > reading.time_since_launch_ms) =>
+            {
+                bail!("Command {id} acked before it was sent");
+            }
+            (SensorEnum::CommandAckLatency, SensorValue::Float(latency)) if *latency <= 0.0 => {
+                bail!("Ack latency of {latency}s");
+            }
+            _ => {}
+        }
+    }
+
+    Ok(format!(
+        "{} channels over {} steps, apogee {:.0} m, {} commands",
+        per_channel.len(),
+        config.get_total_readings().to_formatted_string(&Locale::en),
+        flight.max_altitude_m,
+        sent_ms.len()
+    ))
+}
+
+// With chaos and batch noise on, the run has to come out the same however it's chunked
+fn determinism(args: &GenerateArgs, cancel: &CancelToken) -> Result<String> {
+    let (readings, sha256) = verify_determinism(args, cancel)?;
+    Ok(format!(
+        "{} readings hash to {} as one chunk and a step per chunk",
+        readings.to_formatted_string(&Locale::en),
+        &sha256[..16]
+    ))
+}
@@ -0,0 +1,658 @@
+use crate::daemon::LiveStats;
+use crate::history::{find_run, load_runs};
+use crate::jobs::{JobQueue, JobStatus, SubmitError};
+use clap::ValueEnum;
+use hyper::body::{Bytes, HttpBody};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode, header};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use telemetry_generator::error::{Result, TelemetryError};
+use telemetry_generator::exporters::{FrameFormat, ParquetConfig, ParquetExporter, StreamExporter};
+use telemetry_generator::generators::{CancelToken, TelemetryGenerator};
+use telemetry_generator::models::{
+    MemoryBudget, SampleRate, SensorEnum, SensorNaming, TelemetryConfig, UnitSystem, sensor_label,
+};
+use tokio::io::AsyncReadExt;
+use tracing::{error, info};
+
+// HTTP API the daemon serves with `--listen`, so datasets can be had without the CLI:
+//
+// - `GET /` is a small web UI over the routes below, from `ui/index.html`
+// - `GET /status` has the daemon's counters, the ones `status` prints
+// - `POST /generate` with a JSON `GenerateRequest` returns the run as parquet or NDJSON
+// - `GET /sensors` lists every sensor with its unit and description
+// - `POST /jobs` queues the same request for the worker pool in `jobs.rs`, `GET /jobs` and
+//   `GET /jobs/{id}` report progress, `GET /jobs/{id}/result` downloads the parquet and
+//   `DELETE /jobs/{id}` cancels
+// - `GET /runs` and `GET /runs/{id}` read the run history, where every job that ran ends up
+//
+// `/generate` runs are generated in memory, so they're capped at `MAX_READINGS`
+
+// Readings one request can ask for, around 300 MB while generating
+const MAX_READINGS: usize = 5_000_000;
+
+// Generate requests are a handful of settings, anything bigger is a mistake
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+// Built into the binary so the daemon serves it from anywhere
+const UI_PAGE: &str = include_str!("ui/index.html");
+
+// Job results are sent in pieces of this size rather than read into memory
+const DOWNLOAD_CHUNK_BYTES: usize = 64 * 1024;
+
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+// Serve until `shutdown` is cancelled, letting requests in flight finish
+pub async fn serve(
+    addr: SocketAddr,
+    stats: Arc<LiveStats>,
+    jobs: Arc<JobQueue>,
+    shutdown: CancelToken,
+) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let stats = stats.clone();
+        let jobs = jobs.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let stats = stats.clone();
+                let jobs = jobs.clone();
+                async move {
+                    stats.served();
+                    Ok::<_, Infallible>(route(request, &stats, &jobs).await)
+                }
+            }))
+        }
+    });
+    let server = Server::try_bind(&addr)
+        .map_err(|e| TelemetryError::config(format!("Failed to listen on {addr}: {e}")))?
+        .serve(make_service);
+    info!("Serving the API on http://{}", server.local_addr());
+
+    server
+        .with_graceful_shutdown(async move {
+            while !shutdown.is_cancelled() {
+                tokio::time::sleep(SHUTDOWN_CHECK_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|e| TelemetryError::export("http", format!("API server failed: {e}")))
+}
+
+async fn route(request: Request<Body>, stats: &LiveStats, jobs: &JobQueue) -> Response<Body> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    // `/jobs/{id}` and `/jobs/{id}/result`
+    let job_route = path
+        .strip_prefix("/jobs/")
+        .map(|rest| rest.split_once('/').unwrap_or((rest, "")))
+        .map(|(id, action)| (id.parse::<u64>().ok(), action));
+    let response = match (&method, path.as_str(), job_route) {
+        (&Method::GET, "/", _) => Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(UI_PAGE))
+            .expect("Static headers are valid"),
+        (&Method::GET, "/status", _) => json_response(StatusCode::OK, &stats.snapshot()),
+        (&Method::POST, "/generate", _) => generate(request).await,
+        (&Method::GET, "/sensors", _) => sensors(),
+        (&Method::POST, "/jobs", _) => submit_job(request, stats, jobs).await,
+        (&Method::GET, "/jobs", _) => json_response(StatusCode::OK, &jobs.reports()),
+        (_, _, Some((None, _))) => error_response(StatusCode::NOT_FOUND, "Job ids are numbers"),
+        (&Method::GET, _, Some((Some(id), ""))) => match jobs.report(id) {
+            Some(report) => json_response(StatusCode::OK, &report),
+            None => job_not_found(id),
+        },
+        (&Method::DELETE, _, Some((Some(id), ""))) => match jobs.cancel(id) {
+            Some(report) => json_response(StatusCode::OK, &report),
+            None => job_not_found(id),
+        },
+        (&Method::GET, _, Some((Some(id), "result"))) => job_result(jobs, id).await,
+        (&Method::GET, "/runs", _) => runs(jobs).await,
+        (&Method::GET, run_path, _) if run_path.starts_with("/runs/") => {
+            run(jobs, &run_path["/runs/".len()..]).await
+        }
+        (_, "/" | "/status" | "/generate" | "/sensors" | "/jobs" | "/runs", _)
+        | (_, _, Some((Some(_), "" | "result"))) => method_not_allowed(&method, &path),
+        (_, run_path, _) if run_path.starts_with("/runs/") => method_not_allowed(&method, &path),
+        _ => error_response(StatusCode::NOT_FOUND, format!("No route for {path}")),
+    };
+    info!("{method} {path} {}", response.status());
+    response
+}
+
+fn method_not_allowed(method: &Method, path: &str) -> Response<Body> {
+    error_response(
+        StatusCode::METHOD_NOT_ALLOWED,
+        format!("{method} isn't supported on {path}"),
+    )
+}
+
+// Body of `POST /generate`. Everything is optional and falls back to `TelemetryConfig::default()`
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct GenerateRequest {
+    duration: Option<usize>,
+    // A number or a string like `"333.3"`
+    sample_rate_hz: Option<RequestRate>,
+    launch_id: Option<String>,
+    seed: Option<u64>,
+    max_rows: Option<usize>,
+    timestamp_jitter: Option<f64>,
+    sensor_instances: Option<u16>,
+    units: Option<String>,
+    naming: Option<String>,
+    physics_step_ms: Option<u64>,
+    // `parquet` or `ndjson`
+    format: Option<String>,
+    // Jobs only, the memory to generate in like `"64MB"`. The daemon's --job-memory-limit by
+    // default and at most
+    memory_limit: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RequestRate {
+    Hz(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PayloadFormat {
+    Parquet,
+    Ndjson,
+}
+
+impl GenerateRequest {
+    // Settings of a run of at most `max_readings`
+    fn into_config(self, max_readings: usize) -> Result<(TelemetryConfig, PayloadFormat)> {
+        let mut config = TelemetryConfig::default();
+        if let Some(duration) = self.duration {
+            config.duration = duration;
+        }
+        if let Some(rate) = self.sample_rate_hz {
+            let rate = match rate {
+                RequestRate::Hz(hz) => hz.to_string(),
+                RequestRate::Text(text) => text,
+            };
+            config.sample_rate_hz = rate.parse::<SampleRate>()?;
+        }
+        if let Some(launch_id) = self.launch_id {
+            config.launch_id = launch_id;
+        }
+        if let Some(seed) = self.seed {
+            config.seed = seed;
+        }
+        config.max_rows = self.max_rows;
+        if let Some(jitter) = self.timestamp_jitter {
+            config.timestamp_jitter = jitter;
+        }
+        if let Some(instances) = self.sensor_instances {
+            config.sensor_instances = instances;
+        }
+        if let Some(units) = self.units {
+            config.units = parse_choice::<UnitSystem>("units", &units)?;
+        }
+        if let Some(naming) = self.naming {
+            config.naming = parse_choice::<SensorNaming>("naming", &naming)?;
+        }
+        config.physics_step_ms = self.physics_step_ms;
+
+        let format = match self.format.as_deref() {
+            None | Some("parquet") => PayloadFormat::Parquet,
+            Some("ndjson") => PayloadFormat::Ndjson,
+            Some(other) => {
+                return Err(TelemetryError::config(format!(
+                    "Unknown format {other}. Use parquet or ndjson"
+                )));
+            }
+        };
+        // The generator stops at max_rows, so the run is whichever is smaller
+        let uncapped = config
+            .get_total_readings()
+            .saturating_mul(config.readings_per_step());
+        let readings = config
+            .max_rows
+            .map_or(uncapped, |max_rows| uncapped.min(max_rows));
+        if readings > max_readings {
+            return Err(TelemetryError::config(format!(
+                "The run would have {readings} readings, the API generates at most \
+                {max_readings}. Lower the duration or rate, set max_rows or use the CLI"
+            )));
+        }
+        Ok((config, format))
+    }
+}
+
+fn parse_choice<T: ValueEnum>(field: &str, value: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let choices: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        TelemetryError::config(format!(
+            "Unknown {field} {value}. Use one of {}",
+            choices.join(", ")
+        ))
+    })
+}
+
+// Body of a generate or job request, defaults for an empty one
+async fn read_generate_request(
+    request: Request<Body>,
+) -> std::result::Result<GenerateRequest, Response<Body>> {
+    let too_large = || {
+        error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request bodies are limited to {MAX_BODY_BYTES} bytes"),
+        )
+    };
+    let mut incoming = request.into_body();
+    // Turned away on its Content-Length before any of it is read
+    if incoming.size_hint().lower() > MAX_BODY_BYTES as u64 {
+        return Err(too_large());
+    }
+    // Bodies without one, or that send more than it said, stop once they go over
+    let mut body = Vec::new();
+    while let Some(data) = incoming.data().await {
+        let data = data.map_err(|e| {
+            error_response(StatusCode::BAD_REQUEST, format!("Failed to read body: {e}"))
+        })?;
+        if body.len() + data.len() > MAX_BODY_BYTES {
+            return Err(too_large());
+        }
+        body.extend_from_slice(&data);
+    }
+    // An empty body generates the defaults
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(GenerateRequest::default());
+    }
+    serde_json::from_slice(&body)
+        .map_err(|e| error_response(StatusCode::BAD_REQUEST, format!("Invalid request: {e}")))
+}
+
+async fn generate(request: Request<Body>) -> Response<Body> {
+    let request = match read_generate_request(request).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    if request.memory_limit.is_some() {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "memory_limit only applies to jobs, /generate runs in memory",
+        );
+    }
+    let (config, format) = match request.into_config(MAX_READINGS) {
+        Ok(settings) => settings,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+
+    let file_name = format!(
+        "{}_{}hz_{}s.{}",
+        config.launch_id,
+        config.sample_rate_hz,
+        config.duration,
+        match format {
+            PayloadFormat::Parquet => "parquet",
+            PayloadFormat::Ndjson => "ndjson",
+        }
+    );
+    // Generating and encoding hold a core for a while, so both stay off the request threads
+    let dataset = match TelemetryGenerator::new(config).generate_async(true).await {
+        Ok((_, dataset)) => dataset,
+        Err(e) => {
+            error!("Generate request failed: {e:#}");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+    };
+    let payload = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+        match format {
+            PayloadFormat::Parquet => {
+                ParquetExporter::new(ParquetConfig::default()).write_to(&dataset, Vec::new())
+            }
+            PayloadFormat::Ndjson => StreamExporter::encode(FrameFormat::Ndjson, &dataset),
+        }
+    })
+    .await;
+    let payload = match payload {
+        Ok(Ok(payload)) => payload,
+        Ok(Err(e)) => {
+            error!("Generate request failed: {e:#}");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+        Err(e) => {
+            error!("Generate request panicked: {e}");
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "Generation failed");
+        }
+    };
+
+    let content_type = match format {
+        PayloadFormat::Parquet => "application/vnd.apache.parquet",
+        PayloadFormat::Ndjson => "application/x-ndjson",
+    };
+    Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        )
+        .body(Body::from(payload))
+        .expect("Static headers are valid")
+}
+
+// Queue a run for the worker pool. Answers straight away with the job to poll
+async fn submit_job(request: Request<Body>, stats: &LiveStats, jobs: &JobQueue) -> Response<Body> {
+    let mut request = match read_generate_request(request).await {
+        Ok(request) => request,
+        Err(response) => return response,
+    };
+    let memory = match request
+        .memory_limit
+        .take()
+        .map(|limit| limit.parse::<MemoryBudget>())
+    {
+        Some(Ok(memory)) => Some(memory),
+        Some(Err(e)) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+        None => None,
+    };
+    // The queue checks the size against its own limit
+    let (config, format) = match request.into_config(usize::MAX) {
+        Ok(settings) => settings,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    if format != PayloadFormat::Parquet {
+        return error_response(StatusCode::BAD_REQUEST, "Jobs write parquet");
+    }
+    match jobs.submit(config, memory, stats) {
+        Ok(report) => json_response(StatusCode::ACCEPTED, &report),
+        Err(SubmitError::Rejected(e)) => error_response(StatusCode::BAD_REQUEST, e.to_string()),
+        Err(SubmitError::QueueFull) => error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "The job queue is full, try again once some jobs finish",
+        ),
+    }
+}
+
+// Stream a finished job's parquet back without reading it all in
+async fn job_result(jobs: &JobQueue, id: u64) -> Response<Body> {
+    let Some((report, file)) = jobs.result_file(id) else {
+        return job_not_found(id);
+    };
+    if report.status != JobStatus::Done {
+        return error_response(
+            StatusCode::CONFLICT,
+            format!("Job {id} is {}, not done", report.status.name()),
+        );
+    }
+    let mut parquet = match tokio::fs::File::open(&file).await {
+        Ok(parquet) => parquet,
+        Err(e) => {
+            error!("Failed to open {}: {e}", file.display());
+            return error_response(StatusCode::INTERNAL_SERVER_ERROR, "The result is missing");
+        }
+    };
+    let file_name = file
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut buffer = vec![0; DOWNLOAD_CHUNK_BYTES];
+        loop {
+            match parquet.read(&mut buffer).await {
+                Ok(0) => break,
+                Ok(read) => {
+                    if sender
+                        .send_data(Bytes::copy_from_slice(&buffer[..read]))
+                        .await
+                        .is_err()
+                    {
+                        // The client went away
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to read {}: {e}", file.display());
+                    sender.abort();
+                    break;
+                }
+            }
+        }
+    });
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/vnd.apache.parquet")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        )
+        .body(body)
+        .expect("Static headers are valid")
+}
+
+fn job_not_found(id: u64) -> Response<Body> {
+    error_response(StatusCode::NOT_FOUND, format!("No job {id}"))
+}
+
+// Every run in the daemon's history, oldest first
+async fn runs(jobs: &JobQueue) -> Response<Body> {
+    let history_dir = jobs.history_dir().to_path_buf();
+    match tokio::task::spawn_blocking(move || load_runs(&history_dir, None)).await {
+        Ok(Ok(runs)) => json_response(StatusCode::OK, &runs),
+        Ok(Err(e)) => history_error(e),
+        Err(e) => {
+            error!("Reading the run history panicked: {e}");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read the history",
+            )
+        }
+    }
+}
+
+// A run by its id or any unique prefix of it
+async fn run(jobs: &JobQueue, id: &str) -> Response<Body> {
+    if id.is_empty() {
+        return error_response(StatusCode::NOT_FOUND, "No run id given");
+    }
+    let history_dir = jobs.history_dir().to_path_buf();
+    let prefix = id.to_string();
+    match tokio::task::spawn_blocking(move || find_run(&history_dir, &prefix)).await {
+        Ok(Ok(Some(run))) => json_response(StatusCode::OK, &run),
+        Ok(Ok(None)) => error_response(StatusCode::NOT_FOUND, format!("No run {id}")),
+        Ok(Err(e)) => history_error(e),
+        Err(e) => {
+            error!("Reading the run history panicked: {e}");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read the history",
+            )
+        }
+    }
+}
+
+// An ambiguous id is the request's fault, a history that can't be read the daemon's
+fn history_error(e: TelemetryError) -> Response<Body> {
+    match e {
+        TelemetryError::ConfigError(message) => error_response(StatusCode::BAD_REQUEST, message),
+        e => {
+            error!(
+                "Failed to read the run history: {:#}",
+                anyhow::Error::from(e)
+            );
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to read the history",
+            )
+        }
+    }
+}
+
+// A row of `GET /sensors`, the same as the sensor catalog CSV
+#[derive(Debug, Serialize)]
+struct SensorEntry {
+    sensor_type: String,
+    name: &'static str,
+    unit: &'static str,
+    description: &'static str,
+}
+
+// Sensors of a default run. Instances and redundant copies depend on the request, so they're left
+// out
+fn sensors() -> Response<Body> {
+    let config = TelemetryConfig::default();
+    let entries: Vec<SensorEntry> = SensorEnum::get_all_sensor_enums()
+        .into_iter()
+        .flat_map(|sensor| {
+            let config = &config;
+            config
+                .exported_names(sensor)
+                .into_iter()
+                .map(move |name| SensorEntry {
+                    sensor_type: sensor_label(name, None, None).into_owned(),
+                    name: sensor.name(SensorNaming::Full),
+                    unit: config.units.unit(sensor),
+                    description: sensor.description(),
+                })
+        })
+        .collect();
+    json_response(StatusCode::OK, &entries)
+}
+
+fn error_response(status: StatusCode, message: impl Into<String>) -> Response<Body> {
+    #[derive(Serialize)]
+    struct ApiError {
+        error: String,
+    }
+    json_response(
+        status,
+        &ApiError {
+            error: message.into(),
+        },
+    )
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response<Body> {
+    let json = serde_json::to_vec(body).expect("API responses always serialize");
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(json))
+        .expect("Static headers are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn generate_request(body: Body) -> Request<Body> {
+        Request::builder()
+            .method(Method::POST)
+            .uri("/generate")
+            .body(body)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn max_rows_caps_the_readings_sent() {
+        let request = generate_request(Body::from(
+            r#"{"duration": 10, "sample_rate_hz": 1000, "max_rows": 1, "format": "ndjson"}"#,
+        ));
+        let response = generate(request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body.iter().filter(|byte| **byte == b'\n').count(), 1);
+    }
+
+    #[test]
+    fn runs_over_the_limit_are_refused_without_max_rows() {
+        let request: GenerateRequest =
+            serde_json::from_str(r#"{"duration": 1000, "sample_rate_hz": 1000}"#).unwrap();
+        assert!(request.into_config(MAX_READINGS).is_err());
+        let request: GenerateRequest =
+            serde_json::from_str(r#"{"duration": 18446744073709551615, "sample_rate_hz": 1000}"#)
+                .unwrap();
+        assert!(request.into_config(MAX_READINGS).is_err());
+    }
+
+    async fn get(
+        path: &str,
+        stats: &LiveStats,
+        jobs: &JobQueue,
+    ) -> (StatusCode, serde_json::Value) {
+        let request = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let response = route(request, stats, jobs).await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn finished_jobs_are_served_from_the_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let stats = Arc::new(LiveStats::default());
+        let limits = crate::jobs::JobLimits {
+            workers: 1,
+            queue_size: 1,
+            max_rows: 1000,
+            memory: "64MB".parse().unwrap(),
+        };
+        let jobs = JobQueue::start(
+            &dir.path().join("jobs"),
+            dir.path(),
+            limits,
+            stats.clone(),
+            CancelToken::new(),
+        )
+        .unwrap();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/jobs")
+            .body(Body::from(r#"{"max_rows": 10}"#))
+            .unwrap();
+        assert_eq!(
+            route(request, &stats, &jobs).await.status(),
+            StatusCode::ACCEPTED
+        );
+
+        let runs = loop {
+            let (status, runs) = get("/runs", &stats, &jobs).await;
+            assert_eq!(status, StatusCode::OK);
+            if !runs.as_array().unwrap().is_empty() {
+                break runs;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        let id = runs[0]["id"].as_str().unwrap();
+        assert_eq!(runs[0]["status"], "done");
+        let (status, run) = get(&format!("/runs/{}", &id[..6]), &stats, &jobs).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(run["id"], id);
+        assert_eq!(run["readings"], 10);
+        let (status, _) = get("/runs/nothere", &stats, &jobs).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn oversized_bodies_are_refused() {
+        let declared = generate_request(Body::from(vec![b' '; MAX_BODY_BYTES + 1]));
+        let response = read_generate_request(declared).await.unwrap_err();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // No Content-Length, so it's only caught as it comes in
+        let (mut sender, body) = Body::channel();
+        tokio::spawn(async move {
+            while sender
+                .send_data(Bytes::from(vec![b' '; 16 * 1024]))
+                .await
+                .is_ok()
+            {}
+        });
+        let response = read_generate_request(generate_request(body))
+            .await
+            .unwrap_err();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}